@@ -15,7 +15,7 @@ impl Display for QueryContextError {
         match self {
             QueryContextError::InvalidDate(date) => write!(
                 fmt,
-                "Invalid date provided: {date}! Must be in format YYYY-MM-DD."
+                "Invalid date provided: {date}! Must be in format YYYY-MM-DD, YYYY-MM-DD HH:MM, an RFC 3339 datetime, or a relative date like `yesterday 09:00`, `7d`, or `last-month`."
             ),
         }
     }
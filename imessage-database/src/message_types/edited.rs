@@ -112,7 +112,7 @@ impl<'a> EditedMessage<'a> {
     }
 
     /// Gets a tuple for the message at the provided position
-    pub fn item_at(&self, position: usize) -> Option<&EditedEvent> {
+    pub fn item_at(&self, position: usize) -> Option<&EditedEvent<'_>> {
         self.events.get(position)
     }
 
@@ -49,23 +49,21 @@ impl<'a> BalloonProvider<'a> for MusicMessage<'a> {
 impl<'a> MusicMessage<'a> {
     /// Extract the main dictionary of data from the body of the payload
     ///
-    /// Apple Music stores the URL under `richLinkMetadata` like a normal URL, but has some 
+    /// Apple Music stores the URL under `richLinkMetadata` like a normal URL, but has some
     /// extra data stored under `specialization` that contains the track information.
     fn get_body_and_url(payload: &'a Value) -> Result<(&'a Value, &'a Value), PlistParseError> {
         let base = payload
             .as_dictionary()
-            .ok_or_else(|| PlistParseError::InvalidType(
-                "root".to_string(),
-                "dictionary".to_string(),
-            ))?
+            .ok_or_else(|| {
+                PlistParseError::InvalidType("root".to_string(), "dictionary".to_string())
+            })?
             .get("richLinkMetadata")
             .ok_or_else(|| PlistParseError::MissingKey("richLinkMetadata".to_string()))?;
         Ok((
             base.as_dictionary()
-                .ok_or_else(|| PlistParseError::InvalidType(
-                    "root".to_string(),
-                    "dictionary".to_string(),
-                ))?
+                .ok_or_else(|| {
+                    PlistParseError::InvalidType("root".to_string(), "dictionary".to_string())
+                })?
                 .get("specialization")
                 .ok_or_else(|| PlistParseError::MissingKey("specialization".to_string()))?,
             base,
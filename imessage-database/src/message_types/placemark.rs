@@ -7,7 +7,10 @@ use plist::Value;
 use crate::{
     error::plist::PlistParseError,
     message_types::variants::BalloonProvider,
-    util::plist::{get_string_from_dict, get_string_from_nested_dict},
+    util::{
+        coordinates::Coordinates,
+        plist::{get_string_from_dict, get_string_from_nested_dict},
+    },
 };
 
 /// Representation of Apple's [`CLPlacemark`](https://developer.apple.com/documentation/corelocation/clplacemark) object
@@ -118,6 +121,14 @@ impl<'a> PlacemarkMessage<'a> {
     pub fn get_url(&self) -> Option<&str> {
         self.url.or(self.original_url)
     }
+
+    /// Get the location of the placemark from its Maps URL, if the URL contains it
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        [self.url, self.original_url]
+            .into_iter()
+            .flatten()
+            .find_map(Coordinates::from_url)
+    }
 }
 
 #[cfg(test)]
@@ -127,7 +138,7 @@ mod tests {
             placemark::{Placemark, PlacemarkMessage},
             variants::BalloonProvider,
         },
-        util::plist::parse_plist,
+        util::{coordinates::Coordinates, plist::parse_plist},
     };
     use plist::Value;
     use std::env::current_dir;
@@ -193,4 +204,21 @@ mod tests {
 
         assert_eq!(placemark, expected);
     }
+
+    #[test]
+    fn can_get_coordinates() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/shared_placemark/SharedPlacemark.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let balloon = PlacemarkMessage::from_map(&parsed).unwrap();
+        assert_eq!(
+            balloon.coordinates(),
+            Coordinates::new(33.450858, -118.508212)
+        );
+    }
 }
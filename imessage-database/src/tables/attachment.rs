@@ -71,7 +71,7 @@ impl Table for Attachment {
         })
     }
 
-    fn get(db: &Connection) -> Result<Statement, TableError> {
+    fn get(db: &Connection) -> Result<Statement<'_>, TableError> {
         db.prepare(&format!("SELECT * from {ATTACHMENT}"))
             .map_err(TableError::Attachment)
     }
@@ -112,6 +112,19 @@ impl Attachment {
         Ok(out_l)
     }
 
+    /// Get a single attachment by its `guid`, such as the group photo referenced by a chat's properties
+    pub fn from_guid(db: &Connection, guid: &str) -> Result<Option<Attachment>, TableError> {
+        let mut statement = db
+            .prepare(&format!("SELECT * FROM {ATTACHMENT} WHERE guid = ?1"))
+            .map_err(TableError::Attachment)?;
+
+        let mut iter = statement
+            .query_map([guid], |row| Ok(Attachment::from_row(row)))
+            .map_err(TableError::Attachment)?;
+
+        iter.next().map(Attachment::extract).transpose()
+    }
+
     /// Get the media type of an attachment
     pub fn mime_type(&'_ self) -> MediaType<'_> {
         match &self.mime_type {
@@ -392,6 +405,7 @@ mod tests {
         util::platform::Platform,
     };
 
+    use rusqlite::Connection;
     use std::path::{Path, PathBuf};
 
     fn sample_attachment() -> Attachment {
@@ -408,6 +422,50 @@ mod tests {
         }
     }
 
+    /// Build a database with an `attachment` table that contains one group photo
+    fn sample_connection() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, guid TEXT, filename TEXT, mime_type TEXT, transfer_name TEXT, total_bytes INTEGER);
+            INSERT INTO attachment (guid, filename, mime_type, transfer_name, total_bytes)
+                VALUES ('at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED', '~/Library/Messages/Attachments/a/b/photo.jpeg', 'image/jpeg', 'photo.jpeg', 100);",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_get_from_guid() {
+        let db = sample_connection();
+        let attachment = Attachment::from_guid(&db, "at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(attachment.rowid, 1);
+        assert_eq!(
+            attachment.filename.as_deref(),
+            Some("~/Library/Messages/Attachments/a/b/photo.jpeg")
+        );
+        assert_eq!(attachment.mime_type(), MediaType::Image("image/jpeg"));
+        assert_eq!(attachment.total_bytes, 100);
+    }
+
+    #[test]
+    fn cant_get_from_guid_missing() {
+        let db = sample_connection();
+        let attachment = Attachment::from_guid(&db, "at_0_00000000-0000-0000-0000-000000000000");
+
+        assert!(attachment.unwrap().is_none());
+    }
+
+    #[test]
+    fn cant_get_from_guid_no_table() {
+        let db = Connection::open_in_memory().unwrap();
+        let attachment = Attachment::from_guid(&db, "at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED");
+
+        assert!(attachment.is_err());
+    }
+
     #[test]
     fn can_get_path() {
         let attachment = sample_attachment();
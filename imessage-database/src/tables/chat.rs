@@ -2,15 +2,77 @@
  This module represents common (but not all) columns in the `chat` table.
 */
 
-use std::collections::HashMap;
+use std::{collections::HashMap, io::Cursor};
 
+use plist::Value;
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
 use crate::{
-    error::table::TableError,
-    tables::table::{Cacheable, Table, CHAT},
+    error::{plist::PlistParseError, table::TableError},
+    tables::table::{
+        Cacheable, Table, CHAT, CHAT_MESSAGE_JOIN, MESSAGE, MESSAGE_ATTACHMENT_JOIN, PROPERTIES,
+    },
+    util::plist::{get_bool_from_dict, get_string_from_dict},
 };
 
+/// Represents the settings stored in the `properties` BLOB of a row in the `chat` table.
+///
+/// The BLOB is a binary plist dictionary; keys that are not present fall back to
+/// the default behavior of the Messages app.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChatProperties {
+    /// Whether read receipts are sent for this chat, if the user overrode the global setting
+    pub read_receipts_enabled: Option<bool>,
+    /// The GUID of the most recent message the user has seen
+    pub last_seen_message_guid: Option<String>,
+    /// Whether the chat was forced to send messages as SMS
+    pub forced_sms: bool,
+    /// The GUID of the group photo, which points to a row in the `attachment` table
+    pub group_photo_guid: Option<String>,
+    /// Whether alerts are hidden for this chat
+    pub ignore_alerts: bool,
+    /// Whether the chat has a custom background
+    pub has_chat_background: bool,
+}
+
+impl ChatProperties {
+    /// Build a `ChatProperties` from the deserialized `properties` plist
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use plist::{Dictionary, Value};
+    /// use imessage_database::tables::chat::ChatProperties;
+    ///
+    /// let mut dict = Dictionary::new();
+    /// dict.insert("shouldForceToSMS".to_string(), Value::Boolean(true));
+    ///
+    /// let properties = ChatProperties::from_plist(&Value::Dictionary(dict)).unwrap();
+    /// assert!(properties.forced_sms);
+    /// ```
+    pub fn from_plist(plist: &Value) -> Result<Self, PlistParseError> {
+        let dict = plist.as_dictionary().ok_or_else(|| {
+            PlistParseError::InvalidType(PROPERTIES.to_string(), "dictionary".to_string())
+        })?;
+
+        Ok(Self {
+            read_receipts_enabled: get_bool_from_dict(plist, "EnableReadReceiptForChat"),
+            last_seen_message_guid: get_string_from_dict(plist, "lastSeenMessageGuid")
+                .map(String::from),
+            forced_sms: get_bool_from_dict(plist, "shouldForceToSMS").unwrap_or(false),
+            group_photo_guid: get_string_from_dict(plist, "groupPhotoGuid").map(String::from),
+            ignore_alerts: get_bool_from_dict(plist, "ignoreAlertsFlag").unwrap_or(false),
+            has_chat_background: dict.contains_key("backgroundProperties"),
+        })
+    }
+
+    /// Deserialize the raw `properties` BLOB, returning `None` if it is missing or malformed
+    fn from_blob(blob: Option<Vec<u8>>) -> Option<Self> {
+        let plist = Value::from_reader(Cursor::new(blob?)).ok()?;
+        Self::from_plist(&plist).ok()
+    }
+}
+
 /// Represents a single row in the `chat` table.
 #[derive(Debug)]
 pub struct Chat {
@@ -18,6 +80,8 @@ pub struct Chat {
     pub chat_identifier: String,
     pub service_name: Option<String>,
     pub display_name: Option<String>,
+    /// The parsed contents of the `properties` column, if any
+    pub properties: Option<ChatProperties>,
 }
 
 impl Table for Chat {
@@ -27,10 +91,11 @@ impl Table for Chat {
             chat_identifier: row.get("chat_identifier")?,
             service_name: row.get("service_name")?,
             display_name: row.get("display_name").unwrap_or(None),
+            properties: ChatProperties::from_blob(row.get(PROPERTIES).unwrap_or(None)),
         })
     }
 
-    fn get(db: &Connection) -> Result<Statement, TableError> {
+    fn get(db: &Connection) -> Result<Statement<'_>, TableError> {
         db.prepare(&format!("SELECT * from {CHAT}"))
             .map_err(TableError::Chat)
     }
@@ -99,3 +164,158 @@ impl Chat {
         }
     }
 }
+
+/// Summarizes the messages that belong to a row in the `chat` table.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ChatActivity {
+    /// The number of messages in the chat
+    pub messages: u64,
+    /// The number of attachments sent in the chat
+    pub attachments: u64,
+    /// The `date` of the earliest message in the chat
+    pub first_message: Option<i64>,
+    /// The `date` of the latest message in the chat
+    pub last_message: Option<i64>,
+}
+
+impl ChatActivity {
+    /// Generate a hashmap containing each chatroom's ID pointing to a summary of its messages.
+    ///
+    /// Chats without any messages are not included.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat::ChatActivity;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let activity = ChatActivity::get_all(&conn);
+    /// ```
+    pub fn get_all(db: &Connection) -> Result<HashMap<i32, Self>, TableError> {
+        let mut map = HashMap::new();
+
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT
+                     c.chat_id,
+                     COUNT(*),
+                     COALESCE(SUM(a.attachments), 0),
+                     MIN(m.date),
+                     MAX(m.date)
+                 FROM {CHAT_MESSAGE_JOIN} as c
+                 JOIN {MESSAGE} as m ON m.ROWID = c.message_id
+                 LEFT JOIN (
+                     SELECT message_id, COUNT(*) as attachments
+                     FROM {MESSAGE_ATTACHMENT_JOIN}
+                     GROUP BY message_id
+                 ) as a ON a.message_id = m.ROWID
+                 GROUP BY c.chat_id"
+            ))
+            .map_err(TableError::Chat)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    ChatActivity {
+                        messages: row.get(1)?,
+                        attachments: row.get(2)?,
+                        first_message: row.get(3)?,
+                        last_message: row.get(4)?,
+                    },
+                ))
+            })
+            .map_err(TableError::Chat)?;
+
+        for row in rows {
+            let (chat_id, activity) = row.map_err(TableError::Chat)?;
+            map.insert(chat_id, activity);
+        }
+        Ok(map)
+    }
+
+    /// Combine the activity of another chat into this one, i.e. when both chats were deduplicated
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::tables::chat::ChatActivity;
+    ///
+    /// let mut activity = ChatActivity { messages: 2, attachments: 0, first_message: Some(10), last_message: Some(20) };
+    /// activity.merge(&ChatActivity { messages: 1, attachments: 1, first_message: Some(5), last_message: Some(5) });
+    /// assert_eq!(activity.messages, 3);
+    /// assert_eq!(activity.first_message, Some(5));
+    /// assert_eq!(activity.last_message, Some(20));
+    /// ```
+    pub fn merge(&mut self, other: &Self) {
+        self.messages += other.messages;
+        self.attachments += other.attachments;
+        self.first_message = match (self.first_message, other.first_message) {
+            (Some(ours), Some(theirs)) => Some(ours.min(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        };
+        self.last_message = match (self.last_message, other.last_message) {
+            (Some(ours), Some(theirs)) => Some(ours.max(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tables::chat::ChatProperties;
+    use plist::Value;
+    use std::env::current_dir;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse_properties() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/chat_properties/ChatProp.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = ChatProperties::from_plist(&plist).unwrap();
+
+        let expected = ChatProperties {
+            read_receipts_enabled: Some(true),
+            last_seen_message_guid: Some("FF0615B9-C4AF-4BD8-B9A8-1B5F9351033F".to_string()),
+            forced_sms: false,
+            group_photo_guid: Some("at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED".to_string()),
+            ignore_alerts: true,
+            has_chat_background: true,
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_parse_properties_minimal() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/chat_properties/ChatPropMinimal.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = ChatProperties::from_plist(&plist).unwrap();
+
+        let expected = ChatProperties {
+            read_receipts_enabled: None,
+            last_seen_message_guid: Some("A3B0E8C4-8B4C-4C41-9A5E-2D14A8B7F3C2".to_string()),
+            forced_sms: true,
+            group_photo_guid: None,
+            ignore_alerts: false,
+            has_chat_background: false,
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_parse_properties_invalid() {
+        let plist = Value::Boolean(true);
+        assert!(ChatProperties::from_plist(&plist).is_err());
+    }
+}
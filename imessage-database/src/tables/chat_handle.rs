@@ -27,7 +27,7 @@ impl Table for ChatToHandle {
         })
     }
 
-    fn get(db: &Connection) -> Result<Statement, TableError> {
+    fn get(db: &Connection) -> Result<Statement<'_>, TableError> {
         db.prepare(&format!("SELECT * FROM {CHAT_HANDLE_JOIN}"))
             .map_err(TableError::ChatToHandle)
     }
@@ -19,7 +19,7 @@ use crate::{
         MESSAGE_ATTACHMENT_JOIN, MESSAGE_PAYLOAD, MESSAGE_SUMMARY_INFO, RECENTLY_DELETED,
     },
     util::{
-        dates::{get_local_time, readable_diff},
+        dates::{get_local_time, readable_diff_with, DurationUnits, ENGLISH_UNITS},
         output::{done_processing, processing},
         query_context::QueryContext,
         streamtyped,
@@ -32,6 +32,8 @@ const ATTACHMENT_CHAR: char = '\u{FFFC}';
 const APP_CHAR: char = '\u{FFFD}';
 /// A collection of characters that represent non-text content within body text
 const REPLACEMENT_CHARS: [char; 2] = [ATTACHMENT_CHAR, APP_CHAR];
+/// A message date, or the reason it could not be calculated
+type DateResult = Result<DateTime<Local>, MessageError>;
 
 /// Represents a broad category of messages: standalone, thread originators, and thread replies.
 #[derive(Debug)]
@@ -131,7 +133,7 @@ impl Table for Message {
         })
     }
 
-    fn get(db: &Connection) -> Result<Statement, TableError> {
+    fn get(db: &Connection) -> Result<Statement<'_>, TableError> {
         // If the database has `chat_recoverable_message_join`, we can restore some deleted messages.
         // If database has `thread_originator_guid`, we can parse replies, otherwise default to 0
         Ok(db.prepare(&format!(
@@ -375,7 +377,7 @@ impl Message {
     /// Will have a `body()` of:
     ///
     /// `[BubbleType::Attachment, BubbleType::Text("Check out this photo!")]`
-    pub fn body(&self) -> Vec<BubbleType> {
+    pub fn body(&self) -> Vec<BubbleType<'_>> {
         let mut out_v = vec![];
 
         // If the message is an app, it will be rendered differently, so just escape there
@@ -457,13 +459,35 @@ impl Message {
     /// If more than one message has been sent in a thread before getting read,
     /// only the most recent message will get the tag.
     pub fn time_until_read(&self, offset: &i64) -> Option<String> {
+        self.time_until_read_with(offset, &ENGLISH_UNITS)
+    }
+
+    /// Gets the time until the message was read, using the provided names for each unit.
+    ///
+    /// See [`time_until_read`](Message::time_until_read) for details.
+    pub fn time_until_read_with(&self, offset: &i64, units: &DurationUnits) -> Option<String> {
+        let (start, end) = self.read_window(offset)?;
+        readable_diff_with(start, end, units)
+    }
+
+    /// Gets the number of seconds until the message was read.
+    ///
+    /// See [`time_until_read`](Message::time_until_read) for details.
+    pub fn seconds_until_read(&self, offset: &i64) -> Option<i64> {
+        let (start, end) = self.read_window(offset)?;
+        let seconds = (end.ok()? - start.ok()?).num_seconds();
+        (seconds >= 0).then_some(seconds)
+    }
+
+    /// Get the dates that bound the time until the message was read, if the message has them
+    fn read_window(&self, offset: &i64) -> Option<(DateResult, DateResult)> {
         // Message we received
         if !self.is_from_me && self.date_read != 0 && self.date != 0 {
-            return readable_diff(self.date(offset), self.date_read(offset));
+            return Some((self.date(offset), self.date_read(offset)));
         }
         // Message we sent
         else if self.is_from_me && self.date_delivered != 0 && self.date != 0 {
-            return readable_diff(self.date(offset), self.date_delivered(offset));
+            return Some((self.date(offset), self.date_delivered(offset)));
         }
         None
     }
@@ -766,7 +790,7 @@ impl Message {
     }
 
     /// Get the variant of a message, see [`variants`](crate::message_types::variants) for detail.
-    pub fn variant(&self) -> Variant {
+    pub fn variant(&self) -> Variant<'_> {
         // Check if a message was edited first as those have special properties
         if self.is_edited() {
             return Variant::Edited;
@@ -832,20 +856,20 @@ impl Message {
     }
 
     /// Determine the type of announcement a message contains, if it contains one
-    pub fn get_announcement(&self) -> Option<Announcement> {
+    pub fn get_announcement(&self) -> Option<Announcement<'_>> {
         if let Some(name) = &self.group_title {
             return Some(Announcement::NameChange(name));
         }
 
-        return match &self.group_action_type {
+        match &self.group_action_type {
             0 => None,
             1 => Some(Announcement::PhotoChange),
             other => Some(Announcement::Unknown(other)),
-        };
+        }
     }
 
     /// Determine the service the message was sent from, i.e. iMessage, SMS, IRC, etc.
-    pub fn service(&self) -> Service {
+    pub fn service(&self) -> Service<'_> {
         match self.service.as_deref() {
             Some("iMessage") => Service::iMessage,
             Some("SMS") => Service::SMS,
@@ -856,16 +880,14 @@ impl Message {
 
     /// Extract a blob of data that belongs to a single message from a given column
     fn get_blob<'a>(&self, db: &'a Connection, column: &str) -> Option<Blob<'a>> {
-        match db.blob_open(
+        db.blob_open(
             rusqlite::DatabaseName::Main,
             MESSAGE,
             column,
             self.rowid as i64,
             true,
-        ) {
-            Ok(blob) => Some(blob),
-            Err(_) => None,
-        }
+        )
+        .ok()
     }
 
     /// Get a message's plist from the `payload_data` BLOB column
@@ -903,7 +925,7 @@ impl Message {
     }
 
     /// Determine which expressive the message was sent with
-    pub fn get_expressive(&self) -> Expressive {
+    pub fn get_expressive(&self) -> Expressive<'_> {
         match &self.expressive_send_style_id {
             Some(content) => match content.as_str() {
                 "com.apple.MobileSMS.expressivesend.gentle" => {
@@ -1108,6 +1130,25 @@ mod tests {
         assert_eq!(message.time_until_read(&offset), None);
     }
 
+    #[test]
+    fn can_get_seconds_until_read() {
+        // Get offset
+        let offset = get_offset();
+
+        // Create message
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
+
+        assert_eq!(message.seconds_until_read(&offset), Some(3649));
+
+        // Messages we sent use the delivery date instead
+        message.is_from_me = true;
+        assert_eq!(message.seconds_until_read(&offset), None);
+    }
+
     #[test]
     fn can_get_message_expression_none() {
         let m = blank();
@@ -15,7 +15,7 @@ pub trait Table {
     where
         Self: Sized;
     /// Gets a statement we can execute to iterate over the data in the table
-    fn get(db: &Connection) -> Result<Statement, TableError>;
+    fn get(db: &Connection) -> Result<Statement<'_>, TableError>;
 
     /// Extract valid row data while handling both types of query errors
     fn extract(item: Result<Result<Self, Error>, Error>) -> Result<Self, TableError>
@@ -121,6 +121,8 @@ pub const MESSAGE_PAYLOAD: &str = "payload_data";
 pub const MESSAGE_SUMMARY_INFO: &str = "message_summary_info";
 /// The attributedBody column contains a message's body text with any other attributes
 pub const ATTRIBUTED_BODY: &str = "attributedBody";
+/// The properties column contains a chat's settings, like read receipts and the group photo
+pub const PROPERTIES: &str = "properties";
 
 // Default information
 /// Name used for messages sent by the database owner in a first-person context
@@ -0,0 +1,224 @@
+/*!
+ Contains logic for extracting geographic coordinates from map links.
+
+ Apple Maps, Google Maps, OpenStreetMap, and `geo:` links all encode the location they point to somewhere in the URL.
+*/
+
+/// Query string keys that hold a `latitude,longitude` pair, in order of preference
+///
+/// `ll` and `sll` are used by Apple Maps, `query` and `destination` by Google Maps, and `q` by both.
+const PAIR_KEYS: [&str; 8] = [
+    "ll",
+    "coordinate",
+    "center",
+    "sll",
+    "q",
+    "query",
+    "daddr",
+    "destination",
+];
+/// Query string keys that hold only a latitude, where `mlat` is the marker used by OpenStreetMap
+const LATITUDE_KEYS: [&str; 3] = ["lat", "latitude", "mlat"];
+/// Query string keys that hold only a longitude, where `mlon` is the marker used by OpenStreetMap
+const LONGITUDE_KEYS: [&str; 5] = ["lon", "lng", "long", "longitude", "mlon"];
+
+/// A point on the surface of the Earth, in decimal degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    /// Degrees north of the equator, between -90 and 90
+    pub latitude: f64,
+    /// Degrees east of the prime meridian, between -180 and 180
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// Create coordinates, if the latitude and longitude are in range
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::coordinates::Coordinates;
+    ///
+    /// assert!(Coordinates::new(33.450858, -118.508212).is_some());
+    /// assert!(Coordinates::new(118.508212, 33.450858).is_none());
+    /// ```
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) {
+            return Some(Self {
+                latitude,
+                longitude,
+            });
+        }
+        None
+    }
+
+    /// Parse a `latitude,longitude` pair, as used by most map links
+    fn from_pair(pair: &str) -> Option<Self> {
+        let mut parts = pair.split(',').map(str::trim);
+        let latitude = parts.next()?.parse().ok()?;
+        let longitude = parts.next()?.parse().ok()?;
+        Self::new(latitude, longitude)
+    }
+
+    /// Extract the location a map link points to
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::coordinates::Coordinates;
+    ///
+    /// let coordinates = Coordinates::from_url("https://maps.apple.com/?ll=33.450858,-118.508212&q=Cherry%20Cove");
+    /// assert_eq!(coordinates, Coordinates::new(33.450858, -118.508212));
+    /// ```
+    pub fn from_url(url: &str) -> Option<Self> {
+        // `geo:` links are only a pair, optionally followed by parameters
+        if let Some(rest) = url.strip_prefix("geo:") {
+            let pair = rest.split([';', '?']).next().unwrap_or(rest);
+            return Self::from_pair(&decode(pair));
+        }
+
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let query = query.split('#').next().unwrap_or(query);
+        let params: Vec<(&str, String)> = query
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key, decode(value)))
+            .collect();
+        let get = |keys: &[&str]| {
+            keys.iter().find_map(|key| {
+                params
+                    .iter()
+                    .find(|(param, _)| param == key)
+                    .map(|(_, value)| value.as_str())
+            })
+        };
+
+        if let Some(coordinates) = PAIR_KEYS
+            .iter()
+            .find_map(|key| Self::from_pair(get(&[key])?))
+        {
+            return Some(coordinates);
+        }
+
+        if let (Some(Ok(latitude)), Some(Ok(longitude))) = (
+            get(&LATITUDE_KEYS).map(str::parse),
+            get(&LONGITUDE_KEYS).map(str::parse),
+        ) {
+            if let Some(coordinates) = Self::new(latitude, longitude) {
+                return Some(coordinates);
+            }
+        }
+
+        // Google Maps puts the center of the map in the path, i.e. `/@33.45,-118.50,15z`
+        path.split('/')
+            .find_map(|segment| Self::from_pair(segment.strip_prefix('@')?))
+    }
+}
+
+/// Decode a percent-encoded query string value
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => decoded.push(b' '),
+            b'%' if idx + 2 < bytes.len()
+                && bytes[idx + 1].is_ascii_hexdigit()
+                && bytes[idx + 2].is_ascii_hexdigit() =>
+            {
+                // Both characters are hex digits, so they are ASCII and always parse
+                let hex = std::str::from_utf8(&bytes[idx + 1..idx + 3]).unwrap_or_default();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                idx += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::coordinates::{decode, Coordinates};
+
+    #[test]
+    fn can_decode() {
+        assert_eq!(decode("33.45%2C-118.50"), "33.45,-118.50");
+        assert_eq!(decode("Cherry+Cove%20Avalon"), "Cherry Cove Avalon");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn can_parse_apple_maps() {
+        assert_eq!(
+            Coordinates::from_url("https://maps.apple.com/?address=Cherry%20Cove,%20Avalon,%20CA%20%2090704,%20United%20States&ll=33.450858,-118.508212&q=Cherry%20Cove&t=m"),
+            Coordinates::new(33.450858, -118.508212)
+        );
+        assert_eq!(
+            Coordinates::from_url("https://maps.apple.com/?q=37.3349%2C-122.0090"),
+            Coordinates::new(37.3349, -122.0090)
+        );
+    }
+
+    #[test]
+    fn can_parse_google_maps() {
+        assert_eq!(
+            Coordinates::from_url(
+                "https://www.google.com/maps/place/Avalon/@33.3428,-118.3282,14z/data=abc"
+            ),
+            Coordinates::new(33.3428, -118.3282)
+        );
+        assert_eq!(
+            Coordinates::from_url(
+                "https://www.google.com/maps/search/?api=1&query=47.5951,-122.3316"
+            ),
+            Coordinates::new(47.5951, -122.3316)
+        );
+    }
+
+    #[test]
+    fn can_parse_separate_keys() {
+        assert_eq!(
+            Coordinates::from_url("?lat=51.5007&lng=-0.1246"),
+            Coordinates::new(51.5007, -0.1246)
+        );
+        assert_eq!(
+            Coordinates::from_url(
+                "https://www.openstreetmap.org/?mlat=48.8584&mlon=2.2945#map=17/48.8584/2.2945"
+            ),
+            Coordinates::new(48.8584, 2.2945)
+        );
+    }
+
+    #[test]
+    fn can_parse_geo() {
+        assert_eq!(
+            Coordinates::from_url("geo:-33.8568,151.2153;u=35"),
+            Coordinates::new(-33.8568, 151.2153)
+        );
+    }
+
+    #[test]
+    fn cant_parse_without_coordinates() {
+        assert_eq!(
+            Coordinates::from_url("https://maps.apple.com/?q=Cherry%20Cove"),
+            None
+        );
+        assert_eq!(
+            Coordinates::from_url(
+                "?FindMyMessagePayloadVersionKey=v0&FindMyMessagePayloadZippedDataKey=FAKEDATA"
+            ),
+            None
+        );
+        assert_eq!(Coordinates::from_url("https://example.com/@me"), None);
+    }
+
+    #[test]
+    fn cant_parse_out_of_range() {
+        assert_eq!(Coordinates::from_url("geo:91,0"), None);
+        assert_eq!(Coordinates::from_url("?ll=0,181"), None);
+    }
+}
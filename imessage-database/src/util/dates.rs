@@ -4,12 +4,134 @@
  Dates are stored as nanosecond-precision unix timestamps with an epoch of `1/1/2001 00:00:00` in the local time zone.
 */
 
-use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
+use std::fmt::Display;
+
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDateTime, Offset, SecondsFormat,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
 
 use crate::error::message::MessageError;
 
-const SEPARATOR: &str = ", ";
 pub const TIMESTAMP_FACTOR: i64 = 1000000000;
+/// The default pattern used to display dates, in `strftime` format
+pub const DATE_FORMAT: &str = "%b %d, %Y %l:%M:%S %p";
+
+/// Names for the units used when describing a duration in [`readable_diff_with`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct DurationUnits {
+    pub day: &'static str,
+    pub days: &'static str,
+    pub hour: &'static str,
+    pub hours: &'static str,
+    pub minute: &'static str,
+    pub minutes: &'static str,
+    pub second: &'static str,
+    pub seconds: &'static str,
+    /// Placed between each component of the duration
+    pub separator: &'static str,
+}
+
+/// English names for duration units
+pub const ENGLISH_UNITS: DurationUnits = DurationUnits {
+    day: "day",
+    days: "days",
+    hour: "hour",
+    hours: "hours",
+    minute: "minute",
+    minutes: "minutes",
+    second: "second",
+    seconds: "seconds",
+    separator: ", ",
+};
+
+/// Represents the time zone that dates are displayed and interpreted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timezone {
+    /// The time zone of the machine running the export
+    #[default]
+    Local,
+    /// Coordinated Universal Time
+    Utc,
+    /// A named zone from the IANA time zone database, i.e. `America/New_York`
+    Named(Tz),
+}
+
+impl Timezone {
+    /// Create an instance of the enum given user input
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dates::Timezone;
+    ///
+    /// assert_eq!(Timezone::from_cli("local"), Some(Timezone::Local));
+    /// assert!(Timezone::from_cli("America/New_York").is_some());
+    /// assert!(Timezone::from_cli("Mars/Olympus_Mons").is_none());
+    /// ```
+    pub fn from_cli(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "local" => Some(Self::Local),
+            "utc" => Some(Self::Utc),
+            _ => name.parse::<Tz>().ok().map(Self::Named),
+        }
+    }
+
+    /// Convert a date to the same instant in this time zone
+    pub fn convert<T: TimeZone>(&self, date: &DateTime<T>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::Local => date.with_timezone(&Local).fixed_offset(),
+            Timezone::Utc => date.with_timezone(&Utc).fixed_offset(),
+            Timezone::Named(tz) => date.with_timezone(tz).fixed_offset(),
+        }
+    }
+
+    /// Interpret a wall-clock date and time in this time zone
+    ///
+    /// Times that occur twice when clocks are set back resolve to the first occurrence. Times that
+    /// are skipped when clocks are set forward resolve to the same distance past the transition,
+    /// i.e. `02:30` on a day that jumps from `02:00` to `03:00` becomes `03:30`.
+    pub fn from_local_datetime(&self, date: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Timezone::Local => resolve_local_datetime(&Local, date),
+            Timezone::Utc => resolve_local_datetime(&Utc, date),
+            Timezone::Named(tz) => resolve_local_datetime(tz, date),
+        }
+    }
+}
+
+/// Interpret a wall-clock date and time in `zone`, including times around daylight saving transitions
+fn resolve_local_datetime<T: TimeZone>(
+    zone: &T,
+    date: &NaiveDateTime,
+) -> Option<DateTime<FixedOffset>> {
+    match zone.from_local_datetime(date) {
+        LocalResult::Single(resolved) | LocalResult::Ambiguous(resolved, _) => {
+            Some(resolved.fixed_offset())
+        }
+        LocalResult::None => {
+            // The time was skipped, so read it with the offset used before the clocks changed
+            let before = zone
+                .from_local_datetime(&date.checked_sub_signed(Duration::days(1))?)
+                .earliest()?;
+            let utc = date.checked_sub_signed(Duration::seconds(
+                before.offset().fix().local_minus_utc().into(),
+            ))?;
+            Some(zone.from_utc_datetime(&utc).fixed_offset())
+        }
+    }
+}
+
+impl Display for Timezone {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timezone::Local => write!(fmt, "local"),
+            Timezone::Utc => write!(fmt, "UTC"),
+            Timezone::Named(tz) => write!(fmt, "{}", tz.name()),
+        }
+    }
+}
 
 /// Get the date offset for the iMessage Database
 ///
@@ -31,6 +153,22 @@ pub fn get_local_time(date_stamp: &i64, offset: &i64) -> Result<DateTime<Local>,
     Ok(Local.from_utc_datetime(&utc_stamp))
 }
 
+/// Convert a date from the iMessage table to milliseconds since the unix epoch
+///
+/// This is the timestamp format used by most other messaging platforms, including Android.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::dates::{get_offset, get_unix_millis};
+///
+/// let millis = get_unix_millis(&674526582885055488, &get_offset());
+/// assert_eq!(millis, 1652833782885);
+/// ```
+pub fn get_unix_millis(date_stamp: &i64, offset: &i64) -> i64 {
+    (date_stamp / (TIMESTAMP_FACTOR / 1000)) + (offset * 1000)
+}
+
 /// Format a date from the iMessage table for reading
 ///
 /// # Example:
@@ -42,9 +180,50 @@ pub fn get_local_time(date_stamp: &i64, offset: &i64) -> Result<DateTime<Local>,
 /// let date = format(&Ok(Local::now()));
 /// println!("{date}");
 /// ```
-pub fn format(date: &Result<DateTime<Local>, MessageError>) -> String {
+pub fn format<T: TimeZone>(date: &Result<DateTime<T>, MessageError>) -> String
+where
+    T::Offset: Display,
+{
+    format_with(date, DATE_FORMAT)
+}
+
+/// Format a date from the iMessage table using a custom `strftime` pattern
+///
+/// # Example:
+///
+/// ```
+/// use chrono::offset::Local;
+/// use imessage_database::util::dates::format_with;
+///
+/// let date = format_with(&Ok(Local::now()), "%Y-%m-%d %H:%M");
+/// println!("{date}");
+/// ```
+pub fn format_with<T: TimeZone>(date: &Result<DateTime<T>, MessageError>, pattern: &str) -> String
+where
+    T::Offset: Display,
+{
+    match date {
+        Ok(d) => DateTime::format(d, pattern).to_string(),
+        Err(why) => why.to_string(),
+    }
+}
+
+/// Format a date as an ISO-8601 timestamp in UTC, i.e. `2020-05-20T16:10:11Z`
+///
+/// # Example:
+///
+/// ```
+/// use chrono::offset::Local;
+/// use imessage_database::util::dates::format_iso;
+///
+/// let date = format_iso(&Ok(Local::now()));
+/// println!("{date}");
+/// ```
+pub fn format_iso<T: TimeZone>(date: &Result<DateTime<T>, MessageError>) -> String {
     match date {
-        Ok(d) => DateTime::format(d, "%b %d, %Y %l:%M:%S %p").to_string(),
+        Ok(d) => d
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
         Err(why) => why.to_string(),
     }
 }
@@ -64,12 +243,43 @@ pub fn format(date: &Result<DateTime<Local>, MessageError>) -> String {
 pub fn readable_diff(
     start: Result<DateTime<Local>, MessageError>,
     end: Result<DateTime<Local>, MessageError>,
+) -> Option<String> {
+    readable_diff_with(start, end, &ENGLISH_UNITS)
+}
+
+/// Generate a readable diff from two local timestamps, using the provided names for each unit.
+///
+/// # Example:
+///
+/// ```
+/// use chrono::prelude::*;
+/// use imessage_database::util::dates::{readable_diff_with, ENGLISH_UNITS};
+///
+/// let start = Ok(Local.ymd(2020, 5, 20).and_hms_milli(9, 10, 11, 12));
+/// let end = Ok(Local.ymd(2020, 5, 20).and_hms_milli(9, 15, 11, 12));
+/// println!("{}", readable_diff_with(start, end, &ENGLISH_UNITS).unwrap())
+/// ```
+pub fn readable_diff_with(
+    start: Result<DateTime<Local>, MessageError>,
+    end: Result<DateTime<Local>, MessageError>,
+    units: &DurationUnits,
 ) -> Option<String> {
     // Calculate diff
     let diff: Duration = end.ok()? - start.ok()?;
-    let seconds = diff.num_seconds();
+    readable_duration_with(diff.num_seconds(), units)
+}
 
-    // Early escape for invalid date diff
+/// Generate a readable duration from a number of seconds, using the provided names for each unit.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::dates::{readable_duration_with, ENGLISH_UNITS};
+///
+/// println!("{}", readable_duration_with(3649, &ENGLISH_UNITS).unwrap())
+/// ```
+pub fn readable_duration_with(seconds: i64, units: &DurationUnits) -> Option<String> {
+    // Early escape for invalid durations
     if seconds < 0 {
         return None;
     }
@@ -84,42 +294,24 @@ pub fn readable_diff(
     let minutes = (seconds % 86400 % 3600) / 60;
     let secs = seconds % 86400 % 3600 % 60;
 
-    if days != 0 {
-        let metric = match days {
-            1 => "day",
-            _ => "days",
-        };
-        out_s.push_str(&format!("{days} {metric}"));
-    }
-    if hours != 0 {
-        let metric = match hours {
-            1 => "hour",
-            _ => "hours",
-        };
-        if !out_s.is_empty() {
-            out_s.push_str(SEPARATOR);
-        }
-        out_s.push_str(&format!("{hours} {metric}"));
-    }
-    if minutes != 0 {
-        let metric = match minutes {
-            1 => "minute",
-            _ => "minutes",
-        };
-        if !out_s.is_empty() {
-            out_s.push_str(SEPARATOR);
-        }
-        out_s.push_str(&format!("{minutes} {metric}"));
-    }
-    if secs != 0 {
-        let metric = match secs {
-            1 => "second",
-            _ => "seconds",
-        };
-        if !out_s.is_empty() {
-            out_s.push_str(SEPARATOR);
+    let components = [
+        (days, units.day, units.days),
+        (hours, units.hour, units.hours),
+        (minutes, units.minute, units.minutes),
+        (secs, units.second, units.seconds),
+    ];
+
+    for (amount, singular, plural) in components {
+        if amount != 0 {
+            let metric = match amount {
+                1 => singular,
+                _ => plural,
+            };
+            if !out_s.is_empty() {
+                out_s.push_str(units.separator);
+            }
+            out_s.push_str(&format!("{amount} {metric}"));
         }
-        out_s.push_str(&format!("{secs} {metric}"));
     }
     Some(out_s)
 }
@@ -128,9 +320,13 @@ pub fn readable_diff(
 mod tests {
     use crate::{
         error::message::MessageError,
-        util::dates::{format, readable_diff},
+        util::dates::{
+            format, format_iso, format_with, get_offset, get_unix_millis, readable_diff,
+            readable_diff_with, readable_duration_with, DurationUnits, Timezone, ENGLISH_UNITS,
+        },
     };
     use chrono::prelude::*;
+    use chrono_tz::Tz;
 
     #[test]
     fn can_format_date_single_digit() {
@@ -255,4 +451,140 @@ mod tests {
         let end = Ok(Local.with_ymd_and_hms(2020, 5, 20, 9, 10, 11).unwrap());
         assert_eq!(readable_diff(start, end), Some("".to_owned()));
     }
+
+    #[test]
+    fn can_format_date_iso() {
+        let date = Utc
+            .with_ymd_and_hms(2020, 5, 20, 9, 10, 11)
+            .single()
+            .ok_or(MessageError::InvalidTimestamp(0));
+        assert_eq!(format_iso(&date), "2020-05-20T09:10:11Z");
+    }
+
+    #[test]
+    fn can_format_date_iso_from_offset() {
+        let date = FixedOffset::west_opt(7 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2020, 5, 20, 9, 10, 11)
+            .single()
+            .ok_or(MessageError::InvalidTimestamp(0));
+        assert_eq!(format_iso(&date), "2020-05-20T16:10:11Z");
+    }
+
+    #[test]
+    fn can_parse_timezone() {
+        assert_eq!(Timezone::from_cli("local"), Some(Timezone::Local));
+        assert_eq!(Timezone::from_cli("UTC"), Some(Timezone::Utc));
+        assert_eq!(
+            Timezone::from_cli("Europe/Berlin"),
+            Some(Timezone::Named(Tz::Europe__Berlin))
+        );
+        assert_eq!(Timezone::from_cli("Not/AZone"), None);
+    }
+
+    #[test]
+    fn can_convert_timezone() {
+        let date = Utc.with_ymd_and_hms(2020, 5, 20, 16, 10, 11).unwrap();
+        let converted = Timezone::Named(Tz::America__Los_Angeles).convert(&date);
+        assert_eq!(format(&Ok(converted)), "May 20, 2020  9:10:11 AM");
+    }
+
+    #[test]
+    fn can_interpret_in_timezone() {
+        let naive = NaiveDate::from_ymd_opt(2020, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let date = Timezone::Named(Tz::Asia__Tokyo)
+            .from_local_datetime(&naive)
+            .unwrap();
+        assert_eq!(format_iso(&Ok(date)), "2019-12-31T15:00:00Z");
+    }
+
+    #[test]
+    fn can_interpret_ambiguous_time() {
+        // Clocks in New York were set back from 02:00 to 01:00 on November 5, 2023
+        let naive = NaiveDate::from_ymd_opt(2023, 11, 5)
+            .unwrap()
+            .and_hms_opt(1, 30, 0)
+            .unwrap();
+        let date = Timezone::Named(Tz::America__New_York)
+            .from_local_datetime(&naive)
+            .unwrap();
+        assert_eq!(format_iso(&Ok(date)), "2023-11-05T05:30:00Z");
+    }
+
+    #[test]
+    fn can_interpret_skipped_time() {
+        // Clocks in New York were set forward from 02:00 to 03:00 on March 12, 2023
+        let naive = NaiveDate::from_ymd_opt(2023, 3, 12)
+            .unwrap()
+            .and_hms_opt(2, 30, 0)
+            .unwrap();
+        let date = Timezone::Named(Tz::America__New_York)
+            .from_local_datetime(&naive)
+            .unwrap();
+        assert_eq!(date.to_rfc3339(), "2023-03-12T03:30:00-04:00");
+    }
+
+    #[test]
+    fn can_interpret_skipped_midnight() {
+        // Clocks in São Paulo were set forward from midnight to 01:00 on November 4, 2018
+        let naive = NaiveDate::from_ymd_opt(2018, 11, 4)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let date = Timezone::Named(Tz::America__Sao_Paulo)
+            .from_local_datetime(&naive)
+            .unwrap();
+        assert_eq!(date.to_rfc3339(), "2018-11-04T01:00:00-02:00");
+    }
+
+    #[test]
+    fn can_format_date_pattern() {
+        let date = Local
+            .with_ymd_and_hms(2020, 5, 20, 9, 10, 11)
+            .single()
+            .ok_or(MessageError::InvalidTimestamp(0));
+        assert_eq!(format_with(&date, "%d/%m/%Y %H:%M"), "20/05/2020 09:10");
+    }
+
+    #[test]
+    fn can_format_diff_custom_units() {
+        let units = DurationUnits {
+            day: "día",
+            days: "días",
+            hour: "hora",
+            hours: "horas",
+            minute: "minuto",
+            minutes: "minutos",
+            second: "segundo",
+            seconds: "segundos",
+            separator: " y ",
+        };
+        let start = Ok(Local.with_ymd_and_hms(2020, 5, 20, 9, 10, 11).unwrap());
+        let end = Ok(Local.with_ymd_and_hms(2020, 5, 22, 10, 10, 30).unwrap());
+        assert_eq!(
+            readable_diff_with(start, end, &units),
+            Some("2 días y 1 hora y 19 segundos".to_owned())
+        );
+    }
+
+    #[test]
+    fn can_format_duration() {
+        assert_eq!(
+            readable_duration_with(3649, &ENGLISH_UNITS),
+            Some("1 hour, 49 seconds".to_owned())
+        );
+        assert_eq!(readable_duration_with(-1, &ENGLISH_UNITS), None);
+    }
+
+    #[test]
+    fn can_get_unix_millis() {
+        assert_eq!(get_unix_millis(&0, &get_offset()), 978307200000);
+        assert_eq!(
+            get_unix_millis(&674526582885055488, &get_offset()),
+            1652833782885
+        );
+    }
 }
@@ -2,6 +2,7 @@
  This module defines common utilities used across table queries.
 */
 
+pub mod coordinates;
 pub mod dates;
 pub mod dirs;
 pub mod output;
@@ -1,11 +1,11 @@
 /*!
  Contains logic for handling query filter configurations.
 */
-use chrono::prelude::*;
+use chrono::{prelude::*, Duration, Months};
 
 use crate::{
     error::query_context::QueryContextError,
-    util::dates::{get_offset, TIMESTAMP_FACTOR},
+    util::dates::{get_offset, Timezone, TIMESTAMP_FACTOR},
 };
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -13,11 +13,31 @@ use crate::{
 pub struct QueryContext {
     /// The start date filter. Only messages sent on or after this date will be included.
     pub start: Option<i64>,
-    /// The end date filter. Only messages sent before this date will be included.
+    /// The end date filter. Only messages sent on or before this date will be included.
     pub end: Option<i64>,
+    /// If `true`, messages sent exactly at the end date are excluded as well
+    pub end_exclusive: bool,
+    /// The time zone used to interpret the start and end dates
+    pub timezone: Timezone,
 }
 
 impl QueryContext {
+    /// Set the time zone used to interpret dates passed to [`set_start`](QueryContext::set_start)
+    /// and [`set_end`](QueryContext::set_end)
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::{dates::Timezone, query_context::QueryContext};
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_timezone(Timezone::Utc);
+    /// context.set_start("2023-01-01");
+    /// ```
+    pub fn set_timezone(&mut self, timezone: Timezone) {
+        self.timezone = timezone;
+    }
+
     /// Generate a `QueryContext` with a start date
     /// # Example:
     ///
@@ -28,12 +48,28 @@ impl QueryContext {
     /// context.set_start("2023-01-01");
     /// ```
     pub fn set_start(&mut self, start: &str) -> Result<(), QueryContextError> {
-        let timestamp = QueryContext::sanitize_date(start)
+        let timestamp = QueryContext::sanitize_date(start, &self.timezone)
             .ok_or(QueryContextError::InvalidDate(start.to_string()))?;
         self.start = Some(timestamp);
         Ok(())
     }
 
+    /// Exclude messages sent exactly at the end date
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_end("2023-01-02").unwrap();
+    /// context.set_end_exclusive(true);
+    /// assert!(context.generate_filter_statement().contains("m.date < "));
+    /// ```
+    pub fn set_end_exclusive(&mut self, exclusive: bool) {
+        self.end_exclusive = exclusive;
+    }
+
     /// Generate a `QueryContext` with an end date
     /// # Example:
     ///
@@ -44,14 +80,60 @@ impl QueryContext {
     /// context.set_end("2023-01-01");
     /// ```
     pub fn set_end(&mut self, end: &str) -> Result<(), QueryContextError> {
-        let timestamp = QueryContext::sanitize_date(end)
+        let timestamp = QueryContext::sanitize_date(end, &self.timezone)
             .ok_or(QueryContextError::InvalidDate(end.to_string()))?;
         self.end = Some(timestamp);
         Ok(())
     }
 
-    /// Ensure a date string is valid
-    fn sanitize_date(date: &str) -> Option<i64> {
+    /// Ensure a date string is valid, returning the matching iMessage timestamp
+    ///
+    /// Dates without an explicit offset are interpreted in `timezone`. Supported formats:
+    ///
+    /// - `YYYY-MM-DD`, i.e. midnight on that day
+    /// - `YYYY-MM-DD HH:MM[:SS]` or `YYYY-MM-DDTHH:MM[:SS]`
+    /// - RFC 3339 datetimes with an offset, i.e. `2020-01-01T09:00:00-08:00`
+    /// - `HH:MM[:SS]`, i.e. a time today
+    /// - `now`, `today`, `yesterday`, or `tomorrow`, optionally followed by a time of day
+    /// - `this-week`, `last-week`, `this-month`, `last-month`, `this-year`, or `last-year`,
+    ///   i.e. the start of that period, optionally followed by a time of day
+    /// - An amount of time before now, in minutes (`30min`), hours (`12h`), days (`7d`), or weeks (`2w`)
+    fn sanitize_date(date: &str, timezone: &Timezone) -> Option<i64> {
+        let now = timezone.convert(&Utc::now());
+        QueryContext::sanitize_date_at(date, timezone, &now)
+    }
+
+    /// Ensure a date string is valid, resolving relative dates against `now`
+    fn sanitize_date_at(
+        date: &str,
+        timezone: &Timezone,
+        now: &DateTime<FixedOffset>,
+    ) -> Option<i64> {
+        let date = date.trim();
+        let parsed = match DateTime::parse_from_rfc3339(date) {
+            Ok(exact) => exact,
+            Err(_) => match QueryContext::parse_datetime(date) {
+                Some(wall_clock) => timezone.from_local_datetime(&wall_clock)?,
+                None => QueryContext::parse_relative(&date.to_lowercase(), timezone, now)?,
+            },
+        };
+        let stamp = parsed.timestamp_nanos_opt()?;
+
+        Some(stamp - (get_offset() * TIMESTAMP_FACTOR))
+    }
+
+    /// Parse a `YYYY-MM-DD` date, optionally followed by a time of day
+    fn parse_datetime(date: &str) -> Option<NaiveDateTime> {
+        match date.split_once(['T', ' ']) {
+            Some((day, time)) => {
+                Some(QueryContext::parse_day(day)?.and_time(QueryContext::parse_time(time)?))
+            }
+            None => QueryContext::parse_day(date)?.and_hms_opt(0, 0, 0),
+        }
+    }
+
+    /// Parse a `YYYY-MM-DD` date
+    fn parse_day(date: &str) -> Option<NaiveDate> {
         if date.len() < 9 {
             return None;
         }
@@ -76,10 +158,74 @@ impl QueryContext {
             return None;
         }
 
-        let local = Local.with_ymd_and_hms(year, month, day, 0, 0, 0).single()?;
-        let stamp = local.timestamp_nanos_opt().unwrap_or(0);
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
 
-        Some(stamp - (get_offset() * TIMESTAMP_FACTOR))
+    /// Parse a `HH:MM` or `HH:MM:SS` time of day
+    fn parse_time(time: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()
+    }
+
+    /// Resolve a date relative to `now`, i.e. `yesterday 09:00`, `7d`, or `last-month`
+    fn parse_relative(
+        date: &str,
+        timezone: &Timezone,
+        now: &DateTime<FixedOffset>,
+    ) -> Option<DateTime<FixedOffset>> {
+        let (day, time) = match date.split_once(' ') {
+            Some((day, time)) => (day, Some(QueryContext::parse_time(time)?)),
+            None => (date, None),
+        };
+        let today = now.date_naive();
+        let week_start = today - Duration::days(today.weekday().num_days_from_monday().into());
+        let month_start = today.with_day(1)?;
+
+        let start = match day {
+            "now" => return time.map_or(Some(*now), |_| None),
+            "today" => today,
+            "yesterday" => today.pred_opt()?,
+            "tomorrow" => today.succ_opt()?,
+            "this-week" => week_start,
+            "last-week" => week_start - Duration::weeks(1),
+            "this-month" => month_start,
+            "last-month" => month_start.checked_sub_months(Months::new(1))?,
+            "this-year" => NaiveDate::from_ymd_opt(today.year(), 1, 1)?,
+            "last-year" => NaiveDate::from_ymd_opt(today.year() - 1, 1, 1)?,
+            _ => {
+                // A bare time of day refers to today
+                if let Some(time_of_day) = QueryContext::parse_time(day) {
+                    return match time {
+                        Some(_) => None,
+                        None => timezone.from_local_datetime(&today.and_time(time_of_day)),
+                    };
+                }
+
+                let ago = now.checked_sub_signed(QueryContext::parse_duration(day)?)?;
+                return match time {
+                    Some(time) => timezone.from_local_datetime(&ago.date_naive().and_time(time)),
+                    None => Some(ago),
+                };
+            }
+        };
+
+        timezone.from_local_datetime(&start.and_time(time.unwrap_or_default()))
+    }
+
+    /// Parse an amount of time, i.e. `30min`, `12h`, `7d`, or `2w`
+    fn parse_duration(amount: &str) -> Option<Duration> {
+        let split = amount.find(|c: char| !c.is_ascii_digit())?;
+        let (count, unit) = amount.split_at(split);
+        let count = i64::from(count.parse::<u32>().ok()?);
+
+        match unit {
+            "min" => Some(Duration::minutes(count)),
+            "h" => Some(Duration::hours(count)),
+            "d" => Some(Duration::days(count)),
+            "w" => Some(Duration::weeks(count)),
+            _ => None,
+        }
     }
 
     /// Determine if the current `QueryContext` has any filters present
@@ -117,7 +263,8 @@ impl QueryContext {
             if !filters.is_empty() {
                 filters.push_str(" AND ");
             }
-            filters.push_str(&format!("    m.date <= {end}"));
+            let comparison = if self.end_exclusive { "<" } else { "<=" };
+            filters.push_str(&format!("    m.date {comparison} {end}"));
         }
 
         if !filters.is_empty() {
@@ -135,9 +282,10 @@ mod use_tests {
     use std::env::set_var;
 
     use chrono::prelude::*;
+    use chrono_tz::Tz;
 
     use crate::util::{
-        dates::{format, get_offset, TIMESTAMP_FACTOR},
+        dates::{format, get_offset, Timezone, TIMESTAMP_FACTOR},
         query_context::QueryContext,
     };
 
@@ -233,6 +381,58 @@ mod use_tests {
         assert!(context.has_filters());
     }
 
+    #[test]
+    fn can_create_start_in_timezone() {
+        let mut context = QueryContext::default();
+        context.set_timezone(Timezone::Named(Tz::America__New_York));
+        context.set_start("2020-01-01").unwrap();
+
+        assert_eq!(
+            context.generate_filter_statement(),
+            " WHERE\n                     m.date >= 599547600000000000"
+        );
+    }
+
+    #[test]
+    fn can_create_start_on_skipped_midnight() {
+        // Clocks in São Paulo were set forward from midnight to 01:00 on November 4, 2018
+        let mut context = QueryContext::default();
+        context.set_timezone(Timezone::Named(Tz::America__Sao_Paulo));
+        context.set_start("2018-11-04").unwrap();
+
+        assert_eq!(
+            context.generate_filter_statement(),
+            " WHERE\n                     m.date >= 562993200000000000"
+        );
+    }
+
+    #[test]
+    fn can_create_both_in_utc() {
+        let mut context = QueryContext::default();
+        context.set_timezone(Timezone::Utc);
+        context.set_start("2020-01-01").unwrap();
+        context.set_end("2020-02-02").unwrap();
+
+        assert_eq!(
+            context.generate_filter_statement(),
+            " WHERE\n                     m.date >= 599529600000000000 AND     m.date <= 602294400000000000"
+        );
+    }
+
+    #[test]
+    fn can_create_end_exclusive() {
+        let mut context = QueryContext::default();
+        context.set_timezone(Timezone::Utc);
+        context.set_start("2020-01-01 09:00").unwrap();
+        context.set_end("2020-01-01 17:00").unwrap();
+        context.set_end_exclusive(true);
+
+        assert_eq!(
+            context.generate_filter_statement(),
+            " WHERE\n                     m.date >= 599562000000000000 AND     m.date < 599590800000000000"
+        );
+    }
+
     #[test]
     fn can_create_invalid_start() {
         let mut context = QueryContext::default();
@@ -252,47 +452,128 @@ mod use_tests {
 
 #[cfg(test)]
 mod sanitize_tests {
-    use crate::util::query_context::QueryContext;
+    use chrono::prelude::*;
+
+    use crate::util::{dates::Timezone, query_context::QueryContext};
+
+    /// Wednesday, Jan 15, 2020 12:00:00 PM UTC
+    fn fake_now() -> DateTime<FixedOffset> {
+        Utc.with_ymd_and_hms(2020, 1, 15, 12, 0, 0)
+            .unwrap()
+            .fixed_offset()
+    }
+
+    fn sanitize_relative(date: &str) -> Option<i64> {
+        QueryContext::sanitize_date_at(date, &Timezone::Utc, &fake_now())
+    }
 
     #[test]
     fn can_sanitize_good() {
-        let res = QueryContext::sanitize_date("2020-01-01");
+        let res = QueryContext::sanitize_date("2020-01-01", &Timezone::Local);
         assert!(res.is_some());
     }
 
     #[test]
     fn can_reject_bad_short() {
-        let res = QueryContext::sanitize_date("1-1-20");
+        let res = QueryContext::sanitize_date("1-1-20", &Timezone::Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_order() {
-        let res = QueryContext::sanitize_date("01-01-2020");
+        let res = QueryContext::sanitize_date("01-01-2020", &Timezone::Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_month() {
-        let res = QueryContext::sanitize_date("2020-31-01");
+        let res = QueryContext::sanitize_date("2020-31-01", &Timezone::Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_day() {
-        let res = QueryContext::sanitize_date("2020-01-32");
+        let res = QueryContext::sanitize_date("2020-01-32", &Timezone::Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_data() {
-        let res = QueryContext::sanitize_date("2020-AB-CD");
+        let res = QueryContext::sanitize_date("2020-AB-CD", &Timezone::Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_wrong_hyphen() {
-        let res = QueryContext::sanitize_date("2020–01–01");
+        let res = QueryContext::sanitize_date("2020–01–01", &Timezone::Local);
         assert!(res.is_none());
     }
+
+    #[test]
+    fn can_sanitize_datetime() {
+        let res = QueryContext::sanitize_date("2020-01-01 09:00", &Timezone::Utc);
+        assert_eq!(res, Some(599562000000000000));
+    }
+
+    #[test]
+    fn can_sanitize_datetime_seconds() {
+        let res = QueryContext::sanitize_date("2020-01-01T09:00:30", &Timezone::Utc);
+        assert_eq!(res, Some(599562030000000000));
+    }
+
+    #[test]
+    fn can_sanitize_rfc3339() {
+        // The explicit offset takes priority over the configured time zone
+        let res = QueryContext::sanitize_date("2020-01-01T09:00:00-08:00", &Timezone::Utc);
+        assert_eq!(res, Some(599590800000000000));
+    }
+
+    #[test]
+    fn can_reject_bad_time() {
+        let res = QueryContext::sanitize_date("2020-01-01 25:00", &Timezone::Utc);
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn can_sanitize_now() {
+        assert_eq!(sanitize_relative("now"), Some(600782400000000000));
+    }
+
+    #[test]
+    fn can_sanitize_time_of_day() {
+        assert_eq!(sanitize_relative("17:00"), Some(600800400000000000));
+    }
+
+    #[test]
+    fn can_sanitize_yesterday_with_time() {
+        assert_eq!(
+            sanitize_relative("Yesterday 09:00"),
+            Some(600685200000000000)
+        );
+    }
+
+    #[test]
+    fn can_sanitize_duration() {
+        assert_eq!(sanitize_relative("7d"), Some(600177600000000000));
+        assert_eq!(sanitize_relative("12h"), Some(600739200000000000));
+    }
+
+    #[test]
+    fn can_sanitize_this_week() {
+        assert_eq!(sanitize_relative("this-week"), Some(600566400000000000));
+    }
+
+    #[test]
+    fn can_sanitize_last_month() {
+        assert_eq!(sanitize_relative("last-month"), Some(596851200000000000));
+    }
+
+    #[test]
+    fn can_reject_bad_relative() {
+        assert!(sanitize_relative("7y").is_none());
+        assert!(sanitize_relative("d").is_none());
+        assert!(sanitize_relative("now 09:00").is_none());
+        assert!(sanitize_relative("09:00 10:00").is_none());
+        assert!(sanitize_relative("next-month").is_none());
+    }
 }
@@ -1,27 +1,133 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
     fmt::Display,
-    fs::{copy, create_dir_all, metadata},
+    fs::{copy, create_dir_all, hard_link, metadata, read, remove_file, File},
+    io::{self, ErrorKind},
     path::{Path, PathBuf},
 };
 
+use chrono::{DateTime, Local};
 use filetime::{set_file_times, FileTime};
-use imessage_database::tables::{attachment::Attachment, messages::Message};
-use uuid::Uuid;
+use imessage_database::tables::{attachment::Attachment, chat::Chat, messages::Message};
+use sha1::{Digest, Sha1};
 
 use crate::app::{
+    attachment_names::AttachmentNames,
     converter::{convert_heic, Converter, ImageType},
+    encoding::encode_base64,
+    links::{reflink, symlink},
     runtime::Config,
 };
 
+/// Default size of the largest attachment embedded in an HTML export, in megabytes
+pub const DEFAULT_EMBED_LIMIT_MB: u64 = 10;
+/// Number of bytes in a megabyte
+pub const BYTES_PER_MB: u64 = 1024 * 1024;
+/// Subdirectory of the attachment directory that deduplicated files are copied to
+pub const DEDUPED_DIR: &str = "shared";
+
+/// Hash the contents of a file, returning the digest as lowercase hex
+pub fn hash_file(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher).ok()?;
+    Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect(),
+    )
+}
+
+/// Tracks the attachments copied during an export, so copies never share a name and, when deduplicating,
+/// files with the same contents are only copied once
+///
+/// Exporters only hold a shared reference to the [`Config`], so the store uses interior mutability.
+#[derive(Debug, Default)]
+pub struct AttachmentStore {
+    /// Map of content hash and sticker status to the path of the copy
+    ///
+    /// Stickers are converted differently, so the same file is stored once for each
+    files: RefCell<HashMap<(String, bool), PathBuf>>,
+    /// Lowercase paths of copies named after their original files
+    names: RefCell<HashSet<String>>,
+    /// Number of bytes that were not copied because the file was already stored
+    bytes_saved: Cell<u64>,
+    /// Number of attachments that pointed to an existing copy
+    duplicates: Cell<u64>,
+}
+
+impl AttachmentStore {
+    /// Get the path to an existing copy of a file, counting the bytes we did not need to copy again
+    pub fn find(&self, hash: &str, is_sticker: bool) -> Option<PathBuf> {
+        let path = self
+            .files
+            .borrow()
+            .get(&(hash.to_string(), is_sticker))
+            .cloned()?;
+        self.duplicates.set(self.duplicates.get() + 1);
+        if let Ok(metadata) = metadata(&path) {
+            self.bytes_saved
+                .set(self.bytes_saved.get() + metadata.len());
+        }
+        Some(path)
+    }
+
+    /// Record where a file was copied to
+    pub fn insert(&self, hash: String, is_sticker: bool, path: PathBuf) {
+        self.files.borrow_mut().insert((hash, is_sticker), path);
+    }
+
+    /// Reserve a path in `dir` for a copy named `stem`, adding a numbered suffix if the name is taken
+    ///
+    /// Some file systems ignore case, so names that only differ by case are also treated as taken.
+    pub fn reserve(&self, dir: &Path, stem: &str, extension: &str) -> PathBuf {
+        let mut names = self.names.borrow_mut();
+        let mut suffix = 0;
+        loop {
+            let path = match suffix {
+                0 => dir.join(format!("{stem}.{extension}")),
+                _ => dir.join(format!("{stem} ({suffix}).{extension}")),
+            };
+            if !path.exists() && names.insert(path.to_string_lossy().to_lowercase()) {
+                return path;
+            }
+            suffix += 1;
+        }
+    }
+
+    /// Number of bytes that were not copied because the file was already stored
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_saved.get()
+    }
+
+    /// Number of attachments that pointed to an existing copy
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.get()
+    }
+}
+
 /// Represents different ways the app can interact with attachment data
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Default)]
 pub enum AttachmentManager {
     /// Do not copy attachments
+    #[default]
     Disabled,
     /// Copy and convert attachments to more compatible formats using a [`Converter`]
     Compatible,
     /// Copy attachments without converting; preserves quality but may not display correctly in all browsers
     Efficient,
+    /// Convert attachments like [`AttachmentManager::Compatible`], then write images, audio, and video
+    /// no larger than the given number of bytes directly into HTML exports
+    Embedded(u64),
+    /// Hard link attachments into the export; uses no extra space, but the links share the original files
+    Hardlink,
+    /// Link to attachments in their original location; uses no extra space, but breaks if the originals move
+    Symlink,
+    /// Clone attachments on file systems that support copy-on-write; uses no extra space until either file changes
+    Reflink,
 }
 
 impl AttachmentManager {
@@ -31,6 +137,10 @@ impl AttachmentManager {
             "compatible" => Some(Self::Compatible),
             "efficient" => Some(Self::Efficient),
             "disabled" => Some(Self::Disabled),
+            "embedded" => Some(Self::Embedded(DEFAULT_EMBED_LIMIT_MB * BYTES_PER_MB)),
+            "hardlink" => Some(Self::Hardlink),
+            "symlink" => Some(Self::Symlink),
+            "reflink" => Some(Self::Reflink),
             _ => None,
         }
     }
@@ -43,6 +153,37 @@ impl AttachmentManager {
         message: &Message,
         attachment: &'a mut Attachment,
         config: &Config,
+    ) -> Option<()> {
+        self.copy_attachment(
+            attachment,
+            message.chat_id,
+            message.date(&config.offset).ok(),
+            config,
+        )
+    }
+
+    /// Handle a chat's group photo, copying and converting if requested
+    ///
+    /// If copied, update attachment's `copied_path`
+    pub fn handle_group_photo<'a>(
+        &'a self,
+        chatroom: &Chat,
+        attachment: &'a mut Attachment,
+        config: &Config,
+    ) -> Option<()> {
+        self.copy_attachment(attachment, Some(chatroom.rowid), None, config)
+    }
+
+    /// Copy an attachment into the attachment directory of the conversation `chat_id`
+    ///
+    /// The copy's modification time is set to the message's `date` if provided, otherwise it
+    /// is preserved from the original file
+    fn copy_attachment(
+        &self,
+        attachment: &mut Attachment,
+        chat_id: Option<i32>,
+        date: Option<DateTime<Local>>,
+        config: &Config,
     ) -> Option<()> {
         // Resolve the path to the attachment
         let attachment_path = attachment.resolved_attachment_path(
@@ -60,53 +201,183 @@ impl AttachmentManager {
                 return None;
             }
 
-            // Create a path to copy the file to
-            let mut to = config.attachment_path();
+            // When deduplicating, point to an existing copy of the same file if there is one
+            let hash = if config.options.dedupe {
+                let hash = hash_file(from);
+                if let Some(path) = hash
+                    .as_deref()
+                    .and_then(|hash| config.attachment_store.find(hash, attachment.is_sticker))
+                {
+                    attachment.copied_path = Some(path);
+                    return Some(());
+                }
+                hash
+            } else {
+                None
+            };
 
-            // Add the subdirectory
-            let sub_dir = config.conversation_attachment_path(message.chat_id);
-            to.push(sub_dir);
+            // Deduplicated files are shared between conversations, so they do not go in a conversation's subdirectory
+            let sub_dir = config.conversation_attachment_path(chat_id);
+            let mut dir = config.attachment_path();
+            dir.push(match hash {
+                Some(_) => DEDUPED_DIR,
+                None => &sub_dir,
+            });
 
-            // Add a random filename
-            to.push(Uuid::new_v4().to_string());
+            // Create a path to copy the file to
+            let mut to = match (&config.options.attachment_names, &hash) {
+                // Deduplicated files are named by their contents unless original names are requested
+                (AttachmentNames::Random, Some(hash)) => dir.join(hash),
+                (names, _) => {
+                    let date = date
+                        .as_ref()
+                        .map(|date| config.timezone(chat_id).convert(date));
+                    let stem = names.stem(attachment.filename(), date.as_ref());
+                    match names {
+                        AttachmentNames::Random => dir.join(stem),
+                        // Reserve the name with the extension the copy will have once converted
+                        _ => config.attachment_store.reserve(
+                            &dir,
+                            &stem,
+                            self.output_extension(from, attachment, config)?,
+                        ),
+                    }
+                }
+            };
 
             // Set the new file's extension to the original one
             to.set_extension(attachment.extension()?);
 
+            // Hard links and symlinks share the original file, so its metadata must not change
+            let mut shares_original = false;
             match self {
-                AttachmentManager::Compatible => match &config.converter {
-                    Some(converter) => {
-                        Self::copy_convert(from, &mut to, converter, attachment.is_sticker);
+                AttachmentManager::Compatible | AttachmentManager::Embedded(_) => {
+                    match &config.converter {
+                        Some(converter) => {
+                            Self::copy_convert(from, &mut to, converter, attachment.is_sticker);
+                        }
+                        None => Self::copy_raw(from, &to),
                     }
-                    None => Self::copy_raw(from, &to),
-                },
+                }
                 AttachmentManager::Efficient => Self::copy_raw(from, &to),
+                AttachmentManager::Hardlink
+                | AttachmentManager::Symlink
+                | AttachmentManager::Reflink => {
+                    shares_original =
+                        self.link_or_copy(from, &to) && !matches!(self, AttachmentManager::Reflink);
+                }
                 AttachmentManager::Disabled => unreachable!(),
             };
 
             // Update file metadata
-            if let Ok(metadata) = metadata(from) {
-                let mtime = match &message.date(&config.offset) {
-                    Ok(date) => {
-                        FileTime::from_unix_time(date.timestamp(), date.timestamp_subsec_nanos())
-                    }
-                    Err(_) => FileTime::from_last_modification_time(&metadata),
-                };
+            if !shares_original {
+                if let Ok(metadata) = metadata(from) {
+                    let mtime = date
+                        .map(|date| {
+                            FileTime::from_unix_time(
+                                date.timestamp(),
+                                date.timestamp_subsec_nanos(),
+                            )
+                        })
+                        .unwrap_or_else(|| FileTime::from_last_modification_time(&metadata));
 
-                let atime = FileTime::from_last_access_time(&metadata);
+                    let atime = FileTime::from_last_access_time(&metadata);
 
-                if let Err(why) = set_file_times(&to, atime, mtime) {
-                    eprintln!("Unable to update {to:?} metadata: {why}");
+                    if let Err(why) = set_file_times(&to, atime, mtime) {
+                        eprintln!("Unable to update {to:?} metadata: {why}");
+                    }
                 }
             }
+            if let Some(hash) = hash {
+                config
+                    .attachment_store
+                    .insert(hash, attachment.is_sticker, to.clone());
+            }
             attachment.copied_path = Some(to);
         }
         Some(())
     }
 
-    /// Copy a file without altering it
-    fn copy_raw(from: &Path, to: &Path) {
-        // Ensure the directory tree exists
+    /// Read a copied attachment into a `data:` URI, removing the copy, if attachments are embedded
+    ///
+    /// Returns `None` if the attachment was not copied or is larger than the embed limit; the copy
+    /// is kept so it can be linked to instead.
+    pub fn embed(&self, attachment: &mut Attachment) -> Option<String> {
+        let AttachmentManager::Embedded(limit) = self else {
+            return None;
+        };
+        let path = attachment.copied_path.as_ref()?;
+        if metadata(path).ok()?.len() > *limit {
+            return None;
+        }
+
+        let data = match read(path) {
+            Ok(data) => data,
+            Err(why) => {
+                eprintln!("Unable to read {path:?}: {why}");
+                return None;
+            }
+        };
+        let uri = format!(
+            "data:{};base64,{}",
+            Self::embedded_mime_type(path, attachment),
+            encode_base64(&data)
+        );
+
+        if let Err(why) = remove_file(path) {
+            eprintln!("Unable to remove {path:?}: {why}");
+        }
+        attachment.copied_path = None;
+        Some(uri)
+    }
+
+    /// Determine the MIME type of a copied file, which changes if it was converted
+    fn embedded_mime_type<'a>(path: &Path, attachment: &'a Attachment) -> &'a str {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("gif") => "image/gif",
+            _ => attachment
+                .mime_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+        }
+    }
+
+    /// Link `to` to `from` using the current mode, without copying any data
+    ///
+    /// Only [`AttachmentManager::Hardlink`], [`AttachmentManager::Symlink`], and
+    /// [`AttachmentManager::Reflink`] can link files.
+    pub fn link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match self {
+            AttachmentManager::Hardlink => hard_link(from, to),
+            AttachmentManager::Symlink => symlink(from, to),
+            AttachmentManager::Reflink => reflink(from, to),
+            _ => Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+    }
+
+    /// Link a file, falling back to a full copy if it cannot be linked, i.e. across file systems
+    ///
+    /// Returns `true` if the file was linked.
+    fn link_or_copy(&self, from: &Path, to: &Path) -> bool {
+        Self::create_parent(to);
+        match self.link(from, to) {
+            Ok(()) => true,
+            Err(_) => {
+                Self::copy_raw(from, to);
+                false
+            }
+        }
+    }
+
+    /// Ensure the directory a file will be written to exists
+    fn create_parent(to: &Path) {
         if let Some(folder) = to.parent() {
             if !folder.exists() {
                 if let Err(why) = create_dir_all(folder) {
@@ -114,67 +385,271 @@ impl AttachmentManager {
                 }
             }
         }
+    }
+
+    /// Copy a file without altering it
+    fn copy_raw(from: &Path, to: &Path) {
+        // Ensure the directory tree exists
+        Self::create_parent(to);
         if let Err(why) = copy(from, to) {
             eprintln!("Unable to copy {from:?} to {to:?}: {why}");
         };
     }
 
-    /// Copy a file, converting if possible
+    /// Determine the extension a copy will have, which changes if it is converted
+    fn output_extension<'b>(
+        &self,
+        from: &Path,
+        attachment: &'b Attachment,
+        config: &Config,
+    ) -> Option<&'b str> {
+        let converts = matches!(
+            self,
+            AttachmentManager::Compatible | AttachmentManager::Embedded(_)
+        ) && config.converter.is_some();
+        match Self::converted_type(from, attachment.is_sticker) {
+            Some(output_type) if converts => Some(output_type.to_str()),
+            _ => attachment.extension(),
+        }
+    }
+
+    /// Determine the type a file converts to, if it converts at all
     ///
     /// - Sticker `HEIC` files convert to `PNG`
     /// - Sticker `HEICS` files convert to `GIF`
     /// - Attachment `HEIC` files convert to `JPEG`
-    /// - Other files are copied with their original formats
-    fn copy_convert(from: &Path, to: &mut PathBuf, converter: &Converter, is_sticker: bool) {
-        let original_extension = from.extension().unwrap_or_default();
-
-        // Handle sticker attachments
-        if is_sticker {
-            // Determine the output type of the sticker
-            let output_type: Option<ImageType> = match original_extension.to_str() {
-                // Normal stickers get converted to png
-                Some("heic" | "HEIC") => Some(ImageType::Png),
-                // Animated stickers get converted to gif
-                Some("heics" | "HEICS") => Some(ImageType::Gif),
-                _ => None,
-            };
+    /// - Other files keep their original formats
+    fn converted_type(from: &Path, is_sticker: bool) -> Option<ImageType> {
+        match (is_sticker, from.extension()?.to_str()?) {
+            // Normal stickers get converted to png
+            (true, "heic" | "HEIC") => Some(ImageType::Png),
+            // Animated stickers get converted to gif
+            (true, "heics" | "HEICS") => Some(ImageType::Gif),
+            // Normal attachments always get converted to jpeg
+            (false, "heic" | "HEIC") => Some(ImageType::Jpeg),
+            _ => None,
+        }
+    }
 
-            match output_type {
-                Some(output_type) => {
-                    to.set_extension(output_type.to_str());
-                    if convert_heic(from, to, converter, &output_type).is_none() {
-                        eprintln!("Unable to convert {from:?}");
-                    }
+    /// Copy a file, converting if possible
+    fn copy_convert(from: &Path, to: &mut PathBuf, converter: &Converter, is_sticker: bool) {
+        match Self::converted_type(from, is_sticker) {
+            Some(output_type) => {
+                // Update extension for conversion
+                to.set_extension(output_type.to_str());
+                if convert_heic(from, to, converter, &output_type).is_none() {
+                    eprintln!("Unable to convert {from:?}");
                 }
-                None => Self::copy_raw(from, to),
-            }
-        }
-        // Normal attachments always get converted to jpeg
-        else if original_extension == "heic" || original_extension == "HEIC" {
-            let output_type = ImageType::Jpeg;
-            // Update extension for conversion
-            to.set_extension(output_type.to_str());
-            if convert_heic(from, to, converter, &output_type).is_none() {
-                eprintln!("Unable to convert {from:?}");
             }
-        } else {
-            Self::copy_raw(from, to);
+            None => Self::copy_raw(from, to),
         }
     }
 }
 
-impl Default for AttachmentManager {
-    fn default() -> Self {
-        Self::Disabled
-    }
-}
-
 impl Display for AttachmentManager {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AttachmentManager::Disabled => write!(fmt, "disabled"),
             AttachmentManager::Compatible => write!(fmt, "compatible"),
             AttachmentManager::Efficient => write!(fmt, "efficient"),
+            AttachmentManager::Embedded(_) => write!(fmt, "embedded"),
+            AttachmentManager::Hardlink => write!(fmt, "hardlink"),
+            AttachmentManager::Symlink => write!(fmt, "symlink"),
+            AttachmentManager::Reflink => write!(fmt, "reflink"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, remove_file, symlink_metadata, write},
+    };
+
+    use imessage_database::tables::attachment::Attachment;
+    use uuid::Uuid;
+
+    use crate::app::attachment_manager::{hash_file, AttachmentManager, AttachmentStore};
+
+    fn fake_attachment(bytes: &[u8]) -> Attachment {
+        let path = temp_dir().join(format!("{}.png", Uuid::new_v4()));
+        write(&path, bytes).unwrap();
+        Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.png".to_string()),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some("d.png".to_string()),
+            total_bytes: bytes.len() as u64,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: Some(path),
         }
     }
+
+    #[test]
+    fn can_embed_attachment() {
+        let mut attachment = fake_attachment(b"png");
+        let path = attachment.copied_path.clone().unwrap();
+
+        let actual = AttachmentManager::Embedded(3).embed(&mut attachment);
+
+        assert_eq!(actual, Some("data:image/png;base64,cG5n".to_string()));
+        assert_eq!(attachment.copied_path, None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn cant_embed_attachment_over_limit() {
+        let mut attachment = fake_attachment(b"png");
+        let path = attachment.copied_path.clone().unwrap();
+
+        let actual = AttachmentManager::Embedded(2).embed(&mut attachment);
+
+        assert_eq!(actual, None);
+        assert_eq!(attachment.copied_path, Some(path.clone()));
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn cant_embed_attachment_when_copying() {
+        let mut attachment = fake_attachment(b"png");
+        let path = attachment.copied_path.clone().unwrap();
+
+        let actual = AttachmentManager::Compatible.embed(&mut attachment);
+
+        assert_eq!(actual, None);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn can_hash_file() {
+        let attachment = fake_attachment(b"png");
+        let path = attachment.copied_path.unwrap();
+
+        assert_eq!(
+            hash_file(&path),
+            Some("9040a7d6cdf7a0d6cab1823831c6ceb7d01af97f".to_string())
+        );
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn cant_hash_missing_file() {
+        assert_eq!(
+            hash_file(&temp_dir().join(Uuid::new_v4().to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn can_find_stored_attachment() {
+        let attachment = fake_attachment(b"png");
+        let path = attachment.copied_path.unwrap();
+        let store = AttachmentStore::default();
+
+        assert_eq!(store.find("abc", false), None);
+        store.insert("abc".to_string(), false, path.clone());
+
+        assert_eq!(store.find("abc", false), Some(path.clone()));
+        assert_eq!(store.find("abc", true), None);
+        assert_eq!(store.find("abc", false), Some(path.clone()));
+        assert_eq!(store.duplicates(), 2);
+        assert_eq!(store.bytes_saved(), 6);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn can_reserve_unique_names() {
+        let dir = temp_dir().join(Uuid::new_v4().to_string());
+        let store = AttachmentStore::default();
+
+        assert_eq!(
+            store.reserve(&dir, "IMG_1234", "jpg"),
+            dir.join("IMG_1234.jpg")
+        );
+        assert_eq!(
+            store.reserve(&dir, "img_1234", "JPG"),
+            dir.join("img_1234 (1).JPG")
+        );
+        assert_eq!(
+            store.reserve(&dir, "IMG_1234", "jpg"),
+            dir.join("IMG_1234 (2).jpg")
+        );
+        assert_eq!(
+            store.reserve(&dir, "IMG_1234", "png"),
+            dir.join("IMG_1234.png")
+        );
+    }
+
+    #[test]
+    fn can_parse_link_modes_any_case() {
+        assert_eq!(
+            AttachmentManager::from_cli("HardLink"),
+            Some(AttachmentManager::Hardlink)
+        );
+        assert_eq!(
+            AttachmentManager::from_cli("symlink"),
+            Some(AttachmentManager::Symlink)
+        );
+        assert_eq!(
+            AttachmentManager::from_cli("REFLINK"),
+            Some(AttachmentManager::Reflink)
+        );
+        assert_eq!(AttachmentManager::Reflink.to_string(), "reflink");
+    }
+
+    #[test]
+    fn can_hardlink_attachment() {
+        let attachment = fake_attachment(b"png");
+        let from = attachment.copied_path.unwrap();
+        let to = temp_dir().join(Uuid::new_v4().to_string()).join("d.png");
+
+        assert!(AttachmentManager::Hardlink.link_or_copy(&from, &to));
+        assert_eq!(read(&to).unwrap(), b"png");
+        remove_file(from).unwrap();
+        // The link keeps the data after the original is removed
+        assert_eq!(read(&to).unwrap(), b"png");
+        remove_file(to).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn can_symlink_attachment() {
+        let attachment = fake_attachment(b"png");
+        let from = attachment.copied_path.unwrap();
+        let to = temp_dir().join(Uuid::new_v4().to_string()).join("d.png");
+
+        assert!(AttachmentManager::Symlink.link_or_copy(&from, &to));
+        assert!(symlink_metadata(&to).unwrap().is_symlink());
+        assert_eq!(read(&to).unwrap(), b"png");
+        remove_file(from).unwrap();
+        remove_file(to).unwrap();
+    }
+
+    #[test]
+    fn can_reflink_or_copy_attachment() {
+        let attachment = fake_attachment(b"png");
+        let from = attachment.copied_path.unwrap();
+        let to = temp_dir().join(Uuid::new_v4().to_string()).join("d.png");
+
+        // Whether the file is cloned depends on the file system, but it is always written
+        AttachmentManager::Reflink.link_or_copy(&from, &to);
+        assert!(!symlink_metadata(&to).unwrap().is_symlink());
+        assert_eq!(read(&to).unwrap(), b"png");
+        remove_file(from).unwrap();
+        remove_file(to).unwrap();
+    }
+
+    #[test]
+    fn cant_link_when_copying() {
+        let attachment = fake_attachment(b"png");
+        let from = attachment.copied_path.unwrap();
+        let to = temp_dir().join(Uuid::new_v4().to_string());
+
+        assert!(AttachmentManager::Efficient.link(&from, &to).is_err());
+        assert!(!to.exists());
+        remove_file(from).unwrap();
+    }
 }
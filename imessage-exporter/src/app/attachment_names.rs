@@ -0,0 +1,148 @@
+/*!
+ Contains logic for naming the files that attachments are copied to.
+*/
+
+use std::{fmt::Display, path::Path};
+
+use chrono::{DateTime, FixedOffset};
+use uuid::Uuid;
+
+use crate::app::sanitizers::sanitize_filename;
+
+/// Name used when an attachment's original name is empty once sanitized
+const UNNAMED: &str = "attachment";
+/// Longest original name kept, in bytes, leaving room for the date prefix, collision suffix, and extension
+const MAX_NAME_LENGTH: usize = 200;
+/// Format of the date prefix; colons are not allowed in filenames, so the time is separated with dots
+const DATE_PREFIX_FORMAT: &str = "%Y-%m-%d %H.%M.%S";
+
+/// Represents how copied attachments are named
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum AttachmentNames {
+    /// Name each copy with a random UUID
+    #[default]
+    Random,
+    /// Name each copy after the attachment's original filename
+    Original,
+    /// Name each copy after the attachment's original filename, prefixed with the date the message was sent
+    Dated,
+}
+
+impl AttachmentNames {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(names: &str) -> Option<Self> {
+        match names.to_lowercase().as_str() {
+            "random" => Some(Self::Random),
+            "original" => Some(Self::Original),
+            "dated" => Some(Self::Dated),
+            _ => None,
+        }
+    }
+
+    /// Build the name of a copy, without an extension or collision suffix
+    ///
+    /// Attachments without a date, like group photos, are not prefixed.
+    pub fn stem(&self, filename: &str, date: Option<&DateTime<FixedOffset>>) -> String {
+        match (self, date) {
+            (AttachmentNames::Random, _) => Uuid::new_v4().to_string(),
+            (AttachmentNames::Dated, Some(date)) => format!(
+                "{} {}",
+                date.format(DATE_PREFIX_FORMAT),
+                original_stem(filename)
+            ),
+            (AttachmentNames::Original | AttachmentNames::Dated, _) => original_stem(filename),
+        }
+    }
+}
+
+/// Get the sanitized name of the original file, without its directory or extension
+fn original_stem(filename: &str) -> String {
+    let stem = Path::new(filename)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let sanitized: String = sanitize_filename(&stem)
+        .chars()
+        .filter(|letter| !letter.is_control())
+        .collect();
+
+    // Leading dots hide files on most systems
+    let trimmed = sanitized.trim().trim_start_matches('.');
+    if trimmed.is_empty() {
+        return UNNAMED.to_string();
+    }
+
+    let mut end = trimmed.len().min(MAX_NAME_LENGTH);
+    while !trimmed.is_char_boundary(end) {
+        end -= 1;
+    }
+    trimmed[..end].to_string()
+}
+
+impl Display for AttachmentNames {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachmentNames::Random => write!(fmt, "random"),
+            AttachmentNames::Original => write!(fmt, "original"),
+            AttachmentNames::Dated => write!(fmt, "dated"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::app::attachment_names::{original_stem, AttachmentNames};
+
+    #[test]
+    fn can_parse_names_any_case() {
+        assert_eq!(
+            AttachmentNames::from_cli("original"),
+            Some(AttachmentNames::Original)
+        );
+        assert_eq!(
+            AttachmentNames::from_cli("DaTeD"),
+            Some(AttachmentNames::Dated)
+        );
+        assert_eq!(
+            AttachmentNames::from_cli("Random"),
+            Some(AttachmentNames::Random)
+        );
+        assert_eq!(AttachmentNames::from_cli("hash"), None);
+    }
+
+    #[test]
+    fn can_get_original_stem() {
+        assert_eq!(original_stem("IMG_1234.HEIC"), "IMG_1234");
+        assert_eq!(
+            original_stem("~/Library/Messages/Attachments/a/b/Trip: Day 1.mov"),
+            "Trip_ Day 1"
+        );
+        assert_eq!(original_stem(".hidden.txt"), "hidden");
+        assert_eq!(original_stem(".."), "attachment");
+    }
+
+    #[test]
+    fn can_truncate_original_stem() {
+        let stem = original_stem(&format!("{}.jpg", "é".repeat(150)));
+        assert_eq!(stem, "é".repeat(100));
+    }
+
+    #[test]
+    fn can_build_dated_stem() {
+        let date = DateTime::parse_from_rfc3339("2022-05-17T17:29:42-07:00").unwrap();
+        assert_eq!(
+            AttachmentNames::Dated.stem("IMG_1234.jpg", Some(&date)),
+            "2022-05-17 17.29.42 IMG_1234"
+        );
+        assert_eq!(
+            AttachmentNames::Dated.stem("IMG_1234.jpg", None),
+            "IMG_1234"
+        );
+        assert_eq!(
+            AttachmentNames::Original.stem("IMG_1234.jpg", Some(&date)),
+            "IMG_1234"
+        );
+    }
+}
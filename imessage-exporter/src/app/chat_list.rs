@@ -0,0 +1,393 @@
+/*!
+ Contains logic for listing the conversations stored in a database.
+*/
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Serialize;
+
+use imessage_database::{
+    tables::chat::ChatActivity,
+    util::dates::{get_local_time, TIMESTAMP_FACTOR},
+};
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+/// Represents the format a conversation listing is written in
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ListFormat {
+    /// Human-readable table
+    #[default]
+    Table,
+    /// Machine-readable JSON
+    Json,
+}
+
+impl ListFormat {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ListFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListFormat::Table => write!(fmt, "table"),
+            ListFormat::Json => write!(fmt, "json"),
+        }
+    }
+}
+
+/// Describes a single deduplicated conversation
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ChatListing {
+    /// The lowest `chat` table ID in the conversation, which is stable between runs
+    pub id: i32,
+    /// Every `chat` table ID that was deduplicated into this conversation
+    pub chat_ids: Vec<i32>,
+    /// The name of the conversation, if one was set
+    pub name: Option<String>,
+    /// The resolved names of the other participants
+    pub participants: Vec<String>,
+    /// The services used to send messages in the conversation
+    pub services: Vec<String>,
+    /// The number of messages in the conversation
+    pub messages: u64,
+    /// The number of attachments sent in the conversation
+    pub attachments: u64,
+    /// The date of the earliest message, in the configured time zone
+    pub first_message: Option<DateTime<FixedOffset>>,
+    /// The date of the latest message, in the configured time zone
+    pub last_message: Option<DateTime<FixedOffset>>,
+}
+
+impl ChatListing {
+    /// Build a listing for every deduplicated conversation, sorted by ID
+    pub fn build_all(config: &Config) -> Result<Vec<Self>, RuntimeError> {
+        let activity = ChatActivity::get_all(&config.db).map_err(RuntimeError::DatabaseError)?;
+
+        // Group each chat under its deduplicated ID; chats that were not deduplicated stand alone
+        let mut groups: BTreeMap<(bool, i32), BTreeSet<i32>> = BTreeMap::new();
+        for chat_id in config.chatrooms.keys() {
+            let key = match config.real_chatrooms.get(chat_id) {
+                Some(real_id) => (true, *real_id),
+                None => (false, *chat_id),
+            };
+            groups.entry(key).or_default().insert(*chat_id);
+        }
+
+        let mut listings: Vec<Self> = groups
+            .into_values()
+            .filter_map(|chat_ids| {
+                let id = *chat_ids.first()?;
+                Some(ChatListing::build(config, id, chat_ids, &activity))
+            })
+            .collect();
+        listings.sort_by_key(|listing| listing.id);
+        Ok(listings)
+    }
+
+    /// Build a listing for a single group of deduplicated chats
+    fn build(
+        config: &Config,
+        id: i32,
+        chat_ids: BTreeSet<i32>,
+        activity: &HashMap<i32, ChatActivity>,
+    ) -> Self {
+        let mut name = None;
+        let mut participants = BTreeSet::new();
+        let mut services = BTreeSet::new();
+        let mut totals = ChatActivity::default();
+
+        for chat_id in &chat_ids {
+            if let Some(chatroom) = config.chatrooms.get(chat_id) {
+                if name.is_none() {
+                    name = chatroom.display_name().map(String::from);
+                }
+                if let Some(service) = &chatroom.service_name {
+                    services.insert(service.to_string());
+                }
+            }
+            if let Some(handles) = config.chatroom_participants.get(chat_id) {
+                participants.extend(handles.iter().copied());
+            }
+            if let Some(chat_activity) = activity.get(chat_id) {
+                totals.merge(chat_activity);
+            }
+        }
+
+        ChatListing {
+            id,
+            chat_ids: chat_ids.into_iter().collect(),
+            name,
+            participants: participants
+                .into_iter()
+                .map(|handle_id| config.who(Some(handle_id), false).to_string())
+                .collect(),
+            services: services.into_iter().collect(),
+            messages: totals.messages,
+            attachments: totals.attachments,
+            first_message: totals
+                .first_message
+                .and_then(|date| local_date(date, config.offset))
+                .map(|date| config.options.timezone.convert(&date)),
+            last_message: totals
+                .last_message
+                .and_then(|date| local_date(date, config.offset))
+                .map(|date| config.options.timezone.convert(&date)),
+        }
+    }
+}
+
+/// Convert a `date` column value to a local date
+fn local_date(date: i64, offset: i64) -> Option<DateTime<Local>> {
+    // Older databases store dates in seconds instead of nanoseconds
+    let stamp = if date < TIMESTAMP_FACTOR {
+        date * TIMESTAMP_FACTOR
+    } else {
+        date
+    };
+    get_local_time(&stamp, &offset).ok()
+}
+
+/// Render conversation listings as an aligned plain text table
+pub fn render_table(listings: &[ChatListing], config: &Config) -> String {
+    let header = [
+        "ID",
+        "Name",
+        "Participants",
+        "Service",
+        "Messages",
+        "Attachments",
+        "First Message",
+        "Last Message",
+    ];
+    let format_date = |date: &Option<DateTime<FixedOffset>>, id: i32| match date {
+        Some(date) => config.format_date(&Ok(date.with_timezone(&Local)), Some(id)),
+        None => String::new(),
+    };
+
+    let rows: Vec<[String; 8]> = listings
+        .iter()
+        .map(|listing| {
+            [
+                listing.id.to_string(),
+                listing.name.clone().unwrap_or_default(),
+                listing.participants.join(", "),
+                listing.services.join(", "),
+                listing.messages.to_string(),
+                listing.attachments.to_string(),
+                format_date(&listing.first_message, listing.id),
+                format_date(&listing.last_message, listing.id),
+            ]
+        })
+        .collect();
+
+    // Determine how wide each column needs to be
+    let mut widths = header.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out_s = String::new();
+    let mut push_row = |cells: &[&str]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        out_s.push_str(line.join("  ").trim_end());
+        out_s.push('\n');
+    };
+
+    push_row(&header);
+    let divider: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    push_row(&divider.iter().map(String::as_str).collect::<Vec<_>>());
+    for row in &rows {
+        push_row(&row.iter().map(String::as_str).collect::<Vec<_>>());
+    }
+    out_s
+}
+
+/// Render conversation listings as a JSON array
+pub fn render_json(listings: &[ChatListing]) -> Result<String, RuntimeError> {
+    serde_json::to_string_pretty(listings).map_err(RuntimeError::JsonError)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{
+            chat_list::{render_json, render_table, ChatListing, ListFormat},
+            mode::Mode,
+        },
+        exporters::html,
+        Config, Options,
+    };
+    use imessage_database::tables::{chat::Chat, table::get_connection};
+    use std::{
+        collections::{BTreeSet, HashMap},
+        env::set_var,
+    };
+
+    use chrono::{FixedOffset, TimeZone};
+
+    fn fake_options() -> Options {
+        Options {
+            mode: Mode::ListChats(ListFormat::Table),
+            ..html::tests::fake_options()
+        }
+    }
+
+    fn fake_chat(rowid: i32, display_name: Option<&str>, service: &str) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: format!("chat{rowid}"),
+            service_name: Some(service.to_string()),
+            display_name: display_name.map(String::from),
+            properties: None,
+        }
+    }
+
+    fn fake_app(options: Options) -> Config {
+        let connection = get_connection(&options.db_path).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            reactions: HashMap::new(),
+            options,
+            offset: 0,
+            db: connection,
+            converter: None,
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
+        }
+    }
+
+    fn fake_listing() -> ChatListing {
+        let date = FixedOffset::west_opt(7 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 5, 17, 17, 29, 42)
+            .unwrap();
+        ChatListing {
+            id: 1,
+            chat_ids: vec![1, 3],
+            name: Some("Friends".to_string()),
+            participants: vec!["Person 10".to_string(), "Person 11".to_string()],
+            services: vec!["iMessage".to_string()],
+            messages: 12,
+            attachments: 3,
+            first_message: Some(date),
+            last_message: None,
+        }
+    }
+
+    #[test]
+    fn can_parse_list_format() {
+        assert_eq!(ListFormat::from_cli("table"), Some(ListFormat::Table));
+        assert_eq!(ListFormat::from_cli("JSON"), Some(ListFormat::Json));
+        assert_eq!(ListFormat::from_cli("csv"), None);
+    }
+
+    #[test]
+    fn can_build_listings_deduplicated() {
+        let mut app = fake_app(fake_options());
+
+        app.chatrooms.insert(1, fake_chat(1, None, "iMessage"));
+        app.chatrooms
+            .insert(2, fake_chat(2, Some("Friends"), "iMessage"));
+        app.chatrooms.insert(3, fake_chat(3, None, "SMS"));
+        app.chatrooms.insert(4, fake_chat(4, None, "iMessage"));
+
+        // Chats 1 and 3 share participants, chat 4 was never deduplicated
+        app.real_chatrooms.insert(1, 0);
+        app.real_chatrooms.insert(2, 1);
+        app.real_chatrooms.insert(3, 0);
+
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+        app.chatroom_participants.insert(1, BTreeSet::from([10]));
+        app.chatroom_participants
+            .insert(2, BTreeSet::from([10, 11]));
+        app.chatroom_participants.insert(3, BTreeSet::from([10]));
+
+        let listings = ChatListing::build_all(&app).unwrap();
+
+        let ids: Vec<i32> = listings.iter().map(|listing| listing.id).collect();
+        assert_eq!(ids, vec![1, 2, 4]);
+
+        assert_eq!(listings[0].chat_ids, vec![1, 3]);
+        assert_eq!(listings[0].name, None);
+        assert_eq!(listings[0].participants, vec!["Person 10"]);
+        assert_eq!(listings[0].services, vec!["SMS", "iMessage"]);
+
+        assert_eq!(listings[1].name, Some("Friends".to_string()));
+        assert_eq!(listings[1].participants, vec!["Person 10", "Person 11"]);
+
+        assert_eq!(listings[2].chat_ids, vec![4]);
+        assert!(listings[2].participants.is_empty());
+    }
+
+    #[test]
+    fn can_render_table() {
+        set_var("TZ", "PST");
+        let app = fake_app(fake_options());
+
+        let expected = concat!(
+            "ID  Name     Participants          Service   Messages  Attachments  First Message             Last Message\n",
+            "--  -------  --------------------  --------  --------  -----------  ------------------------  ------------\n",
+            "1   Friends  Person 10, Person 11  iMessage  12        3            May 17, 2022  5:29:42 PM\n",
+        );
+        assert_eq!(render_table(&[fake_listing()], &app), expected);
+    }
+
+    #[test]
+    fn can_render_table_empty() {
+        let app = fake_app(fake_options());
+
+        let expected = concat!(
+            "ID  Name  Participants  Service  Messages  Attachments  First Message  Last Message\n",
+            "--  ----  ------------  -------  --------  -----------  -------------  ------------\n",
+        );
+        assert_eq!(render_table(&[], &app), expected);
+    }
+
+    #[test]
+    fn can_render_json() {
+        let expected = r#"[
+  {
+    "id": 1,
+    "chat_ids": [
+      1,
+      3
+    ],
+    "name": "Friends",
+    "participants": [
+      "Person 10",
+      "Person 11"
+    ],
+    "services": [
+      "iMessage"
+    ],
+    "messages": 12,
+    "attachments": 3,
+    "first_message": "2022-05-17T17:29:42-07:00",
+    "last_message": null
+  }
+]"#;
+        assert_eq!(render_json(&[fake_listing()]).unwrap(), expected);
+    }
+}
@@ -0,0 +1,273 @@
+/*!
+ Contains logic for reading default option values from a TOML file.
+*/
+
+use std::{fs::read_to_string, path::PathBuf};
+
+use clap::ArgMatches;
+use serde::{Deserialize, Deserializer};
+
+use imessage_database::util::dirs::home;
+
+use crate::app::{
+    error::RuntimeError,
+    options::{
+        OPTION_ATTACHMENT_MANAGER, OPTION_ATTACHMENT_NAMES, OPTION_ATTACHMENT_ROOT,
+        OPTION_BYPASS_FREE_SPACE_CHECK, OPTION_CHAT_TIMEZONES, OPTION_CONFIG, OPTION_CONTEXT,
+        OPTION_CONVERSATIONS, OPTION_CUSTOM_CSS, OPTION_CUSTOM_NAME, OPTION_DATE_FORMAT,
+        OPTION_DB_PATH, OPTION_DEDUPE, OPTION_DISABLE_LAZY_LOADING, OPTION_EMBED_LIMIT, OPTION_EML,
+        OPTION_END_DATE, OPTION_EXCLUSIVE_END, OPTION_EXPORT_PATH, OPTION_EXPORT_TYPE,
+        OPTION_INDEX_PATH, OPTION_ISO_TIMESTAMPS, OPTION_LIMIT, OPTION_LINK_CSS,
+        OPTION_LIST_FORMAT, OPTION_LOCALE, OPTION_PAGINATE, OPTION_PLATFORM, OPTION_REBUILD_INDEX,
+        OPTION_START_DATE, OPTION_STATS_FORMAT, OPTION_TEMPLATES, OPTION_THEME, OPTION_TIMEZONE,
+    },
+};
+
+/// Default location of the config file, relative to the user's home directory
+pub const DEFAULT_CONFIG_PATH: &str = ".config/imessage-exporter/config.toml";
+
+/// Option values read from a config file
+///
+/// Keys match the long command line flags, i.e. `db-path` or `copy-method`, except for the
+/// `list-chats` and `stats` output formats, which are set with `list-format` and `stats-format`.
+/// Only the search query cannot be set here, since it is positional and differs for every search.
+/// Values set on the command line take precedence over values set here.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConfigFile {
+    pub db_path: Option<String>,
+    pub attachment_root: Option<String>,
+    pub copy_method: Option<String>,
+    pub format: Option<String>,
+    pub export_path: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub exclusive_end: Option<bool>,
+    pub no_lazy: Option<bool>,
+    pub custom_name: Option<String>,
+    pub platform: Option<String>,
+    pub ignore_disk_warning: Option<bool>,
+    pub timezone: Option<String>,
+    pub chat_timezones: Option<String>,
+    pub iso_timestamps: Option<bool>,
+    pub locale: Option<String>,
+    pub date_format: Option<String>,
+    pub list_format: Option<String>,
+    pub stats_format: Option<String>,
+    #[serde(default, deserialize_with = "string_or_integer")]
+    pub limit: Option<String>,
+    #[serde(default, deserialize_with = "string_or_integer")]
+    pub context: Option<String>,
+    pub index_path: Option<String>,
+    pub rebuild_index: Option<bool>,
+    pub paginate: Option<String>,
+    pub theme: Option<String>,
+    pub css: Option<String>,
+    pub link_css: Option<bool>,
+    pub templates: Option<String>,
+    #[serde(default, deserialize_with = "string_or_integer")]
+    pub embed_limit: Option<String>,
+    pub conversations: Option<String>,
+    pub eml: Option<bool>,
+    pub dedupe: Option<bool>,
+    pub attachment_names: Option<String>,
+}
+
+impl ConfigFile {
+    /// Load the config file selected on the command line
+    ///
+    /// If `--config` was not passed, the file at [`DEFAULT_CONFIG_PATH`] is used if it exists.
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let user_path = args
+            .subcommand()
+            .and_then(|(_, args)| args.try_get_one::<String>(OPTION_CONFIG).ok().flatten());
+
+        match user_path {
+            Some(path) => Self::load(&PathBuf::from(path)),
+            None => {
+                let default_path = PathBuf::from(home()).join(DEFAULT_CONFIG_PATH);
+                if default_path.exists() {
+                    Self::load(&default_path)
+                } else {
+                    Ok(Self::default())
+                }
+            }
+        }
+    }
+
+    /// Read and parse a config file
+    pub fn load(path: &PathBuf) -> Result<Self, RuntimeError> {
+        let contents = read_to_string(path).map_err(|why| {
+            RuntimeError::InvalidOptions(format!("Unable to read config file {path:?}: {why}"))
+        })?;
+        Self::parse(&contents).map_err(|why| {
+            RuntimeError::InvalidOptions(format!("Config file {path:?} is invalid: {why}"))
+        })
+    }
+
+    /// Parse the contents of a config file
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        let mut file: Self = toml::from_str(contents)?;
+
+        // The shell does not expand `~` in config files, so do it here
+        for path in [
+            &mut file.db_path,
+            &mut file.attachment_root,
+            &mut file.export_path,
+            &mut file.index_path,
+            &mut file.css,
+            &mut file.templates,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(rest) = path.strip_prefix('~') {
+                *path = format!("{}{rest}", home());
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Get the value for an argument, given its ID
+    pub fn value(&self, id: &str) -> Option<&String> {
+        match id {
+            OPTION_DB_PATH => self.db_path.as_ref(),
+            OPTION_ATTACHMENT_ROOT => self.attachment_root.as_ref(),
+            OPTION_ATTACHMENT_MANAGER => self.copy_method.as_ref(),
+            OPTION_EXPORT_TYPE => self.format.as_ref(),
+            OPTION_EXPORT_PATH => self.export_path.as_ref(),
+            OPTION_START_DATE => self.start_date.as_ref(),
+            OPTION_END_DATE => self.end_date.as_ref(),
+            OPTION_CUSTOM_NAME => self.custom_name.as_ref(),
+            OPTION_PLATFORM => self.platform.as_ref(),
+            OPTION_TIMEZONE => self.timezone.as_ref(),
+            OPTION_CHAT_TIMEZONES => self.chat_timezones.as_ref(),
+            OPTION_LOCALE => self.locale.as_ref(),
+            OPTION_DATE_FORMAT => self.date_format.as_ref(),
+            OPTION_LIST_FORMAT => self.list_format.as_ref(),
+            OPTION_STATS_FORMAT => self.stats_format.as_ref(),
+            OPTION_LIMIT => self.limit.as_ref(),
+            OPTION_CONTEXT => self.context.as_ref(),
+            OPTION_INDEX_PATH => self.index_path.as_ref(),
+            OPTION_PAGINATE => self.paginate.as_ref(),
+            OPTION_THEME => self.theme.as_ref(),
+            OPTION_CUSTOM_CSS => self.css.as_ref(),
+            OPTION_TEMPLATES => self.templates.as_ref(),
+            OPTION_EMBED_LIMIT => self.embed_limit.as_ref(),
+            OPTION_CONVERSATIONS => self.conversations.as_ref(),
+            OPTION_ATTACHMENT_NAMES => self.attachment_names.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Determine if a flag is set, given its ID
+    pub fn flag(&self, id: &str) -> bool {
+        match id {
+            OPTION_EXCLUSIVE_END => self.exclusive_end,
+            OPTION_DISABLE_LAZY_LOADING => self.no_lazy,
+            OPTION_BYPASS_FREE_SPACE_CHECK => self.ignore_disk_warning,
+            OPTION_ISO_TIMESTAMPS => self.iso_timestamps,
+            OPTION_LINK_CSS => self.link_css,
+            OPTION_EML => self.eml,
+            OPTION_DEDUPE => self.dedupe,
+            OPTION_REBUILD_INDEX => self.rebuild_index,
+            _ => None,
+        }
+        .unwrap_or(false)
+    }
+}
+
+/// A value that can be written as a string or, for counts and sizes, a bare integer
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrInteger {
+    String(String),
+    Integer(u64),
+}
+
+/// Read a value that the command line parses from text, accepting integers as well as strings
+fn string_or_integer<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        Option::<StringOrInteger>::deserialize(deserializer)?.map(|value| match value {
+            StringOrInteger::String(text) => text,
+            StringOrInteger::Integer(number) => number.to_string(),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use imessage_database::util::dirs::home;
+
+    use crate::app::{
+        config_file::ConfigFile,
+        options::{
+            OPTION_ATTACHMENT_MANAGER, OPTION_CONTEXT, OPTION_DB_PATH, OPTION_DISABLE_LAZY_LOADING,
+            OPTION_EMBED_LIMIT, OPTION_EXPORT_TYPE, OPTION_LIMIT, OPTION_REBUILD_INDEX,
+        },
+    };
+
+    #[test]
+    fn can_parse_empty() {
+        assert_eq!(ConfigFile::parse("").unwrap(), ConfigFile::default());
+    }
+
+    #[test]
+    fn can_parse_values() {
+        let file =
+            ConfigFile::parse("format = \"html\"\ncopy-method = \"efficient\"\nno-lazy = true\n")
+                .unwrap();
+
+        assert_eq!(file.value(OPTION_EXPORT_TYPE), Some(&"html".to_string()));
+        assert_eq!(
+            file.value(OPTION_ATTACHMENT_MANAGER),
+            Some(&"efficient".to_string())
+        );
+        assert!(file.flag(OPTION_DISABLE_LAZY_LOADING));
+        assert_eq!(file.value(OPTION_DB_PATH), None);
+    }
+
+    #[test]
+    fn can_parse_integers() {
+        let file =
+            ConfigFile::parse("embed-limit = 1048576\nlimit = 5\ncontext = \"2\"\n").unwrap();
+
+        assert_eq!(file.value(OPTION_EMBED_LIMIT), Some(&"1048576".to_string()));
+        assert_eq!(file.value(OPTION_LIMIT), Some(&"5".to_string()));
+        assert_eq!(file.value(OPTION_CONTEXT), Some(&"2".to_string()));
+    }
+
+    #[test]
+    fn can_parse_search_values() {
+        let file = ConfigFile::parse("rebuild-index = true").unwrap();
+
+        assert!(file.flag(OPTION_REBUILD_INDEX));
+        assert_eq!(file.value(OPTION_LIMIT), None);
+    }
+
+    #[test]
+    fn cant_parse_negative_integer() {
+        assert!(ConfigFile::parse("embed-limit = -1").is_err());
+    }
+
+    #[test]
+    fn can_expand_home() {
+        let file = ConfigFile::parse("db-path = \"~/backup/chat.db\"").unwrap();
+
+        assert_eq!(file.db_path, Some(format!("{}/backup/chat.db", home())));
+    }
+
+    #[test]
+    fn cant_parse_unknown_key() {
+        assert!(ConfigFile::parse("fromat = \"html\"").is_err());
+    }
+
+    #[test]
+    fn cant_parse_wrong_type() {
+        assert!(ConfigFile::parse("no-lazy = \"yes\"").is_err());
+    }
+}
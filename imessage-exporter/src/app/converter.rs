@@ -47,7 +47,7 @@ impl Converter {
 /// Determine if a shell program exists on the system
 fn exists(name: &str) -> bool {
     if let Ok(process) = Command::new("type")
-        .args(&vec![name])
+        .args(vec![name])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .stdin(Stdio::null())
@@ -95,7 +95,7 @@ pub fn convert_heic(
         Converter::Sips => {
             // Build the command
             match Command::new("sips")
-                .args(&vec![
+                .args(vec![
                     "-s",
                     "format",
                     output_image_type.to_str(),
@@ -124,7 +124,7 @@ pub fn convert_heic(
         Converter::Imagemagick => {
             // Build the command
             match Command::new("convert")
-                .args(&vec![from_path, to_path])
+                .args(vec![from_path, to_path])
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .stdin(Stdio::null())
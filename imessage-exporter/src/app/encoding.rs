@@ -0,0 +1,53 @@
+/*!
+ Contains helpers for encoding binary data as text.
+*/
+
+/// Characters used by the standard base64 alphabet, defined in [RFC 4648](https://www.rfc-editor.org/rfc/rfc4648#section-4)
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as standard, padded base64
+pub fn encode_base64(data: &[u8]) -> String {
+    let mut out_s = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, byte)| {
+            group | (u32::from(*byte) << (16 - 8 * idx))
+        });
+
+        // Each group of 3 bytes becomes 4 characters, padded if the chunk is short
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                let index = (group >> (18 - 6 * idx)) & 0b11_1111;
+                out_s.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                out_s.push('=');
+            }
+        }
+    }
+
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::encoding::encode_base64;
+
+    #[test]
+    fn can_encode_empty() {
+        assert_eq!(encode_base64(b""), "");
+    }
+
+    #[test]
+    fn can_encode_padded() {
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn can_encode_binary() {
+        assert_eq!(encode_base64(&[0, 255, 254, 63]), "AP/+Pw==");
+    }
+}
@@ -0,0 +1,377 @@
+/*!
+ Contains a minimal EPUB 3 writer used to export conversations as e-books.
+
+ A book is a ZIP archive of XHTML chapters, images, a table of contents, and a package document that
+ lists them. Each chapter is written to the archive as soon as the next one starts, so a book only
+ keeps a single chapter in memory.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::read,
+    mem::take,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use uuid::Uuid;
+
+use crate::app::zip::ZipArchive;
+
+/// Directory in the archive that holds the book's content
+const CONTENT_DIR: &str = "OEBPS";
+/// Points reading systems to the package document
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+</rootfiles>
+</container>
+"#;
+
+/// Escape text so it can be used in XHTML, removing characters XML does not allow
+pub fn escape_xml(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out_s.push_str("&amp;"),
+            '<' => out_s.push_str("&lt;"),
+            '>' => out_s.push_str("&gt;"),
+            '"' => out_s.push_str("&quot;"),
+            '\'' => out_s.push_str("&#39;"),
+            '\t' | '\n' | '\r' => out_s.push(c),
+            // XML 1.0 does not allow most control characters, even when escaped
+            c if c.is_control() || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+/// Determine the media type of an image from the first bytes of the file
+fn image_type(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(("image/jpeg", "jpg"))
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(("image/png", "png"))
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(("image/gif", "gif"))
+    } else if data.len() > 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some(("image/webp", "webp"))
+    } else {
+        None
+    }
+}
+
+/// An image that has been written to the book
+struct Image {
+    href: String,
+    media_type: &'static str,
+}
+
+/// An EPUB 3 book that is written to disk as chapters are completed
+pub struct EpubBook {
+    archive: ZipArchive,
+    /// Title of the book
+    title: String,
+    /// Language of the book, as a BCP 47 tag
+    language: String,
+    /// Names of the people who wrote the book's contents
+    creators: Vec<String>,
+    /// Markup shown on the first page, below the title
+    title_page: String,
+    /// Titles of the chapters that have been written, in order
+    chapters: Vec<String>,
+    /// Title and markup of the chapter being written
+    chapter: Option<(String, String)>,
+    /// Images that have been written to the book, or `None` if the file could not be used
+    images: HashMap<PathBuf, Option<usize>>,
+    /// Every image written to the book, in order
+    image_list: Vec<Image>,
+    /// Dates of the first and last content in the book
+    dates: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+}
+
+impl EpubBook {
+    /// Start a book that will be written to `path`
+    pub fn new(path: PathBuf, title: &str, language: &str, stylesheet: &str) -> Self {
+        let mut archive = ZipArchive::new(path);
+
+        // The media type must be the first file in the archive so the format can be identified
+        archive.add("mimetype", b"application/epub+zip");
+        archive.add("META-INF/container.xml", CONTAINER.as_bytes());
+        archive.add(&format!("{CONTENT_DIR}/style.css"), stylesheet.as_bytes());
+
+        Self {
+            archive,
+            title: title.to_string(),
+            language: language.to_string(),
+            creators: vec![],
+            title_page: String::new(),
+            chapters: vec![],
+            chapter: None,
+            images: HashMap::new(),
+            image_list: vec![],
+            dates: None,
+        }
+    }
+
+    /// The file the book is written to
+    pub fn path(&self) -> &Path {
+        self.archive.path()
+    }
+
+    /// List someone as a creator of the book
+    pub fn add_creator(&mut self, name: &str) {
+        if !self.creators.iter().any(|creator| creator == name) {
+            self.creators.push(name.to_string());
+        }
+    }
+
+    /// Set the markup shown on the first page, below the title
+    pub fn set_title_page(&mut self, body: String) {
+        self.title_page = body;
+    }
+
+    /// The title of the chapter being written
+    pub fn chapter(&self) -> Option<&str> {
+        self.chapter.as_ref().map(|(title, _)| title.as_str())
+    }
+
+    /// Add an image file to the book, returning the path to use in `img` tags
+    ///
+    /// Returns `None` if the file cannot be read or is not a format reading systems support.
+    pub fn add_image(&mut self, path: &Path) -> Option<String> {
+        if let Some(index) = self.images.get(path) {
+            return index.map(|index| self.image_list[index].href.clone());
+        }
+
+        let index = read(path).ok().and_then(|data| {
+            let (media_type, extension) = image_type(&data)?;
+            let href = format!("images/{}.{extension}", self.image_list.len() + 1);
+            self.archive.add(&format!("{CONTENT_DIR}/{href}"), &data);
+            self.image_list.push(Image { href, media_type });
+            Some(self.image_list.len() - 1)
+        });
+        self.images.insert(path.to_path_buf(), index);
+        index.map(|index| self.image_list[index].href.clone())
+    }
+
+    /// Add markup to the end of the chapter titled `title`
+    ///
+    /// If the chapter being written has a different title, it is completed and a new chapter is started.
+    pub fn add_to_chapter(
+        &mut self,
+        title: &str,
+        date: Option<&DateTime<FixedOffset>>,
+        body: &str,
+    ) {
+        if let Some(date) = date {
+            self.dates = match self.dates {
+                Some((first, last)) => Some((first.min(*date), last.max(*date))),
+                None => Some((*date, *date)),
+            };
+        }
+
+        if self.chapter() != Some(title) {
+            self.write_chapter();
+            self.chapter = Some((title.to_string(), String::new()));
+        }
+        if let Some((_, markup)) = &mut self.chapter {
+            markup.push_str(body);
+        }
+    }
+
+    /// Write the last chapter, the title page, the table of contents, and the package document
+    pub fn finish(&mut self) {
+        self.write_chapter();
+
+        let title = escape_xml(&self.title);
+        let title_page = format!("<h1>{title}</h1>\n{}", self.title_page);
+        self.archive.add(
+            &format!("{CONTENT_DIR}/title.xhtml"),
+            self.page(&title, &title_page).as_bytes(),
+        );
+
+        let mut contents = format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{title}</h1>\n<ol>\n<li><a href=\"title.xhtml\">{title}</a></li>\n"
+        );
+        for (idx, chapter) in self.chapters.iter().enumerate() {
+            contents.push_str(&format!(
+                "<li><a href=\"chapter-{}.xhtml\">{}</a></li>\n",
+                idx + 1,
+                escape_xml(chapter)
+            ));
+        }
+        contents.push_str("</ol>\n</nav>\n");
+        self.archive.add(
+            &format!("{CONTENT_DIR}/nav.xhtml"),
+            self.page(&title, &contents).as_bytes(),
+        );
+
+        self.archive.add(
+            &format!("{CONTENT_DIR}/content.opf"),
+            self.package().as_bytes(),
+        );
+        self.archive.finish();
+    }
+
+    /// Write the chapter being written to the archive
+    fn write_chapter(&mut self) {
+        if let Some((title, markup)) = take(&mut self.chapter) {
+            // Chapters that hold messages without a date are named after the book
+            let title = if title.is_empty() {
+                self.title.clone()
+            } else {
+                title
+            };
+            let body = format!("<h1>{}</h1>\n{markup}", escape_xml(&title));
+            let page = self.page(&escape_xml(&title), &body);
+            self.chapters.push(title);
+            self.archive.add(
+                &format!("{CONTENT_DIR}/chapter-{}.xhtml", self.chapters.len()),
+                page.as_bytes(),
+            );
+        }
+    }
+
+    /// Wrap markup in a complete XHTML document
+    fn page(&self, title: &str, body: &str) -> String {
+        let language = escape_xml(&self.language);
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{language}\" lang=\"{language}\">\n<head>\n<meta charset=\"UTF-8\"/>\n<title>{title}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n<body>\n{body}</body>\n</html>\n"
+        )
+    }
+
+    /// Build the package document, which holds the book's metadata and lists every file in reading order
+    fn package(&self) -> String {
+        let language = escape_xml(&self.language);
+        let mut metadata = format!(
+            "<dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n<dc:title>{}</dc:title>\n<dc:language>{language}</dc:language>\n",
+            Uuid::new_v4(),
+            escape_xml(&self.title)
+        );
+        for creator in &self.creators {
+            metadata.push_str(&format!(
+                "<dc:creator>{}</dc:creator>\n",
+                escape_xml(creator)
+            ));
+        }
+        if let Some((first, last)) = self.dates {
+            metadata.push_str(&format!(
+                "<dc:date>{}</dc:date>\n<dc:coverage>{}/{}</dc:coverage>\n",
+                first.format("%Y-%m-%d"),
+                first.format("%Y-%m-%d"),
+                last.format("%Y-%m-%d")
+            ));
+        }
+        metadata.push_str(&format!(
+            "<meta property=\"dcterms:modified\">{}</meta>\n",
+            Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+        ));
+
+        let mut manifest = String::from(
+            "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n<item id=\"title\" href=\"title.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+        );
+        let mut spine = String::from("<itemref idref=\"title\"/>\n");
+        for idx in 1..=self.chapters.len() {
+            manifest.push_str(&format!(
+                "<item id=\"chapter-{idx}\" href=\"chapter-{idx}.xhtml\" media-type=\"application/xhtml+xml\"/>\n"
+            ));
+            spine.push_str(&format!("<itemref idref=\"chapter-{idx}\"/>\n"));
+        }
+        for (idx, image) in self.image_list.iter().enumerate() {
+            manifest.push_str(&format!(
+                "<item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                idx + 1,
+                image.href,
+                image.media_type
+            ));
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{language}\">\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{metadata}</metadata>\n<manifest>\n{manifest}</manifest>\n<spine>\n{spine}</spine>\n</package>\n"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, remove_file, write},
+    };
+
+    use chrono::DateTime;
+    use uuid::Uuid;
+
+    use crate::app::epub_book::{escape_xml, image_type, EpubBook};
+
+    #[test]
+    fn can_escape_xml() {
+        assert_eq!(
+            escape_xml("<b>Tom & \"Jerry's\"</b>"),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&#39;s&quot;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn can_escape_xml_control_characters() {
+        assert_eq!(escape_xml("a\u{0}b\u{1b}c\nd"), "abc\nd");
+    }
+
+    #[test]
+    fn can_detect_image_types() {
+        assert_eq!(
+            image_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(("image/jpeg", "jpg"))
+        );
+        assert_eq!(
+            image_type(b"\x89PNG\r\n\x1a\n...."),
+            Some(("image/png", "png"))
+        );
+        assert_eq!(image_type(b"GIF89a"), Some(("image/gif", "gif")));
+        assert_eq!(
+            image_type(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(("image/webp", "webp"))
+        );
+        assert_eq!(image_type(b"ftypheic"), None);
+    }
+
+    #[test]
+    fn can_write_book() {
+        let path = temp_dir().join(format!("{}.epub", Uuid::new_v4()));
+        let image = temp_dir().join(format!("{}.png", Uuid::new_v4()));
+        write(&image, b"\x89PNG\r\n\x1a\n").unwrap();
+
+        let mut book = EpubBook::new(path.clone(), "Friends", "en", "body {}");
+        book.add_creator("Me");
+        book.add_creator("Me");
+        let first = DateTime::parse_from_rfc3339("2023-01-31T12:00:00-05:00").unwrap();
+        let last = DateTime::parse_from_rfc3339("2023-02-01T12:00:00-05:00").unwrap();
+        book.add_to_chapter("2023-01", Some(&first), "<p>Hi</p>\n");
+        assert_eq!(book.add_image(&image), Some("images/1.png".to_string()));
+        assert_eq!(book.add_image(&image), Some("images/1.png".to_string()));
+        assert_eq!(book.chapter(), Some("2023-01"));
+        book.add_to_chapter("2023-02", Some(&last), "<p>Bye</p>\n");
+        assert_eq!(book.chapter(), Some("2023-02"));
+        book.finish();
+
+        let data = read(&path).unwrap();
+        remove_file(&path).unwrap();
+        remove_file(&image).unwrap();
+
+        // The media type is stored first, at a fixed position
+        assert_eq!(&data[30..58], b"mimetypeapplication/epub+zip");
+
+        let data = String::from_utf8_lossy(&data);
+        assert!(data.contains("OEBPS/chapter-1.xhtml"));
+        assert!(data.contains("<li><a href=\"chapter-2.xhtml\">2023-02</a></li>"));
+        assert!(data.contains("<dc:creator>Me</dc:creator>\n<dc:date>"));
+        assert!(data.contains("<dc:coverage>2023-01-31/2023-02-01</dc:coverage>"));
+        assert!(
+            data.contains("<item id=\"image-1\" href=\"images/1.png\" media-type=\"image/png\"/>")
+        );
+        assert!(data.contains("<itemref idref=\"title\"/>\n<itemref idref=\"chapter-1\"/>"));
+    }
+}
@@ -18,6 +18,8 @@ pub enum RuntimeError {
     DiskError(IoError),
     DatabaseError(TableError),
     NotEnoughAvailableSpace(u64, u64),
+    JsonError(serde_json::Error),
+    IndexError(rusqlite::Error),
 }
 
 impl Display for RuntimeError {
@@ -26,9 +28,11 @@ impl Display for RuntimeError {
             RuntimeError::InvalidOptions(why) => write!(fmt, "Invalid options!\n{why}"),
             RuntimeError::DiskError(why) => write!(fmt, "{why}"),
             RuntimeError::DatabaseError(why) => write!(fmt, "{why}"),
+            RuntimeError::JsonError(why) => write!(fmt, "Unable to generate JSON: {why}"),
+            RuntimeError::IndexError(why) => write!(fmt, "Search index error: {why}"),
             RuntimeError::NotEnoughAvailableSpace(estimated_bytes, available_bytes) => {
                 write!(
-                    fmt, 
+                    fmt,
                     "Not enough free disk space!\nEstimated export size: {}\nDisk space available: {}\nPass `--{}` to ignore\n",
                     format_file_size(*estimated_bytes),
                     format_file_size(*available_bytes),
@@ -11,6 +11,18 @@ pub enum ExportType {
     Html,
     /// Text file export
     Txt,
+    /// PDF file export
+    Pdf,
+    /// EPUB e-book export
+    Epub,
+    /// Email export, as `mbox` files or individual `.eml` files
+    Mbox,
+    /// Android SMS Backup & Restore XML export
+    Xml,
+    /// Shared location export, as GPX and KML files
+    Locations,
+    /// vCard export of conversation participants
+    Vcf,
 }
 
 impl ExportType {
@@ -19,9 +31,29 @@ impl ExportType {
         match platform.to_lowercase().as_str() {
             "txt" => Some(Self::Txt),
             "html" => Some(Self::Html),
+            "pdf" => Some(Self::Pdf),
+            "epub" => Some(Self::Epub),
+            "mbox" => Some(Self::Mbox),
+            "xml" => Some(Self::Xml),
+            "locations" => Some(Self::Locations),
+            "vcf" => Some(Self::Vcf),
             _ => None,
         }
     }
+
+    /// Get the extensions of the files this export type writes
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ExportType::Txt => &["txt"],
+            ExportType::Html => &["html"],
+            ExportType::Pdf => &["pdf"],
+            ExportType::Epub => &["epub"],
+            ExportType::Mbox => &["mbox"],
+            ExportType::Xml => &["xml"],
+            ExportType::Locations => &["gpx", "kml"],
+            ExportType::Vcf => &["vcf"],
+        }
+    }
 }
 
 impl Display for ExportType {
@@ -29,6 +61,12 @@ impl Display for ExportType {
         match self {
             ExportType::Txt => write!(fmt, "txt"),
             ExportType::Html => write!(fmt, "html"),
+            ExportType::Pdf => write!(fmt, "pdf"),
+            ExportType::Epub => write!(fmt, "epub"),
+            ExportType::Mbox => write!(fmt, "mbox"),
+            ExportType::Xml => write!(fmt, "xml"),
+            ExportType::Locations => write!(fmt, "locations"),
+            ExportType::Vcf => write!(fmt, "vcf"),
         }
     }
 }
@@ -60,9 +98,84 @@ mod tests {
         assert!(matches!(ExportType::from_cli("tXt"), Some(ExportType::Txt)));
     }
 
+    #[test]
+    fn can_parse_pdf_any_case() {
+        assert!(matches!(ExportType::from_cli("pdf"), Some(ExportType::Pdf)));
+        assert!(matches!(ExportType::from_cli("PDF"), Some(ExportType::Pdf)));
+        assert!(matches!(ExportType::from_cli("pDf"), Some(ExportType::Pdf)));
+    }
+
+    #[test]
+    fn can_parse_epub_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("epub"),
+            Some(ExportType::Epub)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("EPUB"),
+            Some(ExportType::Epub)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("ePuB"),
+            Some(ExportType::Epub)
+        ));
+    }
+
+    #[test]
+    fn can_parse_mbox_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("mbox"),
+            Some(ExportType::Mbox)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MBOX"),
+            Some(ExportType::Mbox)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("mBoX"),
+            Some(ExportType::Mbox)
+        ));
+    }
+
+    #[test]
+    fn can_parse_xml_any_case() {
+        assert!(matches!(ExportType::from_cli("xml"), Some(ExportType::Xml)));
+        assert!(matches!(ExportType::from_cli("XML"), Some(ExportType::Xml)));
+        assert!(matches!(ExportType::from_cli("xMl"), Some(ExportType::Xml)));
+    }
+
+    #[test]
+    fn can_parse_locations_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("locations"),
+            Some(ExportType::Locations)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("LOCATIONS"),
+            Some(ExportType::Locations)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("LoCaTiOnS"),
+            Some(ExportType::Locations)
+        ));
+    }
+
+    #[test]
+    fn can_parse_vcf_any_case() {
+        assert!(matches!(ExportType::from_cli("vcf"), Some(ExportType::Vcf)));
+        assert!(matches!(ExportType::from_cli("VCF"), Some(ExportType::Vcf)));
+        assert!(matches!(ExportType::from_cli("vCf"), Some(ExportType::Vcf)));
+    }
+
+    #[test]
+    fn can_get_extensions() {
+        assert_eq!(ExportType::Txt.extensions(), ["txt"]);
+        assert_eq!(ExportType::Locations.extensions(), ["gpx", "kml"]);
+    }
+
     #[test]
     fn cant_parse_invalid() {
-        assert!(ExportType::from_cli("pdf").is_none());
+        assert!(ExportType::from_cli("docx").is_none());
         assert!(ExportType::from_cli("json").is_none());
         assert!(ExportType::from_cli("").is_none());
     }
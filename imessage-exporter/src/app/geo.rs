@@ -0,0 +1,194 @@
+/*!
+ Contains writers for the GPX and KML formats used to export shared locations.
+
+ Both formats are plain XML, so each file is built in memory once all of a conversation's locations are known.
+*/
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use imessage_database::util::coordinates::Coordinates;
+
+use crate::app::epub_book::escape_xml;
+
+/// A location that was shared in a conversation
+#[derive(Debug, PartialEq)]
+pub struct SharedLocation {
+    /// Where the location is
+    pub coordinates: Coordinates,
+    /// The name of the place, if it has one
+    pub name: Option<String>,
+    /// The street address of the place
+    pub address: Option<String>,
+    /// The person who shared the location
+    pub sender: String,
+    /// When the location was shared, if the date is valid
+    pub date: Option<DateTime<Utc>>,
+    /// When the location was shared, formatted for display in the export's time zone
+    pub readable_date: String,
+    /// The link the location was shared with
+    pub url: Option<String>,
+}
+
+impl SharedLocation {
+    /// Get the name to show on the map, falling back to the address and then the sender
+    fn title(&self) -> &str {
+        self.name
+            .as_deref()
+            .or(self.address.as_deref())
+            .unwrap_or(&self.sender)
+    }
+
+    /// Get the date in UTC, which both formats require
+    fn timestamp(&self) -> Option<String> {
+        self.date
+            .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    /// Describe who shared the location, when, and where
+    fn description(&self) -> String {
+        let mut lines = vec![self.sender.clone()];
+        if !self.readable_date.is_empty() {
+            lines.push(self.readable_date.clone());
+        }
+        if let Some(address) = &self.address {
+            lines.push(address.clone());
+        }
+        lines.join("\n")
+    }
+}
+
+/// Build a [GPX 1.1](https://www.topografix.com/GPX/1/1/) file with a waypoint for each location
+pub fn to_gpx(title: &str, locations: &[SharedLocation]) -> String {
+    let mut out_s = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"imessage-exporter\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n  <metadata>\n    <name>{}</name>\n  </metadata>\n",
+        escape_xml(title)
+    );
+
+    // Child elements must be written in the order the schema defines
+    for location in locations {
+        out_s.push_str(&format!(
+            "  <wpt lat=\"{}\" lon=\"{}\">\n",
+            location.coordinates.latitude, location.coordinates.longitude
+        ));
+        if let Some(timestamp) = location.timestamp() {
+            out_s.push_str(&format!("    <time>{timestamp}</time>\n"));
+        }
+        out_s.push_str(&format!(
+            "    <name>{}</name>\n    <desc>{}</desc>\n",
+            escape_xml(location.title()),
+            escape_xml(&location.description())
+        ));
+        if let Some(url) = &location.url {
+            out_s.push_str(&format!("    <link href=\"{}\" />\n", escape_xml(url)));
+        }
+        out_s.push_str("  </wpt>\n");
+    }
+
+    out_s.push_str("</gpx>\n");
+    out_s
+}
+
+/// Build a [KML 2.2](https://www.ogc.org/standard/kml/) file with a placemark for each location
+pub fn to_kml(title: &str, locations: &[SharedLocation]) -> String {
+    let mut out_s = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n    <name>{}</name>\n",
+        escape_xml(title)
+    );
+
+    // Child elements must be written in the order the schema defines
+    for location in locations {
+        out_s.push_str(&format!(
+            "    <Placemark>\n      <name>{}</name>\n",
+            escape_xml(location.title())
+        ));
+        if let Some(address) = &location.address {
+            out_s.push_str(&format!(
+                "      <address>{}</address>\n",
+                escape_xml(address)
+            ));
+        }
+
+        let mut description = location.description();
+        if let Some(url) = &location.url {
+            description.push('\n');
+            description.push_str(url);
+        }
+        out_s.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape_xml(&description)
+        ));
+
+        if let Some(timestamp) = location.timestamp() {
+            out_s.push_str(&format!(
+                "      <TimeStamp>\n        <when>{timestamp}</when>\n      </TimeStamp>\n"
+            ));
+        }
+        // KML lists longitude first
+        out_s.push_str(&format!(
+            "      <Point>\n        <coordinates>{},{}</coordinates>\n      </Point>\n    </Placemark>\n",
+            location.coordinates.longitude, location.coordinates.latitude
+        ));
+    }
+
+    out_s.push_str("  </Document>\n</kml>\n");
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use crate::app::geo::{to_gpx, to_kml, SharedLocation};
+    use imessage_database::util::coordinates::Coordinates;
+
+    fn fake_location() -> SharedLocation {
+        SharedLocation {
+            coordinates: Coordinates::new(33.450858, -118.508212).unwrap(),
+            name: Some("Cherry Cove".to_string()),
+            address: Some("Cherry Cove, Avalon".to_string()),
+            sender: "Me".to_string(),
+            date: Some(
+                DateTime::parse_from_rfc3339("2022-05-17T17:29:42-07:00")
+                    .unwrap()
+                    .with_timezone(&Utc),
+            ),
+            readable_date: "May 17, 2022".to_string(),
+            url: Some(
+                "https://maps.apple.com/?ll=33.450858,-118.508212&q=Cherry%20Cove".to_string(),
+            ),
+        }
+    }
+
+    #[test]
+    fn can_write_gpx() {
+        let gpx = to_gpx("Friends & Family", &[fake_location()]);
+        assert!(gpx.contains("<name>Friends &amp; Family</name>"));
+        assert!(gpx.contains(
+            "  <wpt lat=\"33.450858\" lon=\"-118.508212\">\n    <time>2022-05-18T00:29:42Z</time>\n    <name>Cherry Cove</name>\n    <desc>Me\nMay 17, 2022\nCherry Cove, Avalon</desc>\n    <link href=\"https://maps.apple.com/?ll=33.450858,-118.508212&amp;q=Cherry%20Cove\" />\n  </wpt>\n"
+        ));
+        assert!(gpx.ends_with("</gpx>\n"));
+    }
+
+    #[test]
+    fn can_write_kml() {
+        let kml = to_kml("Friends", &[fake_location()]);
+        assert!(kml.contains("      <address>Cherry Cove, Avalon</address>\n"));
+        assert!(kml.contains("        <when>2022-05-18T00:29:42Z</when>\n"));
+        assert!(kml.contains("        <coordinates>-118.508212,33.450858</coordinates>\n"));
+        assert!(kml.ends_with("</kml>\n"));
+    }
+
+    #[test]
+    fn can_title_unnamed_location() {
+        let mut location = fake_location();
+        location.name = None;
+        location.address = None;
+        location.date = None;
+        location.readable_date = String::new();
+        location.url = None;
+
+        let gpx = to_gpx("Friends", &[location]);
+        assert!(gpx.contains("    <name>Me</name>\n    <desc>Me</desc>\n  </wpt>\n"));
+        assert!(!gpx.contains("<time>"));
+    }
+}
@@ -0,0 +1,66 @@
+/*!
+ Contains helpers for linking and cloning files instead of copying them.
+*/
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Create a symbolic link at `to` that points to `from`
+///
+/// The link points to the absolute path of `from`, so it resolves from anywhere in the export.
+pub fn symlink(from: &Path, to: &Path) -> Result<()> {
+    let from = from.canonicalize()?;
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(from, to)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(from, to)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(Error::from(ErrorKind::Unsupported))
+    }
+}
+
+/// Clone `from` to `to` on file systems that support copy-on-write, like APFS and Btrfs
+///
+/// This uses the system `cp` program, which fails instead of copying if the file cannot be cloned.
+/// Docs: <https://www.unix.com/man-page/osx/1/cp/> (or `man cp`)
+pub fn reflink(from: &Path, to: &Path) -> Result<()> {
+    // Like other links, never replace an existing file
+    if to.exists() {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    // macOS `cp` clones with `-c`, while GNU `cp` uses `--reflink`
+    let flag = if cfg!(target_os = "macos") {
+        "-c"
+    } else {
+        "--reflink=always"
+    };
+    let status = Command::new("cp")
+        .arg(flag)
+        .arg(from)
+        .arg(to)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null())
+        .status()?;
+    if status.success() {
+        return Ok(());
+    }
+
+    // `cp` may leave an empty file behind when it fails
+    if to.exists() {
+        std::fs::remove_file(to)?;
+    }
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        format!("Unable to clone {from:?}"),
+    ))
+}
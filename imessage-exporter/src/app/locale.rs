@@ -0,0 +1,518 @@
+/*!
+ Contains the translatable strings used when writing exports.
+
+ Each [`Locale`] points to a [`Strings`] table. Templates in the table contain
+ `{name}` placeholders that are filled in by the exporters with [`fill`].
+*/
+
+use std::fmt::Display;
+
+use imessage_database::{
+    message_types::{
+        expressives::{BubbleEffect, ScreenEffect},
+        variants::Reaction,
+    },
+    util::dates::{DurationUnits, ENGLISH_UNITS},
+};
+
+/// Represents the language exported data is written in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Locale {
+    /// English
+    #[default]
+    English,
+    /// Spanish
+    Spanish,
+}
+
+impl Locale {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(locale: &str) -> Option<Self> {
+        match locale.to_lowercase().as_str() {
+            "en" | "english" => Some(Self::English),
+            "es" | "spanish" | "español" => Some(Self::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Get the string table for this locale
+    pub fn strings(&self) -> &'static Strings {
+        match self {
+            Locale::English => &ENGLISH,
+            Locale::Spanish => &SPANISH,
+        }
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Locale::English => write!(fmt, "en"),
+            Locale::Spanish => write!(fmt, "es"),
+        }
+    }
+}
+
+/// Table of every translatable string used by the exporters
+#[derive(Debug, PartialEq, Eq)]
+pub struct Strings {
+    /// Default pattern used to display dates, in `strftime` format
+    pub date_format: &'static str,
+    /// Units used when describing how long something took
+    pub durations: DurationUnits,
+
+    // People
+    /// Name used for messages sent by the database owner in a first-person context
+    pub me: &'static str,
+    /// Name used for messages sent by the database owner in a second-person context
+    pub you: &'static str,
+    /// Object form of `you`, as in "read by you"
+    pub you_object: &'static str,
+    /// Stand-in for the other participants of a conversation
+    pub they: &'static str,
+    /// Object form of `they`, as in "read by them"
+    pub them: &'static str,
+
+    // Message annotations
+    /// Template with `{who}` and `{time}`
+    pub read_by: &'static str,
+    pub deleted_from_conversation: &'static str,
+    pub responded_to_earlier: &'static str,
+    pub attachment_missing: &'static str,
+    pub attachment_does_not_exist: &'static str,
+    /// Template with `{error}`
+    pub app_message_error: &'static str,
+    /// Template with `{variant}` and `{error}`
+    pub message_error: &'static str,
+    pub reactions: &'static str,
+    /// Template with `{reaction}` and `{who}`
+    pub reaction_by: &'static str,
+    /// Template with `{who}` and `{path}`
+    pub sticker_from: &'static str,
+    /// Template with `{effect}`, `{who}`, and `{path}`
+    pub sticker_with_effect_from: &'static str,
+    /// Template with `{who}`
+    pub sticker_not_found: &'static str,
+    /// Template with `{effect}`
+    pub sticker_effect: &'static str,
+    pub shareplay_ended: &'static str,
+    /// Same as `shareplay_ended`, split over multiple lines
+    pub shareplay_ended_lines: &'static str,
+    /// Template with `{who}`
+    pub deleted_a_message: &'static str,
+    /// Template with `{time}`
+    pub edited_later: &'static str,
+
+    // Reactions
+    pub loved: &'static str,
+    pub liked: &'static str,
+    pub disliked: &'static str,
+    pub laughed: &'static str,
+    pub emphasized: &'static str,
+    pub questioned: &'static str,
+
+    // Expressives
+    pub sent_with_confetti: &'static str,
+    pub sent_with_echo: &'static str,
+    pub sent_with_fireworks: &'static str,
+    pub sent_with_balloons: &'static str,
+    pub sent_with_heart: &'static str,
+    pub sent_with_lasers: &'static str,
+    pub sent_with_shooting_star: &'static str,
+    pub sent_with_sparkles: &'static str,
+    pub sent_with_spotlight: &'static str,
+    pub sent_with_slam: &'static str,
+    pub sent_with_loud: &'static str,
+    pub sent_with_gentle: &'static str,
+    pub sent_with_invisible_ink: &'static str,
+
+    // Announcements
+    /// Template with `{who}` and `{name}`
+    pub named_conversation: &'static str,
+    /// Template with `{who}` and `{name}`
+    pub renamed_conversation: &'static str,
+    /// Template with `{who}`
+    pub changed_group_photo: &'static str,
+    /// Template with `{who}` and `{action}`
+    pub unknown_action: &'static str,
+    pub unable_to_format_announcement: &'static str,
+
+    // Conversation header
+    pub conversation: &'static str,
+    pub participants: &'static str,
+    pub service: &'static str,
+    pub group_photo: &'static str,
+    pub read_receipts: &'static str,
+    pub enabled: &'static str,
+    pub disabled: &'static str,
+    pub send_as_sms: &'static str,
+    pub always: &'static str,
+    pub alerts: &'static str,
+    pub hidden: &'static str,
+    pub background: &'static str,
+    pub custom: &'static str,
+
+    // Index page
+    pub conversations: &'static str,
+    pub messages: &'static str,
+    pub sort_by: &'static str,
+    pub most_recent: &'static str,
+    pub least_recent: &'static str,
+    pub name: &'static str,
+    pub most_messages: &'static str,
+    pub orphaned_messages: &'static str,
+    pub search: &'static str,
+    pub no_results: &'static str,
+
+    // Pagination
+    pub previous_page: &'static str,
+    pub next_page: &'static str,
+
+    // Check In
+    /// Template with `{date}`
+    pub expected_around: &'static str,
+    /// Template with `{date}`
+    pub was_expected_around: &'static str,
+    /// Template with `{date}`
+    pub checked_in_at: &'static str,
+    /// Template with `{date}`, used by plain text exports
+    pub expected_at: &'static str,
+    /// Template with `{date}`, used by plain text exports
+    pub was_expected_at: &'static str,
+
+    // Contacts
+    pub first_message: &'static str,
+    pub last_message: &'static str,
+}
+
+impl Strings {
+    /// Get the name of a reaction
+    pub fn reaction(&self, reaction: &Reaction) -> &'static str {
+        match reaction {
+            Reaction::Loved => self.loved,
+            Reaction::Liked => self.liked,
+            Reaction::Disliked => self.disliked,
+            Reaction::Laughed => self.laughed,
+            Reaction::Emphasized => self.emphasized,
+            Reaction::Questioned => self.questioned,
+        }
+    }
+
+    /// Get the annotation for a message sent with a screen effect
+    pub fn screen_effect(&self, effect: &ScreenEffect) -> &'static str {
+        match effect {
+            ScreenEffect::Confetti => self.sent_with_confetti,
+            ScreenEffect::Echo => self.sent_with_echo,
+            ScreenEffect::Fireworks => self.sent_with_fireworks,
+            ScreenEffect::Balloons => self.sent_with_balloons,
+            ScreenEffect::Heart => self.sent_with_heart,
+            ScreenEffect::Lasers => self.sent_with_lasers,
+            ScreenEffect::ShootingStar => self.sent_with_shooting_star,
+            ScreenEffect::Sparkles => self.sent_with_sparkles,
+            ScreenEffect::Spotlight => self.sent_with_spotlight,
+        }
+    }
+
+    /// Get the annotation for a message sent with a bubble effect
+    pub fn bubble_effect(&self, effect: &BubbleEffect) -> &'static str {
+        match effect {
+            BubbleEffect::Slam => self.sent_with_slam,
+            BubbleEffect::Loud => self.sent_with_loud,
+            BubbleEffect::Gentle => self.sent_with_gentle,
+            BubbleEffect::InvisibleInk => self.sent_with_invisible_ink,
+        }
+    }
+}
+
+/// Replace each `{name}` placeholder in `template` with its value
+///
+/// Placeholders without a matching value are left as-is.
+///
+/// # Example:
+///
+/// ```
+/// use crate::app::locale::fill;
+///
+/// let text = fill("Read by {who} after {time}", &[("who", "them"), ("time", "5 minutes")]);
+/// assert_eq!(text, "Read by them after 5 minutes");
+/// ```
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out_s = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out_s.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                let key = &after[..end];
+                match values.iter().find(|(name, _)| *name == key) {
+                    Some((_, value)) => out_s.push_str(value),
+                    None => {
+                        out_s.push('{');
+                        out_s.push_str(key);
+                        out_s.push('}');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                out_s.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out_s.push_str(rest);
+    out_s
+}
+
+/// English strings
+pub const ENGLISH: Strings = Strings {
+    date_format: "%b %d, %Y %l:%M:%S %p",
+    durations: ENGLISH_UNITS,
+
+    me: "Me",
+    you: "You",
+    you_object: "you",
+    they: "They",
+    them: "them",
+
+    read_by: "Read by {who} after {time}",
+    deleted_from_conversation: "This message was deleted from the conversation!",
+    responded_to_earlier: "This message responded to an earlier message.",
+    attachment_missing: "Attachment missing!",
+    attachment_does_not_exist: "Attachment does not exist!",
+    app_message_error: "Unable to format app message: {error}",
+    message_error: "Unable to format {variant} message: {error}",
+    reactions: "Reactions:",
+    reaction_by: "{reaction} by {who}",
+    sticker_from: "Sticker from {who}: {path}",
+    sticker_with_effect_from: "{effect} Sticker from {who}: {path}",
+    sticker_not_found: "Sticker from {who} not found!",
+    sticker_effect: "Sent with {effect} effect",
+    shareplay_ended: "SharePlay Message Ended",
+    shareplay_ended_lines: "SharePlay Message\nEnded",
+    deleted_a_message: "{who} deleted a message.",
+    edited_later: "Edited {time} later",
+
+    loved: "Loved",
+    liked: "Liked",
+    disliked: "Disliked",
+    laughed: "Laughed",
+    emphasized: "Emphasized",
+    questioned: "Questioned",
+
+    sent_with_confetti: "Sent with Confetti",
+    sent_with_echo: "Sent with Echo",
+    sent_with_fireworks: "Sent with Fireworks",
+    sent_with_balloons: "Sent with Balloons",
+    sent_with_heart: "Sent with Heart",
+    sent_with_lasers: "Sent with Lasers",
+    sent_with_shooting_star: "Sent with Shooting Star",
+    sent_with_sparkles: "Sent with Sparkles",
+    sent_with_spotlight: "Sent with Spotlight",
+    sent_with_slam: "Sent with Slam",
+    sent_with_loud: "Sent with Loud",
+    sent_with_gentle: "Sent with Gentle",
+    sent_with_invisible_ink: "Sent with Invisible Ink",
+
+    named_conversation: "{who} named the conversation {name}",
+    renamed_conversation: "{who} renamed the conversation to {name}",
+    changed_group_photo: "{who} changed the group photo.",
+    unknown_action: "{who} performed unknown action {action}",
+    unable_to_format_announcement: "Unable to format announcement!",
+
+    conversation: "Conversation",
+    participants: "Participants",
+    service: "Service",
+    group_photo: "Group photo",
+    read_receipts: "Read receipts",
+    enabled: "Enabled",
+    disabled: "Disabled",
+    send_as_sms: "Send as SMS",
+    always: "Always",
+    alerts: "Alerts",
+    hidden: "Hidden",
+    background: "Background",
+    custom: "Custom",
+    conversations: "Conversations",
+    messages: "Messages",
+    sort_by: "Sort by",
+    most_recent: "Most recent",
+    least_recent: "Least recent",
+    name: "Name",
+    most_messages: "Most messages",
+    orphaned_messages: "Messages without a conversation",
+    search: "Search",
+    no_results: "No results",
+    previous_page: "Previous page",
+    next_page: "Next page",
+
+    expected_around: "Expected around {date}",
+    was_expected_around: "Was expected around {date}",
+    checked_in_at: "Checked in at {date}",
+    expected_at: "Expected at {date}",
+    was_expected_at: "Was expected at {date}",
+
+    first_message: "First message",
+    last_message: "Last message",
+};
+
+/// Spanish strings
+pub const SPANISH: Strings = Strings {
+    date_format: "%d/%m/%Y %H:%M:%S",
+    durations: DurationUnits {
+        day: "día",
+        days: "días",
+        hour: "hora",
+        hours: "horas",
+        minute: "minuto",
+        minutes: "minutos",
+        second: "segundo",
+        seconds: "segundos",
+        separator: ", ",
+    },
+
+    me: "Yo",
+    you: "Usted",
+    you_object: "usted",
+    they: "Otra persona",
+    them: "ellos",
+
+    read_by: "Leído por {who} después de {time}",
+    deleted_from_conversation: "¡Este mensaje fue eliminado de la conversación!",
+    responded_to_earlier: "Este mensaje respondió a un mensaje anterior.",
+    attachment_missing: "¡Falta el archivo adjunto!",
+    attachment_does_not_exist: "¡El archivo adjunto no existe!",
+    app_message_error: "No se pudo formatear el mensaje de la app: {error}",
+    message_error: "No se pudo formatear el mensaje {variant}: {error}",
+    reactions: "Reacciones:",
+    reaction_by: "{reaction} por {who}",
+    sticker_from: "Sticker de {who}: {path}",
+    sticker_with_effect_from: "Sticker {effect} de {who}: {path}",
+    sticker_not_found: "¡No se encontró el sticker de {who}!",
+    sticker_effect: "Enviado con el efecto {effect}",
+    shareplay_ended: "Mensaje de SharePlay finalizado",
+    shareplay_ended_lines: "Mensaje de SharePlay\nfinalizado",
+    deleted_a_message: "{who} eliminó un mensaje.",
+    edited_later: "Editado {time} después",
+
+    loved: "Le encantó",
+    liked: "Le gustó",
+    disliked: "No le gustó",
+    laughed: "Le hizo reír",
+    emphasized: "Lo enfatizó",
+    questioned: "Lo cuestionó",
+
+    sent_with_confetti: "Enviado con Confeti",
+    sent_with_echo: "Enviado con Eco",
+    sent_with_fireworks: "Enviado con Fuegos artificiales",
+    sent_with_balloons: "Enviado con Globos",
+    sent_with_heart: "Enviado con Corazón",
+    sent_with_lasers: "Enviado con Láseres",
+    sent_with_shooting_star: "Enviado con Estrella fugaz",
+    sent_with_sparkles: "Enviado con Destellos",
+    sent_with_spotlight: "Enviado con Foco",
+    sent_with_slam: "Enviado con Golpe",
+    sent_with_loud: "Enviado con Fuerte",
+    sent_with_gentle: "Enviado con Suave",
+    sent_with_invisible_ink: "Enviado con Tinta invisible",
+
+    named_conversation: "{who} nombró la conversación {name}",
+    renamed_conversation: "{who} cambió el nombre de la conversación a {name}",
+    changed_group_photo: "{who} cambió la foto del grupo.",
+    unknown_action: "{who} realizó la acción desconocida {action}",
+    unable_to_format_announcement: "¡No se pudo formatear el anuncio!",
+
+    conversation: "Conversación",
+    participants: "Participantes",
+    service: "Servicio",
+    group_photo: "Foto del grupo",
+    read_receipts: "Confirmaciones de lectura",
+    enabled: "Activadas",
+    disabled: "Desactivadas",
+    send_as_sms: "Enviar como SMS",
+    always: "Siempre",
+    alerts: "Alertas",
+    hidden: "Ocultas",
+    background: "Fondo",
+    custom: "Personalizado",
+    conversations: "Conversaciones",
+    messages: "Mensajes",
+    sort_by: "Ordenar por",
+    most_recent: "Más recientes",
+    least_recent: "Menos recientes",
+    name: "Nombre",
+    most_messages: "Más mensajes",
+    orphaned_messages: "Mensajes sin conversación",
+    search: "Buscar",
+    no_results: "Sin resultados",
+    previous_page: "Página anterior",
+    next_page: "Página siguiente",
+
+    expected_around: "Esperado alrededor de {date}",
+    was_expected_around: "Se esperaba alrededor de {date}",
+    checked_in_at: "Llegó a las {date}",
+    expected_at: "Esperado a las {date}",
+    was_expected_at: "Se esperaba a las {date}",
+
+    first_message: "Primer mensaje",
+    last_message: "Último mensaje",
+};
+
+#[cfg(test)]
+mod tests {
+    use crate::app::locale::{fill, Locale, ENGLISH, SPANISH};
+
+    #[test]
+    fn can_parse_locale_any_case() {
+        assert_eq!(Locale::from_cli("en"), Some(Locale::English));
+        assert_eq!(Locale::from_cli("ES"), Some(Locale::Spanish));
+        assert_eq!(Locale::from_cli("Spanish"), Some(Locale::Spanish));
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert!(Locale::from_cli("klingon").is_none());
+        assert!(Locale::from_cli("").is_none());
+    }
+
+    #[test]
+    fn can_get_strings() {
+        assert_eq!(Locale::English.strings(), &ENGLISH);
+        assert_eq!(Locale::Spanish.strings(), &SPANISH);
+    }
+
+    #[test]
+    fn can_fill_template() {
+        assert_eq!(
+            fill(ENGLISH.read_by, &[("who", "them"), ("time", "5 minutes")]),
+            "Read by them after 5 minutes"
+        );
+        assert_eq!(
+            fill(SPANISH.read_by, &[("who", "ellos"), ("time", "5 minutos")]),
+            "Leído por ellos después de 5 minutos"
+        );
+    }
+
+    #[test]
+    fn can_fill_template_missing_value() {
+        assert_eq!(
+            fill("{who} named the conversation {name}", &[("who", "You")]),
+            "You named the conversation {name}"
+        );
+    }
+
+    #[test]
+    fn can_fill_template_does_not_recurse() {
+        assert_eq!(
+            fill("{who} said {text}", &[("who", "{text}"), ("text", "hi")]),
+            "{text} said hi"
+        );
+    }
+
+    #[test]
+    fn can_fill_template_unclosed() {
+        assert_eq!(fill("Hello {who", &[("who", "You")]), "Hello {who");
+    }
+}
@@ -0,0 +1,325 @@
+/*!
+ Contains a minimal MIME message builder used to export messages in email formats.
+
+ Message bodies and attachments are always base64 encoded, so the output is 7-bit clean and no line is
+ longer than the 998 characters allowed by [RFC 5322](https://www.rfc-editor.org/rfc/rfc5322#section-2.1.1).
+*/
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::app::encoding::encode_base64;
+
+/// Domain used for addresses and message IDs, which RFC 2606 reserves so it can never be delivered to
+pub const MESSAGE_DOMAIN: &str = "imessage.invalid";
+/// Longest line of base64 data, defined in [RFC 2045](https://www.rfc-editor.org/rfc/rfc2045#section-6.8)
+const BASE64_LINE_LENGTH: usize = 76;
+/// Most bytes of text to put in a single encoded word, so each stays under 75 characters
+const ENCODED_WORD_BYTES: usize = 45;
+
+/// Encode text for a header, using RFC 2047 encoded words if it is not plain ASCII
+pub fn encode_header(text: &str) -> String {
+    if text.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        return text.to_string();
+    }
+
+    // Encoded words cannot split a character, so chunk the text on character boundaries
+    let mut words = vec![];
+    let mut chunk = String::new();
+    for c in text.chars().filter(|c| !c.is_control()) {
+        if chunk.len() + c.len_utf8() > ENCODED_WORD_BYTES {
+            words.push(format!("=?UTF-8?B?{}?=", encode_base64(chunk.as_bytes())));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(format!("=?UTF-8?B?{}?=", encode_base64(chunk.as_bytes())));
+    }
+    words.join("\n ")
+}
+
+/// Encode text as a quoted string, i.e. for a display name or filename
+fn quote(text: &str) -> String {
+    let encoded = encode_header(text);
+    if encoded != text {
+        return format!("\"{encoded}\"");
+    }
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Encode bytes as base64, broken into lines that are short enough for email
+fn encode_body(data: &[u8]) -> String {
+    let encoded = encode_base64(data);
+    let mut out_s = String::with_capacity(encoded.len() + encoded.len() / BASE64_LINE_LENGTH + 1);
+    // Base64 output is ASCII, so it can be split at any byte
+    for line in encoded.as_bytes().chunks(BASE64_LINE_LENGTH) {
+        out_s.push_str(&String::from_utf8_lossy(line));
+        out_s.push('\n');
+    }
+    out_s
+}
+
+/// Format a message GUID as a message ID
+fn message_id(guid: &str) -> String {
+    format!("<{guid}@{MESSAGE_DOMAIN}>")
+}
+
+/// A person who sent or received a message
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mailbox {
+    /// The name shown for the person
+    pub name: String,
+    /// The email address for the person
+    pub address: String,
+}
+
+impl Mailbox {
+    /// Build a mailbox from a handle, i.e. a phone number or email address
+    ///
+    /// Email addresses are used as-is. Anything else becomes an address at [`MESSAGE_DOMAIN`].
+    pub fn new(name: &str, handle: &str) -> Self {
+        let address = if handle.is_ascii() && handle.contains('@') && !handle.contains(' ') {
+            handle.to_string()
+        } else {
+            let local: String = handle
+                .chars()
+                .filter_map(|c| match c {
+                    c if c.is_ascii_alphanumeric() => Some(c),
+                    '+' | '-' | '.' | '_' => Some(c),
+                    ' ' | '(' | ')' => None,
+                    _ => Some('_'),
+                })
+                .collect();
+            let local = local.trim_matches('.');
+            format!(
+                "{}@{MESSAGE_DOMAIN}",
+                if local.is_empty() { "unknown" } else { local }
+            )
+        };
+        Self {
+            name: name.to_string(),
+            address,
+        }
+    }
+
+    /// Format the mailbox for an address header
+    fn header(&self) -> String {
+        if self.name.is_empty() || self.name == self.address {
+            return format!("<{}>", self.address);
+        }
+        format!("{} <{}>", quote(&self.name), self.address)
+    }
+}
+
+/// A file attached to a message
+#[derive(Debug, PartialEq, Eq)]
+pub struct MimePart {
+    /// The media type of the file, i.e. `image/jpeg`
+    pub content_type: String,
+    /// The name of the file
+    pub filename: String,
+    /// The contents of the file
+    pub data: Vec<u8>,
+}
+
+/// A single email built from a message
+#[derive(Debug, PartialEq, Eq)]
+pub struct MimeMessage {
+    /// The person who sent the message
+    pub from: Mailbox,
+    /// The people who received the message
+    pub to: Vec<Mailbox>,
+    /// When the message was sent, if the date is valid
+    pub date: Option<DateTime<FixedOffset>>,
+    /// The subject line
+    pub subject: String,
+    /// The GUID of the message, used to build its message ID
+    pub guid: String,
+    /// The GUID of the message that started the thread this message replies to
+    pub in_reply_to: Option<String>,
+    /// The text of the message
+    pub body: String,
+    /// Files attached to the message
+    pub attachments: Vec<MimePart>,
+}
+
+impl MimeMessage {
+    /// Render the message with the line endings used by `.eml` files
+    pub fn to_eml(&self) -> String {
+        self.render().replace('\n', "\r\n")
+    }
+
+    /// Render the message as an entry in an `mbox` file
+    ///
+    /// Lines that look like the start of another entry are escaped as described by the `mboxrd` format.
+    pub fn to_mbox(&self) -> String {
+        let date = self
+            .date
+            .map(|date| date.with_timezone(&Utc))
+            .unwrap_or_default();
+        let mut out_s = format!(
+            "From {} {}\n",
+            self.from.address,
+            date.format("%a %b %e %H:%M:%S %Y")
+        );
+        for line in self.render().lines() {
+            if line.trim_start_matches('>').starts_with("From ") {
+                out_s.push('>');
+            }
+            out_s.push_str(line);
+            out_s.push('\n');
+        }
+        out_s.push('\n');
+        out_s
+    }
+
+    /// Render the headers and body, with `\n` line endings
+    fn render(&self) -> String {
+        let mut out_s = format!("From: {}\n", self.from.header());
+
+        let to: Vec<String> = self.to.iter().map(Mailbox::header).collect();
+        if to.is_empty() {
+            out_s.push_str("To: undisclosed-recipients:;\n");
+        } else {
+            out_s.push_str(&format!("To: {}\n", to.join(",\n ")));
+        }
+        if let Some(date) = &self.date {
+            out_s.push_str(&format!("Date: {}\n", date.to_rfc2822()));
+        }
+        out_s.push_str(&format!("Subject: {}\n", encode_header(&self.subject)));
+        out_s.push_str(&format!("Message-ID: {}\n", message_id(&self.guid)));
+        if let Some(thread) = &self.in_reply_to {
+            out_s.push_str(&format!("In-Reply-To: {}\n", message_id(thread)));
+            out_s.push_str(&format!("References: {}\n", message_id(thread)));
+        }
+        out_s.push_str("MIME-Version: 1.0\n");
+
+        let text = format!(
+            "Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: base64\n\n{}",
+            encode_body(self.body.as_bytes())
+        );
+        if self.attachments.is_empty() {
+            out_s.push_str(&text);
+            return out_s;
+        }
+
+        // Base64 never contains `_`, so the boundary cannot appear in any part
+        let boundary: String = format!(
+            "=_{}",
+            self.guid
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+        );
+        out_s.push_str(&format!(
+            "Content-Type: multipart/mixed; boundary=\"{boundary}\"\n\n"
+        ));
+        out_s.push_str(&format!("--{boundary}\n{text}"));
+        for attachment in &self.attachments {
+            let filename = quote(&attachment.filename);
+            out_s.push_str(&format!(
+                "--{boundary}\nContent-Type: {}; name={filename}\nContent-Disposition: attachment; filename={filename}\nContent-Transfer-Encoding: base64\n\n{}",
+                attachment.content_type,
+                encode_body(&attachment.data)
+            ));
+        }
+        out_s.push_str(&format!("--{boundary}--\n"));
+        out_s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::app::mime::{encode_body, encode_header, Mailbox, MimeMessage, MimePart};
+
+    fn fake_message() -> MimeMessage {
+        MimeMessage {
+            from: Mailbox::new("Me", "me"),
+            to: vec![Mailbox::new("+15555550100", "+15555550100")],
+            date: Some(DateTime::parse_from_rfc3339("2022-05-17T17:29:42-07:00").unwrap()),
+            subject: "Friends".to_string(),
+            guid: "ABC-123".to_string(),
+            in_reply_to: None,
+            body: "Hello world".to_string(),
+            attachments: vec![],
+        }
+    }
+
+    #[test]
+    fn can_encode_header() {
+        assert_eq!(encode_header("Friends"), "Friends");
+        assert_eq!(encode_header("Café"), "=?UTF-8?B?Q2Fmw6k=?=");
+    }
+
+    #[test]
+    fn can_encode_long_header() {
+        let encoded = encode_header(&"é".repeat(30));
+        let words: Vec<&str> = encoded.split("\n ").collect();
+        assert_eq!(words.len(), 2);
+        assert!(words.iter().all(|word| word.len() <= 75));
+    }
+
+    #[test]
+    fn can_encode_body_lines() {
+        let encoded = encode_body(&[0; 100]);
+        assert!(encoded.lines().all(|line| line.len() <= 76));
+        assert_eq!(encoded.lines().count(), 2);
+    }
+
+    #[test]
+    fn can_build_mailbox() {
+        assert_eq!(
+            Mailbox::new("Jane", "jane@example.com").address,
+            "jane@example.com"
+        );
+        assert_eq!(
+            Mailbox::new("+1 (555) 555-0100", "+1 (555) 555-0100").address,
+            "+1555555-0100@imessage.invalid"
+        );
+        assert_eq!(Mailbox::new("", "").address, "unknown@imessage.invalid");
+    }
+
+    #[test]
+    fn can_render_message() {
+        let message = fake_message();
+        assert_eq!(
+            message.to_eml(),
+            "From: \"Me\" <me@imessage.invalid>\r\nTo: \"+15555550100\" <+15555550100@imessage.invalid>\r\nDate: Tue, 17 May 2022 17:29:42 -0700\r\nSubject: Friends\r\nMessage-ID: <ABC-123@imessage.invalid>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\nSGVsbG8gd29ybGQ=\r\n"
+        );
+    }
+
+    #[test]
+    fn can_render_reply() {
+        let mut message = fake_message();
+        message.in_reply_to = Some("XYZ".to_string());
+        let eml = message.to_eml();
+        assert!(eml.contains("In-Reply-To: <XYZ@imessage.invalid>\r\n"));
+        assert!(eml.contains("References: <XYZ@imessage.invalid>\r\n"));
+    }
+
+    #[test]
+    fn can_render_attachments() {
+        let mut message = fake_message();
+        message.attachments.push(MimePart {
+            content_type: "image/jpeg".to_string(),
+            filename: "a \"b\".jpg".to_string(),
+            data: vec![1, 2, 3],
+        });
+        let eml = message.to_eml();
+        assert!(eml.contains("Content-Type: multipart/mixed; boundary=\"=_ABC123\"\r\n"));
+        assert!(eml.contains(
+            "--=_ABC123\r\nContent-Type: image/jpeg; name=\"a \\\"b\\\".jpg\"\r\nContent-Disposition: attachment; filename=\"a \\\"b\\\".jpg\"\r\nContent-Transfer-Encoding: base64\r\n\r\nAQID\r\n--=_ABC123--\r\n"
+        ));
+    }
+
+    #[test]
+    fn can_render_mbox() {
+        let mbox = fake_message().to_mbox();
+        assert!(mbox.starts_with("From me@imessage.invalid Wed May 18 00:29:42 2022\nFrom: "));
+        assert!(mbox.ends_with("SGVsbG8gd29ybGQ=\n\n"));
+        // Headers start with `From:`, so only the separator line starts with `From `
+        assert_eq!(mbox.lines().filter(|l| l.starts_with("From ")).count(), 1);
+    }
+}
@@ -1,8 +1,26 @@
 pub mod attachment_manager;
+pub mod attachment_names;
+pub mod chat_list;
+pub mod config_file;
 pub mod converter;
+pub mod encoding;
+pub mod epub_book;
 pub mod error;
 pub mod export_type;
+pub mod geo;
+pub mod links;
+pub mod locale;
+pub mod mime;
+pub mod mode;
 pub mod options;
+pub mod pagination;
+pub mod pdf_document;
 pub mod progress;
 pub mod runtime;
 pub mod sanitizers;
+pub mod search;
+pub mod stats;
+pub mod templates;
+pub mod theme;
+pub mod vcard;
+pub mod zip;
@@ -0,0 +1,20 @@
+/*!
+ Contains data structures used to describe what the binary was asked to do.
+*/
+
+use crate::app::{chat_list::ListFormat, search::SearchQuery, stats::StatsFormat};
+
+/// Represents the subcommand selected on the command line
+#[derive(PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Export conversations to files
+    Export,
+    /// Print diagnostic information about the database
+    Diagnose,
+    /// List the conversations stored in the database
+    ListChats(ListFormat),
+    /// Write a report of message statistics
+    Stats(StatsFormat),
+    /// Search message text using a full-text index
+    Search(SearchQuery),
+}
@@ -1,10 +1,15 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
 
-use clap::{crate_version, Arg, ArgAction, ArgMatches, Command};
+use chrono::format::{Item, StrftimeItems};
+use clap::{crate_version, parser::ValueSource, Arg, ArgAction, ArgMatches, Command};
 
 use imessage_database::{
     tables::{attachment::DEFAULT_ATTACHMENT_ROOT, table::DEFAULT_PATH_IOS},
     util::{
+        dates::Timezone,
         dirs::{default_db_path, home},
         platform::Platform,
         query_context::QueryContext,
@@ -12,17 +17,34 @@ use imessage_database::{
 };
 
 use crate::app::{
-    attachment_manager::AttachmentManager, error::RuntimeError, export_type::ExportType,
+    attachment_manager::{AttachmentManager, BYTES_PER_MB, DEFAULT_EMBED_LIMIT_MB},
+    attachment_names::AttachmentNames,
+    chat_list::ListFormat,
+    config_file::{ConfigFile, DEFAULT_CONFIG_PATH},
+    error::RuntimeError,
+    export_type::ExportType,
+    locale::Locale,
+    mode::Mode,
+    pagination::Pagination,
+    search::{SearchQuery, DEFAULT_INDEX_PATH, DEFAULT_SEARCH_CONTEXT, DEFAULT_SEARCH_LIMIT},
+    stats::StatsFormat,
+    theme::Theme,
 };
 
 /// Default export directory name
 pub const DEFAULT_OUTPUT_DIR: &str = "imessage_export";
 
+// CLI Subcommand Names
+pub const COMMAND_EXPORT: &str = "export";
+pub const COMMAND_DIAGNOSE: &str = "diagnose";
+pub const COMMAND_LIST_CHATS: &str = "list-chats";
+pub const COMMAND_STATS: &str = "stats";
+pub const COMMAND_SEARCH: &str = "search";
+
 // CLI Arg Names
 pub const OPTION_DB_PATH: &str = "db-path";
 pub const OPTION_ATTACHMENT_ROOT: &str = "attachment-root";
 pub const OPTION_ATTACHMENT_MANAGER: &str = "copy-method";
-pub const OPTION_DIAGNOSTIC: &str = "diagnostics";
 pub const OPTION_EXPORT_TYPE: &str = "format";
 pub const OPTION_EXPORT_PATH: &str = "export-path";
 pub const OPTION_START_DATE: &str = "start-date";
@@ -31,17 +53,61 @@ pub const OPTION_DISABLE_LAZY_LOADING: &str = "no-lazy";
 pub const OPTION_CUSTOM_NAME: &str = "custom-name";
 pub const OPTION_PLATFORM: &str = "platform";
 pub const OPTION_BYPASS_FREE_SPACE_CHECK: &str = "ignore-disk-warning";
+pub const OPTION_TIMEZONE: &str = "timezone";
+pub const OPTION_CHAT_TIMEZONES: &str = "chat-timezones";
+pub const OPTION_ISO_TIMESTAMPS: &str = "iso-timestamps";
+pub const OPTION_LOCALE: &str = "locale";
+pub const OPTION_DATE_FORMAT: &str = "date-format";
+pub const OPTION_EXCLUSIVE_END: &str = "exclusive-end";
+pub const OPTION_LIST_FORMAT: &str = "list-format";
+pub const OPTION_STATS_FORMAT: &str = "stats-format";
+pub const OPTION_CONFIG: &str = "config";
+pub const OPTION_QUERY: &str = "query";
+pub const OPTION_LIMIT: &str = "limit";
+pub const OPTION_CONTEXT: &str = "context";
+pub const OPTION_INDEX_PATH: &str = "index-path";
+pub const OPTION_REBUILD_INDEX: &str = "rebuild-index";
+pub const OPTION_PAGINATE: &str = "paginate";
+pub const OPTION_THEME: &str = "theme";
+pub const OPTION_CUSTOM_CSS: &str = "css";
+pub const OPTION_LINK_CSS: &str = "link-css";
+pub const OPTION_TEMPLATES: &str = "templates";
+pub const OPTION_EMBED_LIMIT: &str = "embed-limit";
+pub const OPTION_CONVERSATIONS: &str = "conversations";
+pub const OPTION_EML: &str = "eml";
+pub const OPTION_DEDUPE: &str = "dedupe";
+pub const OPTION_ATTACHMENT_NAMES: &str = "attachment-names";
 
 // Other CLI Text
-pub const SUPPORTED_FILE_TYPES: &str = "txt, html";
+pub const SUPPORTED_FILE_TYPES: &str = "txt, html, pdf, epub, mbox, xml, locations, vcf";
 pub const SUPPORTED_PLATFORMS: &str = "macOS, iOS";
-pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str = "compatible, efficient, disabled";
+pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str =
+    "compatible, efficient, embedded, hardlink, symlink, reflink, disabled";
+pub const SUPPORTED_ATTACHMENT_NAMES: &str = "random, original, dated";
+pub const SUPPORTED_LOCALES: &str = "en, es";
+pub const SUPPORTED_LIST_FORMATS: &str = "table, json";
+pub const SUPPORTED_STATS_FORMATS: &str = "json, html";
+pub const SUPPORTED_PAGINATION_MODES: &str = "year, month, COUNT";
+pub const SUPPORTED_THEMES: &str = "auto, light, dark";
 pub const ABOUT: &str = concat!(
     "The `imessage-exporter` binary exports iMessage data to\n",
-    "`txt` or `html` formats. It can also run diagnostics\n",
-    "to find problems with the iMessage database."
+    "`txt`, `html`, `pdf`, `epub`, `mbox`, or `xml` formats, shared\n",
+    "locations to GPX and KML files, and participants to vCards.\n",
+    "It can also list conversations, report message statistics,\n",
+    "search message text, and run diagnostics to find problems\n",
+    "with the iMessage database."
 );
 
+/// Options that only apply to a single export format
+const FORMAT_SPECIFIC_OPTIONS: [(&str, ExportType); 6] = [
+    (OPTION_PAGINATE, ExportType::Html),
+    (OPTION_THEME, ExportType::Html),
+    (OPTION_CUSTOM_CSS, ExportType::Html),
+    (OPTION_TEMPLATES, ExportType::Html),
+    (OPTION_CONVERSATIONS, ExportType::Epub),
+    (OPTION_EML, ExportType::Mbox),
+];
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Options {
     /// Path to database file
@@ -50,8 +116,8 @@ pub struct Options {
     pub attachment_root: Option<String>,
     /// The attachment manager type used to copy files
     pub attachment_manager: AttachmentManager,
-    /// If true, emit diagnostic information to stdout
-    pub diagnostic: bool,
+    /// The subcommand selected on the command line
+    pub mode: Mode,
     /// The type of file we are exporting data to
     pub export_type: Option<ExportType>,
     /// Where the app will save exported data
@@ -66,22 +132,119 @@ pub struct Options {
     pub platform: Platform,
     /// If true, disable the free disk space check
     pub ignore_disk_space: bool,
+    /// The time zone used to display dates and interpret date filters
+    pub timezone: Timezone,
+    /// Time zones used to display dates in specific conversations, by the IDs shown in `list-chats`
+    pub chat_timezones: BTreeMap<i32, Timezone>,
+    /// If true, emit ISO-8601 timestamps in UTC instead of human-readable dates
+    pub iso_timestamps: bool,
+    /// The language used for text written by the exporter
+    pub locale: Locale,
+    /// Custom `strftime` pattern used to display dates, overriding the locale's default
+    pub date_format: Option<String>,
+    /// How HTML exports split each conversation into multiple files
+    pub pagination: Pagination,
+    /// The color theme used by HTML exports
+    pub theme: Theme,
+    /// Path to a stylesheet applied to HTML exports after the built-in styles
+    pub custom_css: Option<PathBuf>,
+    /// If true, copy the custom stylesheet to the export directory and link to it instead of embedding it
+    pub link_css: bool,
+    /// Path to a directory of templates that replace parts of the HTML output
+    pub template_dir: Option<PathBuf>,
+    /// Conversations to include in EPUB exports, by the IDs shown in `list-chats`
+    pub conversations: Option<BTreeSet<i32>>,
+    /// If true, write each message in `mbox` exports to its own `.eml` file
+    pub eml: bool,
+    /// If true, copy each unique attachment once and point every message that shares it at the same file
+    pub dedupe: bool,
+    /// How copied attachments are named
+    pub attachment_names: AttachmentNames,
 }
 
 impl Options {
-    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
-        let user_path: Option<&String> = args.get_one(OPTION_DB_PATH);
-        let attachment_root: Option<&String> = args.get_one(OPTION_ATTACHMENT_ROOT);
-        let attachment_manager_type: Option<&String> = args.get_one(OPTION_ATTACHMENT_MANAGER);
-        let diagnostic = args.get_flag(OPTION_DIAGNOSTIC);
-        let export_file_type: Option<&String> = args.get_one(OPTION_EXPORT_TYPE);
-        let user_export_path: Option<&String> = args.get_one(OPTION_EXPORT_PATH);
-        let start_date: Option<&String> = args.get_one(OPTION_START_DATE);
-        let end_date: Option<&String> = args.get_one(OPTION_END_DATE);
-        let no_lazy = args.get_flag(OPTION_DISABLE_LAZY_LOADING);
-        let custom_name: Option<&String> = args.get_one(OPTION_CUSTOM_NAME);
-        let platform_type: Option<&String> = args.get_one(OPTION_PLATFORM);
-        let ignore_disk_space = args.get_flag(OPTION_BYPASS_FREE_SPACE_CHECK);
+    /// Build the options from the command line, falling back to values in the config file
+    pub fn from_args(args: &ArgMatches, file: &ConfigFile) -> Result<Self, RuntimeError> {
+        let args = &merge_config(args, file)?;
+
+        // Each subcommand only defines the arguments it supports, so clap rejects the rest
+        let (mode, args) = match args.subcommand() {
+            Some((COMMAND_EXPORT, export_args)) => (Mode::Export, export_args),
+            Some((COMMAND_DIAGNOSE, diagnose_args)) => (Mode::Diagnose, diagnose_args),
+            Some((COMMAND_LIST_CHATS, list_args)) => {
+                let list_format = match get_value(list_args, OPTION_LIST_FORMAT) {
+                    Some(format) => ListFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                        "{format} is not a valid list format! Must be one of <{SUPPORTED_LIST_FORMATS}>"
+                    )))?,
+                    None => ListFormat::default(),
+                };
+                (Mode::ListChats(list_format), list_args)
+            }
+            Some((COMMAND_STATS, stats_args)) => {
+                let stats_format = match get_value(stats_args, OPTION_STATS_FORMAT) {
+                    Some(format) => StatsFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                        "{format} is not a valid stats format! Must be one of <{SUPPORTED_STATS_FORMATS}>"
+                    )))?,
+                    None => StatsFormat::default(),
+                };
+                (Mode::Stats(stats_format), stats_args)
+            }
+            Some((COMMAND_SEARCH, search_args)) => {
+                let limit = parse_count(get_value(search_args, OPTION_LIMIT), OPTION_LIMIT)?
+                    .unwrap_or(DEFAULT_SEARCH_LIMIT);
+                if limit == 0 {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "Option {OPTION_LIMIT} must be greater than 0"
+                    )));
+                }
+                let query = SearchQuery {
+                    query: get_value(search_args, OPTION_QUERY)
+                        .cloned()
+                        .unwrap_or_default(),
+                    limit,
+                    context: parse_count(get_value(search_args, OPTION_CONTEXT), OPTION_CONTEXT)?
+                        .unwrap_or(DEFAULT_SEARCH_CONTEXT),
+                    index_path: get_value(search_args, OPTION_INDEX_PATH)
+                        .map(PathBuf::from)
+                        .unwrap_or_else(SearchQuery::default_index_path),
+                    rebuild: get_flag(search_args, OPTION_REBUILD_INDEX),
+                };
+                (Mode::Search(query), search_args)
+            }
+            _ => {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "No command specified! Must be one of <{COMMAND_EXPORT}, {COMMAND_DIAGNOSE}, {COMMAND_LIST_CHATS}, {COMMAND_STATS}, {COMMAND_SEARCH}>"
+                )))
+            }
+        };
+
+        let user_path = get_value(args, OPTION_DB_PATH);
+        let attachment_root = get_value(args, OPTION_ATTACHMENT_ROOT);
+        let attachment_manager_type = get_value(args, OPTION_ATTACHMENT_MANAGER);
+        let export_file_type = get_value(args, OPTION_EXPORT_TYPE);
+        let user_export_path = get_value(args, OPTION_EXPORT_PATH);
+        let start_date = get_value(args, OPTION_START_DATE);
+        let end_date = get_value(args, OPTION_END_DATE);
+        let no_lazy = get_flag(args, OPTION_DISABLE_LAZY_LOADING);
+        let custom_name = get_value(args, OPTION_CUSTOM_NAME);
+        let platform_type = get_value(args, OPTION_PLATFORM);
+        let ignore_disk_space = get_flag(args, OPTION_BYPASS_FREE_SPACE_CHECK);
+        let timezone_name = get_value(args, OPTION_TIMEZONE);
+        let chat_timezone_names = get_value(args, OPTION_CHAT_TIMEZONES);
+        let iso_timestamps = get_flag(args, OPTION_ISO_TIMESTAMPS);
+        let locale_name = get_value(args, OPTION_LOCALE);
+        let date_format = get_value(args, OPTION_DATE_FORMAT);
+        let exclusive_end = get_flag(args, OPTION_EXCLUSIVE_END);
+        let pagination_mode = get_value(args, OPTION_PAGINATE);
+        let theme_name = get_value(args, OPTION_THEME);
+        let custom_css = get_value(args, OPTION_CUSTOM_CSS);
+        let link_css = get_flag(args, OPTION_LINK_CSS);
+        let template_dir = get_value(args, OPTION_TEMPLATES);
+        let embed_limit = parse_count(get_value(args, OPTION_EMBED_LIMIT), OPTION_EMBED_LIMIT)?;
+        let conversation_ids = get_value(args, OPTION_CONVERSATIONS);
+        let eml = get_flag(args, OPTION_EML);
+        let dedupe = get_flag(args, OPTION_DEDUPE);
+        let attachment_names_type = get_value(args, OPTION_ATTACHMENT_NAMES);
 
         // Build the export type
         let export_type: Option<ExportType> = match export_file_type {
@@ -93,26 +256,13 @@ impl Options {
             None => None,
         };
 
-        // Ensure an export type is specified if other export options are selected
-        if attachment_manager_type.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_ATTACHMENT_MANAGER} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
-        }
-        if user_export_path.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_EXPORT_PATH} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
-        }
-        if start_date.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_START_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
-        }
-        if end_date.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_END_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+        // Clap only knows which arguments depend on each other, not which values they need
+        for (option, format) in FORMAT_SPECIFIC_OPTIONS {
+            if is_set(args, option) && export_type.as_ref() != Some(&format) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {option} is enabled, but the format specified is not `{format}`!"
+                )));
+            }
         }
 
         // Warn the user if they are exporting to a file type for which lazy loading has no effect
@@ -122,35 +272,84 @@ impl Options {
             );
         }
 
-        // Ensure that if diagnostics are enabled, no other options are
-        if diagnostic && attachment_manager_type.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Diagnostics are enabled; {OPTION_ATTACHMENT_MANAGER} is disallowed"
-            )));
-        }
-        if diagnostic && user_export_path.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Diagnostics are enabled; {OPTION_EXPORT_PATH} is disallowed"
-            )));
+        // Build the pagination mode, which only HTML exports support
+        let pagination = match pagination_mode {
+            Some(mode) => Pagination::from_cli(mode).ok_or(RuntimeError::InvalidOptions(format!(
+                "{mode} is not a valid pagination mode! Must be one of <{SUPPORTED_PAGINATION_MODES}>, where COUNT is a number of messages"
+            )))?,
+            None => Pagination::default(),
+        };
+
+        // Build the theme and validate the custom stylesheet, which only HTML exports support
+        let theme = match theme_name {
+            Some(name) => Theme::from_cli(name).ok_or(RuntimeError::InvalidOptions(format!(
+                "{name} is not a valid theme! Must be one of <{SUPPORTED_THEMES}>"
+            )))?,
+            None => Theme::default(),
+        };
+        if let Some(path) = custom_css {
+            if !PathBuf::from(path).is_file() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Supplied {OPTION_CUSTOM_CSS} `{path}` does not exist!"
+                )));
+            }
         }
-        if diagnostic && export_file_type.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Diagnostics are enabled; {OPTION_EXPORT_TYPE} is disallowed"
-            )));
+
+        // Validate the template directory, which only HTML exports support
+        if let Some(path) = template_dir {
+            if !PathBuf::from(path).is_dir() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Supplied {OPTION_TEMPLATES} `{path}` is not a directory!"
+                )));
+            }
         }
-        if diagnostic && start_date.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Diagnostics are enabled; {OPTION_START_DATE} is disallowed"
-            )));
+
+        // Build the set of conversations, which only EPUB exports support
+        let conversations = conversation_ids
+            .map(|ids| parse_conversations(ids))
+            .transpose()?;
+
+        // Build the time zone
+        let timezone = match timezone_name {
+            Some(name) => Timezone::from_cli(name).ok_or(RuntimeError::InvalidOptions(format!(
+                "{name} is not a valid time zone! Must be `local`, `UTC`, or an IANA name like `America/New_York`"
+            )))?,
+            None => Timezone::default(),
+        };
+
+        // Build the time zones for individual conversations
+        let chat_timezones = chat_timezone_names
+            .map(|pairs| parse_chat_timezones(pairs))
+            .transpose()?
+            .unwrap_or_default();
+
+        // Warn the user that time zones have no effect on UTC timestamps
+        if (timezone_name.is_some() || chat_timezone_names.is_some()) && iso_timestamps {
+            eprintln!(
+                "Option {OPTION_TIMEZONE} or {OPTION_CHAT_TIMEZONES} is enabled, but {OPTION_ISO_TIMESTAMPS} is also enabled, so timestamps will be written in UTC!"
+            );
         }
-        if diagnostic && end_date.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
-                "Diagnostics are enabled; {OPTION_END_DATE} is disallowed"
-            )));
+
+        // Build the locale
+        let locale = match locale_name {
+            Some(name) => Locale::from_cli(name).ok_or(RuntimeError::InvalidOptions(format!(
+                "{name} is not a valid locale! Must be one of <{SUPPORTED_LOCALES}>"
+            )))?,
+            None => Locale::default(),
+        };
+
+        // Validate the custom date pattern
+        if let Some(pattern) = date_format {
+            if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "`{pattern}` is not a valid {OPTION_DATE_FORMAT} pattern!"
+                )));
+            }
         }
 
         // Build query context
         let mut query_context = QueryContext::default();
+        query_context.set_timezone(timezone);
         if let Some(start) = start_date {
             if let Err(why) = query_context.set_start(start) {
                 return Err(RuntimeError::InvalidOptions(format!("{why}")));
@@ -161,6 +360,7 @@ impl Options {
                 return Err(RuntimeError::InvalidOptions(format!("{why}")));
             }
         }
+        query_context.set_end_exclusive(exclusive_end);
 
         // We have to allocate a PathBuf here because it can be created from data owned by this function in the default state
         let db_path = match user_path {
@@ -195,7 +395,7 @@ impl Options {
         }
 
         // Determine the attachment manager mode
-        let attachment_manager_mode = match attachment_manager_type {
+        let mut attachment_manager_mode = match attachment_manager_type {
             Some(manager) => {
                 AttachmentManager::from_cli(manager).ok_or(RuntimeError::InvalidOptions(format!(
                     "{manager} is not a valid attachment manager mode! Must be one of <{SUPPORTED_ATTACHMENT_MANAGER_MODES}>"
@@ -204,6 +404,64 @@ impl Options {
             None => AttachmentManager::default(),
         };
 
+        // Embedding attachments writes them into the HTML, so the other formats cannot use it
+        if let AttachmentManager::Embedded(limit) = &mut attachment_manager_mode {
+            if export_type != Some(ExportType::Html) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_ATTACHMENT_MANAGER} is `{attachment_manager_mode}`, but the format specified is not `html`!"
+                )));
+            }
+            match embed_limit {
+                Some(0) => {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "Option {OPTION_EMBED_LIMIT} must be greater than 0"
+                    )))
+                }
+                Some(megabytes) => *limit = megabytes as u64 * BYTES_PER_MB,
+                None => {}
+            }
+        } else if embed_limit.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_EMBED_LIMIT} requires `--{OPTION_ATTACHMENT_MANAGER} embedded`"
+            )));
+        }
+
+        // Deduplicated files are shared between messages, so they must be copied and never removed
+        if dedupe
+            && matches!(
+                attachment_manager_mode,
+                AttachmentManager::Disabled | AttachmentManager::Embedded(_)
+            )
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_DEDUPE} requires attachments to be copied with {OPTION_ATTACHMENT_MANAGER}, but not `embedded`"
+            )));
+        }
+
+        // Build the attachment naming scheme, which only applies to copied attachments
+        let attachment_names = match attachment_names_type {
+            Some(names) => AttachmentNames::from_cli(names).ok_or(RuntimeError::InvalidOptions(format!(
+                "{names} is not a valid attachment naming scheme! Must be one of <{SUPPORTED_ATTACHMENT_NAMES}>"
+            )))?,
+            None => AttachmentNames::default(),
+        };
+        if attachment_names_type.is_some()
+            && matches!(attachment_manager_mode, AttachmentManager::Disabled)
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_ATTACHMENT_NAMES} requires attachments to be copied with {OPTION_ATTACHMENT_MANAGER}"
+            )));
+        }
+
+        // Warn the user that email and SMS backup exports read attachments in place instead of copying them
+        if let Some(export_type @ (ExportType::Mbox | ExportType::Xml)) = &export_type {
+            if attachment_manager_type.is_some() {
+                eprintln!(
+                    "Option {OPTION_ATTACHMENT_MANAGER} is enabled, but `{export_type}` exports read attachments from their original location!"
+                );
+            }
+        }
+
         // Validate the provided export path
         let export_path = validate_path(user_export_path, &export_type.as_ref())?;
 
@@ -211,7 +469,7 @@ impl Options {
             db_path,
             attachment_root: attachment_root.cloned(),
             attachment_manager: attachment_manager_mode,
-            diagnostic,
+            mode,
             export_type,
             export_path,
             query_context,
@@ -219,6 +477,20 @@ impl Options {
             custom_name: custom_name.cloned(),
             platform,
             ignore_disk_space,
+            timezone,
+            chat_timezones,
+            iso_timestamps,
+            locale,
+            date_format: date_format.cloned(),
+            pagination,
+            theme,
+            custom_css: custom_css.map(PathBuf::from),
+            link_css,
+            template_dir: template_dir.map(PathBuf::from),
+            conversations,
+            eml,
+            dedupe,
+            attachment_names,
         })
     }
 
@@ -254,13 +526,11 @@ fn validate_path(
             // Ensure the directory exists and does not contain files of the same export type
             match resolved_path.read_dir() {
                 Ok(files) => {
-                    let export_type_extension = export_type.to_string();
+                    let export_type_extensions = export_type.extensions();
                     for file in files.flatten() {
-                        if file
-                            .path()
-                            .extension()
-                            .is_some_and(|s| s.to_str().unwrap_or("") == export_type_extension)
-                        {
+                        if file.path().extension().is_some_and(|s| {
+                            export_type_extensions.contains(&s.to_str().unwrap_or(""))
+                        }) {
                             return Err(RuntimeError::InvalidOptions(format!(
                                 "{path_word} export path {resolved_path:?} contains existing \"{export_type}\" export data!"
                             )));
@@ -279,83 +549,297 @@ fn validate_path(
     Ok(resolved_path)
 }
 
-/// Build the command line argument parser
-fn get_command() -> Command {
-    Command::new("iMessage Exporter")
-        .version(crate_version!())
-        .about(ABOUT)
-        .arg_required_else_help(true)
-        .arg(
-            Arg::new(OPTION_DIAGNOSTIC)
-            .short('d')
-            .long(OPTION_DIAGNOSTIC)
-            .help("Print diagnostic information and exit\n")
-            .action(ArgAction::SetTrue)
-            .display_order(0),
-        )
-        .arg(
-            Arg::new(OPTION_EXPORT_TYPE)
-            .short('f')
-            .long(OPTION_EXPORT_TYPE)
-            .help("Specify a single file format to export messages into\n")
-            .display_order(1)
-            .value_name(SUPPORTED_FILE_TYPES),
-        )
-        .arg(
-            Arg::new(OPTION_ATTACHMENT_MANAGER)
-            .short('c')
-            .long(OPTION_ATTACHMENT_MANAGER)
-            .help(format!("Specify an optional method to use when copying message attachments\nCompatible will convert HEIC files to JPEG\nEfficient will copy files without converting anything\nIf omitted, the default is `{}`\n", AttachmentManager::default()))
-            .display_order(2)
-            .value_name(SUPPORTED_ATTACHMENT_MANAGER_MODES),
-        )
-        .arg(
-            Arg::new(OPTION_DB_PATH)
-                .short('p')
-                .long(OPTION_DB_PATH)
-                .help(format!("Specify an optional custom path for the iMessage database location\nFor macOS, specify a path to a `chat.db` file\nFor iOS, specify a path to the root of an unencrypted backup directory\nIf omitted, the default directory is {}\n", default_db_path().display()))
-                .display_order(3)
-                .value_name("path/to/source"),
-        )
-        .arg(
-            Arg::new(OPTION_ATTACHMENT_ROOT)
-                .short('r')
-                .long(OPTION_ATTACHMENT_ROOT)
-                .help(format!("Specify an optional custom path to look for attachments in (macOS only)\nOnly use this if attachments are stored separately from the database's default location\nThe default location is {}\n", DEFAULT_ATTACHMENT_ROOT.replacen('~', &home(), 1)))
-                .display_order(4)
-                .value_name("path/to/attachments"),
-        )
-        .arg(
-            Arg::new(OPTION_PLATFORM)
+/// Parse a non-negative number passed to `id`
+fn parse_count(value: Option<&String>, id: &str) -> Result<Option<usize>, RuntimeError> {
+    value
+        .map(|count| {
+            count.parse().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{count} is not a valid {id}! Must be a whole number"
+                ))
+            })
+        })
+        .transpose()
+}
+
+/// Parse a comma-separated list of conversation IDs passed to `--conversations`
+fn parse_conversations(value: &str) -> Result<BTreeSet<i32>, RuntimeError> {
+    let ids = value
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{id} is not a valid conversation ID! Use the IDs shown by `{COMMAND_LIST_CHATS}`"
+                ))
+            })
+        })
+        .collect::<Result<BTreeSet<i32>, RuntimeError>>()?;
+
+    if ids.is_empty() {
+        return Err(RuntimeError::InvalidOptions(format!(
+            "Option {OPTION_CONVERSATIONS} requires at least one conversation ID"
+        )));
+    }
+    Ok(ids)
+}
+
+/// Parse a comma-separated list of `ID=zone` pairs passed to `--chat-timezones`
+fn parse_chat_timezones(value: &str) -> Result<BTreeMap<i32, Timezone>, RuntimeError> {
+    let timezones = value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (id, name) = pair.split_once('=').ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!(
+                    "`{pair}` is not a valid {OPTION_CHAT_TIMEZONES} entry! Use `ID=zone`, i.e. `1=America/New_York`"
+                ))
+            })?;
+            let id = id.trim().parse().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{id} is not a valid conversation ID! Use the IDs shown by `{COMMAND_LIST_CHATS}`"
+                ))
+            })?;
+            let timezone = Timezone::from_cli(name.trim()).ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!(
+                    "{name} is not a valid time zone! Must be `local`, `UTC`, or an IANA name like `America/New_York`"
+                ))
+            })?;
+            Ok((id, timezone))
+        })
+        .collect::<Result<BTreeMap<i32, Timezone>, RuntimeError>>()?;
+
+    if timezones.is_empty() {
+        return Err(RuntimeError::InvalidOptions(format!(
+            "Option {OPTION_CHAT_TIMEZONES} requires at least one `ID=zone` pair"
+        )));
+    }
+    Ok(timezones)
+}
+
+/// Combine the arguments passed on the command line with the values in the config file
+///
+/// Only arguments the selected subcommand defines are read from the file. The combined arguments
+/// are parsed again with the rules from [`add_requirements()`], so clap validates values from the
+/// file the same way as values passed on the command line.
+fn merge_config(args: &ArgMatches, file: &ConfigFile) -> Result<ArgMatches, RuntimeError> {
+    let command = add_requirements(get_command());
+    let Some((name, subcommand_args)) = args.subcommand() else {
+        return Ok(args.clone());
+    };
+    let Some(subcommand) = command.find_subcommand(name) else {
+        return Ok(args.clone());
+    };
+
+    let mut merged = vec![command.get_name().to_string(), name.to_string()];
+    let mut positional = vec![];
+    for arg in subcommand.get_arguments() {
+        let id = arg.get_id().as_str();
+        let from_command_line = subcommand_args.value_source(id) == Some(ValueSource::CommandLine);
+
+        // Positional arguments cannot be set in the config file
+        let Some(long) = arg.get_long() else {
+            if let Some(value) = get_value(subcommand_args, id) {
+                positional.push(value.clone());
+            }
+            continue;
+        };
+
+        if arg.get_action().takes_values() {
+            // Values passed on the command line take precedence over values in the config file
+            let value = match from_command_line {
+                true => get_value(subcommand_args, id),
+                false => file.value(id),
+            };
+            if let Some(value) = value {
+                merged.push(format!("--{long}={value}"));
+            }
+        } else if get_flag(subcommand_args, id) || file.flag(id) {
+            merged.push(format!("--{long}"));
+        }
+    }
+    if !positional.is_empty() {
+        merged.push("--".to_string());
+        merged.extend(positional);
+    }
+
+    command
+        .try_get_matches_from(merged)
+        .map_err(|why| RuntimeError::InvalidOptions(why.to_string()))
+}
+
+/// Add the rules for which arguments require or conflict with each other
+///
+/// Any argument can be set in the config file instead, so these are only checked once the config
+/// file is merged with the command line.
+fn add_requirements(command: Command) -> Command {
+    [COMMAND_EXPORT, COMMAND_STATS, COMMAND_SEARCH]
+        .into_iter()
+        .fold(command, |command, name| {
+            command.mut_subcommand(name, |subcommand| {
+                subcommand.mut_arg(OPTION_EXCLUSIVE_END, |arg| arg.requires(OPTION_END_DATE))
+            })
+        })
+        .mut_subcommand(COMMAND_EXPORT, |export| {
+            export
+                .mut_arg(OPTION_EXPORT_TYPE, |arg| arg.required(true))
+                .mut_arg(OPTION_LINK_CSS, |arg| arg.requires(OPTION_CUSTOM_CSS))
+                .mut_arg(OPTION_EMBED_LIMIT, |arg| {
+                    arg.requires(OPTION_ATTACHMENT_MANAGER)
+                })
+                .mut_arg(OPTION_DEDUPE, |arg| arg.requires(OPTION_ATTACHMENT_MANAGER))
+                .mut_arg(OPTION_ATTACHMENT_NAMES, |arg| {
+                    arg.requires(OPTION_ATTACHMENT_MANAGER)
+                })
+                .mut_arg(OPTION_DATE_FORMAT, |arg| {
+                    arg.conflicts_with(OPTION_ISO_TIMESTAMPS)
+                })
+        })
+}
+
+/// Get the value of an argument, if the selected subcommand supports it
+fn get_value<'a>(args: &'a ArgMatches, id: &str) -> Option<&'a String> {
+    // clap only returns an error for undefined arguments in debug builds
+    args.try_get_one(id).ok().flatten()
+}
+
+/// Determine if an argument was passed, if the selected subcommand supports it
+fn is_set(args: &ArgMatches, id: &str) -> bool {
+    matches!(args.try_contains_id(id), Ok(true))
+        && args.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+/// Determine if a flag is set, if the selected subcommand supports it
+fn get_flag(args: &ArgMatches, id: &str) -> bool {
+    matches!(args.try_get_one(id), Ok(Some(true)))
+}
+
+/// Build the arguments that describe where to read iMessage data from
+fn source_args() -> [Arg; 3] {
+    [
+        Arg::new(OPTION_DB_PATH)
+            .short('p')
+            .long(OPTION_DB_PATH)
+            .help(format!("Specify an optional custom path for the iMessage database location\nFor macOS, specify a path to a `chat.db` file\nFor iOS, specify a path to the root of an unencrypted backup directory\nIf omitted, the default directory is {}\n", default_db_path().display()))
+            .display_order(3)
+            .value_name("path/to/source"),
+        Arg::new(OPTION_ATTACHMENT_ROOT)
+            .short('r')
+            .long(OPTION_ATTACHMENT_ROOT)
+            .help(format!("Specify an optional custom path to look for attachments in (macOS only)\nOnly use this if attachments are stored separately from the database's default location\nThe default location is {}\n", DEFAULT_ATTACHMENT_ROOT.replacen('~', &home(), 1)))
+            .display_order(4)
+            .value_name("path/to/attachments"),
+        Arg::new(OPTION_PLATFORM)
             .short('a')
             .long(OPTION_PLATFORM)
             .help("Specify the platform the database was created on\nIf omitted, the platform type is determined automatically\n")
             .display_order(5)
             .value_name(SUPPORTED_PLATFORMS),
-        )
+    ]
+}
+
+/// Build the arguments that describe how dates and text are written
+fn display_args() -> [Arg; 4] {
+    [
+        Arg::new(OPTION_TIMEZONE)
+            .short('z')
+            .long(OPTION_TIMEZONE)
+            .help("Specify the time zone used to display dates and interpret date filters\nAccepts `local`, `UTC`, or an IANA time zone name\nIf omitted, the default is the local time zone\n")
+            .display_order(12)
+            .value_name("America/New_York"),
+        Arg::new(OPTION_CHAT_TIMEZONES)
+            .long(OPTION_CHAT_TIMEZONES)
+            .help(format!("Specify time zones used to display dates in specific conversations, overriding `--{OPTION_TIMEZONE}`\nUse a comma-separated list of the IDs shown by the `{COMMAND_LIST_CHATS}` command and time zones\n"))
+            .display_order(29)
+            .value_name("1=America/New_York,2=UTC"),
+        Arg::new(OPTION_LOCALE)
+            .short('g')
+            .long(OPTION_LOCALE)
+            .help(format!("Specify the language used for text written by the exporter\nIf omitted, the default is `{}`\n", Locale::default()))
+            .display_order(14)
+            .value_name(SUPPORTED_LOCALES),
+        Arg::new(OPTION_DATE_FORMAT)
+            .short('t')
+            .long(OPTION_DATE_FORMAT)
+            .help("Specify a custom `strftime` pattern used to display dates\nIf omitted, the default pattern for the selected locale is used\n")
+            .display_order(15)
+            .value_name("%Y-%m-%d %H:%M"),
+    ]
+}
+
+/// Build the arguments that filter which messages are read
+fn filter_args() -> [Arg; 3] {
+    [
+        Arg::new(OPTION_START_DATE)
+            .short('s')
+            .long(OPTION_START_DATE)
+            .help("The start date filter\nOnly messages sent on or after this date will be included\nAccepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM`, RFC 3339 datetimes, or relative dates like `yesterday 09:00`, `7d`, or `last-month`\n")
+            .display_order(7)
+            .value_name("YYYY-MM-DD"),
+        Arg::new(OPTION_END_DATE)
+            .short('e')
+            .long(OPTION_END_DATE)
+            .help("The end date filter\nOnly messages sent on or before this date will be included\nAccepts the same formats as the start date filter\n")
+            .display_order(8)
+            .value_name("YYYY-MM-DD"),
+        Arg::new(OPTION_EXCLUSIVE_END)
+            .short('x')
+            .long(OPTION_EXCLUSIVE_END)
+            .help("Exclude messages sent exactly at the end date\nUseful for back-to-back windows, i.e. `-s last-month -e this-month -x`\n")
+            .action(ArgAction::SetTrue)
+            .display_order(16),
+    ]
+}
+
+/// Build the arguments that describe where output is written and how the database owner is named
+fn output_args() -> [Arg; 2] {
+    [
+        Arg::new(OPTION_EXPORT_PATH)
+            .short('o')
+            .long(OPTION_EXPORT_PATH)
+            .help(format!("Specify an optional custom directory for outputting exported data\nIf omitted, the default directory is {}/{DEFAULT_OUTPUT_DIR}\n", home()))
+            .display_order(6)
+            .value_name("path/to/save/files"),
+        Arg::new(OPTION_CUSTOM_NAME)
+            .short('m')
+            .long(OPTION_CUSTOM_NAME)
+            .help("Specify an optional custom name for the database owner's messages in exports\n")
+            .display_order(10),
+    ]
+}
+
+/// Build the `export` subcommand
+fn export_command() -> Command {
+    Command::new(COMMAND_EXPORT)
+        .about("Export messages into a single file format")
+        .args(source_args())
+        .args(display_args())
+        .args(filter_args())
+        .args(output_args())
         .arg(
-            Arg::new(OPTION_EXPORT_PATH)
-                .short('o')
-                .long(OPTION_EXPORT_PATH)
-                .help(format!("Specify an optional custom directory for outputting exported data\nIf omitted, the default directory is {}/{DEFAULT_OUTPUT_DIR}\n", home()))
-                .display_order(6)
-                .value_name("path/to/save/files"),
+            Arg::new(OPTION_EXPORT_TYPE)
+                .short('f')
+                .long(OPTION_EXPORT_TYPE)
+                .help("Specify a single file format to export messages into\nRequired unless set in the config file\n")
+                .display_order(1)
+                .value_name(SUPPORTED_FILE_TYPES),
         )
         .arg(
-            Arg::new(OPTION_START_DATE)
-                .short('s')
-                .long(OPTION_START_DATE)
-                .help("The start date filter\nOnly messages sent on or after this date will be included\n")
-                .display_order(7)
-                .value_name("YYYY-MM-DD"),
+            Arg::new(OPTION_ATTACHMENT_MANAGER)
+                .short('c')
+                .long(OPTION_ATTACHMENT_MANAGER)
+                .help(format!("Specify an optional method to use when copying message attachments\nCompatible will convert HEIC files to JPEG\nEfficient will copy files without converting anything\nEmbedded will convert like Compatible, then write images, audio, and video into each HTML file\nHardlink, Symlink, and Reflink will link or clone files instead of copying them, copying if they cannot be linked\nIf omitted, the default is `{}`\n", AttachmentManager::default()))
+                .display_order(2)
+                .value_name(SUPPORTED_ATTACHMENT_MANAGER_MODES),
         )
         .arg(
-            Arg::new(OPTION_END_DATE)
-                .short('e')
-                .long(OPTION_END_DATE)
-                .help("The end date filter\nOnly messages sent before this date will be included\n")
-                .display_order(8)
-                .value_name("YYYY-MM-DD"),
+            Arg::new(OPTION_EMBED_LIMIT)
+                .long(OPTION_EMBED_LIMIT)
+                .help(format!("Specify the size of the largest attachment to embed, in megabytes\nLarger attachments are copied to the export directory and linked instead\nIf omitted, the default is {DEFAULT_EMBED_LIMIT_MB}\n"))
+                .display_order(24)
+                .value_name("MB"),
         )
         .arg(
             Arg::new(OPTION_DISABLE_LAZY_LOADING)
@@ -365,13 +849,6 @@ fn get_command() -> Command {
                 .action(ArgAction::SetTrue)
                 .display_order(9),
         )
-        .arg(
-            Arg::new(OPTION_CUSTOM_NAME)
-                .short('m')
-                .long(OPTION_CUSTOM_NAME)
-                .help("Specify an optional custom name for the database owner's messages in exports\n")
-                .display_order(10)
-        )
         .arg(
             Arg::new(OPTION_BYPASS_FREE_SPACE_CHECK)
                 .short('b')
@@ -380,6 +857,194 @@ fn get_command() -> Command {
                 .action(ArgAction::SetTrue)
                 .display_order(11)
         )
+        .arg(
+            Arg::new(OPTION_PAGINATE)
+                .short('P')
+                .long(OPTION_PAGINATE)
+                .help("Split each conversation in HTML exports into multiple files\nStart a new file every year, every month, or after COUNT messages\nIf omitted, each conversation is written to a single file\n")
+                .display_order(19)
+                .value_name(SUPPORTED_PAGINATION_MODES),
+        )
+        .arg(
+            Arg::new(OPTION_THEME)
+                .short('T')
+                .long(OPTION_THEME)
+                .help(format!("Specify the color theme used in HTML exports\nAuto follows the reader's system light or dark mode setting\nIf omitted, the default is `{}`\n", Theme::default()))
+                .display_order(20)
+                .value_name(SUPPORTED_THEMES),
+        )
+        .arg(
+            Arg::new(OPTION_CUSTOM_CSS)
+                .long(OPTION_CUSTOM_CSS)
+                .help("Specify an optional stylesheet to apply to HTML exports after the built-in styles\nBy default, the stylesheet is embedded in every exported file\n")
+                .display_order(21)
+                .value_name("path/to/style.css"),
+        )
+        .arg(
+            Arg::new(OPTION_LINK_CSS)
+                .long(OPTION_LINK_CSS)
+                .help("Copy the custom stylesheet to the export directory and link to it instead of embedding it\n")
+                .action(ArgAction::SetTrue)
+                .display_order(22),
+        )
+        .arg(
+            Arg::new(OPTION_TEMPLATES)
+                .long(OPTION_TEMPLATES)
+                .help("Specify an optional directory of templates that replace the markup for messages, reactions, attachments, and app balloons in HTML exports\nFiles that are not present use the built-in markup\n")
+                .display_order(23)
+                .value_name("path/to/templates"),
+        )
+        .arg(
+            Arg::new(OPTION_CONVERSATIONS)
+                .long(OPTION_CONVERSATIONS)
+                .help(format!("Specify a comma-separated list of conversations to include in EPUB exports
+Use the IDs shown by the `{COMMAND_LIST_CHATS}` command
+If omitted, every conversation is exported
+"))
+                .display_order(25)
+                .value_name("1,2,3"),
+        )
+        .arg(
+            Arg::new(OPTION_EML)
+                .long(OPTION_EML)
+                .help("Write each message in mbox exports to its own `.eml` file\nFiles are grouped into a directory for each conversation\n")
+                .action(ArgAction::SetTrue)
+                .display_order(26),
+        )
+        .arg(
+            Arg::new(OPTION_DEDUPE)
+                .long(OPTION_DEDUPE)
+                .help("Copy each unique attachment once, named by a hash of its contents\nMessages that share a file all point to the same copy\n")
+                .action(ArgAction::SetTrue)
+                .display_order(27),
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_NAMES)
+                .long(OPTION_ATTACHMENT_NAMES)
+                .help(format!("Specify how copied attachments are named\nOriginal uses the name the file was sent with, adding a number if the name is taken\nDated adds the date the message was sent before the original name\nIf omitted, the default is `{}`\n", AttachmentNames::default()))
+                .display_order(28)
+                .value_name(SUPPORTED_ATTACHMENT_NAMES),
+        )
+        .arg(
+            Arg::new(OPTION_ISO_TIMESTAMPS)
+                .short('i')
+                .long(OPTION_ISO_TIMESTAMPS)
+                .help("Write timestamps in ISO-8601 format in UTC, i.e. `2020-05-20T16:10:11Z`\n")
+                .action(ArgAction::SetTrue)
+                .display_order(13)
+        )
+}
+
+/// Build the `diagnose` subcommand
+fn diagnose_command() -> Command {
+    Command::new(COMMAND_DIAGNOSE)
+        .about("Print diagnostic information about the database and exit")
+        .args(source_args())
+}
+
+/// Build the `list-chats` subcommand
+fn list_chats_command() -> Command {
+    Command::new(COMMAND_LIST_CHATS)
+        .about("List every conversation with its participants, message counts, and date range")
+        .args(source_args())
+        .args(display_args())
+        .arg(
+            Arg::new(OPTION_LIST_FORMAT)
+                .short('f')
+                .long("format")
+                .help(format!(
+                    "Specify the format used to print the list\nIf omitted, the default is `{}`\n",
+                    ListFormat::default()
+                ))
+                .display_order(1)
+                .value_name(SUPPORTED_LIST_FORMATS),
+        )
+}
+
+/// Build the `stats` subcommand
+fn stats_command() -> Command {
+    Command::new(COMMAND_STATS)
+        .about("Write a report of message counts, attachments, reactions, and response times")
+        .args(source_args())
+        .args(display_args())
+        .args(filter_args())
+        .args(output_args())
+        .arg(
+            Arg::new(OPTION_STATS_FORMAT)
+                .short('f')
+                .long("format")
+                .help(format!("Specify the format used to write the report\nIf omitted, the default is `{}`\n", StatsFormat::default()))
+                .display_order(1)
+                .value_name(SUPPORTED_STATS_FORMATS),
+        )
+}
+
+/// Build the `search` subcommand
+fn search_command() -> Command {
+    Command::new(COMMAND_SEARCH)
+        .about("Search message text, including earlier versions of edited messages")
+        .args(source_args())
+        .args(display_args())
+        .args(filter_args())
+        .arg(
+            Arg::new(OPTION_QUERY)
+                .help("The text to search for\nUse quotes for phrases, i.e. `'\"see you soon\"'`, and `*` for prefixes, i.e. `resta*`\n")
+                .required(true)
+                .value_name("QUERY"),
+        )
+        .arg(
+            Arg::new(OPTION_LIMIT)
+                .short('n')
+                .long(OPTION_LIMIT)
+                .help(format!("The maximum number of matching messages to show\nIf omitted, the default is {DEFAULT_SEARCH_LIMIT}\n"))
+                .display_order(1)
+                .value_name("count"),
+        )
+        .arg(
+            Arg::new(OPTION_CONTEXT)
+                .short('C')
+                .long(OPTION_CONTEXT)
+                .help(format!("The number of messages to show before and after each match\nIf omitted, the default is {DEFAULT_SEARCH_CONTEXT}\n"))
+                .display_order(2)
+                .value_name("count"),
+        )
+        .arg(
+            Arg::new(OPTION_INDEX_PATH)
+                .short('i')
+                .long(OPTION_INDEX_PATH)
+                .help(format!("Specify an optional custom path for the search index\nThe index is built on the first search and rebuilt when the database changes\nIf omitted, the default location is {}/{DEFAULT_INDEX_PATH}\n", home()))
+                .display_order(17)
+                .value_name("path/to/index.db"),
+        )
+        .arg(
+            Arg::new(OPTION_REBUILD_INDEX)
+                .long(OPTION_REBUILD_INDEX)
+                .help("Rebuild the search index even if the database has not changed\n")
+                .action(ArgAction::SetTrue)
+                .display_order(18),
+        )
+}
+
+/// Build the command line argument parser
+fn get_command() -> Command {
+    Command::new("iMessage Exporter")
+        .version(crate_version!())
+        .about(ABOUT)
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new(OPTION_CONFIG)
+                .long(OPTION_CONFIG)
+                .help(format!("Specify an optional path to a TOML file containing default option values\nFlags passed on the command line override values in the file\nIf omitted, the default location is {}/{DEFAULT_CONFIG_PATH}\n", home()))
+                .global(true)
+                .display_order(100)
+                .value_name("path/to/config.toml"),
+        )
+        .subcommand(export_command())
+        .subcommand(diagnose_command())
+        .subcommand(list_chats_command())
+        .subcommand(stats_command())
+        .subcommand(search_command())
 }
 
 /// Parse arguments from the command line
@@ -389,133 +1054,421 @@ pub fn from_command_line() -> ArgMatches {
 
 #[cfg(test)]
 mod arg_tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        path::PathBuf,
+    };
+
     use imessage_database::util::{
-        dirs::default_db_path, platform::Platform, query_context::QueryContext,
+        dates::Timezone, dirs::default_db_path, platform::Platform, query_context::QueryContext,
     };
 
     use crate::app::{
-        attachment_manager::AttachmentManager,
+        attachment_manager::{AttachmentManager, BYTES_PER_MB},
+        attachment_names::AttachmentNames,
+        chat_list::ListFormat,
+        config_file::ConfigFile,
         export_type::ExportType,
+        locale::Locale,
+        mode::Mode,
         options::{get_command, validate_path, Options},
+        pagination::Pagination,
+        search::SearchQuery,
+        stats::StatsFormat,
+        theme::Theme,
     };
 
     #[test]
-    fn can_build_option_diagnostic_flag() {
-        // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d"];
-        let command = get_command();
-        let args = command.get_matches_from(cli_args);
-
-        // Build the Options
-        let actual = Options::from_args(&args).unwrap();
-
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::default(),
-            diagnostic: true,
-            export_type: None,
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-        };
-
-        assert_eq!(actual, expected);
-    }
-
-    #[test]
-    fn cant_build_option_diagnostic_flag_with_export_type() {
+    fn cant_build_option_no_command() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-f", "txt"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt"];
         let command = get_command();
-        let args = command.get_matches_from(cli_args);
-
-        // Build the Options
-        let actual = Options::from_args(&args);
+        let args = command.try_get_matches_from(cli_args);
 
-        assert!(actual.is_err());
+        assert!(args.is_err());
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_export_path() {
+    fn can_build_option_export_from_config_file() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-o", "~/test"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let file = ConfigFile::parse(
+            "format = \"txt\"\ncopy-method = \"efficient\"\ncustom-name = \"Name\"\nplatform = \"macOS\"",
+        )
+        .unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
 
-        assert!(actual.is_err());
+        assert_eq!(actual.export_type, Some(ExportType::Txt));
+        assert_eq!(actual.attachment_manager, AttachmentManager::Efficient);
+        assert_eq!(actual.custom_name, Some("Name".to_string()));
+        assert_eq!(actual.platform, Platform::macOS);
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_attachment_manager() {
+    fn can_build_option_cli_overrides_config_file() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-c", "compatible"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "-m", "CLI"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let file =
+            ConfigFile::parse("format = \"txt\"\ncustom-name = \"File\"\nno-lazy = true").unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
 
-        assert!(actual.is_err());
+        assert_eq!(actual.export_type, Some(ExportType::Html));
+        assert_eq!(actual.custom_name, Some("CLI".to_string()));
+        assert!(actual.no_lazy);
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_start_date() {
+    fn can_build_option_diagnose_ignores_export_config() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-s", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let file = ConfigFile::parse("format = \"txt\"\nstart-date = \"2020-01-01\"").unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
 
-        assert!(actual.is_err());
+        assert_eq!(actual.mode, Mode::Diagnose);
+        assert_eq!(actual.export_type, None);
+        assert_eq!(actual.query_context, QueryContext::default());
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_end() {
+    fn cant_build_option_invalid_config_file_value() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let file = ConfigFile::parse("platform = \"Android\"").unwrap();
+        let actual = Options::from_args(&args, &file);
 
         assert!(actual.is_err());
     }
 
     #[test]
-    fn can_build_option_export_html() {
+    fn can_build_option_diagnose() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-o", "/tmp"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args).unwrap();
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
 
         // Expected data
-        let tmp_dir = String::from("/tmp");
         let expected = Options {
             db_path: default_db_path(),
             attachment_root: None,
             attachment_manager: AttachmentManager::default(),
-            diagnostic: false,
-            export_type: Some(ExportType::Html),
-            export_path: validate_path(Some(&tmp_dir), &None).unwrap(),
+            mode: Mode::Diagnose,
+            export_type: None,
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            timezone: Timezone::Local,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_list_chats() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "list-chats", "-f", "json"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            mode: Mode::ListChats(ListFormat::Json),
+            export_type: None,
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            timezone: Timezone::Local,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_list_chats_default_format() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "list-chats"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.mode, Mode::ListChats(ListFormat::Table));
+        assert_eq!(actual.export_type, None);
+    }
+
+    #[test]
+    fn cant_build_option_invalid_list_format() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "list-chats", "-f", "html"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_list_chats_with_export_path() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "list-chats", "-o", "~/test"];
+        let command = get_command();
+        let args = command.try_get_matches_from(cli_args);
+
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn can_build_option_stats() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "stats",
+            "-f",
+            "html",
+            "-s",
+            "2020-01-01",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.mode, Mode::Stats(StatsFormat::Html));
+        assert_eq!(actual.export_type, None);
+        assert!(actual.query_context.start.is_some());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_stats_format() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "stats", "-f", "table"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_search() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "search",
+            "\"see you soon\"",
+            "-n",
+            "5",
+            "-C",
+            "0",
+            "-i",
+            "/tmp/index.db",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(
+            actual.mode,
+            Mode::Search(SearchQuery {
+                query: "\"see you soon\"".to_string(),
+                limit: 5,
+                context: 0,
+                index_path: "/tmp/index.db".into(),
+                rebuild: false,
+            })
+        );
+        assert_eq!(actual.export_type, None);
+    }
+
+    #[test]
+    fn can_build_option_search_defaults() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "search", "dinner", "--rebuild-index"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(
+            actual.mode,
+            Mode::Search(SearchQuery {
+                query: "dinner".to_string(),
+                limit: 20,
+                context: 2,
+                index_path: SearchQuery::default_index_path(),
+                rebuild: true,
+            })
+        );
+    }
+
+    #[test]
+    fn cant_build_option_search_invalid_limit() {
+        for limit in ["0", "ten"] {
+            // Get matches from sample args
+            let cli_args: Vec<&str> = vec!["imessage-exporter", "search", "dinner", "-n", limit];
+            let command = get_command();
+            let args = command.get_matches_from(cli_args);
+
+            // Build the Options
+            let actual = Options::from_args(&args, &ConfigFile::default());
+
+            assert!(actual.is_err());
+        }
+    }
+
+    #[test]
+    fn cant_build_option_diagnose_with_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose", "-f", "txt"];
+        let command = get_command();
+        let args = command.try_get_matches_from(cli_args);
+
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnose_with_export_path() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose", "-o", "~/test"];
+        let command = get_command();
+        let args = command.try_get_matches_from(cli_args);
+
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnose_with_attachment_manager() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose", "-c", "compatible"];
+        let command = get_command();
+        let args = command.try_get_matches_from(cli_args);
+
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnose_with_start_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose", "-s", "2020-01-01"];
+        let command = get_command();
+        let args = command.try_get_matches_from(cli_args);
+
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnose_with_end() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose", "-e", "2020-01-01"];
+        let command = get_command();
+        let args = command.try_get_matches_from(cli_args);
+
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn can_build_option_export_html() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "-o", "/tmp"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        // Expected data
+        let tmp_dir = String::from("/tmp");
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            mode: Mode::Export,
+            export_type: Some(ExportType::Html),
+            export_path: validate_path(Some(&tmp_dir), &None).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
             platform: Platform::default(),
             ignore_disk_space: false,
+            timezone: Timezone::Local,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
         };
 
         assert_eq!(actual, expected);
@@ -524,19 +1477,19 @@ mod arg_tests {
     #[test]
     fn can_build_option_export_txt_no_lazy() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-l"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "-l"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args).unwrap();
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
 
         // Expected data
         let expected = Options {
             db_path: default_db_path(),
             attachment_root: None,
             attachment_manager: AttachmentManager::default(),
-            diagnostic: false,
+            mode: Mode::Export,
             export_type: Some(ExportType::Txt),
             export_path: validate_path(None, &None).unwrap(),
             query_context: QueryContext::default(),
@@ -544,20 +1497,883 @@ mod arg_tests {
             custom_name: None,
             platform: Platform::default(),
             ignore_disk_space: false,
+            timezone: Timezone::Local,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_export_timezone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "-z",
+            "America/New_York",
+            "-i",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        // Expected data
+        let timezone = Timezone::from_cli("America/New_York").unwrap();
+        let mut query_context = QueryContext::default();
+        query_context.set_timezone(timezone);
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            mode: Mode::Export,
+            export_type: Some(ExportType::Txt),
+            export_path: validate_path(None, &None).unwrap(),
+            query_context,
+            no_lazy: false,
+            custom_name: None,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            timezone,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: true,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_chat_timezones() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "--chat-timezones",
+            "1=America/New_York, 3=utc",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(
+            actual.chat_timezones,
+            BTreeMap::from([
+                (1, Timezone::from_cli("America/New_York").unwrap()),
+                (3, Timezone::Utc),
+            ])
+        );
+        assert_eq!(actual.timezone, Timezone::Local);
+    }
+
+    #[test]
+    fn can_build_option_chat_timezones_from_config_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "list-chats"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let file = ConfigFile::parse("chat-timezones = \"2=Asia/Tokyo\"").unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
+
+        assert_eq!(
+            actual.chat_timezones,
+            BTreeMap::from([(2, Timezone::from_cli("Asia/Tokyo").unwrap())])
+        );
+    }
+
+    #[test]
+    fn cant_build_option_invalid_chat_timezones() {
+        for pairs in ["1", "1=Moon/Base", "one=UTC", ","] {
+            // Get matches from sample args
+            let cli_args: Vec<&str> = vec![
+                "imessage-exporter",
+                "export",
+                "-f",
+                "txt",
+                "--chat-timezones",
+                pairs,
+            ];
+            let command = get_command();
+            let args = command.get_matches_from(cli_args);
+
+            // Build the Options
+            let actual = Options::from_args(&args, &ConfigFile::default());
+
+            assert!(actual.is_err());
+        }
+    }
+
+    #[test]
+    fn cant_build_option_invalid_timezone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "-z",
+            "Moon/Base",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_export_locale_date_format() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-g",
+            "es",
+            "-t",
+            "%Y-%m-%d %H:%M",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            mode: Mode::Export,
+            export_type: Some(ExportType::Html),
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            timezone: Timezone::default(),
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::Spanish,
+            date_format: Some("%Y-%m-%d %H:%M".to_string()),
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
         };
 
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_build_option_paginate() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "-P", "month"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.pagination, Pagination::Month);
+    }
+
+    #[test]
+    fn can_build_option_paginate_count() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "-P", "1000"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.pagination, Pagination::Messages(1000));
+    }
+
+    #[test]
+    fn cant_build_option_paginate_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "-P", "year"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_pagination() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "-P", "week"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_theme() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "-T", "dark"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.theme, Theme::Dark);
+    }
+
+    #[test]
+    fn can_build_option_custom_css() {
+        // Get matches from sample args
+        let css = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/exporters/resources/style.css"
+        );
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "--css",
+            css,
+            "--link-css",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.custom_css, Some(PathBuf::from(css)));
+        assert!(actual.link_css);
+    }
+
+    #[test]
+    fn cant_build_option_theme_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "-T", "dark"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_missing_css() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "--css",
+            "/does/not/exist.css",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_link_css_no_css() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "--link-css"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_templates() {
+        // Get matches from sample args
+        let templates = concat!(env!("CARGO_MANIFEST_DIR"), "/src/exporters/resources");
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "--templates",
+            templates,
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.template_dir, Some(PathBuf::from(templates)));
+    }
+
+    #[test]
+    fn cant_build_option_templates_txt() {
+        // Get matches from sample args
+        let templates = concat!(env!("CARGO_MANIFEST_DIR"), "/src/exporters/resources");
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "--templates",
+            templates,
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_embedded() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-c",
+            "embedded",
+            "--embed-limit",
+            "5",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(
+            actual.attachment_manager,
+            AttachmentManager::Embedded(5 * BYTES_PER_MB)
+        );
+    }
+
+    #[test]
+    fn cant_build_option_embedded_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> =
+            vec!["imessage-exporter", "export", "-f", "txt", "-c", "embedded"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_embed_limit_without_embedded() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-c",
+            "compatible",
+            "--embed-limit",
+            "5",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_embed_limit_zero() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-c",
+            "embedded",
+            "--embed-limit",
+            "0",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_conversations() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "epub",
+            "--conversations",
+            "3, 1,2",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.export_type, Some(ExportType::Epub));
+        assert_eq!(actual.conversations, Some(BTreeSet::from([1, 2, 3])));
+    }
+
+    #[test]
+    fn cant_build_option_conversations_html() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "--conversations",
+            "1",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_conversations() {
+        // Get matches from sample args
+        for ids in ["1,two", ","] {
+            let cli_args: Vec<&str> = vec![
+                "imessage-exporter",
+                "export",
+                "-f",
+                "epub",
+                "--conversations",
+                ids,
+            ];
+            let command = get_command();
+            let args = command.get_matches_from(cli_args);
+
+            // Build the Options
+            let actual = Options::from_args(&args, &ConfigFile::default());
+
+            assert!(actual.is_err());
+        }
+    }
+
+    #[test]
+    fn can_build_option_eml() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "mbox", "--eml"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.export_type, Some(ExportType::Mbox));
+        assert!(actual.eml);
+    }
+
+    #[test]
+    fn cant_build_option_eml_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "--eml"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_link_modes() {
+        for (mode, expected) in [
+            ("hardlink", AttachmentManager::Hardlink),
+            ("symlink", AttachmentManager::Symlink),
+            ("reflink", AttachmentManager::Reflink),
+        ] {
+            // Get matches from sample args
+            let cli_args: Vec<&str> = vec![
+                "imessage-exporter",
+                "export",
+                "-f",
+                "html",
+                "-c",
+                mode,
+                "--dedupe",
+            ];
+            let command = get_command();
+            let args = command.get_matches_from(cli_args);
+
+            // Build the Options
+            let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+            assert_eq!(actual.attachment_manager, expected);
+            assert!(actual.dedupe);
+        }
+    }
+
+    #[test]
+    fn can_build_option_dedupe() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-c",
+            "efficient",
+            "--dedupe",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.attachment_manager, AttachmentManager::Efficient);
+        assert!(actual.dedupe);
+    }
+
+    #[test]
+    fn cant_build_option_dedupe_disabled() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "html", "--dedupe"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_dedupe_embedded() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-c",
+            "embedded",
+            "--dedupe",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_names() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "-c",
+            "efficient",
+            "--attachment-names",
+            "dated",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.attachment_names, AttachmentNames::Dated);
+    }
+
+    #[test]
+    fn cant_build_option_attachment_names_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "-c",
+            "efficient",
+            "--attachment-names",
+            "hash",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_attachment_names_disabled() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "--attachment-names",
+            "original",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_locale() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "-g", "klingon"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_date_format() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "-t", "%Y-%Q"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_date_format_iso_timestamps() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> =
+            vec!["imessage-exporter", "export", "-f", "txt", "-t", "%Y", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_exclusive_end() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "txt",
+            "-z",
+            "UTC",
+            "-s",
+            "2020-01-01 09:00",
+            "-e",
+            "2020-01-01T17:00:00Z",
+            "-x",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default()).unwrap();
+
+        assert_eq!(actual.query_context.start, Some(599562000000000000));
+        assert_eq!(actual.query_context.end, Some(599590800000000000));
+        assert!(actual.query_context.end_exclusive);
+    }
+
+    #[test]
+    fn cant_build_option_exclusive_end_no_end_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt", "-x"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args, &ConfigFile::default());
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_exclusive_end_date_from_config_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "stats", "-x"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let file = ConfigFile::parse("end-date = \"2020-01-01\"").unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
+
+        assert!(actual.query_context.end.is_some());
+        assert!(actual.query_context.end_exclusive);
+    }
+
+    #[test]
+    fn can_build_option_search_from_config_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "search", "dinner", "-C", "1"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let file = ConfigFile::parse(
+            "limit = 5\ncontext = 3\nindex-path = \"/tmp/index.db\"\nrebuild-index = true\n",
+        )
+        .unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
+
+        assert_eq!(
+            actual.mode,
+            Mode::Search(SearchQuery {
+                query: "dinner".to_string(),
+                limit: 5,
+                context: 1,
+                index_path: "/tmp/index.db".into(),
+                rebuild: true,
+            })
+        );
+    }
+
+    #[test]
+    fn can_build_option_embed_limit_from_config_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-c",
+            "embedded",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let file = ConfigFile::parse("embed-limit = 1").unwrap();
+        let actual = Options::from_args(&args, &file).unwrap();
+
+        assert_eq!(
+            actual.attachment_manager,
+            AttachmentManager::Embedded(BYTES_PER_MB)
+        );
+    }
+
+    #[test]
+    fn cant_build_option_theme_from_config_file_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "txt"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let file = ConfigFile::parse("theme = \"dark\"").unwrap();
+        let actual = Options::from_args(&args, &file);
+
+        assert!(actual.is_err());
+    }
+
     #[test]
     fn cant_build_option_attachment_manager_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-c", "compatible"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-c", "compatible"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
@@ -565,12 +2381,12 @@ mod arg_tests {
     #[test]
     fn cant_build_option_export_path_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-o", "~/test"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-o", "~/test"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
@@ -578,12 +2394,12 @@ mod arg_tests {
     #[test]
     fn cant_build_option_start_date_path_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-s", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-s", "2020-01-01"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
@@ -591,12 +2407,12 @@ mod arg_tests {
     #[test]
     fn cant_build_option_end_date_path_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-e", "2020-01-01"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
@@ -604,12 +2420,19 @@ mod arg_tests {
     #[test]
     fn cant_build_option_invalid_date() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "export",
+            "-f",
+            "html",
+            "-e",
+            "2020-32-32",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
@@ -617,12 +2440,12 @@ mod arg_tests {
     #[test]
     fn cant_build_option_invalid_platform() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "diagnose", "-a", "iPad"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
@@ -630,12 +2453,12 @@ mod arg_tests {
     #[test]
     fn cant_build_option_invalid_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "pdf"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "export", "-f", "docx"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args, &ConfigFile::default());
 
         assert!(actual.is_err());
     }
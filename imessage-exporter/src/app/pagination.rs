@@ -0,0 +1,126 @@
+/*!
+ Contains data structures used to describe how HTML exports are split into pages.
+*/
+
+use std::fmt::Display;
+
+use chrono::{DateTime, FixedOffset};
+
+/// Represents how a conversation is split across multiple files
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum Pagination {
+    /// Write each conversation to a single file
+    #[default]
+    Disabled,
+    /// Start a new file for each calendar year
+    Year,
+    /// Start a new file for each calendar month
+    Month,
+    /// Start a new file after the given number of messages
+    Messages(usize),
+}
+
+impl Pagination {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(pagination: &str) -> Option<Self> {
+        match pagination.to_lowercase().as_str() {
+            "year" => Some(Self::Year),
+            "month" => Some(Self::Month),
+            count => match count.parse() {
+                Ok(0) | Err(_) => None,
+                Ok(count) => Some(Self::Messages(count)),
+            },
+        }
+    }
+
+    /// Determine the label of the page a message belongs on
+    ///
+    /// `current` is the label of the page the conversation is currently written to and the number of
+    /// messages already on it, or `None` if nothing has been written yet. Messages without a valid
+    /// date stay on the current page.
+    pub fn label(
+        &self,
+        date: Option<&DateTime<FixedOffset>>,
+        current: Option<(&str, usize)>,
+    ) -> String {
+        let keep = || current.map(|(label, _)| label.to_string());
+        match self {
+            Pagination::Disabled => String::new(),
+            Pagination::Year => date
+                .map(|date| date.format("%Y").to_string())
+                .or_else(keep)
+                .unwrap_or_default(),
+            Pagination::Month => date
+                .map(|date| date.format("%Y-%m").to_string())
+                .or_else(keep)
+                .unwrap_or_default(),
+            Pagination::Messages(size) => match current {
+                Some((label, count)) if count >= *size => label
+                    .parse::<usize>()
+                    .map(|page| (page + 1).to_string())
+                    .unwrap_or_else(|_| label.to_string()),
+                Some((label, _)) => label.to_string(),
+                None => "1".to_string(),
+            },
+        }
+    }
+}
+
+impl Display for Pagination {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pagination::Disabled => write!(fmt, "disabled"),
+            Pagination::Year => write!(fmt, "year"),
+            Pagination::Month => write!(fmt, "month"),
+            Pagination::Messages(count) => write!(fmt, "{count}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+
+    use crate::app::pagination::Pagination;
+
+    #[test]
+    fn can_parse_any_case() {
+        assert_eq!(Pagination::from_cli("YEAR"), Some(Pagination::Year));
+        assert_eq!(Pagination::from_cli("month"), Some(Pagination::Month));
+        assert_eq!(Pagination::from_cli("500"), Some(Pagination::Messages(500)));
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert_eq!(Pagination::from_cli("week"), None);
+        assert_eq!(Pagination::from_cli("0"), None);
+        assert_eq!(Pagination::from_cli("-5"), None);
+    }
+
+    #[test]
+    fn can_label_by_date() {
+        let date = FixedOffset::west_opt(7 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2021, 5, 17, 12, 0, 0)
+            .unwrap();
+
+        assert_eq!(Pagination::Year.label(Some(&date), None), "2021");
+        assert_eq!(
+            Pagination::Month.label(Some(&date), Some(("2021-04", 10))),
+            "2021-05"
+        );
+        assert_eq!(
+            Pagination::Month.label(None, Some(("2021-04", 10))),
+            "2021-04"
+        );
+    }
+
+    #[test]
+    fn can_label_by_count() {
+        let pagination = Pagination::Messages(2);
+
+        assert_eq!(pagination.label(None, None), "1");
+        assert_eq!(pagination.label(None, Some(("1", 1))), "1");
+        assert_eq!(pagination.label(None, Some(("1", 2))), "2");
+    }
+}
@@ -0,0 +1,997 @@
+/*!
+ Contains a minimal PDF writer used to export conversations without a browser or other external tools.
+
+ Text is set in the standard Helvetica fonts that every PDF reader provides, so the only data embedded
+ in a document is its images. Those fonts only cover Western European alphabets, so other characters are
+ replaced with a question mark and counted, letting the exporter warn about them. Each page is appended to the file as soon as it is full, so a document
+ only keeps a single page in memory.
+*/
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{read, File},
+    io::Write,
+    mem::take,
+    path::{Path, PathBuf},
+};
+
+/// Width of a US Letter page, in points
+pub const PAGE_WIDTH: f32 = 612.;
+/// Height of a US Letter page, in points
+pub const PAGE_HEIGHT: f32 = 792.;
+/// Space left empty around the edges of each page, in points
+pub const MARGIN: f32 = 54.;
+/// Width of the area between the margins, in points
+pub const COLUMN_WIDTH: f32 = PAGE_WIDTH - 2. * MARGIN;
+/// Tallest an image can be drawn, in points
+pub const MAX_IMAGE_HEIGHT: f32 = 240.;
+
+/// Object number of the document catalog
+const CATALOG: usize = 1;
+/// Object number of the page tree, which is written last once every page is known
+const PAGE_TREE: usize = 2;
+/// Object number of the regular font
+const REGULAR_FONT: usize = 3;
+/// Object number of the bold font
+const BOLD_FONT: usize = 4;
+/// Height of a line of text, relative to the font size
+const LINE_SPACING: f32 = 1.25;
+/// Distance from the top of a line of text to its baseline, relative to the font size
+const BASELINE: f32 = 0.875;
+/// Radius of the corners of a block's background
+const CORNER_RADIUS: f32 = 8.;
+/// Distance to the control points of a Bézier curve that approximates a quarter circle
+const KAPPA: f32 = 0.552_284_8;
+
+/// Widths of the printable ASCII characters in Helvetica, in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Widths of the printable ASCII characters in Helvetica Bold, in thousandths of the font size
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// One of the standard fonts used to set text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    /// Name of the font in each page's resources
+    fn resource(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+
+    /// Width of a character, in thousandths of the font size
+    fn char_width(&self, character: char) -> u16 {
+        // Characters the font cannot draw are replaced with a question mark
+        let character = match encode_char(character) {
+            Some(_) => base_letter(character),
+            None => '?',
+        };
+        match character {
+            ' '..='~' => match self {
+                Font::Regular => HELVETICA_WIDTHS[character as usize - 32],
+                Font::Bold => HELVETICA_BOLD_WIDTHS[character as usize - 32],
+            },
+            '—' | '…' | '‰' | '™' | 'Æ' | 'Œ' => 1000,
+            'æ' | 'œ' => 944,
+            '‘' | '’' | '‚' | '‹' | '›' => 333,
+            '“' | '”' | '„' => 500,
+            '©' | '®' => 737,
+            '•' => 350,
+            _ => 556,
+        }
+    }
+
+    /// Width of some text set at a given size, in points
+    pub fn width(&self, text: &str, size: f32) -> f32 {
+        text.chars()
+            .map(|character| f32::from(self.char_width(character)))
+            .sum::<f32>()
+            * size
+            / 1000.
+    }
+}
+
+/// A color used to fill text and backgrounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub f32, pub f32, pub f32);
+
+impl Color {
+    pub const BLACK: Color = Color(0., 0., 0.);
+    pub const WHITE: Color = Color(1., 1., 1.);
+    pub const GRAY: Color = Color(0.45, 0.45, 0.47);
+
+    /// The operator that sets this color for the shapes and text that follow it
+    fn operator(&self) -> String {
+        format!("{:.3} {:.3} {:.3} rg", self.0, self.1, self.2)
+    }
+}
+
+/// Where a block is placed between the margins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// A run of text that wraps to the width of its block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub text: String,
+    pub font: Font,
+    pub size: f32,
+    pub color: Color,
+}
+
+impl Text {
+    pub fn new(text: &str, font: Font, size: f32, color: Color) -> Self {
+        Self {
+            text: text.to_string(),
+            font,
+            size,
+            color,
+        }
+    }
+}
+
+/// A piece of content placed inside a block
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    Text(Text),
+    /// An image file, with text to show instead if the file is not a supported image
+    Image {
+        path: PathBuf,
+        fallback: Text,
+    },
+}
+
+/// A rectangle of content in the flow of the document, i.e. a message bubble
+///
+/// Blocks that do not fit on the current page continue on the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub content: Vec<Content>,
+    pub align: Align,
+    /// Space between each margin and the area the block is placed in
+    pub inset: f32,
+    /// Widest the block can be, including its padding
+    pub max_width: f32,
+    /// Color drawn behind the content
+    pub background: Option<Color>,
+    /// Space between the edges of the block and its content
+    pub padding: f32,
+    /// Space left empty below the block
+    pub space_after: f32,
+}
+
+impl Block {
+    /// Create a block that can fill the width of the page, with no background
+    pub fn new(content: Vec<Content>, align: Align) -> Self {
+        Self {
+            content,
+            align,
+            inset: 0.,
+            max_width: COLUMN_WIDTH,
+            background: None,
+            padding: 0.,
+            space_after: 0.,
+        }
+    }
+}
+
+/// A single line of a block after its content is wrapped
+enum Line<'a> {
+    Text {
+        text: String,
+        style: &'a Text,
+        width: f32,
+    },
+    Image {
+        id: usize,
+        width: f32,
+        height: f32,
+    },
+}
+
+impl Line<'_> {
+    fn width(&self) -> f32 {
+        match self {
+            Line::Text { width, .. } | Line::Image { width, .. } => *width,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        match self {
+            Line::Text { style, .. } => style.size * LINE_SPACING,
+            Line::Image { height, .. } => *height,
+        }
+    }
+}
+
+/// An image that has been read from disk and can be written to a document
+struct ImageData {
+    width: u32,
+    height: u32,
+    /// Entries of the image's dictionary that describe how its data is encoded
+    dictionary: String,
+    data: Vec<u8>,
+}
+
+/// An image that has been written to a document
+#[derive(Clone, Copy)]
+struct ImageObject {
+    id: usize,
+    width: u32,
+    height: u32,
+}
+
+/// A PDF file that is written one page at a time
+pub struct PdfDocument {
+    path: PathBuf,
+    /// Bytes that have not been written to the file yet
+    buffer: Vec<u8>,
+    /// Length of the file, including the buffer
+    position: usize,
+    /// Offset in the file of each object, indexed by object number minus one
+    offsets: Vec<usize>,
+    /// Object numbers of the pages that have been written
+    pages: Vec<usize>,
+    /// Drawing operations for the current page
+    page: String,
+    /// Images drawn on the current page
+    page_images: BTreeSet<usize>,
+    /// Images that have been written to the document, or `None` if the file could not be used
+    images: HashMap<PathBuf, Option<ImageObject>>,
+    /// Distance from the bottom of the page to the top of the next block
+    cursor: f32,
+    /// Number of characters the standard fonts cannot draw, which were replaced with a question mark
+    replaced: usize,
+}
+
+impl PdfDocument {
+    /// Start a document that will be written to `path`
+    pub fn new(path: PathBuf) -> Self {
+        let mut document = Self {
+            path,
+            buffer: vec![],
+            position: 0,
+            offsets: vec![],
+            pages: vec![],
+            page: String::new(),
+            page_images: BTreeSet::new(),
+            images: HashMap::new(),
+            cursor: PAGE_HEIGHT - MARGIN,
+            replaced: 0,
+        };
+
+        // The comment with high bytes tells readers that the file contains binary data
+        document.push(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+        document.write_object(
+            CATALOG,
+            format!("<< /Type /Catalog /Pages {PAGE_TREE} 0 R >>").as_bytes(),
+        );
+        document.write_object(
+            REGULAR_FONT,
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+        );
+        document.write_object(
+            BOLD_FONT,
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>",
+        );
+        document
+    }
+
+    /// The file the document is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of characters the standard fonts cannot draw, which were replaced with a question mark
+    pub fn replaced(&self) -> usize {
+        self.replaced
+    }
+
+    /// Start a new page if less than `height` points are left on the current one
+    pub fn ensure_space(&mut self, height: f32) {
+        if self.cursor - height < MARGIN && !self.page.is_empty() {
+            self.new_page();
+        }
+    }
+
+    /// Add a block below the previous one, continuing it on new pages as needed
+    pub fn add_block(&mut self, block: &Block) {
+        let area = COLUMN_WIDTH - 2. * block.inset;
+        let max_width = (block.max_width.min(area) - 2. * block.padding).max(1.);
+
+        let mut lines = vec![];
+        for content in &block.content {
+            match content {
+                Content::Text(text) => lines.extend(wrap(text, max_width)),
+                Content::Image { path, fallback } => match self.image(path) {
+                    Some(image) => {
+                        let (width, height) = fit(&image, max_width);
+                        lines.push(Line::Image {
+                            id: image.id,
+                            width,
+                            height,
+                        });
+                    }
+                    None => lines.extend(wrap(fallback, max_width)),
+                },
+            }
+        }
+        if lines.is_empty() {
+            return;
+        }
+
+        let width = lines.iter().map(Line::width).fold(0., f32::max) + 2. * block.padding;
+        let left = match block.align {
+            Align::Left => MARGIN + block.inset,
+            Align::Right => PAGE_WIDTH - MARGIN - block.inset - width,
+            Align::Center => MARGIN + block.inset + (area - width) / 2.,
+        };
+        let x = left + block.padding;
+
+        // Each page the block is drawn on gets its own segment of the background
+        let mut segment = String::new();
+        let mut top = self.cursor;
+        let mut y = top - block.padding;
+        for line in &lines {
+            let height = line.height();
+            if y - height - block.padding < MARGIN && (!segment.is_empty() || !self.page.is_empty())
+            {
+                self.draw_segment(block, left, width, top, y - block.padding, &segment);
+                self.new_page();
+                segment.clear();
+                top = self.cursor;
+                y = top - block.padding;
+            }
+
+            match line {
+                Line::Text { text, style, .. } => {
+                    self.replaced += count_unencodable(text);
+                    segment.push_str(&format!(
+                        "BT /{} {:.2} Tf {} {x:.2} {:.2} Td ({}) Tj ET\n",
+                        style.font.resource(),
+                        style.size,
+                        style.color.operator(),
+                        y - style.size * BASELINE,
+                        encode_text(text)
+                    ));
+                }
+                Line::Image { id, width, height } => {
+                    segment.push_str(&format!(
+                        "q {width:.2} 0 0 {height:.2} {x:.2} {:.2} cm /Im{id} Do Q\n",
+                        y - height
+                    ));
+                    self.page_images.insert(*id);
+                }
+            }
+            y -= height;
+        }
+        self.draw_segment(block, left, width, top, y - block.padding, &segment);
+        self.cursor = y - block.padding - block.space_after;
+    }
+
+    /// Write the last page and the index of every object, completing the file
+    pub fn finish(&mut self) {
+        if !self.page.is_empty() || self.pages.is_empty() {
+            self.write_page();
+        }
+
+        let kids: Vec<String> = self.pages.iter().map(|id| format!("{id} 0 R")).collect();
+        self.write_object(
+            PAGE_TREE,
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                self.pages.len()
+            )
+            .as_bytes(),
+        );
+
+        let start = self.position;
+        let mut index = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            index.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+        index.push_str(&format!(
+            "trailer\n<< /Size {} /Root {CATALOG} 0 R >>\nstartxref\n{start}\n%%EOF\n",
+            self.offsets.len() + 1
+        ));
+        self.push(index.as_bytes());
+        self.flush();
+    }
+
+    /// Draw the part of a block that fits on the current page
+    fn draw_segment(
+        &mut self,
+        block: &Block,
+        left: f32,
+        width: f32,
+        top: f32,
+        bottom: f32,
+        segment: &str,
+    ) {
+        if segment.is_empty() {
+            return;
+        }
+        if let Some(background) = block.background {
+            self.page.push_str(&background.operator());
+            self.page.push('\n');
+            self.page
+                .push_str(&rounded_rectangle(left, bottom, width, top - bottom));
+        }
+        self.page.push_str(segment);
+    }
+
+    /// Write the current page and start a blank one
+    fn new_page(&mut self) {
+        self.write_page();
+        self.cursor = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Write the current page and its contents to the file
+    fn write_page(&mut self) {
+        let operations = take(&mut self.page);
+        let contents = self.add_stream("", operations.as_bytes());
+
+        let images: String = take(&mut self.page_images)
+            .iter()
+            .map(|id| format!(" /Im{id} {id} 0 R"))
+            .collect();
+        let images = match images.is_empty() {
+            true => images,
+            false => format!(" /XObject <<{images} >>"),
+        };
+
+        let page = self.add_object(
+            format!(
+                "<< /Type /Page /Parent {PAGE_TREE} 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] /Resources << /Font << /F1 {REGULAR_FONT} 0 R /F2 {BOLD_FONT} 0 R >>{images} >> /Contents {contents} 0 R >>"
+            )
+            .as_bytes(),
+        );
+        self.pages.push(page);
+        self.flush();
+    }
+
+    /// Get an image that has been written to the document, writing it first if needed
+    fn image(&mut self, path: &Path) -> Option<ImageObject> {
+        if let Some(image) = self.images.get(path) {
+            return *image;
+        }
+
+        let image = read_image(path).map(|image| ImageObject {
+            id: self.add_stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} {}",
+                    image.width, image.height, image.dictionary
+                ),
+                &image.data,
+            ),
+            width: image.width,
+            height: image.height,
+        });
+        self.images.insert(path.to_path_buf(), image);
+        image
+    }
+
+    /// Write a stream object with the given dictionary entries, returning its object number
+    fn add_stream(&mut self, dictionary: &str, data: &[u8]) -> usize {
+        let entries = format!("{dictionary} /Length {}", data.len());
+        let mut body = format!("<< {} >>\nstream\n", entries.trim_start()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.add_object(&body)
+    }
+
+    /// Write an object with the next free object number, returning that number
+    fn add_object(&mut self, body: &[u8]) -> usize {
+        // The catalog, page tree, and fonts are numbered when the document is created
+        let id = self.offsets.len() + 1;
+        self.write_object(id, body);
+        id
+    }
+
+    /// Write an object with a known object number
+    fn write_object(&mut self, id: usize, body: &[u8]) {
+        if self.offsets.len() < id {
+            self.offsets.resize(id, 0);
+        }
+        self.offsets[id - 1] = self.position;
+        self.push(format!("{id} 0 obj\n").as_bytes());
+        self.push(body);
+        self.push(b"\nendobj\n");
+    }
+
+    /// Add bytes to the end of the file
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        self.position += bytes.len();
+    }
+
+    /// Append the buffered bytes to the file
+    fn flush(&mut self) {
+        match File::options().append(true).create(true).open(&self.path) {
+            Ok(mut file) => {
+                if let Err(why) = file.write_all(&self.buffer) {
+                    eprintln!("Unable to write to {:?}: {why:?}", self.path);
+                }
+            }
+            Err(why) => eprintln!("Unable to write to {:?}: {why:?}", self.path),
+        };
+        self.buffer.clear();
+    }
+}
+
+/// Break text into lines that are no wider than `max_width`
+fn wrap(text: &Text, max_width: f32) -> Vec<Line<'_>> {
+    let space = text.font.width(" ", text.size);
+    let mut lines = vec![];
+
+    for paragraph in text.text.split('\n') {
+        let mut line = String::new();
+        let mut width = 0.;
+        for word in paragraph.split(' ') {
+            let word: String = word.chars().filter(|c| !c.is_control()).collect();
+            if !line.is_empty() && width + space + text.font.width(&word, text.size) > max_width {
+                lines.push(Line::Text {
+                    text: take(&mut line),
+                    style: text,
+                    width,
+                });
+                width = 0.;
+            }
+            if !line.is_empty() {
+                line.push(' ');
+                width += space;
+            }
+
+            // Words that are wider than the line on their own, i.e. links, are broken between characters
+            for character in word.chars() {
+                let character_width = text.font.width(&character.to_string(), text.size);
+                if !line.is_empty() && width + character_width > max_width {
+                    lines.push(Line::Text {
+                        text: take(&mut line),
+                        style: text,
+                        width,
+                    });
+                    width = 0.;
+                }
+                line.push(character);
+                width += character_width;
+            }
+        }
+        lines.push(Line::Text {
+            text: line,
+            style: text,
+            width,
+        });
+    }
+
+    lines
+}
+
+/// Scale an image to fit the width of its block, in points
+fn fit(image: &ImageObject, max_width: f32) -> (f32, f32) {
+    let width = image.width as f32;
+    let height = image.height as f32;
+    let scale = (max_width / width).min(MAX_IMAGE_HEIGHT / height).min(1.);
+    (width * scale, height * scale)
+}
+
+/// Build the path operators that fill a rectangle with rounded corners
+fn rounded_rectangle(x: f32, y: f32, width: f32, height: f32) -> String {
+    let radius = CORNER_RADIUS.min(width / 2.).min(height / 2.);
+    let control = radius * (1. - KAPPA);
+    let (right, top) = (x + width, y + height);
+    format!(
+        "{:.2} {y:.2} m {:.2} {y:.2} l {:.2} {y:.2} {right:.2} {:.2} {right:.2} {:.2} c {right:.2} {:.2} l {right:.2} {:.2} {:.2} {top:.2} {:.2} {top:.2} c {:.2} {top:.2} l {:.2} {top:.2} {x:.2} {:.2} {x:.2} {:.2} c {x:.2} {:.2} l {x:.2} {:.2} {:.2} {y:.2} {:.2} {y:.2} c f\n",
+        x + radius,
+        right - radius,
+        right - control,
+        y + control,
+        y + radius,
+        top - radius,
+        top - control,
+        right - control,
+        right - radius,
+        x + radius,
+        x + control,
+        top - control,
+        top - radius,
+        y + radius,
+        y + control,
+        x + control,
+        x + radius,
+    )
+}
+
+/// Map accented letters to the letter they are based on, which has the same width
+fn base_letter(character: char) -> char {
+    match character {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ð' => 'D',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' | 'Ÿ' => 'Y',
+        'Š' => 'S',
+        'Ž' => 'Z',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'š' => 's',
+        'ž' => 'z',
+        '\u{a0}' => ' ',
+        other => other,
+    }
+}
+
+/// Encode a character in `WinAnsiEncoding`, the encoding used by the standard fonts
+fn encode_char(character: char) -> Option<u8> {
+    match character {
+        ' '..='~' | '\u{a0}'..='ÿ' => Some(character as u8),
+        '€' => Some(0x80),
+        '‚' => Some(0x82),
+        'ƒ' => Some(0x83),
+        '„' => Some(0x84),
+        '…' => Some(0x85),
+        '†' => Some(0x86),
+        '‡' => Some(0x87),
+        'ˆ' => Some(0x88),
+        '‰' => Some(0x89),
+        'Š' => Some(0x8A),
+        '‹' => Some(0x8B),
+        'Œ' => Some(0x8C),
+        'Ž' => Some(0x8E),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        '˜' => Some(0x98),
+        '™' => Some(0x99),
+        'š' => Some(0x9A),
+        '›' => Some(0x9B),
+        'œ' => Some(0x9C),
+        'ž' => Some(0x9E),
+        'Ÿ' => Some(0x9F),
+        _ => None,
+    }
+}
+
+/// Encode text as the contents of a PDF string literal
+///
+/// Characters the standard fonts cannot draw, like emoji, are replaced with a question mark.
+fn encode_text(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for character in text.chars() {
+        match encode_char(character).unwrap_or(b'?') {
+            byte @ (b'(' | b')' | b'\\') => {
+                encoded.push('\\');
+                encoded.push(byte as char);
+            }
+            byte @ b' '..=b'~' => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    encoded
+}
+
+/// Count the characters in text that the standard fonts cannot draw
+fn count_unencodable(text: &str) -> usize {
+    text.chars()
+        .filter(|character| encode_char(*character).is_none())
+        .count()
+}
+
+/// Read an image that can be embedded without decoding it first
+fn read_image(path: &Path) -> Option<ImageData> {
+    let data = read(path).ok()?;
+    if data.starts_with(&[0xFF, 0xD8]) {
+        parse_jpeg(data)
+    } else {
+        parse_png(&data)
+    }
+}
+
+/// Read the size and color space of a JPEG, which PDF readers can decode themselves
+fn parse_jpeg(data: Vec<u8>) -> Option<ImageData> {
+    let mut index = 2;
+    while index + 4 <= data.len() {
+        if data[index] != 0xFF {
+            return None;
+        }
+        let marker = data[index + 1];
+        // Markers can be preceded by any number of fill bytes
+        if marker == 0xFF {
+            index += 1;
+            continue;
+        }
+        let length = u16::from_be_bytes([data[index + 2], data[index + 3]]) as usize;
+
+        // Start of frame markers, excluding the ones that define tables
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let frame = data.get(index + 4..index + 10)?;
+            let height = u32::from(u16::from_be_bytes([frame[1], frame[2]]));
+            let width = u32::from(u16::from_be_bytes([frame[3], frame[4]]));
+            let color_space = match frame[5] {
+                1 => "/DeviceGray",
+                3 => "/DeviceRGB",
+                // CMYK JPEGs are usually written with inverted values
+                4 => "/DeviceCMYK /Decode [1 0 1 0 1 0 1 0]",
+                _ => return None,
+            };
+            return Some(ImageData {
+                width,
+                height,
+                dictionary: format!(
+                    "/ColorSpace {color_space} /BitsPerComponent 8 /Filter /DCTDecode"
+                ),
+                data,
+            });
+        }
+        index += 2 + length;
+    }
+    None
+}
+
+/// Read a PNG whose compressed pixel data can be used as-is
+///
+/// PDF readers can undo PNG's compression, but images with transparency or interlacing would
+/// need to be decoded and compressed again, so those are not supported.
+fn parse_png(data: &[u8]) -> Option<ImageData> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return None;
+    }
+
+    let mut index = 8;
+    let mut header = None;
+    let mut palette = None;
+    let mut pixels = vec![];
+    while index + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[index..index + 4].try_into().ok()?) as usize;
+        let body = data.get(index + 8..index + 8 + length)?;
+        match &data[index + 4..index + 8] {
+            b"IHDR" => header = Some(body),
+            b"PLTE" => palette = Some(body),
+            b"IDAT" => pixels.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Skip the length, type, body, and checksum
+        index += 12 + length;
+    }
+
+    let header = header.filter(|header| header.len() >= 13)?;
+    let width = u32::from_be_bytes(header[0..4].try_into().ok()?);
+    let height = u32::from_be_bytes(header[4..8].try_into().ok()?);
+    let bit_depth = header[8];
+    if bit_depth > 8 || header[12] != 0 {
+        return None;
+    }
+
+    let (colors, color_space) = match header[9] {
+        0 => (1, "/DeviceGray".to_string()),
+        2 => (3, "/DeviceRGB".to_string()),
+        3 => {
+            let palette = palette?;
+            let colors: String = palette.iter().map(|byte| format!("{byte:02X}")).collect();
+            (
+                1,
+                format!(
+                    "[/Indexed /DeviceRGB {} <{colors}>]",
+                    (palette.len() / 3).checked_sub(1)?
+                ),
+            )
+        }
+        _ => return None,
+    };
+
+    Some(ImageData {
+        width,
+        height,
+        dictionary: format!(
+            "/ColorSpace {color_space} /BitsPerComponent {bit_depth} /Filter /FlateDecode /DecodeParms << /Predictor 15 /Colors {colors} /BitsPerComponent {bit_depth} /Columns {width} >>"
+        ),
+        data: pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, remove_file},
+    };
+
+    use uuid::Uuid;
+
+    use crate::app::pdf_document::{
+        count_unencodable, encode_text, parse_jpeg, parse_png, wrap, Align, Block, Color, Content,
+        Font, Line, PdfDocument, Text,
+    };
+
+    fn lines(text: &Text, max_width: f32) -> Vec<String> {
+        wrap(text, max_width)
+            .into_iter()
+            .map(|line| match line {
+                Line::Text { text, .. } => text,
+                Line::Image { .. } => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn can_measure_text() {
+        assert_eq!(Font::Regular.width("Hi", 1000.), 944.);
+        assert_eq!(Font::Bold.width("Hi", 1000.), 1000.);
+        assert_eq!(Font::Regular.width("é", 10.), Font::Regular.width("e", 10.));
+    }
+
+    #[test]
+    fn can_wrap_text() {
+        let text = Text::new("one two three\nfour", Font::Regular, 10., Color::BLACK);
+        assert_eq!(lines(&text, 40.), vec!["one two", "three", "four"]);
+    }
+
+    #[test]
+    fn can_wrap_long_word() {
+        let text = Text::new("aaaaaaaaaa", Font::Regular, 10., Color::BLACK);
+        assert_eq!(lines(&text, 20.), vec!["aaa", "aaa", "aaa", "a"]);
+    }
+
+    #[test]
+    fn can_encode_text() {
+        assert_eq!(encode_text("(a\\b)"), "\\(a\\\\b\\)");
+        assert_eq!(encode_text("café €"), "caf\\351 \\200");
+        assert_eq!(encode_text("hi 👋"), "hi ?");
+    }
+
+    #[test]
+    fn can_encode_non_latin_text() {
+        assert_eq!(encode_text("Привет, 你好"), "??????, ??");
+        assert_eq!(count_unencodable("Привет, 你好"), 8);
+        assert_eq!(count_unencodable("café €"), 0);
+    }
+
+    #[test]
+    fn can_count_replaced_characters() {
+        let path = temp_dir().join(format!("{}.pdf", Uuid::new_v4()));
+        let mut document = PdfDocument::new(path.clone());
+
+        document.add_block(&Block::new(
+            vec![Content::Text(Text::new(
+                "Hello\nПривет, 你好",
+                Font::Regular,
+                12.,
+                Color::BLACK,
+            ))],
+            Align::Left,
+        ));
+        document.finish();
+
+        let data = read(&path).unwrap();
+        assert!(String::from_utf8_lossy(&data).contains("(??????, ??) Tj"));
+        assert_eq!(document.replaced(), 8);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn can_parse_jpeg() {
+        let data = vec![
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00,
+            0x20, 0x00, 0x40, 0x03,
+        ];
+        let image = parse_jpeg(data).unwrap();
+        assert_eq!((image.width, image.height), (64, 32));
+        assert!(image.dictionary.contains("/DeviceRGB"));
+    }
+
+    #[test]
+    fn can_parse_png() {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 13]);
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&[0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&[0, 0, 0, 2]);
+        data.extend_from_slice(b"IDAT");
+        data.extend_from_slice(&[1, 2]);
+        data.extend_from_slice(&[0; 4]);
+
+        let image = parse_png(&data).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.data, vec![1, 2]);
+        assert!(image.dictionary.contains("/Columns 3"));
+    }
+
+    #[test]
+    fn cant_parse_png_with_transparency() {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 13]);
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&[0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+        data.extend_from_slice(&[0; 4]);
+
+        assert!(parse_png(&data).is_none());
+    }
+
+    #[test]
+    fn can_write_document() {
+        let path = temp_dir().join(format!("{}.pdf", Uuid::new_v4()));
+        let mut document = PdfDocument::new(path.clone());
+
+        // Enough text to continue onto a second page
+        let text = vec!["Hello (world)"; 60].join("\n");
+        document.add_block(&Block {
+            background: Some(Color::GRAY),
+            padding: 8.,
+            ..Block::new(
+                vec![Content::Text(Text::new(
+                    &text,
+                    Font::Regular,
+                    12.,
+                    Color::WHITE,
+                ))],
+                Align::Right,
+            )
+        });
+        document.finish();
+
+        // The header contains bytes that are not valid UTF-8, so offsets are checked against the raw data
+        let data = read(&path).unwrap();
+        let contents = String::from_utf8_lossy(&data);
+        assert!(data.starts_with(b"%PDF-1.4"));
+        assert!(data.ends_with(b"%%EOF\n"));
+        assert!(contents.contains("/Count 2"));
+        assert!(contents.contains("(Hello \\(world\\)) Tj"));
+
+        // Every entry in the cross-reference table points to its object
+        let start: usize = contents
+            .lines()
+            .rev()
+            .nth(1)
+            .and_then(|line| line.parse().ok())
+            .unwrap();
+        let index = String::from_utf8_lossy(&data[start..]);
+        for (number, entry) in index.lines().skip(3).enumerate() {
+            if entry.starts_with("trailer") {
+                break;
+            }
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(data[offset..].starts_with(format!("{} 0 obj", number + 1).as_bytes()));
+        }
+
+        remove_file(path).unwrap();
+    }
+}
@@ -1,23 +1,34 @@
 use std::{
     cmp::min,
     collections::{BTreeSet, HashMap, HashSet},
-    fs::create_dir_all,
+    fs::{create_dir_all, remove_file, write},
     path::PathBuf,
 };
 
+use chrono::{DateTime, Local};
 use fs2::available_space;
 use rusqlite::Connection;
+use uuid::Uuid;
 
 use crate::{
     app::{
-        attachment_manager::AttachmentManager, converter::Converter, error::RuntimeError,
-        export_type::ExportType, options::Options, sanitizers::sanitize_filename,
+        attachment_manager::{AttachmentManager, AttachmentStore},
+        chat_list::{render_json, render_table, ChatListing, ListFormat},
+        converter::Converter,
+        error::RuntimeError,
+        export_type::ExportType,
+        locale::Strings,
+        mode::Mode,
+        options::Options,
+        sanitizers::{sanitize_filename, sanitize_link},
+        search::{self, SearchIndex, SearchQuery, SourceState},
+        stats::{self, StatsFormat, StatsReport},
     },
-    Exporter, HTML, TXT,
+    Exporter, EPUB, HTML, LOCATIONS, MBOX, PDF, TXT, VCF, XML,
 };
 
 use imessage_database::{
-    error::table::TableError,
+    error::{message::MessageError, table::TableError},
     tables::{
         attachment::Attachment,
         chat::Chat,
@@ -25,11 +36,14 @@ use imessage_database::{
         handle::Handle,
         messages::Message,
         table::{
-            get_connection, get_db_size, Cacheable, Deduplicate, Diagnostic, ATTACHMENTS_DIR,
-            MAX_LENGTH, ME, ORPHANED, UNKNOWN,
+            get_connection, get_db_size, Cacheable, Deduplicate, Diagnostic, Table,
+            ATTACHMENTS_DIR, MAX_LENGTH, ME, ORPHANED, UNKNOWN,
         },
     },
-    util::{dates::get_offset, size::format_file_size},
+    util::{
+        dates::{format_iso, format_with, get_offset, Timezone},
+        size::format_file_size,
+    },
 };
 
 /// Stores the application state and handles application lifecycle
@@ -54,6 +68,8 @@ pub struct Config {
     pub db: Connection,
     /// Converter type used when converting image files
     pub converter: Option<Converter>,
+    /// Attachments that have been copied, used to skip duplicates when deduplicating
+    pub attachment_store: AttachmentStore,
 }
 
 impl Config {
@@ -113,6 +129,39 @@ impl Config {
         }
     }
 
+    /// Generate a link to an attachment for use in HTML exports
+    ///
+    /// Copies can be named after their original files, so the relative path to a copy is percent-encoded
+    pub fn message_attachment_link(&self, attachment: &Attachment) -> String {
+        match attachment
+            .copied_path
+            .as_ref()
+            .and_then(|path| path.strip_prefix(&self.options.export_path).ok())
+        {
+            Some(relative_path) => sanitize_link(
+                &relative_path
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            ),
+            None => self.message_attachment_path(attachment),
+        }
+    }
+
+    /// Find the file for an attachment on disk, preferring the copy in the export directory
+    pub fn attachment_file(&self, attachment: &Attachment) -> Option<PathBuf> {
+        attachment.copied_path.clone().or_else(|| {
+            attachment
+                .resolved_attachment_path(
+                    &self.options.platform,
+                    &self.options.db_path,
+                    self.options.attachment_root.as_deref(),
+                )
+                .map(PathBuf::from)
+        })
+    }
+
     /// Get a filename for a chat, possibly using cached data.
     ///
     /// If the chat has an assigned name, use that, truncating if necessary.
@@ -177,18 +226,100 @@ impl Config {
         out_s
     }
 
+    /// Get a human-readable name for a chat
+    ///
+    /// If the chat has an assigned name, use that. If it does not, list its members, falling back to
+    /// the unique `chat_identifier` field.
+    pub fn conversation_title(&self, chatroom: &Chat) -> String {
+        if let Some(name) = chatroom.display_name() {
+            return name.to_string();
+        }
+        match self.chatroom_participants.get(&chatroom.rowid) {
+            Some(participants) if !participants.is_empty() => participants
+                .iter()
+                .map(|participant_id| self.who(Some(*participant_id), false))
+                .collect::<Vec<&str>>()
+                .join(", "),
+            _ => chatroom.chat_identifier.clone(),
+        }
+    }
+
+    /// Build a list of labeled details that describe a conversation
+    ///
+    /// Exporters render these at the top of each conversation file.
+    pub fn conversation_details(&self, chatroom: &Chat) -> Vec<(&'static str, String)> {
+        let strings = self.strings();
+        let mut details = vec![];
+
+        if let Some(name) = chatroom.display_name() {
+            details.push((strings.conversation, name.to_string()));
+        }
+
+        if let Some(participants) = self.chatroom_participants.get(&chatroom.rowid) {
+            let names: Vec<&str> = participants
+                .iter()
+                .map(|participant_id| self.who(Some(*participant_id), false))
+                .collect();
+            details.push((strings.participants, names.join(", ")));
+        }
+
+        if let Some(service) = &chatroom.service_name {
+            details.push((strings.service, service.to_string()));
+        }
+
+        if let Some(properties) = &chatroom.properties {
+            if let Some(enabled) = properties.read_receipts_enabled {
+                let status = if enabled {
+                    strings.enabled
+                } else {
+                    strings.disabled
+                };
+                details.push((strings.read_receipts, status.to_string()));
+            }
+            if properties.forced_sms {
+                details.push((strings.send_as_sms, strings.always.to_string()));
+            }
+            if properties.ignore_alerts {
+                details.push((strings.alerts, strings.hidden.to_string()));
+            }
+            if properties.has_chat_background {
+                details.push((strings.background, strings.custom.to_string()));
+            }
+        }
+
+        details
+    }
+
+    /// Resolve a chat's group photo from the `attachment` table, copying it if requested
+    pub fn group_photo(&self, chatroom: &Chat) -> Option<Attachment> {
+        let guid = chatroom.properties.as_ref()?.group_photo_guid.as_ref()?;
+        let mut attachment = match Attachment::from_guid(&self.db, guid) {
+            Ok(attachment) => attachment?,
+            Err(why) => {
+                eprintln!("Unable to read group photo {guid}: {why}");
+                return None;
+            }
+        };
+        self.options
+            .attachment_manager
+            .handle_group_photo(chatroom, &mut attachment, self)?;
+        Some(attachment)
+    }
+
     /// Create a new instance of the application
     ///
     /// # Example:
     ///
     /// ```
     /// use crate::app::{
+    ///    config_file::ConfigFile,
     ///    options::{from_command_line, Options},
     ///    runtime::Config,
     /// };
     ///
     /// let args = from_command_line();
-    /// let options = Options::from_args(&args);
+    /// let file = ConfigFile::from_args(&args).unwrap();
+    /// let options = Options::from_args(&args, &file).unwrap();
     /// let app = Config::new(options).unwrap();
     /// ```
     pub fn new(options: Options) -> Result<Config, RuntimeError> {
@@ -215,6 +346,7 @@ impl Config {
             offset: get_offset(),
             db: conn,
             converter: Converter::determine(),
+            attachment_store: AttachmentStore::default(),
         })
     }
 
@@ -226,28 +358,23 @@ impl Config {
             get_db_size(&self.options.db_path).map_err(RuntimeError::DatabaseError)?;
         let mut estimated_export_size = total_db_size / 10;
 
-        let free_space_at_location =
-            available_space(&self.options.export_path).map_err(RuntimeError::DiskError)?;
+        // Links and clones only use more space if they fall back to copying
+        if !matches!(self.options.attachment_manager, AttachmentManager::Disabled)
+            && !self.attachments_share_storage()
+        {
+            estimated_export_size += Attachment::get_total_attachment_bytes(&self.db)
+                .map_err(RuntimeError::DatabaseError)?;
+        }
 
         // Validate that there is enough disk space free to write the export
-        if let AttachmentManager::Disabled = self.options.attachment_manager {
-            if estimated_export_size >= free_space_at_location {
-                return Err(RuntimeError::NotEnoughAvailableSpace(
-                    estimated_export_size,
-                    free_space_at_location,
-                ));
-            }
-        } else {
-            let total_attachment_size = Attachment::get_total_attachment_bytes(&self.db)
-                .map_err(RuntimeError::DatabaseError)?;
-            estimated_export_size += total_attachment_size;
-            if (estimated_export_size + total_attachment_size) >= free_space_at_location {
-                return Err(RuntimeError::NotEnoughAvailableSpace(
-                    estimated_export_size + total_attachment_size,
-                    free_space_at_location,
-                ));
-            }
-        };
+        let free_space_at_location =
+            available_space(&self.options.export_path).map_err(RuntimeError::DiskError)?;
+        if estimated_export_size >= free_space_at_location {
+            return Err(RuntimeError::NotEnoughAvailableSpace(
+                estimated_export_size,
+                free_space_at_location,
+            ));
+        }
 
         println!(
             "Estimated export size: {}",
@@ -257,6 +384,55 @@ impl Config {
         Ok(())
     }
 
+    /// Determine if attachments will share storage with the originals instead of being copied
+    ///
+    /// Hard links and clones can only be made on the same file system, so we try to make one from
+    /// an attachment to the export directory.
+    fn attachments_share_storage(&self) -> bool {
+        match self.options.attachment_manager {
+            AttachmentManager::Symlink => true,
+            AttachmentManager::Hardlink | AttachmentManager::Reflink => {
+                let Some(source) = self.sample_attachment_file() else {
+                    return false;
+                };
+                let probe = self
+                    .options
+                    .export_path
+                    .join(format!(".{}", Uuid::new_v4()));
+                let linked = self
+                    .options
+                    .attachment_manager
+                    .link(&source, &probe)
+                    .is_ok();
+                if linked {
+                    if let Err(why) = remove_file(&probe) {
+                        eprintln!("Unable to remove {probe:?}: {why}");
+                    }
+                }
+                linked
+            }
+            _ => false,
+        }
+    }
+
+    /// Find the first attachment in the table whose file exists on disk
+    fn sample_attachment_file(&self) -> Option<PathBuf> {
+        let mut statement = Attachment::get(&self.db).ok()?;
+        let attachments = statement
+            .query_map([], |row| Ok(Attachment::from_row(row)))
+            .ok()?;
+
+        for attachment in attachments {
+            let path = Attachment::extract(attachment)
+                .ok()
+                .and_then(|attachment| self.attachment_file(&attachment));
+            if let Some(path) = path.filter(|path| path.is_file()) {
+                return Some(path);
+            }
+        }
+        None
+    }
+
     /// Handles diagnostic tests for database
     fn run_diagnostic(&self) -> Result<(), TableError> {
         println!("\niMessage Database Diagnostics\n");
@@ -297,19 +473,81 @@ impl Config {
     ///
     /// ```
     /// use crate::app::{
+    ///    config_file::ConfigFile,
     ///    options::{from_command_line, Options},
     ///    runtime::Config,
     /// };
     ///
     /// let args = from_command_line();
-    /// let options = Options::from_args(&args);
+    /// let file = ConfigFile::from_args(&args).unwrap();
+    /// let options = Options::from_args(&args, &file).unwrap();
     /// let app = Config::new(options).unwrap();
     /// app.start();
     /// ```
     pub fn start(&self) -> Result<(), RuntimeError> {
-        if self.options.diagnostic {
-            self.run_diagnostic().map_err(RuntimeError::DatabaseError)?;
-        } else if let Some(export_type) = &self.options.export_type {
+        match &self.options.mode {
+            Mode::Diagnose => {
+                self.run_diagnostic().map_err(RuntimeError::DatabaseError)?;
+                println!("Done!");
+            }
+            Mode::Export => {
+                self.run_export()?;
+                println!("Done!");
+            }
+            // Listings are only written to stdout so they can be piped into other tools
+            Mode::ListChats(list_format) => self.run_list_chats(list_format)?,
+            // Results are only written to stdout so they can be piped into other tools
+            Mode::Search(query) => self.run_search(query)?,
+            Mode::Stats(stats_format) => {
+                self.run_stats(stats_format)?;
+                println!("Done!");
+            }
+        }
+        Ok(())
+    }
+
+    /// Print every deduplicated conversation in the selected format
+    fn run_list_chats(&self, list_format: &ListFormat) -> Result<(), RuntimeError> {
+        let listings = ChatListing::build_all(self)?;
+        match list_format {
+            ListFormat::Table => print!("{}", render_table(&listings, self)),
+            ListFormat::Json => println!("{}", render_json(&listings)?),
+        }
+        Ok(())
+    }
+
+    /// Search message text, building the search index first if it is missing or out of date
+    fn run_search(&self, query: &SearchQuery) -> Result<(), RuntimeError> {
+        let index = SearchIndex::open(&query.index_path)?;
+        let source = SourceState::from_config(self)?;
+        if query.rebuild || !index.is_current(&source)? {
+            index.build(self, &source)?;
+        }
+
+        let results = index.search(query, &self.options.query_context)?;
+        let titles = search::conversation_titles(self)?;
+        print!("{}", search::render_results(&results, &titles, self));
+        eprintln!("Found {} matching messages", results.len());
+        Ok(())
+    }
+
+    /// Calculate message statistics and write them to the export directory
+    fn run_stats(&self, stats_format: &StatsFormat) -> Result<(), RuntimeError> {
+        create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
+        let path = self.options.export_path.join(stats_format.filename());
+
+        eprintln!("Writing statistics to {}...", path.display());
+        let report = StatsReport::build(self)?;
+        let contents = match stats_format {
+            StatsFormat::Json => stats::render_json(&report)?,
+            StatsFormat::Html => stats::render_html(&report, self),
+        };
+        write(&path, contents).map_err(RuntimeError::DiskError)
+    }
+
+    /// Export messages into the selected file format
+    fn run_export(&self) -> Result<(), RuntimeError> {
+        if let Some(export_type) = &self.options.export_type {
             // Ensure the path we want to export to exists
             create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
 
@@ -331,18 +569,94 @@ impl Config {
                 ExportType::Txt => {
                     TXT::new(self).iter_messages()?;
                 }
+                ExportType::Pdf => {
+                    PDF::new(self).iter_messages()?;
+                }
+                ExportType::Epub => {
+                    EPUB::new(self).iter_messages()?;
+                }
+                ExportType::Mbox => {
+                    MBOX::new(self).iter_messages()?;
+                }
+                ExportType::Xml => {
+                    XML::new(self).iter_messages()?;
+                }
+                ExportType::Locations => {
+                    LOCATIONS::new(self).iter_messages()?;
+                }
+                ExportType::Vcf => {
+                    VCF::new(self).iter_messages()?;
+                }
+            }
+
+            // Tell the user how much space deduplicating attachments saved
+            if self.options.dedupe {
+                eprintln!(
+                    "Deduplicated {} attachments, saving {}",
+                    self.attachment_store.duplicates(),
+                    format_file_size(self.attachment_store.bytes_saved())
+                );
             }
         }
-        println!("Done!");
         Ok(())
     }
 
+    /// Get the translated strings for the configured locale
+    pub fn strings(&self) -> &'static Strings {
+        self.options.locale.strings()
+    }
+
+    /// Format a date for display using the configured time zone and date pattern
+    ///
+    /// Dates from a chat with its own time zone use that zone instead of the global one.
+    /// If ISO-8601 timestamps were requested, the date is always written in UTC
+    pub fn format_date(
+        &self,
+        date: &Result<DateTime<Local>, MessageError>,
+        chat_id: Option<i32>,
+    ) -> String {
+        if self.options.iso_timestamps {
+            return format_iso(date);
+        }
+        let pattern = self
+            .options
+            .date_format
+            .as_deref()
+            .unwrap_or(self.strings().date_format);
+        match date {
+            Ok(date) => format_with(&Ok(self.timezone(chat_id).convert(date)), pattern),
+            Err(why) => why.to_string(),
+        }
+    }
+
+    /// Get the time zone used to display dates from a chat
+    ///
+    /// Chat time zones are set by the IDs shown in `list-chats`, which may be one of several chats
+    /// that were merged into a single conversation, so any chat in the same conversation matches.
+    pub fn timezone(&self, chat_id: Option<i32>) -> &Timezone {
+        chat_id
+            .and_then(|chat_id| self.real_chatrooms.get(&chat_id))
+            .and_then(|real_id| {
+                self.options
+                    .chat_timezones
+                    .iter()
+                    .find(|(id, _)| self.real_chatrooms.get(id) == Some(real_id))
+            })
+            .map_or(&self.options.timezone, |(_, timezone)| timezone)
+    }
+
     /// Determine who sent a message
     pub fn who(&self, handle_id: Option<i32>, is_from_me: bool) -> &str {
         if is_from_me {
-            return self.options.custom_name.as_deref().unwrap_or(ME);
+            return self
+                .options
+                .custom_name
+                .as_deref()
+                .unwrap_or(self.strings().me);
         } else if let Some(handle_id) = handle_id {
             return match self.participants.get(&handle_id) {
+                // The handle cache names the database owner in English
+                Some(contact) if contact == ME => self.strings().me,
                 Some(contact) => contact,
                 None => UNKNOWN,
             };
@@ -353,34 +667,12 @@ impl Config {
 
 #[cfg(test)]
 mod filename_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
-    use imessage_database::{
-        tables::{
-            chat::Chat,
-            table::{get_connection, MAX_LENGTH},
-        },
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
-    };
-    use std::{
-        collections::{BTreeSet, HashMap},
-        path::PathBuf,
+    use crate::{exporters::html::tests::fake_options, Config, Options};
+    use imessage_database::tables::{
+        chat::{Chat, ChatProperties},
+        table::{get_connection, MAX_LENGTH},
     };
-
-    fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::new(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
+    use std::collections::{BTreeSet, HashMap};
 
     fn fake_chat() -> Chat {
         Chat {
@@ -388,6 +680,7 @@ mod filename_tests {
             chat_identifier: "Default".to_string(),
             service_name: Some(String::new()),
             display_name: None,
+            properties: None,
         }
     }
 
@@ -404,6 +697,7 @@ mod filename_tests {
             offset: 0,
             db: connection,
             converter: Some(crate::app::converter::Converter::Sips),
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
         }
     }
 
@@ -585,32 +879,69 @@ mod filename_tests {
         let filename = app.filename(&chat);
         assert_eq!(filename, "Default");
     }
+
+    #[test]
+    fn can_get_conversation_details() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        // Create chat
+        let mut chat = fake_chat();
+        chat.display_name = Some("Test Chat Name".to_string());
+        chat.service_name = Some("iMessage".to_string());
+        chat.properties = Some(ChatProperties {
+            read_receipts_enabled: Some(false),
+            group_photo_guid: Some("at_0_GUID".to_string()),
+            ignore_alerts: true,
+            ..Default::default()
+        });
+
+        // Create participant data
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+
+        // Add participants
+        let mut people = BTreeSet::new();
+        people.insert(10);
+        people.insert(11);
+        app.chatroom_participants.insert(chat.rowid, people);
+
+        let details = app.conversation_details(&chat);
+        assert_eq!(
+            details,
+            vec![
+                ("Conversation", "Test Chat Name".to_string()),
+                ("Participants", "Person 10, Person 11".to_string()),
+                ("Service", "iMessage".to_string()),
+                ("Read receipts", "Disabled".to_string()),
+                ("Alerts", "Hidden".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_get_conversation_details_empty() {
+        let options = fake_options();
+        let app = fake_app(options);
+
+        // Create chat
+        let mut chat = fake_chat();
+        chat.service_name = None;
+
+        let details = app.conversation_details(&chat);
+        assert!(details.is_empty());
+    }
 }
 
 #[cfg(test)]
 mod who_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
-    use imessage_database::{
-        tables::{chat::Chat, messages::Message, table::get_connection},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+    use crate::{
+        app::locale::Locale,
+        exporters::html::tests::{blank, fake_options},
+        Config, Options,
     };
-    use std::{collections::HashMap, path::PathBuf};
-
-    fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::new(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
+    use imessage_database::tables::{chat::Chat, table::get_connection};
+    use std::collections::HashMap;
 
     fn fake_chat() -> Chat {
         Chat {
@@ -618,6 +949,7 @@ mod who_tests {
             chat_identifier: "Default".to_string(),
             service_name: Some(String::new()),
             display_name: None,
+            properties: None,
         }
     }
 
@@ -634,36 +966,7 @@ mod who_tests {
             offset: 0,
             db: connection,
             converter: Some(crate::app::converter::Converter::Sips),
-        }
-    }
-
-    fn blank() -> Message {
-        Message {
-            rowid: i32::default(),
-            guid: String::default(),
-            text: None,
-            service: Some("iMessage".to_string()),
-            handle_id: Some(i32::default()),
-            subject: None,
-            date: i64::default(),
-            date_read: i64::default(),
-            date_delivered: i64::default(),
-            is_from_me: false,
-            is_read: false,
-            item_type: 0,
-            group_title: None,
-            group_action_type: 0,
-            associated_message_guid: None,
-            associated_message_type: Some(i32::default()),
-            balloon_bundle_id: None,
-            expressive_send_style_id: None,
-            thread_originator_guid: None,
-            thread_originator_part: None,
-            date_edited: 0,
-            chat_id: None,
-            num_attachments: 0,
-            deleted_from: None,
-            num_replies: 0,
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
         }
     }
 
@@ -700,6 +1003,17 @@ mod who_tests {
         assert_eq!(who, "Me".to_string());
     }
 
+    #[test]
+    fn can_get_who_me_locale() {
+        let mut options = fake_options();
+        options.locale = Locale::Spanish;
+        let app = fake_app(options);
+
+        // Get participant name
+        let who = app.who(Some(0), true);
+        assert_eq!(who, "Yo".to_string());
+    }
+
     #[test]
     fn can_get_who_me_custom() {
         let mut options = fake_options();
@@ -812,28 +1126,27 @@ mod who_tests {
 
 #[cfg(test)]
 mod directory_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
+    use crate::{
+        app::{attachment_manager::AttachmentManager, attachment_names::AttachmentNames},
+        exporters::html::tests::{blank, fake_options},
+        Config, Options,
+    };
     use imessage_database::{
-        tables::{attachment::Attachment, table::get_connection},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+        tables::{
+            attachment::Attachment,
+            chat::{Chat, ChatProperties},
+            table::get_connection,
+        },
+        util::dates::Timezone,
     };
-    use std::{collections::HashMap, path::PathBuf};
-
-    fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::new(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
+    use rusqlite::Connection;
+    use std::{
+        collections::{BTreeMap, HashMap},
+        env::temp_dir,
+        fs::{create_dir_all, read, read_dir, remove_dir_all, write},
+        path::PathBuf,
+    };
+    use uuid::Uuid;
 
     fn fake_app(options: Options) -> Config {
         let connection = get_connection(&options.db_path).unwrap();
@@ -848,6 +1161,7 @@ mod directory_tests {
             offset: 0,
             db: connection,
             converter: Some(crate::app::converter::Converter::Sips),
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
         }
     }
 
@@ -952,4 +1266,302 @@ mod directory_tests {
         let expected = String::from("a/b/c/d.jpg");
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn can_get_link_copied() {
+        let mut options = fake_options();
+        // Set an export path
+        options.export_path = PathBuf::from("/Users/ReagentX/exports");
+
+        let app = fake_app(options);
+
+        // Create attachment
+        let mut attachment = fake_attachment();
+        attachment.copied_path = Some(PathBuf::from(
+            "/Users/ReagentX/exports/attachments/1/Trip #1 (2).jpg",
+        ));
+
+        let result = app.message_attachment_link(&attachment);
+        let expected = String::from("attachments/1/Trip%20%231%20%282%29.jpg");
+        assert_eq!(result, expected);
+    }
+
+    fn fake_group_chat(group_photo_guid: &str) -> Chat {
+        Chat {
+            rowid: 0,
+            chat_identifier: "Default".to_string(),
+            service_name: Some(String::new()),
+            display_name: None,
+            properties: Some(ChatProperties {
+                group_photo_guid: Some(group_photo_guid.to_string()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Replace the app's database with one whose attachment table contains each `(guid, filename)`
+    fn fake_attachment_table(app: &mut Config, attachments: &[(&str, &str)]) {
+        app.db = Connection::open_in_memory().unwrap();
+        app.db
+            .execute_batch(
+                "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, guid TEXT, filename TEXT, total_bytes INTEGER)",
+            )
+            .unwrap();
+        for (guid, filename) in attachments {
+            app.db
+                .execute(
+                    "INSERT INTO attachment (guid, filename, total_bytes) VALUES (?1, ?2, 1)",
+                    [guid, filename],
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn can_get_sample_attachment_file() {
+        let dir = temp_dir().join(Uuid::new_v4().to_string());
+        create_dir_all(&dir).unwrap();
+        let file = dir.join("a.png");
+        write(&file, [1, 2, 3]).unwrap();
+
+        let mut app = fake_app(fake_options());
+        fake_attachment_table(
+            &mut app,
+            &[
+                ("at_0", &dir.join("missing.png").to_string_lossy()),
+                ("at_1", &file.to_string_lossy()),
+            ],
+        );
+
+        assert_eq!(app.sample_attachment_file(), Some(file));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_probe_hardlink_with_attachment() {
+        let dir = temp_dir().join(Uuid::new_v4().to_string());
+        let export_path = dir.join("export");
+        create_dir_all(&export_path).unwrap();
+        let file = dir.join("a.png");
+        write(&file, [1, 2, 3]).unwrap();
+
+        let mut options = fake_options();
+        options.attachment_manager = AttachmentManager::Hardlink;
+        options.export_path = export_path.clone();
+        let mut app = fake_app(options);
+        fake_attachment_table(&mut app, &[("at_0", &file.to_string_lossy())]);
+
+        assert!(app.attachments_share_storage());
+        // The probe link is removed
+        assert_eq!(read_dir(&export_path).unwrap().count(), 0);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_probe_hardlink_without_attachment() {
+        let mut options = fake_options();
+        options.attachment_manager = AttachmentManager::Hardlink;
+        options.export_path = temp_dir();
+        let mut app = fake_app(options);
+        fake_attachment_table(&mut app, &[("at_0", "/does/not/exist.png")]);
+
+        assert!(!app.attachments_share_storage());
+    }
+
+    #[test]
+    fn can_get_group_photo() {
+        let mut app = fake_app(fake_options());
+        fake_attachment_table(&mut app, &[("at_0", "a/b/c/d.jpg")]);
+
+        let photo = app.group_photo(&fake_group_chat("at_0")).unwrap();
+        assert_eq!(photo.filename.as_deref(), Some("a/b/c/d.jpg"));
+        assert_eq!(photo.copied_path, None);
+    }
+
+    #[test]
+    fn cant_get_group_photo_missing() {
+        let mut app = fake_app(fake_options());
+        fake_attachment_table(&mut app, &[("at_0", "a/b/c/d.jpg")]);
+
+        assert!(app.group_photo(&fake_group_chat("at_1")).is_none());
+    }
+
+    #[test]
+    fn cant_get_group_photo_no_properties() {
+        let mut app = fake_app(fake_options());
+        fake_attachment_table(&mut app, &[("at_0", "a/b/c/d.jpg")]);
+
+        let mut chat = fake_group_chat("at_0");
+        chat.properties = None;
+        assert!(app.group_photo(&chat).is_none());
+    }
+
+    #[test]
+    fn can_copy_group_photo() {
+        let dir = temp_dir().join(Uuid::new_v4().to_string());
+        create_dir_all(&dir).unwrap();
+        let file = dir.join("photo.png");
+        write(&file, [1, 2, 3]).unwrap();
+
+        let mut options = fake_options();
+        options.attachment_manager = AttachmentManager::Efficient;
+        options.export_path = dir.join("export");
+        let mut app = fake_app(options);
+        app.real_chatrooms.insert(0, 5);
+        fake_attachment_table(&mut app, &[("at_0", &file.to_string_lossy())]);
+
+        // Group photos are copied to the conversation's attachment directory
+        let photo = app.group_photo(&fake_group_chat("at_0")).unwrap();
+        let copied_path = photo.copied_path.unwrap();
+        assert_eq!(
+            copied_path.parent(),
+            Some(app.attachment_path().join("5").as_path())
+        );
+        assert_eq!(copied_path.extension().unwrap(), "png");
+        assert_eq!(read(&copied_path).unwrap(), [1, 2, 3]);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_copy_dated_attachment_in_chat_timezone() {
+        let dir = temp_dir().join(Uuid::new_v4().to_string());
+        create_dir_all(&dir).unwrap();
+        let file = dir.join("photo.png");
+        write(&file, [1, 2, 3]).unwrap();
+
+        let mut options = fake_options();
+        options.attachment_manager = AttachmentManager::Efficient;
+        options.attachment_names = AttachmentNames::Dated;
+        options.export_path = dir.join("export");
+        options.timezone = Timezone::Utc;
+        options.chat_timezones = BTreeMap::from([(1, Timezone::from_cli("Asia/Tokyo").unwrap())]);
+        let mut app = fake_app(options);
+        app.real_chatrooms.insert(1, 1);
+
+        // Sent at 2020-12-31 20:00 UTC, which is 2021-01-01 05:00 in Tokyo
+        let mut message = blank();
+        message.chat_id = Some(1);
+        message.date = 1609444800000000000;
+        let mut attachment = fake_attachment();
+        attachment.filename = Some(file.to_string_lossy().to_string());
+
+        AttachmentManager::Efficient.handle_attachment(&message, &mut attachment, &app);
+        let copied_path = attachment.copied_path.unwrap();
+        assert_eq!(
+            copied_path.file_name().unwrap(),
+            "2021-01-01 05.00.00 d.png"
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod date_tests {
+    use crate::{app::locale::Locale, exporters::html::tests::fake_options, Config, Options};
+    use chrono::{Local, TimeZone, Utc};
+    use imessage_database::{tables::table::get_connection, util::dates::Timezone};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn fake_app(options: Options) -> Config {
+        let connection = get_connection(&options.db_path).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            reactions: HashMap::new(),
+            options,
+            offset: 0,
+            db: connection,
+            converter: Some(crate::app::converter::Converter::Sips),
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
+        }
+    }
+
+    #[test]
+    fn can_format_date_in_timezone() {
+        let mut options = fake_options();
+        options.timezone = Timezone::from_cli("Asia/Tokyo").unwrap();
+        let app = fake_app(options);
+
+        let date = Utc
+            .with_ymd_and_hms(2020, 5, 20, 16, 10, 11)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(app.format_date(&Ok(date), None), "May 21, 2020  1:10:11 AM");
+    }
+
+    #[test]
+    fn can_format_date_in_chat_timezone() {
+        let mut options = fake_options();
+        options.timezone = Timezone::Utc;
+        options.chat_timezones = BTreeMap::from([(1, Timezone::from_cli("Asia/Tokyo").unwrap())]);
+        let mut app = fake_app(options);
+
+        // Chats 1 and 2 were merged into one conversation
+        app.real_chatrooms.insert(1, 0);
+        app.real_chatrooms.insert(2, 0);
+        app.real_chatrooms.insert(3, 1);
+
+        let date = Utc
+            .with_ymd_and_hms(2020, 5, 20, 16, 10, 11)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(
+            app.format_date(&Ok(date), Some(1)),
+            "May 21, 2020  1:10:11 AM"
+        );
+        assert_eq!(
+            app.format_date(&Ok(date), Some(2)),
+            "May 21, 2020  1:10:11 AM"
+        );
+        assert_eq!(
+            app.format_date(&Ok(date), Some(3)),
+            "May 20, 2020  4:10:11 PM"
+        );
+        assert_eq!(app.format_date(&Ok(date), None), "May 20, 2020  4:10:11 PM");
+    }
+
+    #[test]
+    fn can_format_date_iso() {
+        let mut options = fake_options();
+        options.timezone = Timezone::from_cli("Asia/Tokyo").unwrap();
+        options.iso_timestamps = true;
+        let app = fake_app(options);
+
+        let date = Utc
+            .with_ymd_and_hms(2020, 5, 20, 16, 10, 11)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(app.format_date(&Ok(date), None), "2020-05-20T16:10:11Z");
+    }
+
+    #[test]
+    fn can_format_date_custom_pattern() {
+        let mut options = fake_options();
+        options.timezone = Timezone::Utc;
+        options.date_format = Some("%Y-%m-%d %H:%M".to_string());
+        let app = fake_app(options);
+
+        let date = Utc
+            .with_ymd_and_hms(2020, 5, 20, 16, 10, 11)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(app.format_date(&Ok(date), None), "2020-05-20 16:10");
+    }
+
+    #[test]
+    fn can_format_date_locale_pattern() {
+        let mut options = fake_options();
+        options.timezone = Timezone::Utc;
+        options.locale = Locale::Spanish;
+        let app = fake_app(options);
+
+        let date = Utc
+            .with_ymd_and_hms(2020, 5, 20, 16, 10, 11)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(app.format_date(&Ok(date), None), "20/05/2020 16:10:11");
+    }
 }
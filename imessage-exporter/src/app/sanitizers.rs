@@ -4,6 +4,8 @@ use std::borrow::Cow;
 const FILENAME_REPLACEMENT_CHAR: char = '_';
 /// Characters disallowed in a filename
 const FILENAME_DISALLOWED_CHARS: [char; 3] = ['/', '\\', ':'];
+/// Characters `gen_text` leaves in the body to mark attachments and app messages
+const PLACEHOLDER_CHARS: [char; 2] = ['\u{FFFC}', '\u{FFFD}'];
 
 /// Remove unsafe chars in [this list](FILENAME_DISALLOWED_CHARS).
 pub fn sanitize_filename(filename: &str) -> String {
@@ -20,7 +22,7 @@ pub fn sanitize_filename(filename: &str) -> String {
 }
 
 /// Escapes HTML special characters in the input string.
-pub fn sanitize_html(input: &str) -> Cow<str> {
+pub fn sanitize_html(input: &str) -> Cow<'_, str> {
     for (idx, char) in input.char_indices() {
         if matches!(char, '<' | '>' | '"' | '’' | '&') {
             let mut res = String::from(&input[..idx]);
@@ -38,6 +40,24 @@ pub fn sanitize_html(input: &str) -> Cow<str> {
     Cow::Borrowed(input)
 }
 
+/// Remove attachment and app placeholders from message text, trimming what remains.
+pub fn strip_placeholders(text: &str) -> String {
+    text.replace(PLACEHOLDER_CHARS, "").trim().to_string()
+}
+
+/// Percent-encode a relative path so it can be used as a link target.
+pub fn sanitize_link(path: &str) -> String {
+    let mut res = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/') {
+            res.push(byte as char);
+        } else {
+            res.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    res
+}
+
 #[cfg(test)]
 mod test_filename {
     use crate::app::sanitizers::sanitize_filename;
@@ -58,6 +78,47 @@ mod test_filename {
     }
 }
 
+#[cfg(test)]
+mod test_placeholders {
+    use crate::app::sanitizers::strip_placeholders;
+
+    #[test]
+    fn can_strip_placeholders() {
+        assert_eq!(
+            strip_placeholders("\u{FFFC} Hello\u{FFFD} world "),
+            "Hello world"
+        );
+    }
+
+    #[test]
+    fn can_strip_only_placeholders() {
+        assert_eq!(strip_placeholders("\u{FFFC}\u{FFFC}"), "");
+    }
+}
+
+#[cfg(test)]
+mod test_link {
+    use crate::app::sanitizers::sanitize_link;
+
+    #[test]
+    fn doesnt_sanitize_plain_link() {
+        assert_eq!(
+            sanitize_link("attachments/1/a_b-c.html"),
+            "attachments/1/a_b-c.html"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_reserved_chars() {
+        assert_eq!(sanitize_link("Friends #1?.html"), "Friends%20%231%3F.html");
+    }
+
+    #[test]
+    fn can_sanitize_unicode() {
+        assert_eq!(sanitize_link("Café.html"), "Caf%C3%A9.html");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::app::sanitizers::sanitize_html;
@@ -0,0 +1,798 @@
+/*!
+ Contains logic for building a full-text search index of message text and querying it.
+*/
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{edited::EditedMessage, variants::BalloonProvider},
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::{dates::get_local_time, dirs::home, query_context::QueryContext},
+};
+
+use crate::app::{
+    chat_list::ChatListing, error::RuntimeError, progress::build_progress_bar_export,
+    runtime::Config, sanitizers::strip_placeholders,
+};
+
+/// Default location of the search index, relative to the user's home directory
+pub const DEFAULT_INDEX_PATH: &str = ".cache/imessage-exporter/search_index.db";
+/// Default number of matching messages to show
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+/// Default number of messages to show around each match
+pub const DEFAULT_SEARCH_CONTEXT: usize = 2;
+/// Bumped whenever the layout of the index changes so old indexes get rebuilt
+const INDEX_VERSION: &str = "1";
+
+/// Describes a search requested on the command line
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// FTS5 query text, i.e. `dinner`, `"see you soon"`, or `resta*`
+    pub query: String,
+    /// The maximum number of matching messages to return
+    pub limit: usize,
+    /// The number of messages to show before and after each match
+    pub context: usize,
+    /// Where the search index is stored
+    pub index_path: PathBuf,
+    /// If true, rebuild the index even if it is up to date
+    pub rebuild: bool,
+}
+
+impl SearchQuery {
+    /// The index path used when none is provided
+    pub fn default_index_path() -> PathBuf {
+        PathBuf::from(home()).join(DEFAULT_INDEX_PATH)
+    }
+}
+
+/// A message stored in the search index
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IndexedMessage {
+    /// The `ROWID` of the message in the source database
+    pub message_id: i32,
+    /// The deduplicated conversation ID, matching the `list-chats` output
+    pub conversation: Option<i32>,
+    pub handle_id: Option<i32>,
+    pub is_from_me: bool,
+    /// The message date, in the same format as the `date` column in the source database
+    pub date: i64,
+    /// The current text of the message
+    pub text: String,
+}
+
+impl IndexedMessage {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(IndexedMessage {
+            message_id: row.get("message_id")?,
+            conversation: row.get("conversation")?,
+            handle_id: row.get("handle_id")?,
+            is_from_me: row.get("is_from_me")?,
+            date: row.get("date")?,
+            text: row.get("text")?,
+        })
+    }
+}
+
+/// A message that matched a search query
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    pub message: IndexedMessage,
+    /// The text that matched, with matching terms wrapped in `[` and `]`
+    pub highlighted: String,
+    /// If true, the match came from an earlier version of an edited message
+    pub edited: bool,
+    /// Messages sent before the match, oldest first
+    pub before: Vec<IndexedMessage>,
+    /// Messages sent after the match, oldest first
+    pub after: Vec<IndexedMessage>,
+}
+
+/// Identifies the state of the source database an index was built from
+#[derive(Debug, PartialEq, Eq)]
+pub struct SourceState {
+    pub path: String,
+    pub messages: i64,
+    pub max_rowid: i64,
+}
+
+impl SourceState {
+    /// Read the current state of the source database
+    pub fn from_config(config: &Config) -> Result<Self, RuntimeError> {
+        let (messages, max_rowid) = config
+            .db
+            .query_row(
+                "SELECT COUNT(*), IFNULL(MAX(ROWID), 0) FROM message",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|why| RuntimeError::DatabaseError(TableError::Messages(why)))?;
+        let db_path = config.options.get_db_path();
+        Ok(SourceState {
+            path: db_path
+                .canonicalize()
+                .unwrap_or(db_path)
+                .display()
+                .to_string(),
+            messages,
+            max_rowid,
+        })
+    }
+}
+
+/// A sidecar SQLite database containing an FTS5 index of message text
+pub struct SearchIndex {
+    db: Connection,
+}
+
+impl SearchIndex {
+    /// Open the index at `path`, creating it if it does not exist
+    pub fn open(path: &Path) -> Result<Self, RuntimeError> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).map_err(RuntimeError::DiskError)?;
+        }
+        let index = SearchIndex {
+            db: Connection::open(path).map_err(RuntimeError::IndexError)?,
+        };
+        index.create_schema()?;
+        Ok(index)
+    }
+
+    fn create_schema(&self) -> Result<(), RuntimeError> {
+        self.db
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS metadata (
+                     key TEXT PRIMARY KEY,
+                     value TEXT NOT NULL
+                 );
+                 CREATE TABLE IF NOT EXISTS messages (
+                     message_id INTEGER PRIMARY KEY,
+                     conversation INTEGER,
+                     handle_id INTEGER,
+                     is_from_me INTEGER NOT NULL,
+                     date INTEGER NOT NULL,
+                     text TEXT NOT NULL
+                 );
+                 CREATE INDEX IF NOT EXISTS messages_conversation_date ON messages (conversation, date);
+                 CREATE VIRTUAL TABLE IF NOT EXISTS message_text USING fts5 (
+                     text,
+                     message_id UNINDEXED,
+                     edited UNINDEXED,
+                     tokenize = 'unicode61 remove_diacritics 2'
+                 );",
+            )
+            .map_err(RuntimeError::IndexError)
+    }
+
+    /// Determine if the index was built from the source database in its current state
+    pub fn is_current(&self, source: &SourceState) -> Result<bool, RuntimeError> {
+        Ok(self.metadata("version")?.as_deref() == Some(INDEX_VERSION)
+            && self.metadata("source_path")?.as_deref() == Some(source.path.as_str())
+            && self.metadata("source_messages")? == Some(source.messages.to_string())
+            && self.metadata("source_max_rowid")? == Some(source.max_rowid.to_string()))
+    }
+
+    fn metadata(&self, key: &str) -> Result<Option<String>, RuntimeError> {
+        self.db
+            .query_row("SELECT value FROM metadata WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(RuntimeError::IndexError)
+    }
+
+    /// Remove every message from the index
+    pub fn clear(&self) -> Result<(), RuntimeError> {
+        self.db
+            .execute_batch(
+                "DELETE FROM metadata;
+                 DELETE FROM messages;
+                 DELETE FROM message_text;",
+            )
+            .map_err(RuntimeError::IndexError)
+    }
+
+    /// Add a message to the index, along with the text of any earlier versions of it
+    pub fn insert(&self, message: &IndexedMessage, edits: &[String]) -> Result<(), RuntimeError> {
+        self.db
+            .execute(
+                "INSERT OR REPLACE INTO messages (message_id, conversation, handle_id, is_from_me, date, text)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    message.message_id,
+                    message.conversation,
+                    message.handle_id,
+                    message.is_from_me,
+                    message.date,
+                    message.text
+                ],
+            )
+            .map_err(RuntimeError::IndexError)?;
+
+        let mut statement = self
+            .db
+            .prepare_cached(
+                "INSERT INTO message_text (text, message_id, edited) VALUES (?1, ?2, ?3)",
+            )
+            .map_err(RuntimeError::IndexError)?;
+        statement
+            .execute(params![message.text, message.message_id, false])
+            .map_err(RuntimeError::IndexError)?;
+        for edit in edits {
+            statement
+                .execute(params![edit, message.message_id, true])
+                .map_err(RuntimeError::IndexError)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuild the index from every message in the source database
+    pub fn build(&self, config: &Config, source: &SourceState) -> Result<(), RuntimeError> {
+        eprintln!("Building search index...");
+
+        // Map each chat to its deduplicated conversation so results match `list-chats`
+        let conversations: HashMap<i32, i32> = ChatListing::build_all(config)?
+            .into_iter()
+            .flat_map(|listing| {
+                let id = listing.id;
+                listing
+                    .chat_ids
+                    .into_iter()
+                    .map(move |chat_id| (chat_id, id))
+            })
+            .collect();
+
+        // The index covers every message so it can be reused with any date filters
+        let context = QueryContext::default();
+        let total_messages =
+            Message::get_count(&config.db, &context).map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&config.db, &context).map_err(RuntimeError::DatabaseError)?;
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        let transaction = self
+            .db
+            .unchecked_transaction()
+            .map_err(RuntimeError::IndexError)?;
+        self.clear()?;
+
+        let mut seen = HashSet::new();
+        for message in messages {
+            pb.inc(1);
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Messages can be returned more than once if they belong to more than one chat
+            if !seen.insert(msg.rowid) || msg.is_reaction() || msg.is_announcement() {
+                continue;
+            }
+
+            let (text, edits) = match message_text(&mut msg, config) {
+                Some(parts) => parts,
+                None => continue,
+            };
+
+            self.insert(
+                &IndexedMessage {
+                    message_id: msg.rowid,
+                    conversation: msg
+                        .chat_id
+                        .or(msg.deleted_from)
+                        .and_then(|chat_id| conversations.get(&chat_id))
+                        .copied(),
+                    handle_id: msg.handle_id,
+                    is_from_me: msg.is_from_me,
+                    date: msg.date,
+                    text,
+                },
+                &edits,
+            )?;
+        }
+
+        for (key, value) in [
+            ("version", INDEX_VERSION.to_string()),
+            ("source_path", source.path.to_string()),
+            ("source_messages", source.messages.to_string()),
+            ("source_max_rowid", source.max_rowid.to_string()),
+        ] {
+            transaction
+                .execute(
+                    "INSERT INTO metadata (key, value) VALUES (?1, ?2)",
+                    [key, &value],
+                )
+                .map_err(RuntimeError::IndexError)?;
+        }
+        transaction.commit().map_err(RuntimeError::IndexError)?;
+        pb.finish();
+        Ok(())
+    }
+
+    /// Find messages that match a query, in order of relevance
+    ///
+    /// The query uses [FTS5 syntax](https://www.sqlite.org/fts5.html#full_text_query_syntax),
+    /// so `"see you soon"` matches a phrase and `resta*` matches a prefix.
+    pub fn search(
+        &self,
+        query: &SearchQuery,
+        filters: &QueryContext,
+    ) -> Result<Vec<SearchResult>, RuntimeError> {
+        let end_comparison = if filters.end_exclusive { "<" } else { "<=" };
+        let mut statement = self
+            .db
+            .prepare(&format!(
+                "SELECT
+                     m.*,
+                     highlight(message_text, 0, '[', ']') AS highlighted,
+                     message_text.edited AS edited
+                 FROM
+                     message_text
+                     JOIN messages AS m ON m.message_id = message_text.message_id
+                 WHERE
+                     message_text MATCH ?1
+                     AND m.date >= ?2
+                     AND m.date {end_comparison} ?3
+                 ORDER BY
+                     rank"
+            ))
+            .map_err(RuntimeError::IndexError)?;
+
+        let rows = statement
+            .query_map(
+                params![
+                    query.query,
+                    filters.start.unwrap_or(i64::MIN),
+                    filters.end.unwrap_or(i64::MAX)
+                ],
+                |row| {
+                    Ok((
+                        IndexedMessage::from_row(row)?,
+                        row.get::<_, String>("highlighted")?,
+                        row.get::<_, bool>("edited")?,
+                    ))
+                },
+            )
+            .map_err(|why| invalid_query(&query.query, why))?;
+
+        let mut results = vec![];
+        let mut seen = HashSet::new();
+        for row in rows {
+            let (message, highlighted, edited) =
+                row.map_err(|why| invalid_query(&query.query, why))?;
+
+            // A message can match more than once if an earlier version also matched
+            if !seen.insert(message.message_id) {
+                continue;
+            }
+            if results.len() == query.limit {
+                break;
+            }
+
+            let (before, after) = self.context(&message, query.context)?;
+            results.push(SearchResult {
+                message,
+                highlighted,
+                edited,
+                before,
+                after,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Get the messages sent in the same conversation immediately before and after `message`
+    fn context(
+        &self,
+        message: &IndexedMessage,
+        count: usize,
+    ) -> Result<(Vec<IndexedMessage>, Vec<IndexedMessage>), RuntimeError> {
+        let conversation = match message.conversation {
+            Some(conversation) if count > 0 => conversation,
+            _ => return Ok((vec![], vec![])),
+        };
+
+        let query = |sql: &str| -> Result<Vec<IndexedMessage>, RuntimeError> {
+            let mut statement = self.db.prepare(sql).map_err(RuntimeError::IndexError)?;
+            let rows = statement
+                .query_map(
+                    params![conversation, message.date, message.message_id, count],
+                    IndexedMessage::from_row,
+                )
+                .map_err(RuntimeError::IndexError)?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(RuntimeError::IndexError)
+        };
+
+        let mut before = query(
+            "SELECT * FROM messages
+             WHERE conversation = ?1 AND (date < ?2 OR (date = ?2 AND message_id < ?3))
+             ORDER BY date DESC, message_id DESC
+             LIMIT ?4",
+        )?;
+        before.reverse();
+
+        let after = query(
+            "SELECT * FROM messages
+             WHERE conversation = ?1 AND (date > ?2 OR (date = ?2 AND message_id > ?3))
+             ORDER BY date, message_id
+             LIMIT ?4",
+        )?;
+
+        Ok((before, after))
+    }
+}
+
+/// Get the current text of a message and the text of any earlier versions of it
+fn message_text(msg: &mut Message, config: &Config) -> Option<(String, Vec<String>)> {
+    if msg.is_edited() {
+        let payload = msg.message_summary_info(&config.db)?;
+        let edited = EditedMessage::from_map(&payload).ok()?;
+        let mut versions: Vec<String> = edited
+            .events
+            .iter()
+            .map(|event| strip_placeholders(&event.text))
+            .filter(|text| !text.is_empty())
+            .collect();
+        // The last version is the current text of the message
+        let current = versions.pop()?;
+        return Some((current, versions));
+    }
+
+    let text = strip_placeholders(msg.gen_text(&config.db).ok()?);
+    (!text.is_empty()).then_some((text, vec![]))
+}
+
+/// Surface FTS5 syntax errors as invalid options, since they come from the user's query
+fn invalid_query(query: &str, why: rusqlite::Error) -> RuntimeError {
+    match why {
+        // FTS5 reports query parsing problems as generic `SQLITE_ERROR`s
+        rusqlite::Error::SqliteFailure(error, Some(message))
+            if error.code == rusqlite::ErrorCode::Unknown =>
+        {
+            RuntimeError::InvalidOptions(format!(
+                "`{query}` is not a valid search query: {message}\nWrap text containing punctuation in double quotes"
+            ))
+        }
+        why => RuntimeError::IndexError(why),
+    }
+}
+
+/// Get a display title for each deduplicated conversation
+pub fn conversation_titles(config: &Config) -> Result<HashMap<i32, String>, RuntimeError> {
+    Ok(ChatListing::build_all(config)?
+        .into_iter()
+        .map(|listing| {
+            let title = match listing.name {
+                Some(name) => name,
+                None => listing.participants.join(", "),
+            };
+            (listing.id, title)
+        })
+        .collect())
+}
+
+/// Render search results as plain text, marking each matching message with `>`
+pub fn render_results(
+    results: &[SearchResult],
+    titles: &HashMap<i32, String>,
+    config: &Config,
+) -> String {
+    let line = |marker: char, message: &IndexedMessage, text: &str| {
+        format!(
+            "{marker} {} {}: {text}\n",
+            config.format_date(
+                &get_local_time(&message.date, &config.offset),
+                message.conversation,
+            ),
+            config.who(message.handle_id, message.is_from_me),
+        )
+    };
+
+    let mut out_s = String::new();
+    for result in results {
+        if !out_s.is_empty() {
+            out_s.push('\n');
+        }
+        match result
+            .message
+            .conversation
+            .and_then(|id| titles.get(&id).map(|title| (id, title)))
+        {
+            Some((id, title)) => out_s.push_str(&format!("{title} (ID {id})\n")),
+            None => out_s.push_str(&format!("{ORPHANED}\n")),
+        }
+
+        result
+            .before
+            .iter()
+            .for_each(|message| out_s.push_str(&line(' ', message, &message.text)));
+        if result.edited {
+            out_s.push_str(&line(
+                '>',
+                &result.message,
+                &format!(
+                    "{} (previously: {})",
+                    result.message.text, result.highlighted
+                ),
+            ));
+        } else {
+            out_s.push_str(&line('>', &result.message, &result.highlighted));
+        }
+        result
+            .after
+            .iter()
+            .for_each(|message| out_s.push_str(&line(' ', message, &message.text)));
+    }
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{
+            error::RuntimeError,
+            mode::Mode,
+            search::{render_results, IndexedMessage, SearchIndex, SearchQuery, SourceState},
+        },
+        exporters::html,
+        Config, Options,
+    };
+    use imessage_database::{tables::table::get_connection, util::query_context::QueryContext};
+    use std::{
+        collections::HashMap,
+        env::{set_var, temp_dir},
+        fs::remove_file,
+        path::PathBuf,
+    };
+
+    use uuid::Uuid;
+
+    /// Removes the index file when a test finishes
+    struct TempIndex {
+        path: PathBuf,
+        index: SearchIndex,
+    }
+
+    impl TempIndex {
+        fn new() -> Self {
+            let path = temp_dir().join(format!("search_index_{}.db", Uuid::new_v4()));
+            let index = SearchIndex::open(&path).unwrap();
+            TempIndex { path, index }
+        }
+    }
+
+    impl Drop for TempIndex {
+        fn drop(&mut self) {
+            let _ = remove_file(&self.path);
+        }
+    }
+
+    fn fake_options() -> Options {
+        Options {
+            mode: Mode::Search(fake_query("")),
+            ..html::tests::fake_options()
+        }
+    }
+
+    fn fake_app(options: Options) -> Config {
+        let connection = get_connection(&options.db_path).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            reactions: HashMap::new(),
+            options,
+            offset: 0,
+            db: connection,
+            converter: None,
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
+        }
+    }
+
+    fn fake_query(query: &str) -> SearchQuery {
+        SearchQuery {
+            query: query.to_string(),
+            limit: 20,
+            context: 1,
+            index_path: PathBuf::new(),
+            rebuild: false,
+        }
+    }
+
+    fn fake_message(message_id: i32, date: i64, text: &str) -> IndexedMessage {
+        IndexedMessage {
+            message_id,
+            conversation: Some(1),
+            handle_id: Some(0),
+            is_from_me: message_id % 2 == 0,
+            date,
+            text: text.to_string(),
+        }
+    }
+
+    fn fake_index() -> TempIndex {
+        let temp = TempIndex::new();
+        temp.index
+            .insert(&fake_message(1, 100, "Want to get dinner?"), &[])
+            .unwrap();
+        temp.index
+            .insert(&fake_message(2, 200, "Sure, see you soon"), &[])
+            .unwrap();
+        temp.index
+            .insert(
+                &fake_message(3, 300, "The restaurant is on Main"),
+                &["The restaurant is on Elm".to_string()],
+            )
+            .unwrap();
+        temp.index
+            .insert(&fake_message(4, 400, "See you there"), &[])
+            .unwrap();
+        temp
+    }
+
+    fn ids(index: &SearchIndex, query: &str) -> Vec<i32> {
+        index
+            .search(&fake_query(query), &QueryContext::default())
+            .unwrap()
+            .into_iter()
+            .map(|result| result.message.message_id)
+            .collect()
+    }
+
+    #[test]
+    fn can_search_word() {
+        let temp = fake_index();
+        assert_eq!(ids(&temp.index, "dinner"), vec![1]);
+    }
+
+    #[test]
+    fn can_search_phrase() {
+        let temp = fake_index();
+        assert_eq!(ids(&temp.index, "\"see you soon\""), vec![2]);
+
+        let mut both = ids(&temp.index, "see you");
+        both.sort_unstable();
+        assert_eq!(both, vec![2, 4]);
+    }
+
+    #[test]
+    fn can_search_prefix() {
+        let temp = fake_index();
+        assert_eq!(ids(&temp.index, "resta*"), vec![3]);
+        assert!(ids(&temp.index, "resta").is_empty());
+    }
+
+    #[test]
+    fn can_search_edit_history() {
+        let temp = fake_index();
+        let results = temp
+            .index
+            .search(&fake_query("elm"), &QueryContext::default())
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].edited);
+        assert_eq!(results[0].highlighted, "The restaurant is on [Elm]");
+        assert_eq!(results[0].message.text, "The restaurant is on Main");
+    }
+
+    #[test]
+    fn can_search_with_context() {
+        let temp = fake_index();
+        let results = temp
+            .index
+            .search(&fake_query("soon"), &QueryContext::default())
+            .unwrap();
+
+        assert_eq!(
+            results[0].before,
+            vec![fake_message(1, 100, "Want to get dinner?")]
+        );
+        assert_eq!(
+            results[0].after,
+            vec![fake_message(3, 300, "The restaurant is on Main")]
+        );
+    }
+
+    #[test]
+    fn can_search_with_limit() {
+        let temp = fake_index();
+        let mut query = fake_query("see");
+        query.limit = 1;
+
+        assert_eq!(
+            temp.index
+                .search(&query, &QueryContext::default())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn can_search_with_date_filter() {
+        let temp = fake_index();
+        let context = QueryContext {
+            start: Some(300),
+            end: Some(400),
+            end_exclusive: true,
+            ..QueryContext::default()
+        };
+
+        assert!(temp
+            .index
+            .search(&fake_query("see"), &context)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            temp.index
+                .search(&fake_query("restaurant"), &context)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn cant_search_invalid_query() {
+        let temp = fake_index();
+        assert!(matches!(
+            temp.index
+                .search(&fake_query("\"unterminated"), &QueryContext::default()),
+            Err(RuntimeError::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            temp.index
+                .search(&fake_query("see AND"), &QueryContext::default()),
+            Err(RuntimeError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn can_detect_stale_index() {
+        let temp = fake_index();
+        let source = SourceState {
+            path: "chat.db".to_string(),
+            messages: 4,
+            max_rowid: 4,
+        };
+        assert!(!temp.index.is_current(&source).unwrap());
+
+        temp.index.clear().unwrap();
+        assert!(ids(&temp.index, "dinner").is_empty());
+    }
+
+    #[test]
+    fn can_render_results() {
+        set_var("TZ", "PST");
+        let temp = fake_index();
+        let app = fake_app(fake_options());
+        let results = temp
+            .index
+            .search(&fake_query("elm"), &QueryContext::default())
+            .unwrap();
+        let titles = HashMap::from([(1, "Friends".to_string())]);
+
+        let expected = concat!(
+            "Friends (ID 1)\n",
+            "  Dec 31, 1969  4:00:00 PM Me: Sure, see you soon\n",
+            "> Dec 31, 1969  4:00:00 PM Unknown: The restaurant is on Main (previously: The restaurant is on [Elm])\n",
+            "  Dec 31, 1969  4:00:00 PM Me: See you there\n",
+        );
+        assert_eq!(render_results(&results, &titles, &app), expected);
+    }
+}
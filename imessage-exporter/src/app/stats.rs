@@ -0,0 +1,691 @@
+/*!
+ Contains logic for calculating conversation statistics and writing them as a report.
+*/
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use chrono::{DateTime, FixedOffset, Timelike};
+use serde::Serialize;
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::{Reaction, Variant},
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+        table::Table,
+    },
+    util::dates::readable_duration_with,
+};
+
+use crate::app::{
+    chat_list::ChatListing, error::RuntimeError, progress::build_progress_bar_export,
+    runtime::Config, sanitizers::sanitize_html,
+};
+
+const STYLE: &str = "body { font-family: -apple-system, sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }
+th { background: #f2f2f2; }
+.bar { background: #1982fc; height: 1em; }
+details { margin-bottom: 0.5em; }";
+
+/// Represents the format a statistics report is written in
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum StatsFormat {
+    /// Machine-readable JSON
+    #[default]
+    Json,
+    /// HTML report that can be viewed in a browser
+    Html,
+}
+
+impl StatsFormat {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// The name of the file the report is written to
+    pub fn filename(&self) -> &'static str {
+        match self {
+            StatsFormat::Json => "stats.json",
+            StatsFormat::Html => "stats.html",
+        }
+    }
+}
+
+impl Display for StatsFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsFormat::Json => write!(fmt, "json"),
+            StatsFormat::Html => write!(fmt, "html"),
+        }
+    }
+}
+
+/// Statistics for a set of messages
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    /// The number of messages, excluding reactions and announcements
+    pub messages: u64,
+    /// The number of messages sent by the database owner
+    pub sent: u64,
+    /// The number of messages sent by anyone else
+    pub received: u64,
+    /// The number of messages sent for each message received
+    pub sent_received_ratio: Option<f64>,
+    /// Message counts keyed by `YYYY-MM`
+    pub by_month: BTreeMap<String, u64>,
+    /// Message counts for each hour of the day, starting at midnight
+    pub by_hour: [u64; 24],
+    /// Attachment counts keyed by media type
+    pub attachments: BTreeMap<&'static str, u64>,
+    /// Reaction counts keyed by reaction kind
+    pub reactions: BTreeMap<&'static str, u64>,
+    /// The median number of seconds before someone else responded
+    pub median_reply_seconds: Option<i64>,
+    /// The median number of seconds before a message was read
+    pub median_read_seconds: Option<i64>,
+    #[serde(skip)]
+    reply_seconds: Vec<i64>,
+    #[serde(skip)]
+    read_seconds: Vec<i64>,
+}
+
+impl Stats {
+    /// Count a message sent at `date`
+    pub fn add_message(&mut self, date: &DateTime<FixedOffset>, is_from_me: bool) {
+        self.messages += 1;
+        if is_from_me {
+            self.sent += 1;
+        } else {
+            self.received += 1;
+        }
+        *self
+            .by_month
+            .entry(date.format("%Y-%m").to_string())
+            .or_default() += 1;
+        self.by_hour[date.hour() as usize] += 1;
+    }
+
+    /// Count an attachment of the given media type
+    pub fn add_attachment(&mut self, media_type: &'static str) {
+        *self.attachments.entry(media_type).or_default() += 1;
+    }
+
+    /// Count a reaction of the given kind
+    pub fn add_reaction(&mut self, reaction: &'static str) {
+        *self.reactions.entry(reaction).or_default() += 1;
+    }
+
+    /// Record the time it took to respond to a message
+    pub fn add_reply(&mut self, seconds: i64) {
+        self.reply_seconds.push(seconds);
+    }
+
+    /// Record the time it took for a message to be read
+    pub fn add_read(&mut self, seconds: i64) {
+        self.read_seconds.push(seconds);
+    }
+
+    /// Calculate the summary values once all messages have been counted
+    pub fn finish(&mut self) {
+        self.sent_received_ratio =
+            (self.received > 0).then(|| self.sent as f64 / self.received as f64);
+        self.median_reply_seconds = median(&mut self.reply_seconds);
+        self.median_read_seconds = median(&mut self.read_seconds);
+    }
+
+    /// The total number of attachments of any media type
+    pub fn total_attachments(&self) -> u64 {
+        self.attachments.values().sum()
+    }
+
+    /// The total number of reactions of any kind
+    pub fn total_reactions(&self) -> u64 {
+        self.reactions.values().sum()
+    }
+}
+
+/// Statistics for a single deduplicated conversation
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConversationStats {
+    /// The conversation ID, matching the `list-chats` output
+    pub id: i32,
+    /// The name of the conversation, if one was set
+    pub name: Option<String>,
+    /// The resolved names of the other participants
+    pub participants: Vec<String>,
+    pub stats: Stats,
+}
+
+/// Statistics for a single participant across every conversation
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ParticipantStats {
+    /// The resolved name of the participant
+    pub name: String,
+    pub stats: Stats,
+}
+
+/// Statistics for every message that matches the query filters
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub totals: Stats,
+    pub conversations: Vec<ConversationStats>,
+    pub participants: Vec<ParticipantStats>,
+}
+
+impl StatsReport {
+    /// Calculate statistics for every message that matches the query filters
+    ///
+    /// Reply latency is attributed to the person who replied, while read latency is
+    /// attributed to the sender of the message that was read.
+    pub fn build(config: &Config) -> Result<Self, RuntimeError> {
+        let listings = ChatListing::build_all(config)?;
+        let conversation_index: HashMap<i32, usize> = listings
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, listing)| listing.chat_ids.iter().map(move |id| (*id, idx)))
+            .collect();
+
+        let mut totals = Stats::default();
+        let mut conversations: Vec<Stats> = listings.iter().map(|_| Stats::default()).collect();
+        // Participants are keyed by their deduplicated handle ID, `None` is the database owner
+        let mut participants: BTreeMap<Option<i32>, Stats> = BTreeMap::new();
+        // The sender and date of the latest message in each conversation
+        let mut latest: HashMap<usize, (Option<i32>, DateTime<FixedOffset>)> = HashMap::new();
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let total_messages = Message::get_count(&config.db, &config.options.query_context)
+            .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement = Message::stream_rows(&config.db, &config.options.query_context)
+            .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            pb.inc(1);
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Messages can be returned more than once if they belong to more than one chat
+            if msg.rowid == current_message_row || msg.is_announcement() {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            let date = match msg.date(&config.offset) {
+                Ok(date) => config.options.timezone.convert(&date),
+                Err(_) => continue,
+            };
+            let sender = match msg.is_from_me {
+                true => None,
+                false => msg.handle_id.map(|handle_id| {
+                    *config
+                        .real_participants
+                        .get(&handle_id)
+                        .unwrap_or(&handle_id)
+                }),
+            };
+            let conversation = msg
+                .chat_id
+                .or(msg.deleted_from)
+                .and_then(|chat_id| conversation_index.get(&chat_id))
+                .copied();
+
+            // Collect every set of statistics this message counts towards
+            let mut targets: Vec<&mut Stats> = vec![&mut totals];
+            targets.push(participants.entry(sender).or_default());
+            if let Some(idx) = conversation {
+                targets.push(&mut conversations[idx]);
+            }
+
+            if let Variant::Reaction(_, added, reaction) = msg.variant() {
+                // Removing a reaction does not count as reacting
+                if added {
+                    targets
+                        .iter_mut()
+                        .for_each(|stats| stats.add_reaction(reaction_name(&reaction)));
+                }
+                continue;
+            }
+
+            let media_types: Vec<&'static str> = match msg.has_attachments() {
+                true => Attachment::from_message(&config.db, &msg)
+                    .map_err(RuntimeError::DatabaseError)?
+                    .iter()
+                    .map(|attachment| media_type_name(&attachment.mime_type()))
+                    .collect(),
+                false => vec![],
+            };
+            let read_seconds = msg.seconds_until_read(&config.offset);
+
+            for stats in &mut targets {
+                stats.add_message(&date, msg.is_from_me);
+                media_types
+                    .iter()
+                    .for_each(|media_type| stats.add_attachment(media_type));
+                if let Some(seconds) = read_seconds {
+                    stats.add_read(seconds);
+                }
+            }
+
+            // A reply is a message sent by someone other than the sender of the previous message
+            if let Some(idx) = conversation {
+                if let Some((previous_sender, previous_date)) = latest.insert(idx, (sender, date)) {
+                    let seconds = (date - previous_date).num_seconds();
+                    if previous_sender != sender && seconds >= 0 {
+                        targets
+                            .iter_mut()
+                            .for_each(|stats| stats.add_reply(seconds));
+                    }
+                }
+            }
+        }
+        pb.finish();
+
+        totals.finish();
+
+        let conversations = listings
+            .into_iter()
+            .zip(conversations)
+            .filter(|(_, stats)| stats.messages > 0 || !stats.reactions.is_empty())
+            .map(|(listing, mut stats)| {
+                stats.finish();
+                ConversationStats {
+                    id: listing.id,
+                    name: listing.name,
+                    participants: listing.participants,
+                    stats,
+                }
+            })
+            .collect();
+
+        let mut participants: Vec<ParticipantStats> = participants
+            .into_iter()
+            .map(|(handle_id, mut stats)| {
+                stats.finish();
+                ParticipantStats {
+                    name: config.who(handle_id, handle_id.is_none()).to_string(),
+                    stats,
+                }
+            })
+            .collect();
+        participants.sort_by_key(|participant| Reverse(participant.stats.messages));
+
+        Ok(StatsReport {
+            totals,
+            conversations,
+            participants,
+        })
+    }
+}
+
+/// Get the middle value of a set of durations
+fn median(values: &mut [i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    match values.len() % 2 {
+        0 => Some((values[mid - 1] + values[mid]) / 2),
+        _ => Some(values[mid]),
+    }
+}
+
+/// Get the name used to group an attachment's media type
+fn media_type_name(media_type: &MediaType) -> &'static str {
+    match media_type {
+        MediaType::Image(_) => "image",
+        MediaType::Video(_) => "video",
+        MediaType::Audio(_) => "audio",
+        MediaType::Text(_) => "text",
+        MediaType::Application(_) => "application",
+        MediaType::Other(_) => "other",
+        MediaType::Unknown => "unknown",
+    }
+}
+
+/// Get the name used to group a reaction
+fn reaction_name(reaction: &Reaction) -> &'static str {
+    match reaction {
+        Reaction::Loved => "loved",
+        Reaction::Liked => "liked",
+        Reaction::Disliked => "disliked",
+        Reaction::Laughed => "laughed",
+        Reaction::Emphasized => "emphasized",
+        Reaction::Questioned => "questioned",
+    }
+}
+
+/// Render a statistics report as JSON
+pub fn render_json(report: &StatsReport) -> Result<String, RuntimeError> {
+    serde_json::to_string_pretty(report).map_err(RuntimeError::JsonError)
+}
+
+/// Render a statistics report as a standalone HTML page
+pub fn render_html(report: &StatsReport, config: &Config) -> String {
+    let mut out_s = String::from("<html>\n<head>\n<meta charset=\"UTF-8\">\n<style>\n");
+    out_s.push_str(STYLE);
+    out_s.push_str("\n</style>\n</head>\n<body>\n<h1>Statistics</h1>\n");
+
+    out_s.push_str("<h2>All Messages</h2>\n");
+    out_s.push_str(&summary_table(
+        [("All Messages", &report.totals)].into_iter(),
+        config,
+    ));
+    out_s.push_str(&breakdown(&report.totals));
+
+    out_s.push_str("<h2>Conversations</h2>\n");
+    let names: Vec<String> = report
+        .conversations
+        .iter()
+        .map(|conversation| match &conversation.name {
+            Some(name) => name.to_string(),
+            None => conversation.participants.join(", "),
+        })
+        .collect();
+    out_s.push_str(&summary_table(
+        names
+            .iter()
+            .map(String::as_str)
+            .zip(report.conversations.iter().map(|c| &c.stats)),
+        config,
+    ));
+    for (name, conversation) in names.iter().zip(&report.conversations) {
+        out_s.push_str(&details(name, &conversation.stats));
+    }
+
+    out_s.push_str("<h2>Participants</h2>\n");
+    out_s.push_str(&summary_table(
+        report
+            .participants
+            .iter()
+            .map(|participant| (participant.name.as_str(), &participant.stats)),
+        config,
+    ));
+    for participant in &report.participants {
+        out_s.push_str(&details(&participant.name, &participant.stats));
+    }
+
+    out_s.push_str("</body></html>\n");
+    out_s
+}
+
+/// Render one row of summary values for each set of statistics
+fn summary_table<'a>(rows: impl Iterator<Item = (&'a str, &'a Stats)>, config: &Config) -> String {
+    let units = &config.strings().durations;
+    let duration = |seconds: Option<i64>| match seconds {
+        // Durations under one second have no components to display
+        Some(0) => format!("0 {}", units.seconds),
+        Some(seconds) => readable_duration_with(seconds, units).unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let mut out_s = String::from("<table>\n<tr><th>Name</th><th>Messages</th><th>Sent</th><th>Received</th><th>Sent/Received</th><th>Attachments</th><th>Reactions</th><th>Median Reply Time</th><th>Median Read Time</th></tr>\n");
+    for (name, stats) in rows {
+        out_s.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            sanitize_html(name),
+            stats.messages,
+            stats.sent,
+            stats.received,
+            stats
+                .sent_received_ratio
+                .map(|ratio| format!("{ratio:.2}"))
+                .unwrap_or_default(),
+            stats.total_attachments(),
+            stats.total_reactions(),
+            duration(stats.median_reply_seconds),
+            duration(stats.median_read_seconds),
+        ));
+    }
+    out_s.push_str("</table>\n");
+    out_s
+}
+
+/// Render a collapsible breakdown for a set of statistics
+fn details(name: &str, stats: &Stats) -> String {
+    format!(
+        "<details>\n<summary>{}</summary>\n{}</details>\n",
+        sanitize_html(name),
+        breakdown(stats)
+    )
+}
+
+/// Render tables of counts by month, hour, media type, and reaction kind
+fn breakdown(stats: &Stats) -> String {
+    let mut out_s = String::new();
+    out_s.push_str(&count_table(
+        "Month",
+        stats
+            .by_month
+            .iter()
+            .map(|(month, count)| (month.to_string(), *count)),
+    ));
+    out_s.push_str(&count_table(
+        "Hour",
+        stats
+            .by_hour
+            .iter()
+            .enumerate()
+            .map(|(hour, count)| (format!("{hour:02}:00"), *count)),
+    ));
+    if !stats.attachments.is_empty() {
+        out_s.push_str(&count_table(
+            "Attachment Type",
+            stats
+                .attachments
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count)),
+        ));
+    }
+    if !stats.reactions.is_empty() {
+        out_s.push_str(&count_table(
+            "Reaction",
+            stats
+                .reactions
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count)),
+        ));
+    }
+    out_s
+}
+
+/// Render a table of labeled counts, with a bar scaled to the largest count
+fn count_table(title: &str, rows: impl Iterator<Item = (String, u64)>) -> String {
+    let rows: Vec<(String, u64)> = rows.collect();
+    let max = rows
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut out_s = format!("<table>\n<tr><th>{title}</th><th>Count</th><th></th></tr>\n");
+    for (label, count) in rows {
+        out_s.push_str(&format!(
+            "<tr><td>{label}</td><td>{count}</td><td><div class=\"bar\" style=\"width: {}px\"></div></td></tr>\n",
+            count * 200 / max
+        ));
+    }
+    out_s.push_str("</table>\n");
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{
+            mode::Mode,
+            stats::{
+                media_type_name, median, render_html, render_json, ConversationStats,
+                ParticipantStats, Stats, StatsFormat, StatsReport,
+            },
+        },
+        exporters::html,
+        Config, Options,
+    };
+    use imessage_database::tables::{attachment::MediaType, table::get_connection};
+    use std::collections::HashMap;
+
+    use chrono::{FixedOffset, TimeZone};
+
+    fn fake_options() -> Options {
+        Options {
+            mode: Mode::Stats(StatsFormat::Json),
+            ..html::tests::fake_options()
+        }
+    }
+
+    fn fake_app(options: Options) -> Config {
+        let connection = get_connection(&options.db_path).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            reactions: HashMap::new(),
+            options,
+            offset: 0,
+            db: connection,
+            converter: None,
+            attachment_store: crate::app::attachment_manager::AttachmentStore::default(),
+        }
+    }
+
+    fn fake_stats() -> Stats {
+        let zone = FixedOffset::west_opt(7 * 3600).unwrap();
+        let mut stats = Stats::default();
+        stats.add_message(
+            &zone.with_ymd_and_hms(2022, 5, 17, 17, 29, 42).unwrap(),
+            true,
+        );
+        stats.add_message(
+            &zone.with_ymd_and_hms(2022, 5, 17, 17, 31, 0).unwrap(),
+            false,
+        );
+        stats.add_message(&zone.with_ymd_and_hms(2022, 6, 1, 9, 0, 0).unwrap(), false);
+        stats.add_attachment("image");
+        stats.add_attachment("image");
+        stats.add_reaction("loved");
+        stats.add_reply(30);
+        stats.add_reply(90);
+        stats.add_read(3649);
+        stats.finish();
+        stats
+    }
+
+    #[test]
+    fn can_parse_stats_format() {
+        assert_eq!(StatsFormat::from_cli("json"), Some(StatsFormat::Json));
+        assert_eq!(StatsFormat::from_cli("HTML"), Some(StatsFormat::Html));
+        assert_eq!(StatsFormat::from_cli("pdf"), None);
+    }
+
+    #[test]
+    fn can_count_messages() {
+        let stats = fake_stats();
+
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.sent, 1);
+        assert_eq!(stats.received, 2);
+        assert_eq!(stats.sent_received_ratio, Some(0.5));
+        assert_eq!(stats.by_month.get("2022-05"), Some(&2));
+        assert_eq!(stats.by_month.get("2022-06"), Some(&1));
+        assert_eq!(stats.by_hour[17], 2);
+        assert_eq!(stats.by_hour[9], 1);
+        assert_eq!(stats.total_attachments(), 2);
+        assert_eq!(stats.total_reactions(), 1);
+        assert_eq!(stats.median_reply_seconds, Some(60));
+        assert_eq!(stats.median_read_seconds, Some(3649));
+    }
+
+    #[test]
+    fn can_finish_empty() {
+        let mut stats = Stats::default();
+        stats.finish();
+
+        assert_eq!(stats.sent_received_ratio, None);
+        assert_eq!(stats.median_reply_seconds, None);
+        assert_eq!(stats.median_read_seconds, None);
+    }
+
+    #[test]
+    fn can_get_median() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [5, 1, 3]), Some(3));
+        assert_eq!(median(&mut [4, 1, 3, 10]), Some(3));
+    }
+
+    #[test]
+    fn can_get_media_type_name() {
+        assert_eq!(media_type_name(&MediaType::Image("jpeg")), "image");
+        assert_eq!(media_type_name(&MediaType::Unknown), "unknown");
+    }
+
+    #[test]
+    fn can_build_report_empty() {
+        let app = fake_app(fake_options());
+        let report = StatsReport::build(&app).unwrap();
+
+        assert_eq!(report.totals.messages, 0);
+        assert!(report.conversations.is_empty());
+        assert!(report.participants.is_empty());
+    }
+
+    #[test]
+    fn can_render_json() {
+        let report = StatsReport {
+            totals: fake_stats(),
+            conversations: vec![],
+            participants: vec![],
+        };
+        let json = render_json(&report).unwrap();
+
+        assert!(json.contains("\"sent_received_ratio\": 0.5"));
+        assert!(json.contains("\"2022-06\": 1"));
+        assert!(json.contains("\"median_reply_seconds\": 60"));
+        assert!(!json.contains("reply_seconds\": ["));
+    }
+
+    #[test]
+    fn can_render_html() {
+        let app = fake_app(fake_options());
+        let report = StatsReport {
+            totals: fake_stats(),
+            conversations: vec![ConversationStats {
+                id: 1,
+                name: None,
+                participants: vec!["<Person>".to_string()],
+                stats: fake_stats(),
+            }],
+            participants: vec![ParticipantStats {
+                name: "Me".to_string(),
+                stats: fake_stats(),
+            }],
+        };
+        let html = render_html(&report, &app);
+
+        assert!(html.contains("<tr><td>All Messages</td><td>3</td><td>1</td><td>2</td><td>0.50</td><td>2</td><td>1</td><td>1 minute</td><td>1 hour, 49 seconds</td></tr>"));
+        assert!(html.contains("<summary>&lt;Person&gt;</summary>"));
+        assert!(html.contains("<summary>Me</summary>"));
+        assert!(html.contains("<tr><td>2022-06</td><td>1</td>"));
+    }
+}
@@ -0,0 +1,172 @@
+/*!
+ Contains logic for loading user-supplied templates that replace parts of the HTML output.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+use crate::app::locale::fill;
+
+/// A piece of HTML output that can be replaced with a user template
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Template {
+    /// A single message, including its parts, reactions, and replies
+    Message,
+    /// A single reaction to a message part
+    Reaction,
+    /// An attachment or sticker embedded in a message
+    Attachment,
+    /// A URL preview balloon
+    Url,
+    /// An Apple Music balloon
+    Music,
+    /// A Rich Collaboration balloon
+    Collaboration,
+    /// An App Store balloon
+    AppStore,
+    /// An Apple Maps balloon
+    Placemark,
+    /// An Apple Pay balloon
+    ApplePay,
+    /// An Apple Fitness balloon
+    Fitness,
+    /// A Photo Slideshow balloon
+    Slideshow,
+    /// A Find My balloon
+    FindMy,
+    /// A Check In balloon
+    CheckIn,
+    /// A balloon sent by any other app
+    App,
+}
+
+impl Template {
+    /// Every template that can be overridden
+    pub const ALL: [Template; 14] = [
+        Template::Message,
+        Template::Reaction,
+        Template::Attachment,
+        Template::Url,
+        Template::Music,
+        Template::Collaboration,
+        Template::AppStore,
+        Template::Placemark,
+        Template::ApplePay,
+        Template::Fitness,
+        Template::Slideshow,
+        Template::FindMy,
+        Template::CheckIn,
+        Template::App,
+    ];
+
+    /// The name of the file in the template directory that overrides this template
+    pub fn filename(&self) -> &'static str {
+        match self {
+            Template::Message => "message.html",
+            Template::Reaction => "reaction.html",
+            Template::Attachment => "attachment.html",
+            Template::Url => "url.html",
+            Template::Music => "music.html",
+            Template::Collaboration => "collaboration.html",
+            Template::AppStore => "app_store.html",
+            Template::Placemark => "placemark.html",
+            Template::ApplePay => "apple_pay.html",
+            Template::Fitness => "fitness.html",
+            Template::Slideshow => "slideshow.html",
+            Template::FindMy => "find_my.html",
+            Template::CheckIn => "check_in.html",
+            Template::App => "app.html",
+        }
+    }
+}
+
+/// Templates read from the directory passed to `--templates`
+///
+/// Each template is plain HTML with `{name}` placeholders for the values described in
+/// `docs/binary/templates.md`. Output that has no template uses the built-in markup.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Templates {
+    templates: HashMap<Template, String>,
+}
+
+impl Templates {
+    /// Read every known template from a directory, skipping any that are missing
+    pub fn load(directory: &Path) -> Self {
+        let mut templates = HashMap::new();
+
+        for template in Template::ALL {
+            let path = directory.join(template.filename());
+            if !path.exists() {
+                continue;
+            }
+            match read_to_string(&path) {
+                Ok(contents) => {
+                    templates.insert(template, contents);
+                }
+                Err(why) => eprintln!("Unable to read template {path:?}: {why}"),
+            }
+        }
+
+        // Warn about files that look like templates but will never be used, i.e. typos
+        if let Ok(entries) = read_dir(directory) {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| name.ends_with(".html"))
+                .filter(|name| !Template::ALL.iter().any(|t| t.filename() == name))
+                .for_each(|name| eprintln!("Ignoring unknown template {name}"));
+        }
+
+        Self { templates }
+    }
+
+    /// Render a template with the given values, or `None` if it was not overridden
+    ///
+    /// Values are inserted as-is, so they must already be escaped.
+    pub fn render(&self, template: Template, values: &[(&str, &str)]) -> Option<String> {
+        self.templates
+            .get(&template)
+            .map(|contents| fill(contents, values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+    };
+
+    use uuid::Uuid;
+
+    use crate::app::templates::{Template, Templates};
+
+    #[test]
+    fn can_load_templates() {
+        let directory = temp_dir().join(format!("templates_{}", Uuid::new_v4()));
+        create_dir_all(&directory).unwrap();
+        write(directory.join("reaction.html"), "<i>{text}</i>").unwrap();
+        write(directory.join("reactions.html"), "typo").unwrap();
+
+        let templates = Templates::load(&directory);
+
+        assert_eq!(
+            templates.render(Template::Reaction, &[("text", "Loved by Me")]),
+            Some("<i>Loved by Me</i>".to_string())
+        );
+        assert_eq!(templates.render(Template::Message, &[]), None);
+
+        remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn can_use_unique_filenames() {
+        let mut filenames: Vec<&str> = Template::ALL.iter().map(|t| t.filename()).collect();
+        filenames.sort_unstable();
+        filenames.dedup();
+        assert_eq!(filenames.len(), Template::ALL.len());
+    }
+}
@@ -0,0 +1,65 @@
+/*!
+ Contains data structures used to describe the color themes available to HTML exports.
+*/
+
+use std::fmt::Display;
+
+/// Represents the color theme used by HTML exports
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum Theme {
+    /// Follow the reader's system setting using `prefers-color-scheme`
+    #[default]
+    Auto,
+    /// Always use light colors
+    Light,
+    /// Always use dark colors
+    Dark,
+}
+
+impl Theme {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(theme: &str) -> Option<Self> {
+        match theme.to_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "light" => Some(Self::Light),
+            "dark" => Some(Self::Dark),
+            _ => None,
+        }
+    }
+
+    /// The value of the `color-scheme` meta tag, which styles form controls and scrollbars
+    pub fn color_scheme(&self) -> &'static str {
+        match self {
+            Theme::Auto => "light dark",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Auto => write!(fmt, "auto"),
+            Theme::Light => write!(fmt, "light"),
+            Theme::Dark => write!(fmt, "dark"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::theme::Theme;
+
+    #[test]
+    fn can_parse_any_case() {
+        assert_eq!(Theme::from_cli("auto"), Some(Theme::Auto));
+        assert_eq!(Theme::from_cli("Light"), Some(Theme::Light));
+        assert_eq!(Theme::from_cli("DARK"), Some(Theme::Dark));
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert_eq!(Theme::from_cli("solarized"), None);
+    }
+}
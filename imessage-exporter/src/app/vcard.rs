@@ -0,0 +1,119 @@
+/*!
+ Contains a minimal vCard builder used to export conversation participants.
+
+ Cards are written in the [vCard 3.0](https://www.rfc-editor.org/rfc/rfc2426) format, which every contacts app can import.
+*/
+
+/// Longest line allowed before it must be folded, in bytes
+const LINE_LENGTH: usize = 75;
+
+/// Escape text for use in a property value
+fn escape(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out_s.push_str("\\\\"),
+            ',' => out_s.push_str("\\,"),
+            ';' => out_s.push_str("\\;"),
+            '\n' => out_s.push_str("\\n"),
+            '\r' => {}
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+/// Fold a content line so no line is longer than [`LINE_LENGTH`] bytes
+///
+/// Continuation lines start with a space, and lines are never split in the middle of a character.
+fn fold(line: &str) -> String {
+    let mut out_s = String::with_capacity(line.len() + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            out_s.push_str("\r\n ");
+            // The leading space counts toward the length of the new line
+            length = 1;
+        }
+        out_s.push(c);
+        length += c.len_utf8();
+    }
+    out_s.push_str("\r\n");
+    out_s
+}
+
+/// A contact card for a single person
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VCard {
+    /// The name shown for the person
+    pub name: String,
+    /// Phone numbers the person messaged from
+    pub phones: Vec<String>,
+    /// Email addresses the person messaged from
+    pub emails: Vec<String>,
+    /// Free-form text about the person
+    pub note: Option<String>,
+}
+
+impl VCard {
+    /// Render the card with the line endings the format requires
+    pub fn render(&self) -> String {
+        let mut out_s = String::from("BEGIN:VCARD\r\nVERSION:3.0\r\n");
+        out_s.push_str(&fold(&format!("FN:{}", escape(&self.name))));
+        // `N` is required, but handles do not have separate first and last names
+        out_s.push_str("N:;;;;\r\n");
+        for phone in &self.phones {
+            out_s.push_str(&fold(&format!("TEL;TYPE=CELL:{}", escape(phone))));
+        }
+        for email in &self.emails {
+            out_s.push_str(&fold(&format!("EMAIL;TYPE=INTERNET:{}", escape(email))));
+        }
+        if let Some(note) = &self.note {
+            out_s.push_str(&fold(&format!("NOTE:{}", escape(note))));
+        }
+        out_s.push_str("END:VCARD\r\n");
+        out_s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::vcard::{escape, fold, VCard};
+
+    #[test]
+    fn can_escape() {
+        assert_eq!(escape("a,b;c\\d\r\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+
+    #[test]
+    fn can_fold() {
+        let folded = fold(&"é".repeat(50));
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(
+            folded.replace("\r\n ", ""),
+            format!("{}\r\n", "é".repeat(50))
+        );
+    }
+
+    #[test]
+    fn can_fold_short() {
+        assert_eq!(fold("FN:Jane"), "FN:Jane\r\n");
+    }
+
+    #[test]
+    fn can_render() {
+        let card = VCard {
+            name: "+15555550100".to_string(),
+            phones: vec!["+15555550100".to_string()],
+            emails: vec!["jane@example.com".to_string()],
+            note: Some("First message: May 17, 2022\nLast message: May 18, 2022".to_string()),
+        };
+        assert_eq!(
+            card.render(),
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:+15555550100\r\nN:;;;;\r\nTEL;TYPE=CELL:+15555550100\r\nEMAIL;TYPE=INTERNET:jane@example.com\r\nNOTE:First message: May 17\\, 2022\\nLast message: May 18\\, 2022\r\nEND:VCARD\r\n"
+        );
+    }
+}
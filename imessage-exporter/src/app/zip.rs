@@ -0,0 +1,259 @@
+/*!
+ Contains a minimal ZIP writer used to package exports that are made of many files.
+
+ Entries are stored without compression, since the only large files in an export are images and
+ videos that are already compressed. Each entry is appended to the archive as soon as it is added,
+ so an archive only keeps the list of its entries in memory.
+*/
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, Local, Timelike};
+
+/// Signature at the start of each entry's local header
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+/// Signature at the start of each entry in the central directory
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+/// Signature at the start of the record that ends the archive
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+/// Version of the format needed to read stored entries, 1.0
+const VERSION: u16 = 10;
+/// Flag set on entries whose names contain characters outside of ASCII
+const UTF8_NAME: u16 = 1 << 11;
+
+/// Lookup table for the CRC-32 checksum used by ZIP, built at compile time
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        let mut crc = idx as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[idx] = crc;
+        idx += 1;
+    }
+    table
+};
+
+/// Calculate the CRC-32 checksum of some bytes
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// An entry that has been written to the archive
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A ZIP archive that is written to disk as entries are added
+pub struct ZipArchive {
+    /// The file the archive is written to
+    path: PathBuf,
+    /// Bytes that have not been written to the file yet
+    buffer: Vec<u8>,
+    /// Number of bytes in the archive so far
+    position: usize,
+    /// Every entry written so far, in order
+    entries: Vec<Entry>,
+    /// Modification time of each entry, in MS-DOS format
+    time: u16,
+    /// Modification date of each entry, in MS-DOS format
+    date: u16,
+}
+
+impl ZipArchive {
+    /// Start an archive that will be written to `path`
+    pub fn new(path: PathBuf) -> Self {
+        let now = Local::now();
+        Self {
+            path,
+            buffer: vec![],
+            position: 0,
+            entries: vec![],
+            time: ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16,
+            date: ((now.year().saturating_sub(1980) as u32) << 9 | (now.month() << 5) | now.day())
+                as u16,
+        }
+    }
+
+    /// The file the archive is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a file to the end of the archive
+    pub fn add(&mut self, name: &str, data: &[u8]) {
+        // Without the ZIP64 extensions, sizes and offsets must fit in 32 bits
+        if u32::try_from(self.position + data.len()).is_err() {
+            eprintln!(
+                "Unable to add {name} to {:?}: archive is too large",
+                self.path
+            );
+            return;
+        }
+
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32(data),
+            size: data.len() as u32,
+            offset: self.position as u32,
+        };
+
+        let mut header = vec![];
+        header.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        self.push_entry_fields(&mut header, &entry);
+        header.extend_from_slice(name.as_bytes());
+
+        self.push(&header);
+        self.push(data);
+        self.entries.push(entry);
+        self.flush();
+    }
+
+    /// Write the central directory, completing the archive
+    pub fn finish(&mut self) {
+        let start = self.position;
+
+        let mut directory = vec![];
+        for entry in &self.entries {
+            directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            // Version made by, then version needed to extract
+            directory.extend_from_slice(&VERSION.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes());
+            self.push_entry_fields(&mut directory, entry);
+            // Comment length, disk number, internal attributes, and external attributes
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let count = self.entries.len() as u16;
+        let mut end = vec![];
+        end.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+        // Number of this disk, then the disk the directory starts on
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        end.extend_from_slice(&(start as u32).to_le_bytes());
+        // Comment length
+        end.extend_from_slice(&[0; 2]);
+
+        self.push(&directory);
+        self.push(&end);
+        self.flush();
+    }
+
+    /// Add the fields shared by local headers and the central directory, from the flags to the extra field length
+    fn push_entry_fields(&self, bytes: &mut Vec<u8>, entry: &Entry) {
+        let flags = if entry.name.is_ascii() { 0 } else { UTF8_NAME };
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        // Compression method, where 0 means stored
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&self.time.to_le_bytes());
+        bytes.extend_from_slice(&self.date.to_le_bytes());
+        bytes.extend_from_slice(&entry.crc.to_le_bytes());
+        // Compressed and uncompressed sizes are the same for stored entries
+        bytes.extend_from_slice(&entry.size.to_le_bytes());
+        bytes.extend_from_slice(&entry.size.to_le_bytes());
+        bytes.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        // Extra field length
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    /// Add bytes to the end of the file
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        self.position += bytes.len();
+    }
+
+    /// Append the buffered bytes to the file
+    fn flush(&mut self) {
+        match File::options().append(true).create(true).open(&self.path) {
+            Ok(mut file) => {
+                if let Err(why) = file.write_all(&self.buffer) {
+                    eprintln!("Unable to write to {:?}: {why:?}", self.path);
+                }
+            }
+            Err(why) => eprintln!("Unable to write to {:?}: {why:?}", self.path),
+        };
+        self.buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, remove_file},
+    };
+
+    use uuid::Uuid;
+
+    use crate::app::zip::{crc32, ZipArchive};
+
+    /// Read a little-endian number from part of a byte slice
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u16(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn can_calculate_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"application/epub+zip"), 0x2CAB_616F);
+    }
+
+    #[test]
+    fn can_write_archive() {
+        let path = temp_dir().join(format!("{}.zip", Uuid::new_v4()));
+        let mut archive = ZipArchive::new(path.clone());
+        archive.add("mimetype", b"application/epub+zip");
+        archive.add("a/b.txt", b"Hello world");
+        archive.finish();
+
+        let data = read(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        // The first entry starts the file, with its name and data right after the header
+        assert_eq!(read_u32(&data, 0), 0x0403_4b50);
+        assert_eq!(&data[30..38], b"mimetype");
+        assert_eq!(&data[38..58], b"application/epub+zip");
+
+        // The end record points at a directory listing both entries
+        let end = data.len() - 22;
+        assert_eq!(read_u32(&data, end), 0x0605_4b50);
+        assert_eq!(read_u16(&data, end + 10), 2);
+        let start = read_u32(&data, end + 16) as usize;
+        assert_eq!(start + read_u32(&data, end + 12) as usize, end);
+        assert_eq!(read_u32(&data, start), 0x0201_4b50);
+
+        // The second entry's offset points at its local header
+        let second = start + 46 + "mimetype".len();
+        assert_eq!(&data[second + 46..second + 53], b"a/b.txt");
+        let offset = read_u32(&data, second + 42) as usize;
+        assert_eq!(read_u32(&data, offset), 0x0403_4b50);
+        assert_eq!(&data[offset + 37..offset + 48], b"Hello world");
+    }
+}
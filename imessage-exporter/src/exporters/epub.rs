@@ -0,0 +1,656 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    app::{
+        epub_book::{escape_xml, EpubBook},
+        error::RuntimeError,
+        locale::fill,
+        pagination::Pagination,
+        progress::build_progress_bar_export,
+        runtime::Config,
+    },
+    exporters::exporter::{Exporter, Writer},
+    TXT,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Variant,
+    tables::{
+        attachment::{Attachment, MediaType},
+        chat::Chat,
+        messages::{BubbleType, Message},
+        table::{Table, FITNESS_RECEIVER, ORPHANED},
+    },
+};
+
+/// Stylesheet included in every book
+const STYLE: &str = include_str!("resources/epub.css");
+
+pub struct EPUB<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Formats the text of app balloons, edits, and announcements
+    pub txt: TXT<'a>,
+    /// Handles to files we want to write messages to
+    /// Map of internal unique chatroom ID to a filename
+    pub files: HashMap<i32, PathBuf>,
+    /// Books that have been started, by filename
+    pub books: HashMap<PathBuf, EpubBook>,
+    /// Path to file for orphaned messages
+    pub orphaned: PathBuf,
+    /// Internal unique chatroom IDs of the conversations to export, or `None` to export all of them
+    pub selected: Option<HashSet<i32>>,
+}
+
+impl<'a> Exporter<'a> for EPUB<'a> {
+    fn new(config: &'a Config) -> Self {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("epub");
+
+        // Conversations are selected by the IDs shown in `list-chats`, which may be one of several
+        // chats that were merged into a single conversation
+        let selected = config.options.conversations.as_ref().map(|ids| {
+            ids.iter()
+                .filter_map(|id| {
+                    let real_id = config.real_chatrooms.get(id);
+                    if real_id.is_none() {
+                        eprintln!("Conversation {id} does not exist, skipping!");
+                    }
+                    real_id.copied()
+                })
+                .collect()
+        });
+
+        EPUB {
+            config,
+            txt: TXT::new(config),
+            files: HashMap::new(),
+            books: HashMap::new(),
+            orphaned,
+            selected,
+        }
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as epub...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row || !self.is_selected(&msg) {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                self.write_message(&msg, |exporter, _| Ok(exporter.format_announcement(&msg)))?;
+            }
+            // Message replies and reactions are rendered in context, so no need to render them separately
+            else if !msg.is_reaction() {
+                let _ = msg.gen_text(&self.config.db);
+                self.write_message(&msg, |exporter, book| {
+                    exporter.format_message(&msg, book, 0)
+                })?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Chapters are written as they end, so only the last chapter of each book is left
+        self.books.values_mut().for_each(EpubBook::finish);
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(&mut self, message: &Message) -> &Path {
+        match self.config.conversation(message) {
+            Some((chatroom, id)) => self.files.entry(*id).or_insert_with(|| {
+                let mut path = self.config.options.export_path.clone();
+                path.push(self.config.filename(chatroom));
+                path.set_extension("epub");
+
+                // If the book already exists, don't write the title page again
+                // This can happen if multiple chats use the same group name
+                self.books
+                    .entry(path.clone())
+                    .or_insert_with(|| EPUB::create_book(self.config, chatroom, &path));
+
+                path
+            }),
+            None => &self.orphaned,
+        }
+    }
+}
+
+impl<'a> EPUB<'a> {
+    /// Determine if a message belongs to one of the conversations selected for export
+    fn is_selected(&self, message: &Message) -> bool {
+        match &self.selected {
+            Some(selected) => self
+                .config
+                .conversation(message)
+                .is_some_and(|(_, id)| selected.contains(id)),
+            None => true,
+        }
+    }
+
+    /// Start a book for a conversation, with its participants as the authors
+    ///
+    /// The title page lists the conversation's details and group photo.
+    fn create_book(config: &Config, chatroom: &Chat, path: &Path) -> EpubBook {
+        let participants: Vec<&str> = config
+            .chatroom_participants
+            .get(&chatroom.rowid)
+            .map(|participants| {
+                participants
+                    .iter()
+                    .map(|participant_id| config.who(Some(*participant_id), false))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut book = EpubBook::new(
+            path.to_path_buf(),
+            &config.conversation_title(chatroom),
+            &config.options.locale.to_string(),
+            STYLE,
+        );
+
+        book.add_creator(config.who(None, true));
+        participants
+            .iter()
+            .for_each(|participant| book.add_creator(participant));
+
+        let mut title_page = String::from("<dl class=\"details\">\n");
+        for (label, value) in config.conversation_details(chatroom) {
+            title_page.push_str(&format!(
+                "<dt>{}</dt>\n<dd>{}</dd>\n",
+                escape_xml(label),
+                escape_xml(&value)
+            ));
+        }
+        title_page.push_str("</dl>\n");
+        if let Some(photo) = config.group_photo(chatroom) {
+            if let Some(href) = config
+                .attachment_file(&photo)
+                .and_then(|file| book.add_image(&file))
+            {
+                title_page.push_str(&format!(
+                    "<p class=\"group_photo\"><img src=\"{href}\" alt=\"{}\"/></p>\n",
+                    escape_xml(config.strings().group_photo)
+                ));
+            }
+        }
+        book.set_title_page(title_page);
+
+        book
+    }
+
+    /// Add a message to the end of the chapter for the month it was sent in
+    ///
+    /// `format` builds the message's markup, and can add images to the book it will be written to.
+    fn write_message<F>(&mut self, message: &Message, format: F) -> Result<(), RuntimeError>
+    where
+        F: FnOnce(&Self, &mut EpubBook) -> Result<String, TableError>,
+    {
+        let path = self.get_or_create_file(message).to_path_buf();
+        let mut book = match self.books.remove(&path) {
+            Some(book) => book,
+            None => EpubBook::new(
+                path.clone(),
+                self.config.strings().orphaned_messages,
+                &self.config.options.locale.to_string(),
+                STYLE,
+            ),
+        };
+
+        let date = message
+            .date(&self.config.offset)
+            .ok()
+            .map(|date| self.config.timezone(message.chat_id).convert(&date));
+        let chapter = Pagination::Month.label(date.as_ref(), book.chapter().map(|c| (c, 0)));
+
+        let markup = format(self, &mut book);
+        if let Ok(markup) = &markup {
+            book.add_to_chapter(&chapter, date.as_ref(), markup);
+        }
+        self.books.insert(path, book);
+        markup.map(|_| ()).map_err(RuntimeError::DatabaseError)
+    }
+
+    /// Build the markup for a message, including its reactions and replies
+    ///
+    /// `depth` is the number of threads the message is nested in.
+    fn format_message(
+        &self,
+        message: &Message,
+        book: &mut EpubBook,
+        depth: usize,
+    ) -> Result<String, TableError> {
+        let strings = self.config.strings();
+        let mut out_s = format!("<div class=\"message {}\">\n", EPUB::direction(message));
+
+        // Add message sender and date
+        out_s.push_str(&format!(
+            "<p class=\"header\"><span class=\"sender\">{}</span> <span class=\"timestamp\">{}</span></p>\n",
+            escape_xml(self.config.who(message.handle_id, message.is_from_me)),
+            escape_xml(&self.txt.get_time(message))
+        ));
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            out_s.push_str(&EPUB::note(strings.deleted_from_conversation));
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            out_s.push_str(&format!(
+                "<p class=\"{}\"><b>{}</b></p>\n",
+                EPUB::bubble_class(message),
+                EPUB::text(subject)
+            ));
+        }
+
+        // If message was removed, display it
+        if message_parts.is_empty() && message.is_edited() {
+            out_s.push_str(&EPUB::bubble(message, &self.format_edited(message)));
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            out_s.push_str(&EPUB::bubble(message, self.txt.format_shareplay()));
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            // Render edited messages
+            if message.is_edited() {
+                out_s.push_str(&EPUB::bubble(message, &self.format_edited(message)));
+                continue;
+            }
+            match message_part {
+                // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
+                BubbleType::Text(text) => {
+                    let text = text.replace(FITNESS_RECEIVER, strings.you);
+                    out_s.push_str(&EPUB::bubble(message, &text));
+                }
+                BubbleType::Attachment => match attachments.get_mut(attachment_index) {
+                    Some(attachment) => {
+                        if attachment.is_sticker {
+                            out_s.push_str(&self.format_sticker(attachment, message, book));
+                        } else {
+                            attachment_index += 1;
+                            out_s.push_str(&self.format_attachment(attachment, message, book));
+                        }
+                    }
+                    // Attachment does not exist in attachments table
+                    None => out_s.push_str(&EPUB::note(strings.attachment_missing)),
+                },
+                BubbleType::App => match self.txt.format_app(message, &mut attachments, "") {
+                    Ok(balloon) => out_s.push_str(&format!(
+                        "<p class=\"balloon\">{}</p>\n",
+                        EPUB::text(&balloon)
+                    )),
+                    Err(why) => out_s.push_str(&EPUB::note(&fill(
+                        strings.app_message_error,
+                        &[("error", &why.to_string())],
+                    ))),
+                },
+            };
+
+            // Handle expressives
+            let expressive = self.txt.format_expressive(message);
+            if !expressive.is_empty() {
+                out_s.push_str(&EPUB::note(expressive));
+            }
+
+            // Handle Reactions
+            if let Some(reactions) = self
+                .config
+                .reactions
+                .get(&message.guid)
+                .and_then(|reactions_map| reactions_map.get(&idx))
+            {
+                for reaction in reactions {
+                    if let Variant::Sticker(_) = reaction.variant() {
+                        let mut stickers = Attachment::from_message(&self.config.db, reaction)?;
+                        // Sticker messages have only one attachment, the sticker image
+                        if let Some(sticker) = stickers.get_mut(0) {
+                            out_s.push_str(&self.format_sticker(sticker, reaction, book));
+                            continue;
+                        }
+                    }
+                    let formatted = self.txt.format_reaction(reaction)?;
+                    if !formatted.is_empty() {
+                        out_s.push_str(&EPUB::note(&formatted));
+                    }
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                out_s.push_str("<div class=\"replies\">\n");
+                for reply in replies {
+                    let _ = reply.gen_text(&self.config.db);
+                    if !reply.is_reaction() {
+                        out_s.push_str(&self.format_message(reply, book, depth + 1)?);
+                    }
+                }
+                out_s.push_str("</div>\n");
+            }
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && depth == 0 {
+            out_s.push_str(&EPUB::note(strings.responded_to_earlier));
+        }
+
+        out_s.push_str("</div>\n");
+        Ok(out_s)
+    }
+
+    /// Build the markup for an attachment, adding it to the book if it is an image
+    fn format_attachment(
+        &self,
+        attachment: &mut Attachment,
+        message: &Message,
+        book: &mut EpubBook,
+    ) -> String {
+        // Copy the file, if requested, and build a relative filepath to show if it cannot be included
+        let label = match self.txt.format_attachment(attachment, message) {
+            Ok(path) => path,
+            Err(filename) => filename.to_string(),
+        };
+        match attachment.mime_type() {
+            MediaType::Image(_) => EPUB::image(self.config, attachment, &label, book)
+                .unwrap_or_else(|| EPUB::note(&label)),
+            _ => EPUB::note(&label),
+        }
+    }
+
+    /// Build the markup for a sticker, adding it to the book if it is an image
+    fn format_sticker(
+        &self,
+        sticker: &mut Attachment,
+        message: &Message,
+        book: &mut EpubBook,
+    ) -> String {
+        let label = self.txt.format_sticker(sticker, message);
+        EPUB::image(self.config, sticker, &label, book).unwrap_or_else(|| EPUB::note(&label))
+    }
+
+    /// Build the markup for an announcement, i.e. a group name change
+    fn format_announcement(&self, message: &Message) -> String {
+        format!(
+            "<p class=\"announcement\">{}</p>\n",
+            EPUB::text(&self.txt.format_announcement(message))
+        )
+    }
+
+    /// Format the history of an edited message, or the reason it could not be read
+    fn format_edited(&self, message: &Message) -> String {
+        match self.txt.format_edited(message, "") {
+            Ok(edited) => edited,
+            Err(why) => format!("{}, {}", message.guid, why),
+        }
+    }
+
+    /// Add an attachment's file to the book, returning the markup to show it
+    ///
+    /// Returns `None` if the file is missing or is not a format reading systems support.
+    fn image(
+        config: &Config,
+        attachment: &Attachment,
+        label: &str,
+        book: &mut EpubBook,
+    ) -> Option<String> {
+        let href = book.add_image(&config.attachment_file(attachment)?)?;
+        Some(format!(
+            "<p class=\"image\"><img src=\"{href}\" alt=\"{}\"/></p>\n",
+            escape_xml(label)
+        ))
+    }
+
+    /// Messages sent by the database owner are styled like they are in Messages
+    fn direction(message: &Message) -> &'static str {
+        match message.is_from_me {
+            true => "sent",
+            false => "received",
+        }
+    }
+
+    /// Classes that color a bubble by its direction and service
+    fn bubble_class(message: &Message) -> String {
+        let service = match message.service.as_deref() {
+            Some("iMessage") => "imessage",
+            _ => "sms",
+        };
+        format!("bubble {} {service}", EPUB::direction(message))
+    }
+
+    /// Escape text and keep its line breaks
+    fn text(text: &str) -> String {
+        escape_xml(text.trim_end()).replace('\n', "<br/>\n")
+    }
+
+    /// Build the markup for a single part of a message in a colored bubble
+    fn bubble(message: &Message, text: &str) -> String {
+        format!(
+            "<p class=\"{}\">{}</p>\n",
+            EPUB::bubble_class(message),
+            EPUB::text(text)
+        )
+    }
+
+    /// Build the markup for a small line of text next to a message, i.e. a reaction
+    fn note(text: &str) -> String {
+        format!("<p class=\"note\">{}</p>\n", EPUB::text(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        env::{set_var, temp_dir},
+        fs::{remove_file, write},
+        path::PathBuf,
+    };
+
+    use uuid::Uuid;
+
+    use crate::{
+        app::epub_book::EpubBook,
+        exporters::{
+            epub::STYLE,
+            html::tests::{blank, fake_options},
+        },
+        Config, Exporter, EPUB,
+    };
+    use imessage_database::tables::attachment::Attachment;
+
+    /// Start a book in the temporary directory, returning its path so it can be removed
+    fn fake_book() -> (EpubBook, PathBuf) {
+        let path = temp_dir().join(format!("{}.epub", Uuid::new_v4()));
+        (EpubBook::new(path.clone(), "Test", "en", STYLE), path)
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = EPUB::new(&config);
+        assert_eq!(exporter.files.len(), 0);
+        assert_eq!(exporter.books.len(), 0);
+        assert_eq!(exporter.selected, None);
+    }
+
+    #[test]
+    fn can_select_conversations() {
+        let mut options = fake_options();
+        options.conversations = Some([1, 2].into());
+        let mut config = Config::new(options).unwrap();
+        // Chat 2 was merged into the conversation for chat 1, and chat 3 was not selected
+        config.real_chatrooms = [(1, 1), (2, 1), (3, 3)].into();
+
+        let exporter = EPUB::new(&config);
+
+        assert_eq!(exporter.selected, Some(HashSet::from([1])));
+    }
+
+    #[test]
+    fn can_format_epub_from_me_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = EPUB::new(&config);
+        let (mut book, path) = fake_book();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello <world> & friends\nBye".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, &mut book, 0).unwrap();
+        remove_file(&path).unwrap();
+
+        assert_eq!(
+            actual,
+            "<div class=\"message sent\">\n<p class=\"header\"><span class=\"sender\">Me</span> <span class=\"timestamp\">May 17, 2022  5:29:42 PM</span></p>\n<p class=\"bubble sent imessage\">Hello &lt;world&gt; &amp; friends<br/>\nBye</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_epub_from_them_sms() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = EPUB::new(&config);
+        let (mut book, path) = fake_book();
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        message.service = Some("SMS".to_string());
+
+        let actual = exporter.format_message(&message, &mut book, 0).unwrap();
+        remove_file(&path).unwrap();
+
+        assert!(actual.starts_with("<div class=\"message received\">"));
+        assert!(actual.contains("<p class=\"bubble received sms\">Hello world</p>"));
+    }
+
+    #[test]
+    fn can_format_epub_announcement() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = EPUB::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+
+        let actual = exporter.format_announcement(&message);
+
+        assert_eq!(
+            actual,
+            "<p class=\"announcement\">May 17, 2022  5:29:42 PM You renamed the conversation to Hello world</p>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_epub_attachment() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = EPUB::new(&config);
+        let (mut book, path) = fake_book();
+
+        let image = temp_dir().join(format!("{}.png", Uuid::new_v4()));
+        write(&image, b"\x89PNG\r\n\x1a\n").unwrap();
+
+        let message = blank();
+        let mut attachment = Attachment {
+            rowid: 0,
+            filename: Some(image.display().to_string()),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some("d.png".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+        };
+
+        let actual = exporter.format_attachment(&mut attachment, &message, &mut book);
+
+        assert_eq!(
+            actual,
+            format!(
+                "<p class=\"image\"><img src=\"images/1.png\" alt=\"{}\"/></p>\n",
+                image.display()
+            )
+        );
+
+        // Files that are not images are listed by path
+        attachment.mime_type = Some("video/quicktime".to_string());
+        attachment.filename = Some("a/b/c/d.mov".to_string());
+        let actual = exporter.format_attachment(&mut attachment, &message, &mut book);
+
+        assert_eq!(actual, "<p class=\"note\">a/b/c/d.mov</p>\n");
+
+        remove_file(&path).unwrap();
+        remove_file(&image).unwrap();
+    }
+}
@@ -1,14 +1,21 @@
 use std::{
     collections::HashMap,
-    fs::File,
+    fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir, write, File},
     io::Write,
     path::{Path, PathBuf},
 };
 
 use crate::{
     app::{
-        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
-        sanitizers::sanitize_html,
+        attachment_manager::AttachmentManager,
+        error::RuntimeError,
+        locale::fill,
+        pagination::Pagination,
+        progress::build_progress_bar_export,
+        runtime::Config,
+        sanitizers::{sanitize_html, sanitize_link, strip_placeholders},
+        templates::{Template, Templates},
+        theme::Theme,
     },
     exporters::exporter::{BalloonFormatter, Exporter, Writer},
 };
@@ -20,7 +27,7 @@ use imessage_database::{
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
         edited::EditedMessage,
-        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        expressives::Expressive,
         handwriting::HandwrittenMessage,
         music::MusicMessage,
         placemark::PlacemarkMessage,
@@ -29,11 +36,12 @@ use imessage_database::{
     },
     tables::{
         attachment::{Attachment, MediaType},
+        chat::Chat,
         messages::{BubbleType, Message},
-        table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
+        table::{Table, FITNESS_RECEIVER, ORPHANED},
     },
     util::{
-        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        dates::{get_local_time, readable_diff_with, TIMESTAMP_FACTOR},
         plist::parse_plist,
     },
 };
@@ -41,6 +49,17 @@ use imessage_database::{
 const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">";
 const FOOTER: &str = "</body></html>";
 const STYLE: &str = include_str!("resources/style.css");
+const DARK_STYLE: &str = include_str!("resources/dark.css");
+const SORT_SCRIPT: &str = include_str!("resources/sort.js");
+const SEARCH_SCRIPT: &str = include_str!("resources/search.js");
+/// Directory inside the export that holds the search index for each conversation
+const SEARCH_DIRECTORY: &str = "search";
+/// Name of the copy of the user's stylesheet that exported files link to
+const CUSTOM_STYLE_FILENAME: &str = "custom.css";
+/// Name of the page that links to every exported conversation
+const INDEX_FILENAME: &str = "index.html";
+/// Maximum number of characters of the latest message shown on the index page
+const PREVIEW_LENGTH: usize = 120;
 
 pub struct HTML<'a> {
     /// Data that is setup from the application's runtime
@@ -48,8 +67,103 @@ pub struct HTML<'a> {
     /// Handles to files we want to write messages to
     /// Map of internal unique chatroom ID to a filename
     pub files: HashMap<i32, PathBuf>,
+    /// The document head written to the top of every file, including the theme and custom stylesheet
+    pub header: String,
+    /// User templates that replace parts of the built-in markup
+    pub templates: Templates,
     /// Path to file for orphaned messages
     pub orphaned: PathBuf,
+    /// Map of the first file of each conversation to a summary of the conversation
+    pub summaries: HashMap<PathBuf, ConversationSummary>,
+    /// Map of the first file of each conversation to the page its search index currently refers to
+    pub search_pages: HashMap<PathBuf, PathBuf>,
+    /// Map of internal unique chatroom ID to its pages, if pagination is enabled
+    pub pages: HashMap<i32, Pages>,
+    /// Map of message GUID to the page it was written to, for messages that are part of a thread
+    pub thread_pages: HashMap<String, PathBuf>,
+    /// Map of reply GUID to the GUID of the message that started its thread
+    pub thread_originators: HashMap<String, String>,
+}
+
+/// Tracks the files a paginated conversation has been written to
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pages {
+    /// Every page written so far, oldest first
+    pub paths: Vec<PathBuf>,
+    /// The label of the current page, i.e. `2021`, `2021-05`, or `3`
+    pub label: String,
+    /// The number of messages written to the current page
+    pub messages: usize,
+    /// The `ROWID` of the last message placed, since a message can request its file more than once
+    last_message: i32,
+}
+
+/// A single searchable message, stored as `[rowid, date, sender, text]` to keep the index small
+type SearchEntry<'a> = (i32, &'a str, &'a str, &'a str);
+
+/// Details about an exported conversation, shown on the index page
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConversationSummary {
+    /// The name of the conversation, falling back to its participants
+    pub title: String,
+    pub participants: Vec<String>,
+    /// The number of messages written to the conversation's file
+    pub messages: u64,
+    /// The date of the most recent message, in the same format as the `date` column
+    pub last_message: i64,
+    /// The beginning of the most recent message that contains text
+    pub preview: Option<String>,
+}
+
+impl ConversationSummary {
+    /// Create an empty summary for a chat, or for orphaned messages if there is no chat
+    fn new(config: &Config, chatroom: Option<&Chat>) -> Self {
+        let strings = config.strings();
+        let (title, participants) = match chatroom {
+            Some(chatroom) => {
+                let participants: Vec<String> = config
+                    .chatroom_participants
+                    .get(&chatroom.rowid)
+                    .map(|handles| {
+                        handles
+                            .iter()
+                            .map(|handle_id| config.who(Some(*handle_id), false).to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let title = match chatroom.display_name() {
+                    Some(name) => name.to_string(),
+                    None if !participants.is_empty() => participants.join(", "),
+                    None => chatroom.chat_identifier.clone(),
+                };
+                (title, participants)
+            }
+            None => (strings.orphaned_messages.to_string(), vec![]),
+        };
+
+        ConversationSummary {
+            title,
+            participants,
+            messages: 0,
+            last_message: 0,
+            preview: None,
+        }
+    }
+
+    /// Count a message, which must be newer than every message added before it
+    fn add(&mut self, message: &Message) {
+        self.messages += 1;
+        self.last_message = message.date;
+        if let Some(text) = &message.text {
+            let text = strip_placeholders(text);
+            if !text.is_empty() {
+                self.preview = Some(match text.char_indices().nth(PREVIEW_LENGTH) {
+                    Some((idx, _)) => format!("{}…", text[..idx].trim_end()),
+                    None => text,
+                });
+            }
+        }
+    }
 }
 
 impl<'a> Exporter<'a> for HTML<'a> {
@@ -60,7 +174,19 @@ impl<'a> Exporter<'a> for HTML<'a> {
         HTML {
             config,
             files: HashMap::new(),
+            header: HTML::build_header(config),
+            templates: config
+                .options
+                .template_dir
+                .as_deref()
+                .map(Templates::load)
+                .unwrap_or_default(),
             orphaned,
+            summaries: HashMap::new(),
+            search_pages: HashMap::new(),
+            pages: HashMap::new(),
+            thread_pages: HashMap::new(),
+            thread_originators: HashMap::new(),
         }
     }
 
@@ -72,7 +198,22 @@ impl<'a> Exporter<'a> for HTML<'a> {
         );
 
         // Write orphaned file headers
-        HTML::write_headers(&self.orphaned);
+        HTML::write_headers(&self.header, &self.orphaned);
+
+        // Create the directory that holds the search index
+        create_dir_all(self.search_directory()).map_err(RuntimeError::DiskError)?;
+
+        // Copy the custom stylesheet next to the exported files so they can link to it
+        if let (Some(css), true) = (
+            &self.config.options.custom_css,
+            self.config.options.link_css,
+        ) {
+            copy(
+                css,
+                self.config.options.export_path.join(CUSTOM_STYLE_FILENAME),
+            )
+            .map_err(RuntimeError::DiskError)?;
+        }
 
         // Keep track of current message ROWID
         let mut current_message_row = -1;
@@ -107,6 +248,7 @@ impl<'a> Exporter<'a> for HTML<'a> {
             if msg.is_announcement() {
                 let announcement = self.format_announcement(&msg);
                 HTML::write_to_file(self.get_or_create_file(&msg), &announcement);
+                self.summarize(&msg, false);
             }
             // Message replies and reactions are rendered in context, so no need to render them separately
             else if !msg.is_reaction() {
@@ -114,7 +256,22 @@ impl<'a> Exporter<'a> for HTML<'a> {
                 let message = self
                     .format_message(&msg, 0)
                     .map_err(RuntimeError::DatabaseError)?;
-                HTML::write_to_file(self.get_or_create_file(&msg), &message);
+                let path = self.get_or_create_file(&msg).to_path_buf();
+                // Anchor that search results link to
+                HTML::write_to_file(&path, &format!("<a id=\"m-{}\"></a>\n", msg.rowid));
+                HTML::write_to_file(&path, &message);
+                self.summarize(&msg, true);
+
+                // Remember where threads are written so links between pages can be resolved
+                if self.config.options.pagination != Pagination::Disabled
+                    && (msg.has_replies() || msg.is_reply())
+                {
+                    if let Some(originator) = &msg.thread_originator_guid {
+                        self.thread_originators
+                            .insert(msg.guid.clone(), originator.clone());
+                    }
+                    self.thread_pages.insert(msg.guid.clone(), path);
+                }
             }
             current_message += 1;
             if current_message % 99 == 0 {
@@ -128,13 +285,43 @@ impl<'a> Exporter<'a> for HTML<'a> {
             .iter()
             .for_each(|(_, path)| HTML::write_to_file(path, FOOTER));
         HTML::write_to_file(&self.orphaned, FOOTER);
+        self.search_pages
+            .keys()
+            .for_each(|path| HTML::write_to_file(&self.search_path(path), "]);\n"));
+
+        if self.config.options.pagination != Pagination::Disabled {
+            eprintln!("Resolving links between pages...");
+            self.resolve_thread_links()?;
+        }
+
+        // Embedded attachments are removed once they are written, so remove the folders that held them
+        if matches!(
+            self.config.options.attachment_manager,
+            AttachmentManager::Embedded(_)
+        ) {
+            self.remove_empty_attachment_dirs();
+        }
+
+        eprintln!("Writing HTML index...");
+        let mut index = self.config.options.export_path.clone();
+        index.push(INDEX_FILENAME);
+        HTML::write_headers(&self.header, &index);
+        HTML::write_to_file(&index, &self.format_index());
+        HTML::write_to_file(&index, FOOTER);
 
         Ok(())
     }
 
     /// Create a file for the given chat, caching it so we don't need to build it later
+    ///
+    /// If pagination is enabled, a new file is started when the message belongs on a new page.
     fn get_or_create_file(&mut self, message: &Message) -> &Path {
-        match self.config.conversation(message) {
+        let config = self.config;
+        match config.conversation(message) {
+            Some((chatroom, id)) if config.options.pagination != Pagination::Disabled => {
+                self.turn_page(chatroom, *id, message);
+                &self.files[id]
+            }
             Some((chatroom, id)) => self.files.entry(*id).or_insert_with(|| {
                 let mut path = self.config.options.export_path.clone();
                 path.push(self.config.filename(chatroom));
@@ -144,7 +331,8 @@ impl<'a> Exporter<'a> for HTML<'a> {
                 // This can happen if multiple chats use the same group name
                 if !path.exists() {
                     // Write headers if the file does not exist
-                    HTML::write_headers(&path);
+                    HTML::write_headers(&self.header, &path);
+                    HTML::write_conversation_header(self.config, chatroom, &path);
                 }
 
                 path
@@ -159,12 +347,17 @@ impl<'a> Writer<'a> for HTML<'a> {
         // Data we want to write to a file
         let mut formatted_message = String::new();
 
+        // Add an ID for any top-level message so we can link to them in threads
+        let anchor = match message.is_reply() && indent_size == 0 {
+            true => format!("r-{}", message.guid),
+            false => String::new(),
+        };
+
         // Message div
-        if message.is_reply() && indent_size == 0 {
-            // Add an ID for any top-level message so we can link to them in threads
+        if !anchor.is_empty() {
             self.add_line(
                 &mut formatted_message,
-                &format!("<div class=\"message\", id=\"r-{}\">", message.guid),
+                &format!("<div class=\"message\", id=\"{anchor}\">"),
                 "",
                 "",
             );
@@ -186,51 +379,49 @@ impl<'a> Writer<'a> for HTML<'a> {
         }
 
         // Add message date
+        let timestamp = self.get_time(message);
         self.add_line(
             &mut formatted_message,
-            &self.get_time(message),
+            &timestamp,
             "<p><span class=\"timestamp\">",
             "</span>",
         );
 
         // Add reply anchor if necessary
-        if message.is_reply() {
-            if indent_size > 0 {
-                // If we are indented it means we are rendering in a thread
-                self.add_line(
-                    &mut formatted_message,
-                    &format!("<a href=\"#r-{}\">⇲</a>", message.guid),
-                    "<span class=\"reply_anchor\">",
-                    "</span>",
-                );
-            } else {
-                // If there is no ident we are rendering a top-level message
-                self.add_line(
-                    &mut formatted_message,
-                    &format!("<a href=\"#{}\">⇱</a>", message.guid),
-                    "<span class=\"reply_anchor\">",
-                    "</span>",
-                );
-            }
-        }
+        let reply_anchor = match (message.is_reply(), indent_size > 0) {
+            // If we are indented it means we are rendering in a thread
+            (true, true) => format!("<a href=\"#r-{}\">⇲</a>", message.guid),
+            // If there is no ident we are rendering a top-level message
+            (true, false) => format!("<a href=\"#{}\">⇱</a>", message.guid),
+            (false, _) => String::new(),
+        };
+        self.add_line(
+            &mut formatted_message,
+            &reply_anchor,
+            "<span class=\"reply_anchor\">",
+            "</span>",
+        );
 
         // Add message sender
+        let sender = self.config.who(message.handle_id, message.is_from_me);
         self.add_line(
             &mut formatted_message,
-            self.config.who(message.handle_id, message.is_from_me),
+            sender,
             "<span class=\"sender\">",
             "</span></p>",
         );
 
         // If message was deleted, annotate it
-        if message.is_deleted() {
-            self.add_line(
-                &mut formatted_message,
-                "This message was deleted from the conversation!",
-                "<span class=\"deleted\">",
-                "</span></p>",
-            );
-        }
+        let deleted = match message.is_deleted() {
+            true => self.config.strings().deleted_from_conversation,
+            false => "",
+        };
+        self.add_line(
+            &mut formatted_message,
+            deleted,
+            "<span class=\"deleted\">",
+            "</span></p>",
+        );
 
         // Useful message metadata
         let message_parts = message.body();
@@ -241,15 +432,20 @@ impl<'a> Writer<'a> for HTML<'a> {
         let mut attachment_index: usize = 0;
 
         // Add message subject
-        if let Some(subject) = &message.subject {
-            // Add message sender
-            self.add_line(
-                &mut formatted_message,
-                &sanitize_html(subject),
-                "<p>Subject: <span class=\"subject\">",
-                "</span></p>",
-            );
-        }
+        let subject = message
+            .subject
+            .as_deref()
+            .map(sanitize_html)
+            .unwrap_or_default();
+        self.add_line(
+            &mut formatted_message,
+            &subject,
+            "<p>Subject: <span class=\"subject\">",
+            "</span></p>",
+        );
+
+        // Everything between the message's details and its closing tags
+        let mut body = String::new();
 
         // If message was removed, display it
         if message_parts.is_empty() && message.is_edited() {
@@ -258,18 +454,13 @@ impl<'a> Writer<'a> for HTML<'a> {
                 Ok(s) => return Ok(s),
                 Err(why) => format!("{}, {}", message.guid, why),
             };
-            self.add_line(
-                &mut formatted_message,
-                &edited,
-                "<div class=\"edited\">",
-                "</div>",
-            );
+            self.add_line(&mut body, &edited, "<div class=\"edited\">", "</div>");
         }
 
         // Handle SharePlay
         if message.is_shareplay() {
             self.add_line(
-                &mut formatted_message,
+                &mut body,
                 self.format_shareplay(),
                 "<span class=\"shareplay\">",
                 "</span>",
@@ -279,12 +470,7 @@ impl<'a> Writer<'a> for HTML<'a> {
         // Generate the message body from it's components
         for (idx, message_part) in message_parts.iter().enumerate() {
             // Write the part div start
-            self.add_line(
-                &mut formatted_message,
-                "<hr><div class=\"message_part\">",
-                "",
-                "",
-            );
+            self.add_line(&mut body, "<hr><div class=\"message_part\">", "", "");
 
             // Render edited messages
             if message.is_edited() {
@@ -292,12 +478,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                     Ok(s) => s,
                     Err(why) => format!("{}, {}", message.guid, why),
                 };
-                self.add_line(
-                    &mut formatted_message,
-                    &edited,
-                    "<div class=\"edited\">",
-                    "</div>",
-                );
+                self.add_line(&mut body, &edited, "<div class=\"edited\">", "</div>");
             }
 
             match message_part {
@@ -307,14 +488,14 @@ impl<'a> Writer<'a> for HTML<'a> {
                     if !message.is_edited() {
                         if text.starts_with(FITNESS_RECEIVER) {
                             self.add_line(
-                                &mut formatted_message,
-                                &text.replace(FITNESS_RECEIVER, YOU),
+                                &mut body,
+                                &text.replace(FITNESS_RECEIVER, self.config.strings().you),
                                 "<span class=\"bubble\">",
                                 "</span>",
                             );
                         } else {
                             self.add_line(
-                                &mut formatted_message,
+                                &mut body,
                                 &sanitize_html(text),
                                 "<span class=\"bubble\">",
                                 "</span>",
@@ -328,7 +509,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                             if attachment.is_sticker {
                                 let result = self.format_sticker(attachment, message);
                                 self.add_line(
-                                    &mut formatted_message,
+                                    &mut body,
                                     &result,
                                     "<div class=\"sticker\">",
                                     "</div>",
@@ -338,7 +519,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                                     Ok(result) => {
                                         attachment_index += 1;
                                         self.add_line(
-                                            &mut formatted_message,
+                                            &mut body,
                                             &result,
                                             "<div class=\"attachment\">",
                                             "</div>",
@@ -346,7 +527,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                                     }
                                     Err(result) => {
                                         self.add_line(
-                                        &mut formatted_message,
+                                        &mut body,
                                         result,
                                         "<span class=\"attachment_error\">Unable to locate attachment: ",
                                         "</span>",
@@ -357,23 +538,26 @@ impl<'a> Writer<'a> for HTML<'a> {
                         }
                         // Attachment does not exist in attachments table
                         None => self.add_line(
-                            &mut formatted_message,
-                            "Attachment does not exist!",
+                            &mut body,
+                            self.config.strings().attachment_does_not_exist,
                             "",
                             "",
                         ),
                     }
                 }
                 BubbleType::App => match self.format_app(message, &mut attachments, "") {
-                    Ok(ok_bubble) => self.add_line(
-                        &mut formatted_message,
-                        &ok_bubble,
-                        "<div class=\"app\">",
-                        "</div>",
-                    ),
+                    Ok(ok_bubble) => {
+                        self.add_line(&mut body, &ok_bubble, "<div class=\"app\">", "</div>")
+                    }
                     Err(why) => self.add_line(
-                        &mut formatted_message,
-                        &format!("Unable to format {:?} message: {why}", message.variant()),
+                        &mut body,
+                        &fill(
+                            self.config.strings().message_error,
+                            &[
+                                ("variant", &format!("{:?}", message.variant())),
+                                ("error", &why.to_string()),
+                            ],
+                        ),
                         "<div class=\"app_error\">",
                         "</div>",
                     ),
@@ -381,12 +565,12 @@ impl<'a> Writer<'a> for HTML<'a> {
             };
 
             // Write the part div end
-            self.add_line(&mut formatted_message, "</div>", "", "");
+            self.add_line(&mut body, "</div>", "", "");
 
             // Handle expressives
             if message.expressive_send_style_id.is_some() {
                 self.add_line(
-                    &mut formatted_message,
+                    &mut body,
                     self.format_expressive(message),
                     "<span class=\"expressive\">",
                     "</span>",
@@ -415,20 +599,20 @@ impl<'a> Writer<'a> for HTML<'a> {
 
                     if !formatted_reactions.is_empty() {
                         self.add_line(
-                            &mut formatted_message,
-                            "<hr><p>Reactions:</p>",
+                            &mut body,
+                            &format!("<hr><p>{}</p>", self.config.strings().reactions),
                             "<div class=\"reactions\">",
                             "",
                         );
-                        self.add_line(&mut formatted_message, &formatted_reactions, "", "");
+                        self.add_line(&mut body, &formatted_reactions, "", "");
                     }
-                    self.add_line(&mut formatted_message, "</div>", "", "");
+                    self.add_line(&mut body, "</div>", "", "");
                 }
             }
 
             // Handle Replies
             if let Some(replies) = replies.get_mut(&idx) {
-                self.add_line(&mut formatted_message, "<div class=\"replies\">", "", "");
+                self.add_line(&mut body, "<div class=\"replies\">", "", "");
                 replies
                     .iter_mut()
                     .try_for_each(|reply| -> Result<(), TableError> {
@@ -436,7 +620,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                         if !reply.is_reaction() {
                             // Set indent to 1 so we know this is a recursive call
                             self.add_line(
-                                &mut formatted_message,
+                                &mut body,
                                 &self.format_message(reply, 1)?,
                                 &format!("<div class=\"reply\" id=\"{}\">", reply.guid),
                                 "</div>",
@@ -444,20 +628,52 @@ impl<'a> Writer<'a> for HTML<'a> {
                         }
                         Ok(())
                     })?;
-                self.add_line(&mut formatted_message, "</div>", "", "");
+                self.add_line(&mut body, "</div>", "", "");
             }
         }
 
         // Add a note if the message is a reply and not rendered in a thread
-        if message.is_reply() && indent_size == 0 {
-            self.add_line(
-                &mut formatted_message,
-                "This message responded to an earlier message.",
-                "<span class=\"reply_context\">",
-                "</span>",
-            );
+        let reply_context = match message.is_reply() && indent_size == 0 {
+            true => self.config.strings().responded_to_earlier,
+            false => "",
+        };
+
+        // Render the user's template instead of the built-in markup, if there is one
+        if let Some(rendered) = self.templates.render(
+            Template::Message,
+            &[
+                ("guid", &message.guid),
+                ("rowid", &message.rowid.to_string()),
+                ("id", &anchor),
+                (
+                    "direction",
+                    if message.is_from_me {
+                        "sent"
+                    } else {
+                        "received"
+                    },
+                ),
+                ("service", &format!("{:?}", message.service())),
+                ("timestamp", &timestamp),
+                ("sender", sender),
+                ("reply_anchor", &reply_anchor),
+                ("deleted", deleted),
+                ("subject", &subject),
+                ("body", &body),
+                ("reply_context", reply_context),
+            ],
+        ) {
+            return Ok(rendered);
         }
 
+        formatted_message.push_str(&body);
+        self.add_line(
+            &mut formatted_message,
+            reply_context,
+            "<span class=\"reply_context\">",
+            "</span>",
+        );
+
         // End message type div
         self.add_line(&mut formatted_message, "</div>", "", "");
 
@@ -473,16 +689,44 @@ impl<'a> Writer<'a> for HTML<'a> {
         message: &Message,
     ) -> Result<String, &'a str> {
         // Copy the file, if requested
-        self.config
+        if self
+            .config
             .options
             .attachment_manager
             .handle_attachment(message, attachment, self.config)
-            .ok_or(attachment.filename())?;
+            .is_none()
+        {
+            return Err(attachment.filename());
+        }
+
+        // Write images, audio, and video into the file if attachments are embedded
+        let playable = matches!(
+            attachment.mime_type(),
+            MediaType::Image(_) | MediaType::Video(_) | MediaType::Audio(_)
+        );
+        let data_uri = match playable {
+            true => self.config.options.attachment_manager.embed(attachment),
+            false => None,
+        };
+        let embedded = data_uri.is_some();
+        // Attachments too large to embed are linked instead
+        let link_only = playable
+            && !embedded
+            && matches!(
+                self.config.options.attachment_manager,
+                AttachmentManager::Embedded(_)
+            );
 
         // Build a relative filepath from the fully qualified one on the `Attachment`
-        let embed_path = self.config.message_attachment_path(attachment);
+        let embed_path =
+            data_uri.unwrap_or_else(|| self.config.message_attachment_link(attachment));
 
-        return Ok(match attachment.mime_type() {
+        let embed = match attachment.mime_type() {
+            _ if link_only => format!(
+                "<a href=\"{embed_path}\">Click to download {} ({})</a>",
+                attachment.filename(),
+                attachment.file_size()
+            ),
             MediaType::Image(_) => {
                 if self.config.options.no_lazy {
                     format!("<img src=\"{embed_path}\">")
@@ -490,6 +734,12 @@ impl<'a> Writer<'a> for HTML<'a> {
                     format!("<img src=\"{embed_path}\" loading=\"lazy\">")
                 }
             }
+            // Embedded videos only get one source so the data is not written twice
+            MediaType::Video(media_type) if embedded => {
+                format!(
+                    "<video controls> <source src=\"{embed_path}\" type=\"{media_type}\"> </video>"
+                )
+            }
             MediaType::Video(media_type) => {
                 // See https://github.com/ReagentX/imessage-exporter/issues/73 for why duplicate the source tag
                 format!("<video controls> <source src=\"{embed_path}\" type=\"{media_type}\"> <source src=\"{embed_path}\"> </video>")
@@ -515,7 +765,31 @@ impl<'a> Writer<'a> for HTML<'a> {
             MediaType::Other(media_type) => {
                 format!("<p>Unable to embed {media_type} attachments: {embed_path}</p>")
             }
-        });
+        };
+
+        let (kind, mime_type) = match attachment.mime_type() {
+            MediaType::Image(mime_type) => ("image", mime_type),
+            MediaType::Video(mime_type) => ("video", mime_type),
+            MediaType::Audio(mime_type) => ("audio", mime_type),
+            MediaType::Text(mime_type) => ("text", mime_type),
+            MediaType::Application(mime_type) => ("application", mime_type),
+            MediaType::Other(mime_type) => ("other", mime_type),
+            MediaType::Unknown => ("unknown", ""),
+        };
+        Ok(self
+            .templates
+            .render(
+                Template::Attachment,
+                &[
+                    ("src", &embed_path),
+                    ("kind", kind),
+                    ("mime_type", mime_type),
+                    ("filename", &sanitize_html(attachment.filename())),
+                    ("size", &attachment.file_size()),
+                    ("embed", &embed),
+                ],
+            )
+            .unwrap_or(embed))
     }
 
     fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
@@ -527,7 +801,13 @@ impl<'a> Writer<'a> for HTML<'a> {
                     self.config.options.attachment_root.as_deref(),
                 );
                 if let Ok(Some(sticker_effect)) = sticker_effect {
-                    return format!("{sticker_embed}\n<div class=\"sticker_effect\">Sent with {sticker_effect} effect</div>");
+                    let effect = fill(
+                        self.config.strings().sticker_effect,
+                        &[("effect", &sticker_effect.to_string())],
+                    );
+                    return format!(
+                        "{sticker_embed}\n<div class=\"sticker_effect\">{effect}</div>"
+                    );
                 }
                 sticker_embed
             }
@@ -617,10 +897,29 @@ impl<'a> Writer<'a> for HTML<'a> {
                 if !added {
                     return Ok(String::new());
                 }
+                let strings = self.config.strings();
+                let who = self.config.who(msg.handle_id, msg.is_from_me);
+                let kind = strings.reaction(&reaction);
+                if let Some(rendered) = self.templates.render(
+                    Template::Reaction,
+                    &[
+                        ("kind", kind),
+                        ("sender", who),
+                        (
+                            "text",
+                            &fill(strings.reaction_by, &[("reaction", kind), ("who", who)]),
+                        ),
+                    ],
+                ) {
+                    return Ok(rendered);
+                }
+                let reaction = format!("<b>{kind}</b>");
                 Ok(format!(
-                    "<span class=\"reaction\"><b>{:?}</b> by {}</span>",
-                    reaction,
-                    self.config.who(msg.handle_id, msg.is_from_me),
+                    "<span class=\"reaction\">{}</span>",
+                    fill(
+                        strings.reaction_by,
+                        &[("reaction", &reaction), ("who", who)],
+                    )
                 ))
             }
             Variant::Sticker(_) => {
@@ -629,9 +928,10 @@ impl<'a> Writer<'a> for HTML<'a> {
                 // Sticker messages have only one attachment, the sticker image
                 Ok(match paths.get_mut(0) {
                     Some(sticker) => self.format_sticker(sticker, msg),
-                    None => {
-                        format!("<span class=\"reaction\">Sticker from {who} not found!</span>")
-                    }
+                    None => format!(
+                        "<span class=\"reaction\">{}</span>",
+                        fill(self.config.strings().sticker_not_found, &[("who", who)])
+                    ),
                 })
             }
             _ => unreachable!(),
@@ -640,63 +940,67 @@ impl<'a> Writer<'a> for HTML<'a> {
 
     fn format_expressive(&self, msg: &'a Message) -> &'a str {
         match msg.get_expressive() {
-            Expressive::Screen(effect) => match effect {
-                ScreenEffect::Confetti => "Sent with Confetti",
-                ScreenEffect::Echo => "Sent with Echo",
-                ScreenEffect::Fireworks => "Sent with Fireworks",
-                ScreenEffect::Balloons => "Sent with Balloons",
-                ScreenEffect::Heart => "Sent with Heart",
-                ScreenEffect::Lasers => "Sent with Lasers",
-                ScreenEffect::ShootingStar => "Sent with Shooting Star",
-                ScreenEffect::Sparkles => "Sent with Sparkles",
-                ScreenEffect::Spotlight => "Sent with Spotlight",
-            },
-            Expressive::Bubble(effect) => match effect {
-                BubbleEffect::Slam => "Sent with Slam",
-                BubbleEffect::Loud => "Sent with Loud",
-                BubbleEffect::Gentle => "Sent with Gentle",
-                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
-            },
+            Expressive::Screen(effect) => self.config.strings().screen_effect(&effect),
+            Expressive::Bubble(effect) => self.config.strings().bubble_effect(&effect),
             Expressive::Unknown(effect) => effect,
             Expressive::None => "",
         }
     }
 
     fn format_announcement(&self, msg: &'a Message) -> String {
+        let strings = self.config.strings();
         let mut who = self.config.who(msg.handle_id, msg.is_from_me);
         // Rename yourself so we render the proper grammar here
-        if who == ME {
-            who = self.config.options.custom_name.as_deref().unwrap_or("You");
+        if who == strings.me {
+            who = self
+                .config
+                .options
+                .custom_name
+                .as_deref()
+                .unwrap_or(strings.you);
         }
-        let timestamp = format(&msg.date(&self.config.offset));
+        let timestamp = self
+            .config
+            .format_date(&msg.date(&self.config.offset), msg.chat_id);
 
-        return match msg.get_announcement() {
+        match msg.get_announcement() {
             Some(announcement) => match announcement {
                 Announcement::NameChange(name) => {
-                    let clean_name = sanitize_html(name);
+                    let clean_name = format!("<b>{}</b>", sanitize_html(name));
+                    let text = fill(
+                        strings.named_conversation,
+                        &[("who", who), ("name", &clean_name)],
+                    );
                     format!(
-                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} named the conversation <b>{clean_name}</b></p></div>\n"
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {text}</p></div>\n"
                     )
                 }
                 Announcement::PhotoChange => {
+                    let photo = self.format_group_photo(msg).unwrap_or_default();
+                    let text = fill(strings.changed_group_photo, &[("who", who)]);
                     format!(
-                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} changed the group photo.</p></div>\n"
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {text}</p>{photo}</div>\n"
                     )
                 }
                 Announcement::Unknown(num) => {
+                    let text = fill(
+                        strings.unknown_action,
+                        &[("who", who), ("action", &num.to_string())],
+                    );
                     format!(
-                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} performed unknown action {num}</p></div>\n"
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {text}</p></div>\n"
                     )
                 }
             },
-            None => String::from(
-                "\n<div class =\"announcement\"><p>Unable to format announcement!</p></div>\n",
+            None => format!(
+                "\n<div class =\"announcement\"><p>{}</p></div>\n",
+                strings.unable_to_format_announcement
             ),
-        };
+        }
     }
 
     fn format_shareplay(&self) -> &str {
-        "SharePlay Message Ended"
+        self.config.strings().shareplay_ended
     }
 
     fn format_edited(&self, msg: &'a Message, _: &str) -> Result<String, MessageError> {
@@ -707,16 +1011,25 @@ impl<'a> Writer<'a> for HTML<'a> {
             let mut out_s = String::new();
             let mut previous_timestamp: Option<&i64> = None;
 
+            let strings = self.config.strings();
+
             if edited_message.is_deleted() {
                 let who = if msg.is_from_me {
-                    self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                    self.config
+                        .options
+                        .custom_name
+                        .as_deref()
+                        .unwrap_or(strings.you)
                 } else {
-                    "They"
+                    strings.they
                 };
-                let timestamp = format(&msg.date(&self.config.offset));
+                let timestamp = self
+                    .config
+                    .format_date(&msg.date(&self.config.offset), msg.chat_id);
+                let text = fill(strings.deleted_a_message, &[("who", who)]);
 
                 out_s.push_str(&format!(
-                    "<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} deleted a message.</p></div>"
+                    "<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {text}</p></div>"
                 ));
             } else {
                 out_s.push_str("<table>");
@@ -730,9 +1043,10 @@ impl<'a> Writer<'a> for HTML<'a> {
                             let end = get_local_time(&event.date, &self.config.offset);
                             let start = get_local_time(prev_timestamp, &self.config.offset);
 
-                            let diff = readable_diff(start, end).unwrap_or_default();
+                            let diff = readable_diff_with(start, end, &strings.durations)
+                                .unwrap_or_default();
                             out_s.push_str(&self.edited_to_html(
-                                &format!("Edited {diff} later"),
+                                &fill(strings.edited_later, &[("time", &diff)]),
                                 &clean_text,
                                 last,
                             ));
@@ -763,6 +1077,23 @@ impl<'a> Writer<'a> for HTML<'a> {
 
 impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     fn format_url(&self, balloon: &URLMessage, _: &Message) -> String {
+        let url = balloon.get_url().unwrap_or_default();
+        if let Some(rendered) = self.templates.render(
+            Template::Url,
+            &[
+                ("url", url),
+                ("site_name", balloon.site_name.unwrap_or(url)),
+                ("title", &sanitize_html(balloon.title.unwrap_or_default())),
+                (
+                    "summary",
+                    &sanitize_html(balloon.summary.unwrap_or_default()),
+                ),
+                ("image", balloon.images.first().copied().unwrap_or_default()),
+            ],
+        ) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         // Make the whole bubble clickable
@@ -829,6 +1160,19 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_music(&self, balloon: &MusicMessage, _: &Message) -> String {
+        if let Some(rendered) = self.templates.render(
+            Template::Music,
+            &[
+                ("url", balloon.url.unwrap_or_default()),
+                ("track_name", balloon.track_name.unwrap_or_default()),
+                ("artist", balloon.artist.unwrap_or_default()),
+                ("album", balloon.album.unwrap_or_default()),
+                ("preview", balloon.preview.unwrap_or_default()),
+            ],
+        ) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         // Header section
@@ -888,6 +1232,20 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_collaboration(&self, balloon: &CollaborationMessage, _: &Message) -> String {
+        if let Some(rendered) = self.templates.render(
+            Template::Collaboration,
+            &[
+                ("url", balloon.get_url().unwrap_or_default()),
+                (
+                    "app_name",
+                    balloon.app_name.or(balloon.bundle_id).unwrap_or_default(),
+                ),
+                ("title", balloon.title.unwrap_or_default()),
+            ],
+        ) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         // Header section
@@ -945,6 +1303,19 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_app_store(&self, balloon: &AppStoreMessage, _: &'a Message) -> String {
+        if let Some(rendered) = self.templates.render(
+            Template::AppStore,
+            &[
+                ("url", balloon.url.unwrap_or_default()),
+                ("app_name", balloon.app_name.unwrap_or_default()),
+                ("description", balloon.description.unwrap_or_default()),
+                ("platform", balloon.platform.unwrap_or_default()),
+                ("genre", balloon.genre.unwrap_or_default()),
+            ],
+        ) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         // Header section
@@ -1004,6 +1375,30 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a Message) -> String {
+        let url = balloon.get_url().unwrap_or_default();
+        if let Some(rendered) = self.templates.render(
+            Template::Placemark,
+            &[
+                ("url", url),
+                ("place_name", balloon.place_name.unwrap_or(url)),
+                ("address", balloon.placemark.address.unwrap_or_default()),
+                (
+                    "postal_code",
+                    balloon.placemark.postal_code.unwrap_or_default(),
+                ),
+                ("country", balloon.placemark.country.unwrap_or_default()),
+                (
+                    "area",
+                    balloon
+                        .placemark
+                        .sub_administrative_area
+                        .unwrap_or_default(),
+                ),
+            ],
+        ) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         // Make the whole bubble clickable
@@ -1081,6 +1476,10 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_apple_pay(&self, balloon: &AppMessage, _: &Message) -> String {
+        if let Some(rendered) = self.render_app(Template::ApplePay, balloon, "", "") {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         out_s.push_str("<div class=\"app_header\">");
@@ -1108,14 +1507,18 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_fitness(&self, balloon: &AppMessage, message: &Message) -> String {
-        self.balloon_to_html(balloon, "Fitness", &mut [], message)
+        self.balloon_to_html(Template::Fitness, balloon, "Fitness", &mut [], message)
     }
 
     fn format_slideshow(&self, balloon: &AppMessage, message: &Message) -> String {
-        self.balloon_to_html(balloon, "Slideshow", &mut [], message)
+        self.balloon_to_html(Template::Slideshow, balloon, "Slideshow", &mut [], message)
     }
 
     fn format_find_my(&self, balloon: &AppMessage, _: &'a Message) -> String {
+        if let Some(rendered) = self.render_app(Template::FindMy, balloon, "", "") {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         out_s.push_str("<div class=\"app_header\">");
@@ -1143,13 +1546,51 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
     }
 
     fn format_check_in(&self, balloon: &AppMessage, _: &Message) -> String {
+        let strings = self.config.strings();
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Parse a date from the message's query string and describe the check in with it
+        let describe = |date_str: &str, text: &str| {
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = self.config.format_date(&date_time, None);
+            fill(text, &[("date", &date_string)])
+        };
+
+        // Before manual check-in
+        let status = if let Some(date_str) = metadata.get("estimatedEndTime") {
+            Some(describe(date_str, strings.expected_around))
+        }
+        // Expired check-in
+        else if let Some(date_str) = metadata.get("triggerTime") {
+            Some(describe(date_str, strings.was_expected_around))
+        }
+        // Accepted check-in
+        else {
+            metadata
+                .get("sendDate")
+                .map(|date_str| describe(date_str, strings.checked_in_at))
+        };
+
+        let app_name = balloon.app_name.unwrap_or("Check In");
+        if let Some(rendered) = self.templates.render(
+            Template::CheckIn,
+            &[
+                ("app_name", app_name),
+                ("ldtext", balloon.ldtext.unwrap_or_default()),
+                ("status", status.as_deref().unwrap_or_default()),
+            ],
+        ) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
 
         out_s.push_str("<div class=\"app_header\">");
 
         // Name
         out_s.push_str("<div class=\"name\">");
-        out_s.push_str(balloon.app_name.unwrap_or("Check In"));
+        out_s.push_str(app_name);
         out_s.push_str("</div>");
 
         // ldtext
@@ -1163,49 +1604,11 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         out_s.push_str("</div>");
 
         // Only write the footer if there is data to write
-        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
-
-        // Before manual check-in
-        if let Some(date_str) = metadata.get("estimatedEndTime") {
-            // Parse the estimated end time from the message's query string
-            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
-            let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
-
-            out_s.push_str("<div class=\"app_footer\">");
-
-            out_s.push_str("<div class=\"caption\">Expected around ");
-            out_s.push_str(&date_string);
-            out_s.push_str("</div>");
-
-            out_s.push_str("</div>");
-        }
-        // Expired check-in
-        else if let Some(date_str) = metadata.get("triggerTime") {
-            // Parse the estimated end time from the message's query string
-            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
-            let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
-
-            out_s.push_str("<div class=\"app_footer\">");
-
-            out_s.push_str("<div class=\"caption\">Was expected around ");
-            out_s.push_str(&date_string);
-            out_s.push_str("</div>");
-
-            out_s.push_str("</div>");
-        }
-        // Accepted check-in
-        else if let Some(date_str) = metadata.get("sendDate") {
-            // Parse the estimated end time from the message's query string
-            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
-            let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
-
+        if let Some(status) = status {
             out_s.push_str("<div class=\"app_footer\">");
 
-            out_s.push_str("<div class=\"caption\">Checked in at ");
-            out_s.push_str(&date_string);
+            out_s.push_str("<div class=\"caption\">");
+            out_s.push_str(&status);
             out_s.push_str("</div>");
 
             out_s.push_str("</div>");
@@ -1221,22 +1624,330 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         attachments: &mut Vec<Attachment>,
         message: &Message,
     ) -> String {
-        self.balloon_to_html(balloon, bundle_id, attachments, message)
+        self.balloon_to_html(Template::App, balloon, bundle_id, attachments, message)
     }
 }
 
 impl<'a> HTML<'a> {
+    /// Start a new page for a paginated conversation if `message` does not belong on the current one
+    fn turn_page(&mut self, chatroom: &Chat, id: i32, message: &Message) {
+        let config = self.config;
+        let current = self.pages.get_mut(&id);
+        if matches!(&current, Some(pages) if pages.last_message == message.rowid) {
+            return;
+        }
+
+        let date = message
+            .date(&config.offset)
+            .ok()
+            .map(|date| config.timezone(message.chat_id).convert(&date));
+        let label = config.options.pagination.label(
+            date.as_ref(),
+            current
+                .as_ref()
+                .map(|pages| (pages.label.as_str(), pages.messages)),
+        );
+
+        let previous = match current {
+            Some(pages) if pages.label == label => {
+                pages.messages += 1;
+                pages.last_message = message.rowid;
+                return;
+            }
+            Some(pages) => pages
+                .paths
+                .last()
+                .map(|path| (path.clone(), pages.label.clone())),
+            None => None,
+        };
+
+        let mut path = config.options.export_path.clone();
+        path.push(format!("{} - {label}.html", config.filename(chatroom)));
+        let strings = config.strings();
+
+        if let Some((previous_path, _)) = &previous {
+            HTML::write_to_file(
+                previous_path,
+                &HTML::page_link(&path, &format!("{} ({label}) →", strings.next_page)),
+            );
+            HTML::write_to_file(previous_path, FOOTER);
+        }
+
+        // If the file already exists, don't write the headers again
+        // This can happen if multiple chats use the same group name
+        if !path.exists() {
+            HTML::write_headers(&self.header, &path);
+            HTML::write_conversation_header(config, chatroom, &path);
+            if let Some((previous_path, previous_label)) = &previous {
+                HTML::write_to_file(
+                    &path,
+                    &HTML::page_link(
+                        previous_path,
+                        &format!("← {} ({previous_label})", strings.previous_page),
+                    ),
+                );
+            }
+        }
+
+        let pages = self.pages.entry(id).or_insert_with(|| Pages {
+            paths: vec![],
+            label: String::new(),
+            messages: 0,
+            last_message: message.rowid,
+        });
+        pages.paths.push(path.clone());
+        pages.label = label;
+        pages.messages = 1;
+        pages.last_message = message.rowid;
+        self.files.insert(id, path);
+    }
+
+    /// Render a link to another page of the same conversation
+    fn page_link(path: &Path, text: &str) -> String {
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        format!(
+            "<div class=\"pagination\"><a href=\"{}\">{}</a></div>\n",
+            sanitize_link(&filename),
+            sanitize_html(text)
+        )
+    }
+
+    /// Point thread links at the page that contains their target
+    ///
+    /// Replies are written on the page for the date they were sent, but they also appear in the thread on
+    /// the page of the message that started it, so either end of a link can be on a different page.
+    fn resolve_thread_links(&self) -> Result<(), RuntimeError> {
+        for page in self.pages.values().flat_map(|pages| &pages.paths) {
+            let contents = read_to_string(page).map_err(RuntimeError::DiskError)?;
+            let mut resolved = String::with_capacity(contents.len());
+            let mut rest = contents.as_str();
+
+            while let Some(start) = rest.find("href=\"#") {
+                let (before, link) = rest.split_at(start + "href=\"".len());
+                resolved.push_str(before);
+                let end = link.find('"').unwrap_or(link.len());
+                if let Some(target) = self.thread_link_target(&link[1..end]) {
+                    if target != page {
+                        let filename = target
+                            .file_name()
+                            .map(|name| name.to_string_lossy())
+                            .unwrap_or_default();
+                        resolved.push_str(&sanitize_link(&filename));
+                    }
+                }
+                resolved.push_str(&link[..end]);
+                rest = &link[end..];
+            }
+            resolved.push_str(rest);
+
+            if resolved != contents {
+                write(page, resolved).map_err(RuntimeError::DiskError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the page that contains the element a thread link points to
+    fn thread_link_target(&self, anchor: &str) -> Option<&PathBuf> {
+        match anchor.strip_prefix("r-") {
+            // Links from a thread to where the reply was written on its own
+            Some(guid) => self.thread_pages.get(guid),
+            // Links from a reply to its place in the thread of the message that started it
+            None => self
+                .thread_originators
+                .get(anchor)
+                .and_then(|originator| self.thread_pages.get(originator)),
+        }
+    }
+
+    /// Get the first file a message's conversation was written to
+    fn first_page(&self, message: &Message, path: &Path) -> PathBuf {
+        self.config
+            .conversation(message)
+            .and_then(|(_, id)| self.pages.get(id))
+            .and_then(|pages| pages.paths.first())
+            .map_or_else(|| path.to_path_buf(), PathBuf::clone)
+    }
+
+    /// Record a message in the summary of its conversation, adding it to the search index if requested
+    fn summarize(&mut self, message: &Message, searchable: bool) {
+        let config = self.config;
+        let path = self.get_or_create_file(message).to_path_buf();
+        let first = self.first_page(message, &path);
+        let search_path = self.search_path(&first);
+
+        let summary = self.summaries.entry(first.clone()).or_insert_with(|| {
+            ConversationSummary::new(
+                config,
+                config.conversation(message).map(|(chatroom, _)| chatroom),
+            )
+        });
+        summary.add(message);
+
+        // Each search file is a script so the index works when opened from disk,
+        // with one call for each page so results link to the page that contains them
+        if self.search_pages.get(&first) != Some(&path) {
+            if self.search_pages.contains_key(&first) {
+                HTML::write_to_file(&search_path, "]);\n");
+            }
+            let filename = path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            HTML::write_to_file(
+                &search_path,
+                &format!(
+                    "imessageSearch({}, {}, [\n",
+                    serde_json::Value::from(sanitize_link(&filename)),
+                    serde_json::Value::from(summary.title.as_str()),
+                ),
+            );
+            self.search_pages.insert(first, path);
+        }
+
+        if searchable {
+            if let Some(text) = message.text.as_deref().map(strip_placeholders) {
+                if !text.is_empty() {
+                    let date = config.format_date(&message.date(&config.offset), message.chat_id);
+                    let entry: SearchEntry = (
+                        message.rowid,
+                        &date,
+                        config.who(message.handle_id, message.is_from_me),
+                        &text,
+                    );
+                    if let Ok(entry) = serde_json::to_string(&entry) {
+                        HTML::write_to_file(&search_path, &format!("{entry},\n"));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the directory that holds the search index
+    fn search_directory(&self) -> PathBuf {
+        self.config.options.export_path.join(SEARCH_DIRECTORY)
+    }
+
+    /// Get the path to the search index for an exported file
+    fn search_path(&self, path: &Path) -> PathBuf {
+        let mut search_path = self.search_directory();
+        search_path.push(path.file_name().unwrap_or_default());
+        search_path.set_extension("js");
+        search_path
+    }
+
+    /// Render the body of the index page, listing the most recent conversations first
+    fn format_index(&self) -> String {
+        let strings = self.config.strings();
+        let mut summaries: Vec<(&PathBuf, &ConversationSummary)> = self.summaries.iter().collect();
+        summaries.sort_by(|(a_path, a), (b_path, b)| {
+            b.last_message
+                .cmp(&a.last_message)
+                .then_with(|| a_path.cmp(b_path))
+        });
+
+        let mut index = format!(
+            "<div class=\"index_header\">\n<h1>{}</h1>\n<input id=\"search\" type=\"search\" placeholder=\"{}\">\n<label>{}: <select id=\"index_sort\">\n<option value=\"recent\">{}</option>\n<option value=\"oldest\">{}</option>\n<option value=\"name\">{}</option>\n<option value=\"messages\">{}</option>\n</select></label>\n</div>\n<div id=\"search_results\" class=\"index\" data-empty=\"{}\" hidden></div>\n<div id=\"index\" class=\"index\">\n",
+            strings.conversations,
+            strings.search,
+            strings.sort_by,
+            strings.most_recent,
+            strings.least_recent,
+            strings.name,
+            strings.most_messages,
+            strings.no_results,
+        );
+        let mut scripts = String::from("<script>\n");
+        scripts.push_str(SEARCH_SCRIPT);
+        scripts.push_str("\n</script>\n");
+
+        for (path, summary) in summaries {
+            let filename = path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            if let Ok(search_path) = self
+                .search_path(path)
+                .strip_prefix(&self.config.options.export_path)
+            {
+                scripts.push_str(&format!(
+                    "<script src=\"{}\"></script>\n",
+                    sanitize_link(&search_path.to_string_lossy())
+                ));
+            }
+            let date = self.config.format_date(
+                &get_local_time(&summary.last_message, &self.config.offset),
+                None,
+            );
+
+            index.push_str(&format!(
+                "<a class=\"index_entry\" href=\"{}\" data-date=\"{}\" data-messages=\"{}\" data-name=\"{}\">\n",
+                sanitize_link(&filename),
+                summary.last_message,
+                summary.messages,
+                sanitize_html(&summary.title.to_lowercase()),
+            ));
+            self.add_line(
+                &mut index,
+                &sanitize_html(&summary.title),
+                "<span class=\"index_title\">",
+                "</span>",
+            );
+            self.add_line(&mut index, &date, "<span class=\"index_date\">", "</span>");
+            // Untitled conversations already use their participants as the title
+            if summary.title != summary.participants.join(", ") {
+                self.add_line(
+                    &mut index,
+                    &sanitize_html(&summary.participants.join(", ")),
+                    "<span class=\"index_participants\">",
+                    "</span>",
+                );
+            }
+            self.add_line(
+                &mut index,
+                &format!("{}: {}", strings.messages, summary.messages),
+                "<span class=\"index_count\">",
+                "</span>",
+            );
+            if let Some(preview) = &summary.preview {
+                self.add_line(
+                    &mut index,
+                    &sanitize_html(preview),
+                    "<span class=\"index_preview\">",
+                    "</span>",
+                );
+            }
+            index.push_str("</a>\n");
+        }
+        index.push_str("</div>\n<script>\n");
+        index.push_str(SORT_SCRIPT);
+        index.push_str("\n</script>\n");
+        index.push_str(&scripts);
+        index
+    }
+
     fn get_time(&self, message: &Message) -> String {
-        let mut date = format(&message.date(&self.config.offset));
-        let read_after = message.time_until_read(&self.config.offset);
+        let mut date = self
+            .config
+            .format_date(&message.date(&self.config.offset), message.chat_id);
+        let strings = self.config.strings();
+        let read_after = message.time_until_read_with(&self.config.offset, &strings.durations);
         if let Some(time) = read_after {
             if !time.is_empty() {
                 let who = if message.is_from_me {
-                    "them"
+                    strings.them
                 } else {
-                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                    self.config
+                        .options
+                        .custom_name
+                        .as_deref()
+                        .unwrap_or(strings.you_object)
                 };
-                date.push_str(&format!(" (Read by {who} after {time})"));
+                let read_by = fill(strings.read_by, &[("who", who), ("time", &time)]);
+                date.push_str(&format!(" ({read_by})"));
             }
         }
         date
@@ -1251,29 +1962,168 @@ impl<'a> HTML<'a> {
         }
     }
 
-    fn write_headers(path: &Path) {
-        // Write file header
-        HTML::write_to_file(path, HEADER);
+    fn write_headers(header: &str, path: &Path) {
+        HTML::write_to_file(path, header);
+    }
+
+    /// Build the document head, including the styles for the selected theme and the user's stylesheet
+    ///
+    /// The user's stylesheet comes last so its rules override the built-in ones.
+    fn build_header(config: &Config) -> String {
+        let theme = config.options.theme;
+        let mut header = format!(
+            "{HEADER}\n<meta name=\"color-scheme\" content=\"{}\">\n",
+            theme.color_scheme()
+        );
 
         // Write CSS
-        HTML::write_to_file(path, "<style>\n");
-        HTML::write_to_file(path, STYLE);
-        HTML::write_to_file(path, "\n</style>");
-        HTML::write_to_file(path, "\n</head>\n<body>\n");
+        header.push_str("<style>\n");
+        header.push_str(STYLE);
+        match theme {
+            Theme::Auto => {
+                header.push_str("\n@media (prefers-color-scheme: dark) {\n");
+                header.push_str(DARK_STYLE);
+                header.push('}');
+            }
+            Theme::Dark => {
+                header.push('\n');
+                header.push_str(DARK_STYLE);
+            }
+            Theme::Light => {}
+        }
+        header.push_str("\n</style>");
+
+        if let Some(css) = &config.options.custom_css {
+            if config.options.link_css {
+                header.push_str(&format!(
+                    "\n<link rel=\"stylesheet\" href=\"{CUSTOM_STYLE_FILENAME}\">"
+                ));
+            } else {
+                match read_to_string(css) {
+                    Ok(style) => header.push_str(&format!("\n<style>\n{style}\n</style>")),
+                    Err(why) => eprintln!("Unable to read stylesheet {css:?}: {why}"),
+                }
+            }
+        }
+        header.push_str("\n</head>\n<body>\n");
+        header
     }
 
-    fn edited_to_html(&self, timestamp: &str, text: &str, last: bool) -> String {
-        let tag = if last { "tfoot" } else { "tbody" };
+    fn write_conversation_header(config: &Config, chatroom: &Chat, path: &Path) {
+        let group_photo = config.group_photo(chatroom);
+        let details = config.conversation_details(chatroom);
+        if details.is_empty() && group_photo.is_none() {
+            return;
+        }
+
+        let mut header = String::from("<div class=\"conversation_header\">\n");
+        if let Some(mut photo) = group_photo {
+            let src = config
+                .options
+                .attachment_manager
+                .embed(&mut photo)
+                .unwrap_or_else(|| config.message_attachment_link(&photo));
+            header.push_str(&format!("<img class=\"group_photo\" src=\"{src}\">\n"));
+        }
+        for (label, value) in details {
+            header.push_str(&format!(
+                "<p><span class=\"detail_label\">{label}:</span> {}</p>\n",
+                sanitize_html(&value)
+            ));
+        }
+        header.push_str("</div>\n");
+
+        HTML::write_to_file(path, &header);
+    }
+
+    /// Render the new group photo attached to a photo change announcement
+    fn format_group_photo(&self, message: &Message) -> Option<String> {
+        let mut attachments = Attachment::from_message(&self.config.db, message).ok()?;
+        let photo = attachments.first_mut()?;
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, photo, self.config)?;
+        let src = self
+            .config
+            .options
+            .attachment_manager
+            .embed(photo)
+            .unwrap_or_else(|| self.config.message_attachment_link(photo));
+        Some(format!("<img class=\"group_photo\" src=\"{src}\">"))
+    }
+
+    fn edited_to_html(&self, timestamp: &str, text: &str, last: bool) -> String {
+        let tag = if last { "tfoot" } else { "tbody" };
         format!("<{tag}><tr><td><span class=\"timestamp\">{timestamp}</span></td><td>{text}</td></tr></{tag}>")
     }
 
+    /// Remove attachment folders that are empty because every file in them was embedded
+    ///
+    /// Folders that still contain attachments too large to embed are kept.
+    fn remove_empty_attachment_dirs(&self) {
+        let attachment_path = self.config.attachment_path();
+        if let Ok(entries) = read_dir(&attachment_path) {
+            entries.flatten().for_each(|entry| {
+                // This fails if the folder is not empty, which is what we want
+                let _ = remove_dir(entry.path());
+            });
+        }
+        let _ = remove_dir(&attachment_path);
+    }
+
+    /// Render the user's template for an app balloon, if there is one
+    fn render_app(
+        &self,
+        template: Template,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        attachment: &str,
+    ) -> Option<String> {
+        self.templates.render(
+            template,
+            &[
+                ("url", balloon.url.unwrap_or_default()),
+                ("image", balloon.image.unwrap_or_default()),
+                ("attachment", attachment),
+                ("app_name", balloon.app_name.unwrap_or(bundle_id)),
+                ("bundle_id", bundle_id),
+                ("title", balloon.title.unwrap_or_default()),
+                ("subtitle", balloon.subtitle.unwrap_or_default()),
+                ("ldtext", balloon.ldtext.unwrap_or_default()),
+                ("caption", balloon.caption.unwrap_or_default()),
+                ("subcaption", balloon.subcaption.unwrap_or_default()),
+                (
+                    "trailing_caption",
+                    balloon.trailing_caption.unwrap_or_default(),
+                ),
+                (
+                    "trailing_subcaption",
+                    balloon.trailing_subcaption.unwrap_or_default(),
+                ),
+            ],
+        )
+    }
+
     fn balloon_to_html(
         &self,
+        template: Template,
         balloon: &AppMessage,
         bundle_id: &str,
         attachments: &mut [Attachment],
         message: &Message,
     ) -> String {
+        // Apps without a preview image use the first attachment instead
+        let attachment = match (balloon.image, attachments.get_mut(0)) {
+            (None, Some(attachment)) => self
+                .format_attachment(attachment, message)
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        if let Some(rendered) = self.render_app(template, balloon, bundle_id, &attachment) {
+            return rendered;
+        }
+
         let mut out_s = String::new();
         if let Some(url) = balloon.url {
             out_s.push_str("<a href=\"");
@@ -1287,12 +2137,8 @@ impl<'a> HTML<'a> {
             out_s.push_str("<img src=\"");
             out_s.push_str(image);
             out_s.push_str("\">");
-        } else if let Some(attachment) = attachments.get_mut(0) {
-            out_s.push_str(
-                &self
-                    .format_attachment(attachment, message)
-                    .unwrap_or_default(),
-            );
+        } else {
+            out_s.push_str(&attachment);
         }
 
         // Name
@@ -1370,19 +2216,33 @@ impl<'a> HTML<'a> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::{
-        env::{current_dir, set_var},
+        collections::{BTreeMap, BTreeSet},
+        env::{current_dir, set_var, temp_dir},
+        fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write},
         path::PathBuf,
     };
 
+    use rusqlite::Connection;
+    use uuid::Uuid;
+
     use crate::{
-        app::attachment_manager::AttachmentManager, exporters::exporter::Writer, Config, Exporter,
-        Options, HTML,
+        app::{
+            attachment_manager::AttachmentManager, attachment_names::AttachmentNames,
+            locale::Locale, mode::Mode, pagination::Pagination, templates::Templates, theme::Theme,
+        },
+        exporters::{
+            exporter::Writer,
+            html::{ConversationSummary, Pages},
+        },
+        Config, Exporter, Options, HTML,
     };
     use imessage_database::{
-        tables::{attachment::Attachment, messages::Message},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+        tables::{attachment::Attachment, chat::Chat, messages::Message},
+        util::{
+            dates::Timezone, dirs::default_db_path, platform::Platform, query_context::QueryContext,
+        },
     };
 
     pub fn blank() -> Message {
@@ -1420,7 +2280,7 @@ mod tests {
             db_path: default_db_path(),
             attachment_root: None,
             attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
+            mode: Mode::Export,
             export_type: None,
             export_path: PathBuf::new(),
             query_context: QueryContext::default(),
@@ -1428,9 +2288,35 @@ mod tests {
             custom_name: None,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            timezone: Timezone::Local,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
         }
     }
 
+    /// Load templates from a temporary directory containing the given files
+    pub fn fake_templates(files: &[(&str, &str)]) -> Templates {
+        let directory = temp_dir().join(format!("html_templates_{}", Uuid::new_v4()));
+        create_dir_all(&directory).unwrap();
+        files
+            .iter()
+            .for_each(|(name, contents)| write(directory.join(name), contents).unwrap());
+        let templates = Templates::load(&directory);
+        remove_dir_all(&directory).unwrap();
+        templates
+    }
+
     pub fn fake_attachment() -> Attachment {
         Attachment {
             rowid: 0,
@@ -1445,6 +2331,21 @@ mod tests {
         }
     }
 
+    /// Replace the config's database with one where message 1 has a group photo attached
+    pub fn fake_group_photo_table(config: &mut Config) {
+        config.db = Connection::open_in_memory().unwrap();
+        config
+            .db
+            .execute_batch(
+                "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, guid TEXT, filename TEXT, mime_type TEXT, transfer_name TEXT, total_bytes INTEGER);
+                CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+                INSERT INTO attachment (guid, filename, mime_type, transfer_name, total_bytes)
+                    VALUES ('at_0', 'a/b/c/photo.jpeg', 'image/jpeg', 'photo.jpeg', 100);
+                INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1);",
+            )
+            .unwrap();
+    }
+
     #[test]
     fn can_create() {
         let options = fake_options();
@@ -1453,6 +2354,320 @@ mod tests {
         assert_eq!(exporter.files.len(), 0);
     }
 
+    #[test]
+    fn can_summarize_conversation() {
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        config.participants.insert(1, "Person 1".to_string());
+        config.participants.insert(2, "Person 2".to_string());
+        config
+            .chatroom_participants
+            .insert(5, BTreeSet::from([1, 2]));
+        let chatroom = Chat {
+            rowid: 5,
+            chat_identifier: "chat5".to_string(),
+            service_name: Some("iMessage".to_string()),
+            display_name: None,
+            properties: None,
+        };
+
+        let mut summary = ConversationSummary::new(&config, Some(&chatroom));
+
+        let mut message = blank();
+        message.date = 100;
+        message.text = Some("Hello".to_string());
+        summary.add(&message);
+
+        // Attachments without text don't replace the preview
+        message.date = 200;
+        message.text = Some("\u{FFFC}".to_string());
+        summary.add(&message);
+
+        assert_eq!(
+            summary,
+            ConversationSummary {
+                title: "Person 1, Person 2".to_string(),
+                participants: vec!["Person 1".to_string(), "Person 2".to_string()],
+                messages: 2,
+                last_message: 200,
+                preview: Some("Hello".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn can_summarize_orphaned() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+
+        let summary = ConversationSummary::new(&config, None);
+
+        assert_eq!(summary.title, "Messages without a conversation");
+        assert!(summary.participants.is_empty());
+    }
+
+    #[test]
+    fn can_truncate_preview() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut summary = ConversationSummary::new(&config, None);
+
+        let mut message = blank();
+        message.text = Some(format!("{} end", "é".repeat(119)));
+        summary.add(&message);
+
+        assert_eq!(summary.preview, Some(format!("{}…", "é".repeat(119))));
+    }
+
+    #[test]
+    fn can_format_index() {
+        set_var("TZ", "PST");
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+
+        for (path, title, last_message) in [
+            ("Old Friends - 1.html", "Old Friends", 100),
+            ("+15555550100.html", "+15555550100", 200),
+        ] {
+            exporter.summaries.insert(
+                PathBuf::from(path),
+                ConversationSummary {
+                    title: title.to_string(),
+                    participants: vec!["+15555550100".to_string()],
+                    messages: 3,
+                    last_message,
+                    preview: Some("<b>hi</b>".to_string()),
+                },
+            );
+        }
+
+        let index = exporter.format_index();
+        let newest = index.find("href=\"%2B15555550100.html\"").unwrap();
+        let oldest = index.find("href=\"Old%20Friends%20-%201.html\"").unwrap();
+
+        assert!(newest < oldest);
+        assert!(index.contains("<span class=\"index_preview\">&lt;b&gt;hi&lt;/b&gt;</span>"));
+        // Untitled conversations don't repeat their participants
+        assert_eq!(
+            index.matches("<span class=\"index_participants\">").count(),
+            1
+        );
+        assert!(index.contains("<script src=\"search/%2B15555550100.js\"></script>"));
+        assert!(index.contains("<input id=\"search\" type=\"search\" placeholder=\"Search\">"));
+    }
+
+    #[test]
+    fn can_get_search_path() {
+        let mut options = fake_options();
+        options.export_path = PathBuf::from("/export");
+        let config = Config::new(options).unwrap();
+        let exporter = HTML::new(&config);
+
+        assert_eq!(
+            exporter.search_path(&PathBuf::from("/export/Friends - 1.html")),
+            PathBuf::from("/export/search/Friends - 1.js")
+        );
+    }
+
+    #[test]
+    fn can_turn_pages() {
+        let export_path = temp_dir().join(format!("html_pages_{}", Uuid::new_v4()));
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = fake_options();
+        options.export_path = export_path.clone();
+        options.pagination = Pagination::Messages(2);
+        let mut config = Config::new(options).unwrap();
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: "chat1".to_string(),
+                service_name: None,
+                display_name: Some("Friends".to_string()),
+                properties: None,
+            },
+        );
+        config.real_chatrooms.insert(1, 1);
+        let mut exporter = HTML::new(&config);
+
+        let mut message = blank();
+        message.chat_id = Some(1);
+        for rowid in 1..=3 {
+            message.rowid = rowid;
+            // Files are requested more than once for each message
+            exporter.get_or_create_file(&message);
+            exporter.get_or_create_file(&message);
+        }
+
+        let pages = &exporter.pages[&1];
+        assert_eq!(
+            pages.paths,
+            vec![
+                export_path.join("Friends - 1 - 1.html"),
+                export_path.join("Friends - 1 - 2.html")
+            ]
+        );
+        assert_eq!(pages.messages, 1);
+        assert_eq!(exporter.files[&1], export_path.join("Friends - 1 - 2.html"));
+
+        let first = read_to_string(&pages.paths[0]).unwrap();
+        let second = read_to_string(&pages.paths[1]).unwrap();
+        assert!(first.contains("<a href=\"Friends%20-%201%20-%202.html\">Next page (2) →</a>"));
+        assert!(second.contains("<a href=\"Friends%20-%201%20-%201.html\">← Previous page (1)</a>"));
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_turn_pages_in_chat_timezone() {
+        let export_path = temp_dir().join(format!("html_pages_{}", Uuid::new_v4()));
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = fake_options();
+        options.export_path = export_path.clone();
+        options.pagination = Pagination::Year;
+        options.timezone = Timezone::Utc;
+        options.chat_timezones = BTreeMap::from([(1, Timezone::from_cli("Asia/Tokyo").unwrap())]);
+        let mut config = Config::new(options).unwrap();
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: "chat1".to_string(),
+                service_name: None,
+                display_name: Some("Friends".to_string()),
+                properties: None,
+            },
+        );
+        config.real_chatrooms.insert(1, 1);
+        let mut exporter = HTML::new(&config);
+
+        // Sent at 2020-12-31 20:00 UTC, which is already 2021 in Tokyo
+        let mut message = blank();
+        message.chat_id = Some(1);
+        message.date = 631137600000000000;
+        exporter.get_or_create_file(&message);
+
+        assert_eq!(
+            exporter.pages[&1].paths,
+            vec![export_path.join("Friends - 1 - 2021.html")]
+        );
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_resolve_thread_links() {
+        let export_path = temp_dir().join(format!("html_threads_{}", Uuid::new_v4()));
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = fake_options();
+        options.export_path = export_path.clone();
+        options.pagination = Pagination::Year;
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+
+        // The thread starts in 2020 and the reply is sent in 2021
+        let old_page = export_path.join("Friends - 1 - 2020.html");
+        let new_page = export_path.join("Friends - 1 - 2021.html");
+        write(
+            &old_page,
+            "<div class=\"reply\" id=\"reply\"><a href=\"#r-reply\">⇲</a></div><a href=\"#r-other\">",
+        )
+        .unwrap();
+        write(
+            &new_page,
+            "<div class=\"message\", id=\"r-reply\"><a href=\"#reply\">⇱</a></div>",
+        )
+        .unwrap();
+
+        exporter.pages.insert(
+            1,
+            Pages {
+                paths: vec![old_page.clone(), new_page.clone()],
+                label: "2021".to_string(),
+                messages: 1,
+                last_message: 0,
+            },
+        );
+        exporter
+            .thread_pages
+            .insert("start".to_string(), old_page.clone());
+        exporter
+            .thread_pages
+            .insert("reply".to_string(), new_page.clone());
+        exporter
+            .thread_originators
+            .insert("reply".to_string(), "start".to_string());
+
+        exporter.resolve_thread_links().unwrap();
+
+        assert_eq!(
+            read_to_string(&old_page).unwrap(),
+            "<div class=\"reply\" id=\"reply\"><a href=\"Friends%20-%201%20-%202021.html#r-reply\">⇲</a></div><a href=\"#r-other\">"
+        );
+        assert_eq!(
+            read_to_string(&new_page).unwrap(),
+            "<div class=\"message\", id=\"r-reply\"><a href=\"Friends%20-%201%20-%202020.html#reply\">⇱</a></div>"
+        );
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_build_header_themes() {
+        let mut options = fake_options();
+        options.theme = Theme::Auto;
+        let config = Config::new(options).unwrap();
+        let auto = HTML::new(&config).header;
+        assert!(auto.contains("<meta name=\"color-scheme\" content=\"light dark\">"));
+        assert!(auto.contains("@media (prefers-color-scheme: dark) {"));
+
+        let mut options = fake_options();
+        options.theme = Theme::Dark;
+        let config = Config::new(options).unwrap();
+        let dark = HTML::new(&config).header;
+        assert!(dark.contains("<meta name=\"color-scheme\" content=\"dark\">"));
+        assert!(dark.contains("background-color: #3a3a3c;"));
+        assert!(!dark.contains("@media (prefers-color-scheme: dark)"));
+
+        let mut options = fake_options();
+        options.theme = Theme::Light;
+        let config = Config::new(options).unwrap();
+        let light = HTML::new(&config).header;
+        assert!(light.contains("<meta name=\"color-scheme\" content=\"light\">"));
+        assert!(!light.contains("background-color: #3a3a3c;"));
+        assert!(light.ends_with("</style>\n</head>\n<body>\n"));
+    }
+
+    #[test]
+    fn can_build_header_custom_css() {
+        let css = temp_dir().join(format!("html_style_{}.css", Uuid::new_v4()));
+        write(&css, ".sent.iMessage { background-color: purple; }").unwrap();
+
+        let mut options = fake_options();
+        options.custom_css = Some(css.clone());
+        let config = Config::new(options).unwrap();
+        let embedded = HTML::new(&config).header;
+        assert!(embedded.ends_with(
+            "<style>\n.sent.iMessage { background-color: purple; }\n</style>\n</head>\n<body>\n"
+        ));
+
+        let mut options = fake_options();
+        options.custom_css = Some(css.clone());
+        options.link_css = true;
+        let config = Config::new(options).unwrap();
+        let linked = HTML::new(&config).header;
+        assert!(linked.ends_with(
+            "</style>\n<link rel=\"stylesheet\" href=\"custom.css\">\n</head>\n<body>\n"
+        ));
+        assert!(!linked.contains("purple"));
+
+        remove_file(&css).unwrap();
+    }
+
     #[test]
     fn can_get_time_valid() {
         // Set timezone to PST for consistent Local time
@@ -1478,6 +2693,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_get_time_valid_locale() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = fake_options();
+        options.locale = Locale::Spanish;
+        let config = Config::new(options).unwrap();
+        let exporter = HTML::new(&config);
+
+        // Create fake message
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
+
+        assert_eq!(
+            exporter.get_time(&message),
+            "17/05/2022 17:29:42 (Leído por usted después de 1 hora, 49 segundos)"
+        );
+    }
+
     #[test]
     fn can_get_time_invalid() {
         // Set timezone to PST for consistent Local time
@@ -1564,6 +2805,62 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_message_template() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+        exporter.templates = fake_templates(&[(
+            "message.html",
+            "<article id=\"{id}\" class=\"{direction} {service}\"><header>{sender} at {timestamp}{unknown}</header>{body}</article>",
+        )]);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<article id=\"\" class=\"sent iMessage\"><header>Me at May 17, 2022  5:29:42 PM{unknown}</header><hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</article>";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_message_template_reply_id() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+        exporter.templates = fake_templates(&[("message.html", "<article id=\"{id}\"></article>")]);
+
+        let mut message = blank();
+        message.guid = "guid".to_string();
+        message.thread_originator_guid = Some("start".to_string());
+        message.chat_id = Some(0);
+
+        // Replies shown outside their thread get the same anchor as the built-in markup
+        let built_in = HTML::new(&config).format_message(&message, 0).unwrap();
+        assert!(built_in.starts_with("<div class=\"message\", id=\"r-guid\">"));
+        assert_eq!(
+            exporter.format_message(&message, 0).unwrap(),
+            "<article id=\"r-guid\"></article>"
+        );
+
+        // Replies rendered inside a thread do not
+        assert_eq!(
+            exporter.format_message(&message, 1).unwrap(),
+            "<article id=\"\"></article>"
+        );
+    }
+
     #[test]
     fn can_format_html_message_with_html() {
         // Set timezone to PST for consistent Local time
@@ -1763,6 +3060,28 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_announcement_locale() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = fake_options();
+        options.locale = Locale::Spanish;
+        let config = Config::new(options).unwrap();
+        let exporter = HTML::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">17/05/2022 17:29:42</span> Usted nombró la conversación <b>Hello world</b></p></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_announcement_custom_name() {
         // Set timezone to PST for consistent Local time
@@ -1785,6 +3104,88 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_group_photo() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        fake_group_photo_table(&mut config);
+        let exporter = HTML::new(&config);
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.num_attachments = 1;
+
+        let actual = exporter.format_group_photo(&message);
+        let expected = "<img class=\"group_photo\" src=\"a/b/c/photo.jpeg\">";
+
+        assert_eq!(actual.as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn cant_format_html_group_photo_missing() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        fake_group_photo_table(&mut config);
+        let exporter = HTML::new(&config);
+
+        // This message has no row in the attachment join table
+        let mut message = blank();
+        message.rowid = 2;
+        message.num_attachments = 1;
+
+        assert_eq!(exporter.format_group_photo(&message), None);
+    }
+
+    #[test]
+    fn can_format_html_announcement_photo_change() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        fake_group_photo_table(&mut config);
+        let exporter = HTML::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.rowid = 1;
+        message.num_attachments = 1;
+        message.group_action_type = 1;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> You changed the group photo.</p><img class=\"group_photo\" src=\"a/b/c/photo.jpeg\"></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_announcement_photo_change_missing() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        fake_group_photo_table(&mut config);
+        let exporter = HTML::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.rowid = 2;
+        message.num_attachments = 1;
+        message.group_action_type = 1;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> You changed the group photo.</p></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_reaction_me() {
         // Set timezone to PST for consistent Local time
@@ -1807,6 +3208,27 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_reaction_template() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+        exporter.templates = fake_templates(&[(
+            "reaction.html",
+            "<span class=\"{kind}\">{sender}: {text}</span>",
+        )]);
+
+        let mut message = blank();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+
+        let actual = exporter.format_reaction(&message).unwrap();
+        let expected = "<span class=\"Loved\">Me: Loved by Me</span>";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_reaction_them() {
         // Set timezone to PST for consistent Local time
@@ -1937,7 +3359,7 @@ mod tests {
 mod balloon_format_tests {
     use std::env::set_var;
 
-    use super::tests::{blank, fake_options};
+    use super::tests::{blank, fake_options, fake_templates};
     use crate::{exporters::exporter::BalloonFormatter, Config, Exporter, HTML};
     use imessage_database::message_types::{
         app::AppMessage,
@@ -1973,6 +3395,33 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_url_template() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+        exporter.templates =
+            fake_templates(&[("url.html", "<a href=\"{url}\">{title} — {site_name}</a>")]);
+
+        let balloon = URLMessage {
+            title: Some("<title>"),
+            summary: None,
+            url: None,
+            original_url: Some("original_url"),
+            item_type: None,
+            images: vec![],
+            icons: vec![],
+            site_name: None,
+            placeholder: false,
+        };
+
+        let expected = exporter.format_url(&balloon, &blank());
+        let actual = "<a href=\"original_url\">&lt;title&gt; — original_url</a>";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_html_url_no_lazy() {
         // Create exporter
@@ -2068,6 +3517,42 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_app_template() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let mut exporter = HTML::new(&config);
+        exporter.templates = fake_templates(&[(
+            "app.html",
+            "<figure><img src=\"{image}\"><figcaption>{app_name} ({bundle_id}): {caption}</figcaption></figure>",
+        )]);
+
+        let balloon = AppMessage {
+            image: Some("image"),
+            url: Some("url"),
+            title: Some("title"),
+            subtitle: Some("subtitle"),
+            caption: Some("caption"),
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: None,
+            ldtext: None,
+        };
+
+        let expected = exporter.format_generic_app(&balloon, "bundle_id", &mut vec![], &blank());
+        let actual = "<figure><img src=\"image\"><figcaption>bundle_id (bundle_id): caption</figcaption></figure>";
+
+        assert_eq!(expected, actual);
+
+        // Other balloon types still use the built-in markup
+        let expected = exporter.format_apple_pay(&balloon, &blank());
+        let actual = "<div class=\"app_header\"></div><div class=\"app_footer\"></div>";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_html_fitness() {
         // Create exporter
@@ -0,0 +1,283 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::write,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+
+use crate::app::{
+    error::RuntimeError,
+    geo::{to_gpx, to_kml, SharedLocation},
+    progress::build_progress_bar_export,
+    runtime::Config,
+};
+use crate::exporters::exporter::Exporter;
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        app::AppMessage,
+        url::URLMessage,
+        variants::{BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::{coordinates::Coordinates, plist::parse_plist},
+};
+
+/// Prefixes of links that point to a place on a map, after the scheme
+const MAP_LINKS: [&str; 6] = [
+    "maps.apple.com",
+    "maps.google.",
+    "www.google.com/maps",
+    "google.com/maps",
+    "www.openstreetmap.org",
+    "openstreetmap.org",
+];
+
+/// Determine if a link points to a place on a map, so other links with coordinate-like query strings are ignored
+fn is_map_link(url: &str) -> bool {
+    url.starts_with("geo:")
+        || url
+            .split_once("://")
+            .is_some_and(|(_, rest)| MAP_LINKS.iter().any(|prefix| rest.starts_with(prefix)))
+}
+
+/// The locations shared in a single conversation
+pub struct Map {
+    /// The name of the conversation
+    pub title: String,
+    /// Every location shared in the conversation, in the order they were sent
+    pub locations: Vec<SharedLocation>,
+}
+
+pub struct LOCATIONS<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of internal unique chatroom ID to a filename, without an extension
+    pub files: HashMap<i32, PathBuf>,
+    /// Path to files for orphaned messages, without an extension
+    pub orphaned: PathBuf,
+    /// Map of filenames to the locations that will be written to them
+    pub maps: BTreeMap<PathBuf, Map>,
+}
+
+impl<'a> Exporter<'a> for LOCATIONS<'a> {
+    fn new(config: &'a Config) -> Self {
+        LOCATIONS {
+            config,
+            files: HashMap::new(),
+            orphaned: config.options.export_path.join(ORPHANED),
+            maps: BTreeMap::new(),
+        }
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as gpx and kml...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Only app messages can contain locations, so skip everything else without reading its payload
+            if let Some(location) = self.format_location(&msg) {
+                let title = match self.config.conversation(&msg) {
+                    Some((chatroom, _)) => self.config.conversation_title(chatroom),
+                    None => self.config.strings().orphaned_messages.to_string(),
+                };
+                let path = self.get_or_create_file(&msg).to_path_buf();
+                self.maps
+                    .entry(path)
+                    .or_insert_with(|| Map {
+                        title,
+                        locations: vec![],
+                    })
+                    .locations
+                    .push(location);
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        self.write_maps()
+    }
+
+    /// Create a filename for the given chat, caching it so we don't need to build it later
+    ///
+    /// Each conversation is written to a `.gpx` and a `.kml` file with this name.
+    fn get_or_create_file(&mut self, message: &Message) -> &Path {
+        match self.config.conversation(message) {
+            Some((chatroom, id)) => self.files.entry(*id).or_insert_with(|| {
+                let mut path = self.config.options.export_path.clone();
+                path.push(self.config.filename(chatroom));
+                path
+            }),
+            None => &self.orphaned,
+        }
+    }
+}
+
+impl<'a> LOCATIONS<'a> {
+    /// Write a GPX and a KML file for each conversation that shared a location
+    fn write_maps(&self) -> Result<(), RuntimeError> {
+        if self.maps.is_empty() {
+            eprintln!("No shared locations found!");
+        }
+
+        for (path, map) in &self.maps {
+            let mut gpx = path.clone();
+            gpx.set_extension("gpx");
+            write(&gpx, to_gpx(&map.title, &map.locations)).map_err(RuntimeError::DiskError)?;
+
+            let mut kml = path.clone();
+            kml.set_extension("kml");
+            write(&kml, to_kml(&map.title, &map.locations)).map_err(RuntimeError::DiskError)?;
+        }
+        Ok(())
+    }
+
+    /// Build a location from a shared placemark, map link, Check In, or Find My message
+    ///
+    /// Messages that are not one of these, or that do not include coordinates, are skipped.
+    fn format_location(&self, message: &Message) -> Option<SharedLocation> {
+        let Variant::App(balloon) = message.variant() else {
+            return None;
+        };
+        if matches!(balloon, CustomBalloon::Handwriting) {
+            return None;
+        }
+
+        let payload = message.payload_data(&self.config.db)?;
+        let parsed = parse_plist(&payload).ok()?;
+
+        let (coordinates, name, address, url) = if message.is_url() {
+            match URLMessage::get_url_message_override(&parsed).ok()? {
+                URLOverride::SharedPlacemark(placemark) => (
+                    placemark.coordinates()?,
+                    placemark.placemark.name.or(placemark.place_name),
+                    placemark.placemark.address.or(placemark.place_name),
+                    placemark.url.or(placemark.original_url),
+                ),
+                URLOverride::Normal(link) => {
+                    let url = link
+                        .url
+                        .or(link.original_url)
+                        .filter(|url| is_map_link(url))?;
+                    (Coordinates::from_url(url)?, link.title, None, Some(url))
+                }
+                _ => return None,
+            }
+        } else {
+            match balloon {
+                CustomBalloon::CheckIn | CustomBalloon::FindMy => {
+                    let app = AppMessage::from_map(&parsed).ok()?;
+                    (
+                        Coordinates::from_url(app.url?)?,
+                        app.caption.or(app.app_name),
+                        app.subcaption,
+                        None,
+                    )
+                }
+                _ => return None,
+            }
+        };
+
+        let date = message.date(&self.config.offset);
+        Some(SharedLocation {
+            coordinates,
+            name: name.map(String::from),
+            address: address.map(String::from),
+            sender: self
+                .config
+                .who(message.handle_id, message.is_from_me)
+                .to_string(),
+            date: date.as_ref().ok().map(|date| date.with_timezone(&Utc)),
+            readable_date: self.config.format_date(&date, message.chat_id),
+            url: url.map(String::from),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        exporters::{
+            html::tests::{blank, fake_options},
+            locations::is_map_link,
+        },
+        Config, Exporter, LOCATIONS,
+    };
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = LOCATIONS::new(&config);
+        assert_eq!(exporter.files.len(), 0);
+        assert_eq!(exporter.orphaned, PathBuf::from("orphaned"));
+    }
+
+    #[test]
+    fn can_detect_map_links() {
+        assert!(is_map_link(
+            "https://maps.apple.com/?ll=33.450858,-118.508212"
+        ));
+        assert!(is_map_link(
+            "https://www.google.com/maps/@33.3428,-118.3282,14z"
+        ));
+        assert!(is_map_link("geo:33.450858,-118.508212"));
+        assert!(!is_map_link(
+            "https://example.com/?ll=33.450858,-118.508212"
+        ));
+    }
+
+    #[test]
+    fn cant_format_location_text() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = LOCATIONS::new(&config);
+
+        let mut message = blank();
+        message.text = Some("https://maps.apple.com/?ll=33.450858,-118.508212".to_string());
+
+        assert!(exporter.format_location(&message).is_none());
+    }
+}
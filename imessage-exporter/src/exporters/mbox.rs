@@ -0,0 +1,445 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        locale::fill,
+        mime::{Mailbox, MimeMessage, MimePart},
+        progress::build_progress_bar_export,
+        runtime::Config,
+        sanitizers::sanitize_filename,
+    },
+    exporters::exporter::{Exporter, Writer},
+    TXT,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        attachment::Attachment,
+        messages::{BubbleType, Message},
+        table::{Table, FITNESS_RECEIVER, ME, ORPHANED, UNKNOWN},
+    },
+};
+
+pub struct MBOX<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Formats the text of app balloons, edits, reactions, and announcements
+    pub txt: TXT<'a>,
+    /// Handles to files we want to write messages to
+    /// Map of internal unique chatroom ID to a filename, or a directory when writing `.eml` files
+    pub files: HashMap<i32, PathBuf>,
+    /// Path to file for orphaned messages
+    pub orphaned: PathBuf,
+}
+
+impl<'a> Exporter<'a> for MBOX<'a> {
+    fn new(config: &'a Config) -> Self {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        if !config.options.eml {
+            orphaned.set_extension("mbox");
+        }
+        MBOX {
+            config,
+            txt: TXT::new(config),
+            files: HashMap::new(),
+            orphaned,
+        }
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as {}...",
+            self.config.options.export_path.display(),
+            if self.config.options.eml {
+                "eml"
+            } else {
+                "mbox"
+            }
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Reactions are listed in the body of the message they react to, so no need to render them separately
+            // Replies are written in place and linked to the start of their thread with headers
+            if !msg.is_reaction() {
+                let _ = msg.gen_text(&self.config.db);
+                let email = self
+                    .format_email(&msg)
+                    .map_err(RuntimeError::DatabaseError)?;
+                self.write_email(&msg, &email);
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    ///
+    /// When writing `.eml` files, this is the directory that holds the chat's messages instead.
+    fn get_or_create_file(&mut self, message: &Message) -> &Path {
+        match self.config.conversation(message) {
+            Some((chatroom, id)) => self.files.entry(*id).or_insert_with(|| {
+                let mut path = self.config.options.export_path.clone();
+                path.push(self.config.filename(chatroom));
+                if !self.config.options.eml {
+                    path.set_extension("mbox");
+                }
+                path
+            }),
+            None => &self.orphaned,
+        }
+    }
+}
+
+impl<'a> MBOX<'a> {
+    /// Add an email to the end of its conversation's `mbox` file, or write it to its own `.eml` file
+    fn write_email(&mut self, message: &Message, email: &MimeMessage) {
+        let path = self.get_or_create_file(message).to_path_buf();
+        if self.config.options.eml {
+            if let Err(why) = create_dir_all(&path) {
+                eprintln!("Unable to create {path:?}: {why}");
+                return;
+            }
+            let file = path.join(format!("{}.eml", sanitize_filename(&message.guid)));
+            if let Err(why) = write(&file, email.to_eml()) {
+                eprintln!("Unable to write to {file:?}: {why:?}");
+            }
+        } else {
+            TXT::write_to_file(&path, &email.to_mbox());
+        }
+    }
+
+    /// Build an email from a message, attaching its files
+    fn format_email(&self, message: &Message) -> Result<MimeMessage, TableError> {
+        let chatroom = self
+            .config
+            .conversation(message)
+            .map(|(chatroom, _)| chatroom);
+
+        let from = self.mailbox(message.handle_id, message.is_from_me);
+
+        // Everyone in the conversation received the message except the sender
+        let mut to = vec![];
+        if !message.is_from_me {
+            to.push(self.mailbox(None, true));
+        }
+        if let Some(participants) =
+            chatroom.and_then(|chatroom| self.config.chatroom_participants.get(&chatroom.rowid))
+        {
+            participants
+                .iter()
+                .map(|participant| self.mailbox(Some(*participant), false))
+                .filter(|mailbox| mailbox.address != from.address)
+                .for_each(|mailbox| to.push(mailbox));
+        }
+
+        let subject = match (&message.subject, chatroom) {
+            (Some(subject), _) => subject.to_string(),
+            (None, Some(chatroom)) => self.config.conversation_title(chatroom),
+            (None, None) => self.config.strings().orphaned_messages.to_string(),
+        };
+
+        let (body, attachments) = if message.is_announcement() {
+            (self.txt.format_announcement(message), vec![])
+        } else {
+            self.format_body(message)?
+        };
+
+        Ok(MimeMessage {
+            from,
+            to,
+            date: message
+                .date(&self.config.offset)
+                .ok()
+                .map(|date| self.config.timezone(message.chat_id).convert(&date)),
+            subject,
+            guid: message.guid.clone(),
+            in_reply_to: message.thread_originator_guid.clone(),
+            body,
+            attachments,
+        })
+    }
+
+    /// Build the text of a message and read the files attached to it
+    pub(super) fn format_body(
+        &self,
+        message: &Message,
+    ) -> Result<(String, Vec<MimePart>), TableError> {
+        let strings = self.config.strings();
+        let mut lines: Vec<String> = vec![];
+        let mut parts = vec![];
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            lines.push(strings.deleted_from_conversation.to_string());
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // If message was removed, display it
+        if message_parts.is_empty() && message.is_edited() {
+            lines.push(self.format_edited(message));
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            lines.push(self.txt.format_shareplay().to_string());
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            // Render edited messages
+            if message.is_edited() {
+                lines.push(self.format_edited(message));
+                continue;
+            }
+            match message_part {
+                // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
+                BubbleType::Text(text) => {
+                    lines.push(text.replace(FITNESS_RECEIVER, strings.you));
+                }
+                BubbleType::Attachment => match attachments.get(attachment_index) {
+                    Some(attachment) => {
+                        if !attachment.is_sticker {
+                            attachment_index += 1;
+                        }
+                        match self.format_attachment(attachment) {
+                            Some(part) => parts.push(part),
+                            None => lines.push(format!(
+                                "{} {}",
+                                strings.attachment_does_not_exist,
+                                attachment.filename()
+                            )),
+                        }
+                    }
+                    // Attachment does not exist in attachments table
+                    None => lines.push(strings.attachment_missing.to_string()),
+                },
+                BubbleType::App => match self.txt.format_app(message, &mut attachments, "") {
+                    Ok(balloon) => lines.push(balloon.trim_end().to_string()),
+                    Err(why) => lines.push(fill(
+                        strings.app_message_error,
+                        &[("error", &why.to_string())],
+                    )),
+                },
+            };
+
+            // Handle expressives
+            let expressive = self.txt.format_expressive(message);
+            if !expressive.is_empty() {
+                lines.push(expressive.to_string());
+            }
+
+            // Handle Reactions
+            if let Some(reactions) = self
+                .config
+                .reactions
+                .get(&message.guid)
+                .and_then(|reactions_map| reactions_map.get(&idx))
+            {
+                for reaction in reactions {
+                    let formatted = self.txt.format_reaction(reaction)?;
+                    if !formatted.is_empty() {
+                        lines.push(formatted);
+                    }
+                }
+            }
+        }
+
+        Ok((lines.join("\n"), parts))
+    }
+
+    /// Read an attachment from the disk so it can be attached to an email
+    fn format_attachment(&self, attachment: &Attachment) -> Option<MimePart> {
+        let data = match attachment.as_bytes(
+            &self.config.options.platform,
+            &self.config.options.db_path,
+            self.config.options.attachment_root.as_deref(),
+        ) {
+            Ok(data) => data?,
+            Err(why) => {
+                eprintln!("{why}");
+                return None;
+            }
+        };
+        Some(MimePart {
+            content_type: attachment
+                .mime_type
+                .clone()
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+            filename: attachment.filename().to_string(),
+            data,
+        })
+    }
+
+    /// Format the history of an edited message, or the reason it could not be read
+    fn format_edited(&self, message: &Message) -> String {
+        match self.txt.format_edited(message, "") {
+            Ok(edited) => edited.trim_end().to_string(),
+            Err(why) => format!("{}, {}", message.guid, why),
+        }
+    }
+
+    /// Build the mailbox for a participant, or for the database owner
+    fn mailbox(&self, handle_id: Option<i32>, is_from_me: bool) -> Mailbox {
+        let name = self.config.who(handle_id, is_from_me);
+        let handle = match is_from_me {
+            true => ME,
+            false => handle_id
+                .and_then(|id| self.config.participants.get(&id))
+                .map_or(UNKNOWN, String::as_str),
+        };
+        Mailbox::new(name, &handle.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::set_var, path::PathBuf};
+
+    use crate::{
+        exporters::html::tests::{blank, fake_options},
+        Config, Exporter, MBOX,
+    };
+    use imessage_database::tables::attachment::Attachment;
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = MBOX::new(&config);
+        assert_eq!(exporter.files.len(), 0);
+        assert_eq!(exporter.orphaned, PathBuf::from("orphaned.mbox"));
+    }
+
+    #[test]
+    fn can_create_eml() {
+        let mut options = fake_options();
+        options.eml = true;
+        let config = Config::new(options).unwrap();
+        let exporter = MBOX::new(&config);
+        assert_eq!(exporter.orphaned, PathBuf::from("orphaned"));
+    }
+
+    #[test]
+    fn can_format_email_from_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        config.participants.insert(1, "+15555550100".to_string());
+        let exporter = MBOX::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "ABC".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.thread_originator_guid = Some("XYZ".to_string());
+
+        let actual = exporter.format_email(&message).unwrap();
+
+        assert_eq!(actual.from.address, "me@imessage.invalid");
+        assert_eq!(actual.from.name, "Me");
+        assert!(actual.to.is_empty());
+        assert_eq!(actual.subject, "Messages without a conversation");
+        assert_eq!(
+            actual.date.unwrap().to_rfc2822(),
+            "Tue, 17 May 2022 17:29:42 -0700"
+        );
+        assert_eq!(actual.in_reply_to, Some("XYZ".to_string()));
+        assert_eq!(actual.body, "Hello world");
+    }
+
+    #[test]
+    fn can_format_email_from_them() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        config
+            .participants
+            .insert(1, "Jane@Example.com".to_string());
+        let exporter = MBOX::new(&config);
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        message.subject = Some("Plans".to_string());
+        message.handle_id = Some(1);
+
+        let actual = exporter.format_email(&message).unwrap();
+
+        assert_eq!(actual.from.address, "jane@example.com");
+        assert_eq!(actual.to.len(), 1);
+        assert_eq!(actual.to[0].address, "me@imessage.invalid");
+        assert_eq!(actual.subject, "Plans");
+    }
+
+    #[test]
+    fn can_format_missing_attachment() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = MBOX::new(&config);
+
+        let attachment = Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.jpg".to_string()),
+            uti: Some("public.jpeg".to_string()),
+            mime_type: Some("image/jpeg".to_string()),
+            transfer_name: Some("d.jpg".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+        };
+
+        assert!(exporter.format_attachment(&attachment).is_none());
+    }
+}
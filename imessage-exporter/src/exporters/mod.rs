@@ -1,3 +1,9 @@
+pub mod epub;
 pub mod exporter;
 pub mod html;
+pub mod locations;
+pub mod mbox;
+pub mod pdf;
 pub mod txt;
+pub mod vcf;
+pub mod xml;
@@ -0,0 +1,683 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        locale::fill,
+        pdf_document::{Align, Block, Color, Content, Font, PdfDocument, Text, COLUMN_WIDTH},
+        progress::build_progress_bar_export,
+        runtime::Config,
+    },
+    exporters::exporter::{Exporter, Writer},
+    TXT,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Variant,
+    tables::{
+        attachment::{Attachment, MediaType},
+        chat::Chat,
+        messages::{BubbleType, Message},
+        table::{Table, FITNESS_RECEIVER, ORPHANED},
+    },
+};
+
+/// Size of message text, in points
+const TEXT_SIZE: f32 = 11.;
+/// Size of names, timestamps, reactions, and other notes, in points
+const NOTE_SIZE: f32 = 8.;
+/// Distance each level of threaded replies is indented from both margins, in points
+const THREAD_INSET: f32 = 24.;
+/// Widest a bubble can be, relative to the width of the column it is in
+const BUBBLE_WIDTH: f32 = 0.6;
+/// Space between the edges of a bubble and its text, in points
+const BUBBLE_PADDING: f32 = 8.;
+/// Space between the last part of a message and the next message, in points
+const MESSAGE_SPACING: f32 = 12.;
+/// Space needed below a sender's name to start their message on the same page, in points
+const MESSAGE_START: f32 = 60.;
+/// Background of messages sent with iMessage
+const IMESSAGE_BLUE: Color = Color(0.098, 0.510, 0.988);
+/// Background of messages sent with other services, i.e. SMS
+const SMS_GREEN: Color = Color(0.396, 0.769, 0.400);
+/// Background of received messages and app balloons
+const RECEIVED_GRAY: Color = Color(0.847, 0.847, 0.847);
+
+pub struct PDF<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Formats the text of app balloons, edits, and announcements
+    pub txt: TXT<'a>,
+    /// Handles to files we want to write messages to
+    /// Map of internal unique chatroom ID to a filename
+    pub files: HashMap<i32, PathBuf>,
+    /// Documents that have been started, by filename
+    pub documents: HashMap<PathBuf, PdfDocument>,
+    /// Path to file for orphaned messages
+    pub orphaned: PathBuf,
+}
+
+impl<'a> Exporter<'a> for PDF<'a> {
+    fn new(config: &'a Config) -> Self {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("pdf");
+        PDF {
+            config,
+            txt: TXT::new(config),
+            files: HashMap::new(),
+            documents: HashMap::new(),
+            orphaned,
+        }
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as pdf...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                self.write_blocks(&msg, &[announcement]);
+            }
+            // Message replies and reactions are rendered in context, so no need to render them separately
+            else if !msg.is_reaction() {
+                let _ = msg.gen_text(&self.config.db);
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                self.write_blocks(&msg, &message);
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Pages are written as they fill up, so only the last page of each document is left
+        for document in self.documents.values_mut() {
+            document.finish();
+            if document.replaced() > 0 {
+                eprintln!(
+                    "Warning: {} characters in {:?} cannot be drawn with the standard PDF fonts and were replaced with `?`; use the txt or html format to keep them",
+                    document.replaced(),
+                    document.path()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(&mut self, message: &Message) -> &Path {
+        match self.config.conversation(message) {
+            Some((chatroom, id)) => self.files.entry(*id).or_insert_with(|| {
+                let mut path = self.config.options.export_path.clone();
+                path.push(self.config.filename(chatroom));
+                path.set_extension("pdf");
+
+                // If the document already exists, don't write the header again
+                // This can happen if multiple chats use the same group name
+                self.documents
+                    .entry(path.clone())
+                    .or_insert_with(|| PDF::create_document(self.config, chatroom, &path));
+
+                path
+            }),
+            None => &self.orphaned,
+        }
+    }
+}
+
+impl<'a> PDF<'a> {
+    /// Start a document for a conversation, beginning with its details and group photo
+    fn create_document(config: &Config, chatroom: &Chat, path: &Path) -> PdfDocument {
+        let mut document = PdfDocument::new(path.to_path_buf());
+
+        let mut content: Vec<Content> = config
+            .conversation_details(chatroom)
+            .iter()
+            .map(|(label, value)| {
+                Content::Text(Text::new(
+                    &format!("{label}: {value}"),
+                    Font::Regular,
+                    NOTE_SIZE,
+                    Color::BLACK,
+                ))
+            })
+            .collect();
+        if let Some(photo) = config.group_photo(chatroom) {
+            if let Some(file) = config.attachment_file(&photo) {
+                content.push(Content::Image {
+                    path: file,
+                    fallback: Text::new(
+                        &format!(
+                            "{}: {}",
+                            config.strings().group_photo,
+                            config.message_attachment_path(&photo)
+                        ),
+                        Font::Regular,
+                        NOTE_SIZE,
+                        Color::BLACK,
+                    ),
+                });
+            }
+        }
+
+        document.add_block(&Block {
+            background: Some(RECEIVED_GRAY),
+            padding: BUBBLE_PADDING,
+            space_after: MESSAGE_SPACING,
+            ..Block::new(content, Align::Left)
+        });
+        document
+    }
+
+    /// Add blocks to the end of the document for a message's conversation
+    fn write_blocks(&mut self, message: &Message, blocks: &[Block]) {
+        let path = self.get_or_create_file(message).to_path_buf();
+        let document = self
+            .documents
+            .entry(path.clone())
+            .or_insert_with(|| PdfDocument::new(path));
+
+        // Keep the sender's name on the same page as the start of their message
+        document.ensure_space(MESSAGE_START);
+        blocks.iter().for_each(|block| document.add_block(block));
+    }
+
+    /// Lay out a message, including its reactions and replies
+    ///
+    /// `depth` is the number of threads the message is nested in.
+    fn format_message(&self, message: &Message, depth: usize) -> Result<Vec<Block>, TableError> {
+        let strings = self.config.strings();
+        let mut blocks = vec![];
+
+        // Add message sender and date
+        blocks.push(PDF::note(
+            message,
+            self.config.who(message.handle_id, message.is_from_me),
+            Font::Bold,
+            depth,
+        ));
+        blocks.push(PDF::note(
+            message,
+            &self.txt.get_time(message),
+            Font::Regular,
+            depth,
+        ));
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            blocks.push(PDF::note(
+                message,
+                strings.deleted_from_conversation,
+                Font::Regular,
+                depth,
+            ));
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            blocks.push(PDF::bubble(message, subject, Font::Bold, depth));
+        }
+
+        // If message was removed, display it
+        if message_parts.is_empty() && message.is_edited() {
+            blocks.push(PDF::bubble(
+                message,
+                &self.format_edited(message),
+                Font::Regular,
+                depth,
+            ));
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            blocks.push(PDF::bubble(
+                message,
+                self.txt.format_shareplay(),
+                Font::Regular,
+                depth,
+            ));
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            // Render edited messages
+            if message.is_edited() {
+                blocks.push(PDF::bubble(
+                    message,
+                    &self.format_edited(message),
+                    Font::Regular,
+                    depth,
+                ));
+                continue;
+            }
+            match message_part {
+                // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
+                BubbleType::Text(text) => {
+                    let text = text.replace(FITNESS_RECEIVER, strings.you);
+                    blocks.push(PDF::bubble(message, &text, Font::Regular, depth));
+                }
+                BubbleType::Attachment => match attachments.get_mut(attachment_index) {
+                    Some(attachment) => {
+                        if attachment.is_sticker {
+                            blocks.push(self.format_sticker(attachment, message, depth));
+                        } else {
+                            attachment_index += 1;
+                            blocks.push(self.format_attachment(attachment, message, depth));
+                        }
+                    }
+                    // Attachment does not exist in attachments table
+                    None => blocks.push(PDF::note(
+                        message,
+                        strings.attachment_missing,
+                        Font::Regular,
+                        depth,
+                    )),
+                },
+                BubbleType::App => match self.txt.format_app(message, &mut attachments, "") {
+                    Ok(balloon) => blocks.push(PDF::balloon(message, &balloon, depth)),
+                    Err(why) => blocks.push(PDF::note(
+                        message,
+                        &fill(strings.app_message_error, &[("error", &why.to_string())]),
+                        Font::Regular,
+                        depth,
+                    )),
+                },
+            };
+
+            // Handle expressives
+            let expressive = self.txt.format_expressive(message);
+            if !expressive.is_empty() {
+                blocks.push(PDF::note(message, expressive, Font::Regular, depth));
+            }
+
+            // Handle Reactions
+            if let Some(reactions) = self
+                .config
+                .reactions
+                .get(&message.guid)
+                .and_then(|reactions_map| reactions_map.get(&idx))
+            {
+                for reaction in reactions {
+                    if let Variant::Sticker(_) = reaction.variant() {
+                        let mut stickers = Attachment::from_message(&self.config.db, reaction)?;
+                        // Sticker messages have only one attachment, the sticker image
+                        if let Some(sticker) = stickers.get_mut(0) {
+                            blocks.push(self.format_sticker(sticker, reaction, depth));
+                            continue;
+                        }
+                    }
+                    let formatted = self.txt.format_reaction(reaction)?;
+                    if !formatted.is_empty() {
+                        blocks.push(PDF::note(message, &formatted, Font::Regular, depth));
+                    }
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                for reply in replies {
+                    let _ = reply.gen_text(&self.config.db);
+                    if !reply.is_reaction() {
+                        blocks.extend(self.format_message(reply, depth + 1)?);
+                    }
+                }
+            }
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && depth == 0 {
+            blocks.push(PDF::note(
+                message,
+                strings.responded_to_earlier,
+                Font::Regular,
+                depth,
+            ));
+        }
+
+        if let Some(last) = blocks.last_mut() {
+            last.space_after += MESSAGE_SPACING;
+        }
+
+        Ok(blocks)
+    }
+
+    /// Lay out an attachment, drawing it inline if it is an image
+    fn format_attachment(
+        &self,
+        attachment: &mut Attachment,
+        message: &Message,
+        depth: usize,
+    ) -> Block {
+        // Copy the file, if requested, and build a relative filepath to show if it cannot be drawn
+        let label = match self.txt.format_attachment(attachment, message) {
+            Ok(path) => path,
+            Err(filename) => filename.to_string(),
+        };
+        match (
+            attachment.mime_type(),
+            self.config.attachment_file(attachment),
+        ) {
+            (MediaType::Image(_), Some(path)) => PDF::image(message, path, &label, depth),
+            _ => PDF::bubble(message, &label, Font::Regular, depth),
+        }
+    }
+
+    /// Lay out a sticker, drawing it inline if it is an image
+    fn format_sticker(&self, sticker: &mut Attachment, message: &Message, depth: usize) -> Block {
+        let label = self.txt.format_sticker(sticker, message);
+        match self.config.attachment_file(sticker) {
+            Some(path) => PDF::image(message, path, &label, depth),
+            None => PDF::note(message, &label, Font::Regular, depth),
+        }
+    }
+
+    /// Lay out an announcement, i.e. a group name change
+    fn format_announcement(&self, message: &Message) -> Block {
+        let announcement = self.txt.format_announcement(message);
+        Block {
+            space_after: MESSAGE_SPACING,
+            ..Block::new(
+                vec![Content::Text(Text::new(
+                    announcement.trim_end(),
+                    Font::Regular,
+                    NOTE_SIZE,
+                    Color::GRAY,
+                ))],
+                Align::Center,
+            )
+        }
+    }
+
+    /// Format the history of an edited message, or the reason it could not be read
+    fn format_edited(&self, message: &Message) -> String {
+        match self.txt.format_edited(message, "") {
+            Ok(edited) => edited.trim_end().to_string(),
+            Err(why) => format!("{}, {}", message.guid, why),
+        }
+    }
+
+    /// Messages sent by the database owner are placed on the right, like in Messages
+    fn align(message: &Message) -> Align {
+        match message.is_from_me {
+            true => Align::Right,
+            false => Align::Left,
+        }
+    }
+
+    /// Widest a bubble in a thread at `depth` can be
+    fn bubble_width(depth: usize) -> f32 {
+        (COLUMN_WIDTH - 2. * THREAD_INSET * depth as f32) * BUBBLE_WIDTH
+    }
+
+    /// Lay out a single part of a message in a colored bubble
+    fn bubble(message: &Message, text: &str, font: Font, depth: usize) -> Block {
+        let (background, color) = match (message.is_from_me, message.service.as_deref()) {
+            (true, Some("iMessage")) => (IMESSAGE_BLUE, Color::WHITE),
+            (true, _) => (SMS_GREEN, Color::WHITE),
+            (false, _) => (RECEIVED_GRAY, Color::BLACK),
+        };
+        Block {
+            inset: THREAD_INSET * depth as f32,
+            max_width: PDF::bubble_width(depth),
+            background: Some(background),
+            padding: BUBBLE_PADDING,
+            space_after: 2.,
+            ..Block::new(
+                vec![Content::Text(Text::new(text, font, TEXT_SIZE, color))],
+                PDF::align(message),
+            )
+        }
+    }
+
+    /// Lay out an app balloon, which is gray regardless of who sent it
+    fn balloon(message: &Message, text: &str, depth: usize) -> Block {
+        Block {
+            content: vec![Content::Text(Text::new(
+                text,
+                Font::Regular,
+                TEXT_SIZE,
+                Color::BLACK,
+            ))],
+            background: Some(RECEIVED_GRAY),
+            ..PDF::bubble(message, text, Font::Regular, depth)
+        }
+    }
+
+    /// Lay out an image, with a label to show instead if it cannot be drawn
+    fn image(message: &Message, path: PathBuf, label: &str, depth: usize) -> Block {
+        Block {
+            inset: THREAD_INSET * depth as f32,
+            max_width: PDF::bubble_width(depth),
+            space_after: 2.,
+            ..Block::new(
+                vec![Content::Image {
+                    path,
+                    fallback: Text::new(label, Font::Regular, NOTE_SIZE, Color::GRAY),
+                }],
+                PDF::align(message),
+            )
+        }
+    }
+
+    /// Lay out a small line of text next to a message, i.e. a timestamp
+    fn note(message: &Message, text: &str, font: Font, depth: usize) -> Block {
+        Block {
+            inset: THREAD_INSET * depth as f32,
+            max_width: PDF::bubble_width(depth),
+            space_after: 2.,
+            ..Block::new(
+                vec![Content::Text(Text::new(text, font, NOTE_SIZE, Color::GRAY))],
+                PDF::align(message),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::set_var, path::PathBuf};
+
+    use crate::{
+        app::pdf_document::{Align, Block, Content},
+        exporters::{
+            html::tests::{blank, fake_options},
+            pdf::{IMESSAGE_BLUE, RECEIVED_GRAY, SMS_GREEN, THREAD_INSET},
+        },
+        Config, Exporter, PDF,
+    };
+    use imessage_database::tables::attachment::Attachment;
+
+    /// Get the text of a block's first piece of content
+    fn text(block: &Block) -> &str {
+        match &block.content[0] {
+            Content::Text(text) => &text.text,
+            Content::Image { fallback, .. } => &fallback.text,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = PDF::new(&config);
+        assert_eq!(exporter.files.len(), 0);
+        assert_eq!(exporter.documents.len(), 0);
+    }
+
+    #[test]
+    fn can_format_pdf_from_me_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = PDF::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+
+        assert_eq!(actual.len(), 3);
+        assert_eq!(text(&actual[0]), "Me");
+        assert_eq!(text(&actual[1]), "May 17, 2022  5:29:42 PM");
+        assert_eq!(text(&actual[2]), "Hello world");
+        assert_eq!(actual[2].background, Some(IMESSAGE_BLUE));
+        assert!(actual.iter().all(|block| block.align == Align::Right));
+    }
+
+    #[test]
+    fn can_format_pdf_from_them_sms() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = PDF::new(&config);
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        message.service = Some("SMS".to_string());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+
+        assert_eq!(actual[2].background, Some(RECEIVED_GRAY));
+        assert!(actual.iter().all(|block| block.align == Align::Left));
+
+        message.is_from_me = true;
+        let actual = exporter.format_message(&message, 0).unwrap();
+
+        assert_eq!(actual[2].background, Some(SMS_GREEN));
+    }
+
+    #[test]
+    fn can_format_pdf_reply_inset() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = PDF::new(&config);
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+
+        let actual = exporter.format_message(&message, 2).unwrap();
+
+        assert!(actual.iter().all(|block| block.inset == 2. * THREAD_INSET));
+    }
+
+    #[test]
+    fn can_format_pdf_announcement() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = PDF::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+
+        let actual = exporter.format_announcement(&message);
+
+        assert_eq!(
+            text(&actual),
+            "May 17, 2022  5:29:42 PM You renamed the conversation to Hello world"
+        );
+        assert_eq!(actual.align, Align::Center);
+    }
+
+    #[test]
+    fn can_format_pdf_attachment() {
+        // Create exporter
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = PDF::new(&config);
+
+        let message = blank();
+        let mut attachment = Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.jpg".to_string()),
+            uti: Some("public.jpeg".to_string()),
+            mime_type: Some("image/jpeg".to_string()),
+            transfer_name: Some("d.jpg".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+        };
+
+        let actual = exporter.format_attachment(&mut attachment, &message, 0);
+
+        assert!(matches!(
+            &actual.content[0],
+            Content::Image { path, .. } if path == &PathBuf::from("a/b/c/d.jpg")
+        ));
+        assert_eq!(text(&actual), "a/b/c/d.jpg");
+
+        // Files that are not images are listed by path
+        attachment.mime_type = Some("video/quicktime".to_string());
+        attachment.filename = Some("a/b/c/d.mov".to_string());
+        let actual = exporter.format_attachment(&mut attachment, &message, 0);
+
+        assert!(matches!(&actual.content[0], Content::Text(_)));
+        assert_eq!(text(&actual), "a/b/c/d.mov");
+    }
+}
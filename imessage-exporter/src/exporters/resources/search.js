@@ -0,0 +1,65 @@
+// Search every exported conversation without leaving the index page
+// Each file in the search directory calls `imessageSearch` with its messages as `[rowid, date, sender, text]`
+const conversations = [];
+const MAX_RESULTS = 200;
+
+function imessageSearch(file, title, messages) {
+	conversations.push({
+		file,
+		title,
+		messages: messages.map(([rowid, date, sender, text]) => ({ rowid, date, sender, text, search: normalize(text) })),
+	});
+}
+
+// Ignore case and accents, so `cafe` matches `Café`
+function normalize(text) {
+	return text.normalize("NFD").replace(/[\u0300-\u036f]/g, "").toLowerCase();
+}
+
+function span(className, text) {
+	const element = document.createElement("span");
+	element.className = className;
+	element.textContent = text;
+	return element;
+}
+
+window.addEventListener("DOMContentLoaded", () => {
+	const input = document.getElementById("search");
+	const results = document.getElementById("search_results");
+	const listing = document.getElementById("index");
+
+	input.addEventListener("input", () => {
+		const terms = normalize(input.value).split(/\s+/).filter((term) => term.length > 0);
+		results.replaceChildren();
+		results.hidden = terms.length === 0;
+		listing.hidden = terms.length > 0;
+		if (terms.length === 0) {
+			return;
+		}
+
+		let found = 0;
+		for (const conversation of conversations) {
+			for (const message of conversation.messages) {
+				if (found === MAX_RESULTS) {
+					break;
+				}
+				if (terms.every((term) => message.search.includes(term))) {
+					const link = document.createElement("a");
+					link.className = "index_entry";
+					link.href = `${conversation.file}#m-${message.rowid}`;
+					link.append(
+						span("index_title", conversation.title),
+						span("index_date", message.date),
+						span("index_participants", message.sender),
+						span("index_preview", message.text),
+					);
+					results.appendChild(link);
+					found += 1;
+				}
+			}
+		}
+		if (found === 0) {
+			results.appendChild(span("index_participants", results.dataset.empty));
+		}
+	});
+});
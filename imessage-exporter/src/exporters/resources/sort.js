@@ -0,0 +1,13 @@
+// Reorder the conversations on the index page when a new sort order is selected
+const index = document.getElementById("index");
+const compare = {
+	recent: (a, b) => Number(b.dataset.date) - Number(a.dataset.date),
+	oldest: (a, b) => Number(a.dataset.date) - Number(b.dataset.date),
+	name: (a, b) => a.dataset.name.localeCompare(b.dataset.name),
+	messages: (a, b) => Number(b.dataset.messages) - Number(a.dataset.messages),
+};
+document.getElementById("index_sort").addEventListener("change", (event) => {
+	const entries = Array.from(index.children);
+	entries.sort(compare[event.target.value]);
+	entries.forEach((entry) => index.appendChild(entry));
+});
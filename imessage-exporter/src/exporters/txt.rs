@@ -6,7 +6,9 @@ use std::{
 };
 
 use crate::{
-    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    app::{
+        error::RuntimeError, locale::fill, progress::build_progress_bar_export, runtime::Config,
+    },
     exporters::exporter::{BalloonFormatter, Exporter, Writer},
 };
 
@@ -17,7 +19,7 @@ use imessage_database::{
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
         edited::EditedMessage,
-        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        expressives::Expressive,
         handwriting::HandwrittenMessage,
         music::MusicMessage,
         placemark::PlacemarkMessage,
@@ -26,11 +28,12 @@ use imessage_database::{
     },
     tables::{
         attachment::Attachment,
+        chat::Chat,
         messages::{BubbleType, Message},
-        table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
+        table::{Table, FITNESS_RECEIVER, ORPHANED},
     },
     util::{
-        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        dates::{get_local_time, readable_diff_with, TIMESTAMP_FACTOR},
         plist::parse_plist,
     },
 };
@@ -122,6 +125,13 @@ impl<'a> Exporter<'a> for TXT<'a> {
                 let mut path = self.config.options.export_path.clone();
                 path.push(self.config.filename(chatroom));
                 path.set_extension("txt");
+
+                // If the file already exists, don't write the header again
+                // This can happen if multiple chats use the same group name
+                if !path.exists() {
+                    TXT::write_conversation_header(self.config, chatroom, &path);
+                }
+
                 path
             }),
             None => &self.orphaned,
@@ -149,7 +159,7 @@ impl<'a> Writer<'a> for TXT<'a> {
         if message.is_deleted() {
             self.add_line(
                 &mut formatted_message,
-                "This message was deleted from the conversation!",
+                self.config.strings().deleted_from_conversation,
                 &indent,
             );
         }
@@ -198,7 +208,7 @@ impl<'a> Writer<'a> for TXT<'a> {
                     if text.starts_with(FITNESS_RECEIVER) {
                         self.add_line(
                             &mut formatted_message,
-                            &text.replace(FITNESS_RECEIVER, YOU),
+                            &text.replace(FITNESS_RECEIVER, self.config.strings().you),
                             &indent,
                         );
                     } else {
@@ -223,14 +233,21 @@ impl<'a> Writer<'a> for TXT<'a> {
                         }
                     }
                     // Attachment does not exist in attachments table
-                    None => self.add_line(&mut formatted_message, "Attachment missing!", &indent),
+                    None => self.add_line(
+                        &mut formatted_message,
+                        self.config.strings().attachment_missing,
+                        &indent,
+                    ),
                 },
                 BubbleType::App => match self.format_app(message, &mut attachments, &indent) {
                     // We use an empty indent here because `format_app` handles building the entire message
                     Ok(ok_bubble) => self.add_line(&mut formatted_message, &ok_bubble, ""),
                     Err(why) => self.add_line(
                         &mut formatted_message,
-                        &format!("Unable to format app message: {why}"),
+                        &fill(
+                            self.config.strings().app_message_error,
+                            &[("error", &why.to_string())],
+                        ),
                         &indent,
                     ),
                 },
@@ -264,7 +281,11 @@ impl<'a> Writer<'a> for TXT<'a> {
                         })?;
 
                     if !formatted_reactions.is_empty() {
-                        self.add_line(&mut formatted_message, "Reactions:", &indent);
+                        self.add_line(
+                            &mut formatted_message,
+                            self.config.strings().reactions,
+                            &indent,
+                        );
                         self.add_line(&mut formatted_message, &formatted_reactions, &indent);
                     }
                 }
@@ -292,7 +313,7 @@ impl<'a> Writer<'a> for TXT<'a> {
         if message.is_reply() && indent.is_empty() {
             self.add_line(
                 &mut formatted_message,
-                "This message responded to an earlier message.",
+                self.config.strings().responded_to_earlier,
                 &indent,
             );
         }
@@ -322,6 +343,7 @@ impl<'a> Writer<'a> for TXT<'a> {
     }
 
     fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        let strings = self.config.strings();
         let who = self.config.who(message.handle_id, message.is_from_me);
         match self.format_attachment(sticker, message) {
             Ok(path_to_sticker) => {
@@ -331,11 +353,21 @@ impl<'a> Writer<'a> for TXT<'a> {
                     self.config.options.attachment_root.as_deref(),
                 );
                 if let Ok(Some(sticker_effect)) = sticker_effect {
-                    return format!("{sticker_effect} Sticker from {who}: {path_to_sticker}");
+                    return fill(
+                        strings.sticker_with_effect_from,
+                        &[
+                            ("effect", &sticker_effect.to_string()),
+                            ("who", who),
+                            ("path", &path_to_sticker),
+                        ],
+                    );
                 }
-                format!("Sticker from {who}: {path_to_sticker}")
+                fill(
+                    strings.sticker_from,
+                    &[("who", who), ("path", &path_to_sticker)],
+                )
             }
-            Err(path) => format!("Sticker from {who}: {path}"),
+            Err(path) => fill(strings.sticker_from, &[("who", who), ("path", path)]),
         }
     }
 
@@ -411,10 +443,13 @@ impl<'a> Writer<'a> for TXT<'a> {
                 if !added {
                     return Ok(String::new());
                 }
-                Ok(format!(
-                    "{:?} by {}",
-                    reaction,
-                    self.config.who(msg.handle_id, msg.is_from_me),
+                let strings = self.config.strings();
+                Ok(fill(
+                    strings.reaction_by,
+                    &[
+                        ("reaction", strings.reaction(&reaction)),
+                        ("who", self.config.who(msg.handle_id, msg.is_from_me)),
+                    ],
                 ))
             }
             Variant::Sticker(_) => {
@@ -424,7 +459,7 @@ impl<'a> Writer<'a> for TXT<'a> {
                 Ok(if let Some(sticker) = paths.get_mut(0) {
                     self.format_sticker(sticker, msg)
                 } else {
-                    format!("Sticker from {who} not found!")
+                    fill(self.config.strings().sticker_not_found, &[("who", who)])
                 })
             }
             _ => unreachable!(),
@@ -433,55 +468,57 @@ impl<'a> Writer<'a> for TXT<'a> {
 
     fn format_expressive(&self, msg: &'a Message) -> &'a str {
         match msg.get_expressive() {
-            Expressive::Screen(effect) => match effect {
-                ScreenEffect::Confetti => "Sent with Confetti",
-                ScreenEffect::Echo => "Sent with Echo",
-                ScreenEffect::Fireworks => "Sent with Fireworks",
-                ScreenEffect::Balloons => "Sent with Balloons",
-                ScreenEffect::Heart => "Sent with Heart",
-                ScreenEffect::Lasers => "Sent with Lasers",
-                ScreenEffect::ShootingStar => "Sent with Shooting Star",
-                ScreenEffect::Sparkles => "Sent with Sparkles",
-                ScreenEffect::Spotlight => "Sent with Spotlight",
-            },
-            Expressive::Bubble(effect) => match effect {
-                BubbleEffect::Slam => "Sent with Slam",
-                BubbleEffect::Loud => "Sent with Loud",
-                BubbleEffect::Gentle => "Sent with Gentle",
-                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
-            },
+            Expressive::Screen(effect) => self.config.strings().screen_effect(&effect),
+            Expressive::Bubble(effect) => self.config.strings().bubble_effect(&effect),
             Expressive::Unknown(effect) => effect,
             Expressive::None => "",
         }
     }
 
     fn format_announcement(&self, msg: &'a Message) -> String {
+        let strings = self.config.strings();
         let mut who = self.config.who(msg.handle_id, msg.is_from_me);
         // Rename yourself so we render the proper grammar here
-        if who == ME {
-            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        if who == strings.me {
+            who = self
+                .config
+                .options
+                .custom_name
+                .as_deref()
+                .unwrap_or(strings.you);
         }
 
-        let timestamp = format(&msg.date(&self.config.offset));
+        let timestamp = self
+            .config
+            .format_date(&msg.date(&self.config.offset), msg.chat_id);
 
-        return match msg.get_announcement() {
+        match msg.get_announcement() {
             Some(announcement) => match announcement {
                 Announcement::NameChange(name) => {
-                    format!("{timestamp} {who} renamed the conversation to {name}\n\n")
+                    let text = fill(
+                        strings.renamed_conversation,
+                        &[("who", who), ("name", name)],
+                    );
+                    format!("{timestamp} {text}\n\n")
                 }
                 Announcement::PhotoChange => {
-                    format!("{timestamp} {who} changed the group photo.\n\n")
+                    let text = fill(strings.changed_group_photo, &[("who", who)]);
+                    format!("{timestamp} {text}\n\n")
                 }
                 Announcement::Unknown(num) => {
-                    format!("{timestamp} {who} performed unknown action {num}.\n\n")
+                    let text = fill(
+                        strings.unknown_action,
+                        &[("who", who), ("action", &num.to_string())],
+                    );
+                    format!("{timestamp} {text}.\n\n")
                 }
             },
-            None => String::from("Unable to format announcement!\n\n"),
-        };
+            None => format!("{}\n\n", strings.unable_to_format_announcement),
+        }
     }
 
     fn format_shareplay(&self) -> &str {
-        "SharePlay Message\nEnded"
+        self.config.strings().shareplay_ended_lines
     }
 
     fn format_edited(&self, msg: &'a Message, indent: &str) -> Result<String, MessageError> {
@@ -493,21 +530,28 @@ impl<'a> Writer<'a> for TXT<'a> {
             let mut out_s = String::new();
             let mut previous_timestamp: Option<&i64> = None;
 
+            let strings = self.config.strings();
+
             if edited_message.is_deleted() {
                 let who = if msg.is_from_me {
-                    self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                    self.config
+                        .options
+                        .custom_name
+                        .as_deref()
+                        .unwrap_or(strings.you)
                 } else {
-                    "They"
+                    strings.they
                 };
-                out_s.push_str(who);
-                out_s.push_str(" deleted a message.");
+                out_s.push_str(&fill(strings.deleted_a_message, &[("who", who)]));
             } else {
                 for event in &edited_message.events {
                     match previous_timestamp {
                         // Original message get an absolute timestamp
                         None => {
-                            let parsed_timestamp =
-                                format(&get_local_time(&event.date, &self.config.offset));
+                            let parsed_timestamp = self.config.format_date(
+                                &get_local_time(&event.date, &self.config.offset),
+                                msg.chat_id,
+                            );
                             out_s.push_str(&parsed_timestamp);
                             out_s.push(' ');
                         }
@@ -515,11 +559,10 @@ impl<'a> Writer<'a> for TXT<'a> {
                         Some(prev_timestamp) => {
                             let end = get_local_time(&event.date, &self.config.offset);
                             let start = get_local_time(prev_timestamp, &self.config.offset);
-                            if let Some(diff) = readable_diff(start, end) {
+                            if let Some(diff) = readable_diff_with(start, end, &strings.durations) {
                                 out_s.push_str(indent);
-                                out_s.push_str("Edited ");
-                                out_s.push_str(&diff);
-                                out_s.push_str(" later: ");
+                                out_s.push_str(&fill(strings.edited_later, &[("time", &diff)]));
+                                out_s.push_str(": ");
                             }
                         }
                     };
@@ -772,30 +815,39 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time, None);
 
-            out_s.push_str("\nExpected at ");
-            out_s.push_str(&date_string);
+            out_s.push('\n');
+            out_s.push_str(&fill(
+                self.config.strings().expected_at,
+                &[("date", &date_string)],
+            ));
         }
         // Expired check-in
         else if let Some(date_str) = metadata.get("triggerTime") {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time, None);
 
-            out_s.push_str("\nWas expected at ");
-            out_s.push_str(&date_string);
+            out_s.push('\n');
+            out_s.push_str(&fill(
+                self.config.strings().was_expected_at,
+                &[("date", &date_string)],
+            ));
         }
         // Accepted check-in
         else if let Some(date_str) = metadata.get("sendDate") {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time, None);
 
-            out_s.push_str("\nChecked in at ");
-            out_s.push_str(&date_string);
+            out_s.push('\n');
+            out_s.push_str(&fill(
+                self.config.strings().checked_in_at,
+                &[("date", &date_string)],
+            ));
         }
 
         out_s
@@ -850,17 +902,48 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
 }
 
 impl<'a> TXT<'a> {
-    fn get_time(&self, message: &Message) -> String {
-        let mut date = format(&message.date(&self.config.offset));
-        let read_after = message.time_until_read(&self.config.offset);
+    fn write_conversation_header(config: &Config, chatroom: &Chat, path: &Path) {
+        let group_photo = config.group_photo(chatroom);
+        let details = config.conversation_details(chatroom);
+        if details.is_empty() && group_photo.is_none() {
+            return;
+        }
+
+        let mut header = String::new();
+        for (label, value) in details {
+            header.push_str(&format!("{label}: {value}\n"));
+        }
+        if let Some(photo) = group_photo {
+            header.push_str(&format!(
+                "{}: {}\n",
+                config.strings().group_photo,
+                config.message_attachment_path(&photo)
+            ));
+        }
+        header.push('\n');
+
+        TXT::write_to_file(path, &header);
+    }
+
+    pub(super) fn get_time(&self, message: &Message) -> String {
+        let mut date = self
+            .config
+            .format_date(&message.date(&self.config.offset), message.chat_id);
+        let strings = self.config.strings();
+        let read_after = message.time_until_read_with(&self.config.offset, &strings.durations);
         if let Some(time) = read_after {
             if !time.is_empty() {
                 let who = if message.is_from_me {
-                    "them"
+                    strings.them
                 } else {
-                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                    self.config
+                        .options
+                        .custom_name
+                        .as_deref()
+                        .unwrap_or(strings.you_object)
                 };
-                date.push_str(&format!(" (Read by {who} after {time})"));
+                let read_by = fill(strings.read_by, &[("who", who), ("time", &time)]);
+                date.push_str(&format!(" ({read_by})"));
             }
         }
         date
@@ -878,17 +961,24 @@ impl<'a> TXT<'a> {
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::BTreeMap,
         env::{current_dir, set_var},
         path::PathBuf,
     };
 
     use crate::{
-        app::attachment_manager::AttachmentManager, exporters::exporter::Writer, Config, Exporter,
-        Options, TXT,
+        app::{
+            attachment_manager::AttachmentManager, attachment_names::AttachmentNames,
+            locale::Locale, mode::Mode, pagination::Pagination, theme::Theme,
+        },
+        exporters::exporter::Writer,
+        Config, Exporter, Options, TXT,
     };
     use imessage_database::{
         tables::{attachment::Attachment, messages::Message},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+        util::{
+            dates::Timezone, dirs::default_db_path, platform::Platform, query_context::QueryContext,
+        },
     };
 
     fn blank() -> Message {
@@ -926,7 +1016,7 @@ mod tests {
             db_path: default_db_path(),
             attachment_root: None,
             attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
+            mode: Mode::Export,
             export_type: None,
             export_path: PathBuf::new(),
             query_context: QueryContext::default(),
@@ -934,6 +1024,20 @@ mod tests {
             custom_name: None,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            timezone: Timezone::Local,
+            chat_timezones: BTreeMap::new(),
+            iso_timestamps: false,
+            locale: Locale::English,
+            date_format: None,
+            pagination: Pagination::Disabled,
+            theme: Theme::default(),
+            custom_css: None,
+            link_css: false,
+            template_dir: None,
+            conversations: None,
+            eml: false,
+            dedupe: false,
+            attachment_names: AttachmentNames::Random,
         }
     }
 
@@ -1232,6 +1336,30 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_txt_announcement_locale() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = fake_options();
+        options.locale = Locale::Spanish;
+        options.date_format = Some("%Y-%m-%d %H:%M".to_string());
+        let config = Config::new(options).unwrap();
+        let exporter = TXT::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+
+        let actual = exporter.format_announcement(&message);
+        let expected =
+            "2022-05-17 17:29 Usted cambió el nombre de la conversación a Hello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_txt_announcement_custom_name() {
         // Set timezone to PST for consistent Local time
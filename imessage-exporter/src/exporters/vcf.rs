@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::write,
+    path::{Path, PathBuf},
+};
+
+use crate::app::{
+    error::RuntimeError, progress::build_progress_bar_export, runtime::Config, vcard::VCard,
+};
+use crate::exporters::exporter::Exporter;
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{handle::Handle, messages::Message, table::Table},
+    util::dates::get_local_time,
+};
+
+/// Name of the file that holds every card
+const CONTACTS_FILE: &str = "contacts.vcf";
+
+/// When a person's messages were sent, tracked while iterating over the messages table
+#[derive(Debug, PartialEq, Eq)]
+pub struct Activity {
+    /// The number of messages exchanged with the person
+    pub messages: u64,
+    /// The `date` of the earliest message
+    pub first_message: i64,
+    /// The `date` of the latest message
+    pub last_message: i64,
+}
+
+pub struct VCF<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the file the cards are written to
+    pub file: PathBuf,
+    /// Map of deduplicated participant ID to when their messages were sent
+    pub activity: HashMap<i32, Activity>,
+}
+
+impl<'a> Exporter<'a> for VCF<'a> {
+    fn new(config: &'a Config) -> Self {
+        VCF {
+            config,
+            file: config.options.export_path.join(CONTACTS_FILE),
+            activity: HashMap::new(),
+        }
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as vcf...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            self.add_message(&msg);
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        let cards = self.build_cards()?;
+        let contents: String = cards.iter().map(VCard::render).collect();
+        write(&self.file, contents).map_err(RuntimeError::DiskError)?;
+        eprintln!("Wrote {} contacts", cards.len());
+
+        Ok(())
+    }
+
+    /// Every card is written to a single file
+    fn get_or_create_file(&mut self, _: &Message) -> &Path {
+        &self.file
+    }
+}
+
+impl<'a> VCF<'a> {
+    /// Record the date of a message against the person it was exchanged with
+    ///
+    /// Handle `0` is the database owner, which group chats use for messages we sent.
+    fn add_message(&mut self, message: &Message) {
+        let Some(participant) = message
+            .handle_id
+            .filter(|handle_id| *handle_id != 0)
+            .and_then(|handle_id| self.config.real_participants.get(&handle_id))
+        else {
+            return;
+        };
+
+        self.activity
+            .entry(*participant)
+            .and_modify(|activity| {
+                activity.messages += 1;
+                activity.first_message = activity.first_message.min(message.date);
+                activity.last_message = activity.last_message.max(message.date);
+            })
+            .or_insert(Activity {
+                messages: 1,
+                first_message: message.date,
+                last_message: message.date,
+            });
+    }
+
+    /// Build a card for each deduplicated participant, sorted by name
+    fn build_cards(&self) -> Result<Vec<VCard>, RuntimeError> {
+        // The handle cache joins the IDs of duplicated handles, so read each handle's own ID from the table
+        let mut statement = Handle::get(&self.config.db).map_err(RuntimeError::DatabaseError)?;
+        let handles = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+
+        let mut people: BTreeMap<i32, BTreeSet<String>> = BTreeMap::new();
+        for handle in handles {
+            let handle = Handle::extract(handle).map_err(RuntimeError::DatabaseError)?;
+            if let Some(participant) = self.config.real_participants.get(&handle.rowid) {
+                people.entry(*participant).or_default().insert(handle.id);
+            }
+        }
+
+        let mut cards: Vec<VCard> = people
+            .iter()
+            .map(|(participant, ids)| self.build_card(ids, self.activity.get(participant)))
+            .collect();
+        cards.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(cards)
+    }
+
+    /// Build a card from every ID that belongs to a person, noting when we exchanged messages with them
+    fn build_card(&self, ids: &BTreeSet<String>, activity: Option<&Activity>) -> VCard {
+        let (emails, phones): (Vec<String>, Vec<String>) =
+            ids.iter().cloned().partition(|id| id.contains('@'));
+
+        let note = activity.map(|activity| {
+            let strings = self.config.strings();
+            let format = |date: &i64| {
+                self.config
+                    .format_date(&get_local_time(date, &self.config.offset), None)
+            };
+            format!(
+                "{}: {}\n{}: {}\n{}: {}",
+                strings.first_message,
+                format(&activity.first_message),
+                strings.last_message,
+                format(&activity.last_message),
+                strings.messages,
+                activity.messages
+            )
+        });
+
+        VCard {
+            // Phone numbers are listed first since they are what most people are saved under
+            name: phones
+                .first()
+                .or(emails.first())
+                .cloned()
+                .unwrap_or_default(),
+            phones,
+            emails,
+            note,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, env::set_var, path::PathBuf};
+
+    use crate::{
+        exporters::{
+            html::tests::{blank, fake_options},
+            vcf::Activity,
+        },
+        Config, Exporter, VCF,
+    };
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = VCF::new(&config);
+        assert_eq!(exporter.file, PathBuf::from("contacts.vcf"));
+        assert!(exporter.activity.is_empty());
+    }
+
+    #[test]
+    fn can_add_messages() {
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        // Handles 1 and 2 are the same person
+        config.real_participants.insert(0, 0);
+        config.real_participants.insert(1, 1);
+        config.real_participants.insert(2, 1);
+        let mut exporter = VCF::new(&config);
+
+        let mut message = blank();
+        message.handle_id = Some(1);
+        message.date = 20;
+        exporter.add_message(&message);
+        message.handle_id = Some(2);
+        message.date = 10;
+        exporter.add_message(&message);
+        message.handle_id = Some(0);
+        message.date = 30;
+        exporter.add_message(&message);
+
+        assert_eq!(exporter.activity.len(), 1);
+        assert_eq!(
+            exporter.activity.get(&1),
+            Some(&Activity {
+                messages: 2,
+                first_message: 10,
+                last_message: 20
+            })
+        );
+    }
+
+    #[test]
+    fn can_build_card() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = VCF::new(&config);
+
+        let ids = BTreeSet::from(["jane@example.com".to_string(), "+15555550100".to_string()]);
+        let activity = Activity {
+            messages: 3,
+            // May 17, 2022  8:29:42 PM
+            first_message: 674526582885055488,
+            last_message: 674526582885055488,
+        };
+        let card = exporter.build_card(&ids, Some(&activity));
+
+        assert_eq!(card.name, "+15555550100");
+        assert_eq!(card.phones, vec!["+15555550100"]);
+        assert_eq!(card.emails, vec!["jane@example.com"]);
+        assert_eq!(
+            card.note,
+            Some("First message: May 17, 2022  5:29:42 PM\nLast message: May 17, 2022  5:29:42 PM\nMessages: 3".to_string())
+        );
+    }
+
+    #[test]
+    fn can_build_card_without_messages() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = VCF::new(&config);
+
+        let ids = BTreeSet::from(["jane@example.com".to_string()]);
+        let card = exporter.build_card(&ids, None);
+
+        assert_eq!(card.name, "jane@example.com");
+        assert!(card.phones.is_empty());
+        assert!(card.note.is_none());
+    }
+}
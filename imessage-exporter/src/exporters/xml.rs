@@ -0,0 +1,539 @@
+use std::{
+    fs::{remove_file, File},
+    io::{copy, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        encoding::encode_base64, epub_book::escape_xml, error::RuntimeError, mime::MimePart,
+        progress::build_progress_bar_export, runtime::Config,
+    },
+    exporters::exporter::{Exporter, Writer},
+    MBOX, TXT,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, UNKNOWN},
+    },
+    util::dates::get_unix_millis,
+};
+
+/// Name of the finished backup, which SMS Backup & Restore can restore from directly
+const BACKUP_FILE: &str = "sms.xml";
+/// Name of the file that rows are written to until the total count is known
+const ROWS_FILE: &str = "sms.xml.tmp";
+/// Content type Android uses for every MMS
+const MMS_CONTENT_TYPE: &str = "application/vnd.wap.multipart.related";
+/// Address Android uses in place of the device owner's own number
+const OWNER_ADDRESS: &str = "insert-address-token";
+/// The `MIBenum` for UTF-8, which Android uses to identify character sets
+const UTF_8: &str = "106";
+/// Address type for the sender of an MMS
+const ADDRESS_FROM: &str = "137";
+/// Address type for a recipient of an MMS
+const ADDRESS_TO: &str = "151";
+
+/// Escape text so it can be used as an attribute value
+///
+/// Parsers replace raw whitespace in attributes with spaces, so line breaks must be written as character references.
+fn escape_attribute(text: &str) -> String {
+    escape_xml(text)
+        .replace('\r', "&#13;")
+        .replace('\n', "&#10;")
+        .replace('\t', "&#9;")
+}
+
+/// Format the attributes of an element, escaping each value
+fn attributes(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| format!(" {name}=\"{}\"", escape_attribute(value)))
+        .collect()
+}
+
+pub struct XML<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Formats the text of messages and reads the files attached to them
+    pub mbox: MBOX<'a>,
+    /// Path to the finished backup file
+    pub backup: PathBuf,
+    /// Path to the file rows are written to before the backup is finished
+    pub rows: PathBuf,
+    /// Number of rows written to the backup
+    pub count: usize,
+}
+
+impl<'a> Exporter<'a> for XML<'a> {
+    fn new(config: &'a Config) -> Self {
+        XML {
+            config,
+            mbox: MBOX::new(config),
+            backup: config.options.export_path.join(BACKUP_FILE),
+            rows: config.options.export_path.join(ROWS_FILE),
+            count: 0,
+        }
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as xml...",
+            self.config.options.export_path.display()
+        );
+
+        // Remove rows left behind by an export that did not finish
+        if self.rows.exists() {
+            remove_file(&self.rows).map_err(RuntimeError::DiskError)?;
+        }
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Reactions are listed in the body of the message they react to, and Android has no equivalent of announcements
+            if !msg.is_reaction() && !msg.is_announcement() {
+                let _ = msg.gen_text(&self.config.db);
+                let row = self.format_row(&msg).map_err(RuntimeError::DatabaseError)?;
+                TXT::write_to_file(self.get_or_create_file(&msg), &row);
+                self.count += 1;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        self.write_backup()
+    }
+
+    /// All messages are written to a single backup, so every message shares the same file
+    fn get_or_create_file(&mut self, _: &Message) -> &Path {
+        &self.rows
+    }
+}
+
+impl<'a> XML<'a> {
+    /// Write the root element around the rows, now that we know how many there are
+    fn write_backup(&self) -> Result<(), RuntimeError> {
+        let mut file = File::create(&self.backup).map_err(RuntimeError::DiskError)?;
+        let header = format!(
+            "<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>\n<smses{}>\n",
+            attributes(&[
+                ("count", &self.count.to_string()),
+                ("backup_set", &Uuid::new_v4().to_string()),
+                ("backup_date", &Utc::now().timestamp_millis().to_string()),
+                ("type", "full"),
+            ])
+        );
+        file.write_all(header.as_bytes())
+            .map_err(RuntimeError::DiskError)?;
+
+        if self.rows.exists() {
+            let mut rows = File::open(&self.rows).map_err(RuntimeError::DiskError)?;
+            copy(&mut rows, &mut file).map_err(RuntimeError::DiskError)?;
+            remove_file(&self.rows).map_err(RuntimeError::DiskError)?;
+        }
+
+        file.write_all(b"</smses>\n")
+            .map_err(RuntimeError::DiskError)
+    }
+
+    /// Build the row for a message
+    ///
+    /// Plain text in a conversation with one other person is an SMS. Anything with attachments,
+    /// a subject, or more than one other person is an MMS.
+    fn format_row(&self, message: &Message) -> Result<String, TableError> {
+        let (body, parts) = self.mbox.format_body(message)?;
+        let recipients = self.recipients(message);
+
+        if parts.is_empty() && message.subject.is_none() && recipients.len() <= 1 {
+            Ok(self.format_sms(message, &body, &recipients))
+        } else {
+            Ok(self.format_mms(message, &body, &parts, &recipients))
+        }
+    }
+
+    /// Build an `sms` row for a plain text message
+    fn format_sms(&self, message: &Message, body: &str, recipients: &[(&str, &str)]) -> String {
+        let (address, contact_name) = recipients.first().copied().unwrap_or((UNKNOWN, UNKNOWN));
+        let date = get_unix_millis(&message.date, &self.config.offset).to_string();
+        format!(
+            "  <sms{} />\n",
+            attributes(&[
+                ("protocol", "0"),
+                ("address", address),
+                ("date", &date),
+                ("type", if message.is_from_me { "2" } else { "1" }),
+                ("subject", "null"),
+                ("body", body),
+                ("toa", "null"),
+                ("sc_toa", "null"),
+                ("service_center", "null"),
+                ("read", Self::read(message)),
+                ("status", "-1"),
+                ("locked", "0"),
+                ("date_sent", &date),
+                ("sub_id", "-1"),
+                ("readable_date", &self.readable_date(message)),
+                ("contact_name", contact_name),
+            ])
+        )
+    }
+
+    /// Build an `mms` row with a part for the text and each attachment, and an address for each participant
+    fn format_mms(
+        &self,
+        message: &Message,
+        body: &str,
+        attachments: &[MimePart],
+        recipients: &[(&str, &str)],
+    ) -> String {
+        let date = get_unix_millis(&message.date, &self.config.offset).to_string();
+        let address = recipients
+            .iter()
+            .map(|(address, _)| *address)
+            .collect::<Vec<&str>>()
+            .join("~");
+        let contact_name = recipients
+            .iter()
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        let mut out_s = format!(
+            "  <mms{}>\n    <parts>\n",
+            attributes(&[
+                ("date", &date),
+                ("rr", "null"),
+                ("sub", message.subject.as_deref().unwrap_or("null")),
+                ("ct_t", MMS_CONTENT_TYPE),
+                ("read_status", "null"),
+                ("seen", "1"),
+                ("msg_box", if message.is_from_me { "2" } else { "1" }),
+                ("address", &address),
+                (
+                    "sub_cs",
+                    if message.subject.is_some() {
+                        UTF_8
+                    } else {
+                        "null"
+                    }
+                ),
+                ("text_only", if attachments.is_empty() { "1" } else { "0" }),
+                ("locked", "0"),
+                ("m_id", &message.guid),
+                ("date_sent", "0"),
+                ("read", Self::read(message)),
+                ("sub_id", "-1"),
+                ("m_cls", "personal"),
+                ("d_rpt", "null"),
+                ("v", "18"),
+                ("m_type", if message.is_from_me { "128" } else { "132" }),
+                ("readable_date", &self.readable_date(message)),
+                ("contact_name", &contact_name),
+            ])
+        );
+
+        if !body.is_empty() {
+            out_s.push_str(&format!(
+                "      <part{} />\n",
+                attributes(&[
+                    ("seq", "0"),
+                    ("ct", "text/plain"),
+                    ("name", "null"),
+                    ("chset", UTF_8),
+                    ("cd", "null"),
+                    ("fn", "null"),
+                    ("cid", "<text0>"),
+                    ("cl", "text0.txt"),
+                    ("ctt_s", "null"),
+                    ("ctt_t", "null"),
+                    ("text", body),
+                ])
+            ));
+        }
+
+        for attachment in attachments {
+            out_s.push_str(&format!(
+                "      <part{} />\n",
+                attributes(&[
+                    ("seq", "0"),
+                    ("ct", &attachment.content_type),
+                    ("name", &attachment.filename),
+                    ("chset", "null"),
+                    ("cd", "null"),
+                    ("fn", "null"),
+                    ("cid", &format!("<{}>", attachment.filename)),
+                    ("cl", &attachment.filename),
+                    ("ctt_s", "null"),
+                    ("ctt_t", "null"),
+                    ("text", "null"),
+                    ("data", &encode_base64(&attachment.data)),
+                ])
+            ));
+        }
+        out_s.push_str("    </parts>\n    <addrs>\n");
+
+        // The sender is listed first, followed by everyone who received the message, including
+        // the device owner when someone else sent it
+        let sender = match message.is_from_me {
+            true => OWNER_ADDRESS,
+            false => self.handle(message.handle_id),
+        };
+        out_s.push_str(&Self::format_address(sender, ADDRESS_FROM));
+        recipients
+            .iter()
+            .filter(|(address, _)| *address != sender)
+            .for_each(|(address, _)| out_s.push_str(&Self::format_address(address, ADDRESS_TO)));
+        if !message.is_from_me {
+            out_s.push_str(&Self::format_address(OWNER_ADDRESS, ADDRESS_TO));
+        }
+        out_s.push_str("    </addrs>\n  </mms>\n");
+
+        out_s
+    }
+
+    /// Build an `addr` element for a participant in an MMS
+    fn format_address(address: &str, kind: &str) -> String {
+        format!(
+            "      <addr{} />\n",
+            attributes(&[("address", address), ("type", kind), ("charset", UTF_8)])
+        )
+    }
+
+    /// Get the handles and names of everyone in the conversation besides the database owner
+    fn recipients(&self, message: &Message) -> Vec<(&str, &str)> {
+        let participants = self
+            .config
+            .conversation(message)
+            .and_then(|(chatroom, _)| self.config.chatroom_participants.get(&chatroom.rowid));
+
+        match participants {
+            Some(participants) if !participants.is_empty() => participants
+                .iter()
+                .map(|participant| {
+                    (
+                        self.handle(Some(*participant)),
+                        self.config.who(Some(*participant), false),
+                    )
+                })
+                .collect(),
+            // Without a conversation, the sender is the only other person we know of
+            _ if !message.is_from_me => vec![(
+                self.handle(message.handle_id),
+                self.config.who(message.handle_id, false),
+            )],
+            _ => vec![],
+        }
+    }
+
+    /// Get the phone number or email address for a handle
+    fn handle(&self, handle_id: Option<i32>) -> &str {
+        handle_id
+            .and_then(|id| self.config.participants.get(&id))
+            .map_or(UNKNOWN, String::as_str)
+    }
+
+    /// Messages we sent are always read
+    fn read(message: &Message) -> &'static str {
+        if message.is_from_me || message.is_read {
+            "1"
+        } else {
+            "0"
+        }
+    }
+
+    /// Format the date of a message the way it is shown in the backup viewer
+    fn readable_date(&self, message: &Message) -> String {
+        self.config
+            .format_date(&message.date(&self.config.offset), message.chat_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, env::set_var, path::PathBuf};
+
+    use crate::{
+        app::mime::MimePart,
+        exporters::{
+            html::tests::{blank, fake_options},
+            xml::escape_attribute,
+        },
+        Config, Exporter, XML,
+    };
+    use imessage_database::tables::chat::Chat;
+
+    fn fake_group(config: &mut Config) {
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: "chat123".to_string(),
+                service_name: Some("iMessage".to_string()),
+                display_name: Some("Friends".to_string()),
+                properties: None,
+            },
+        );
+        config.real_chatrooms.insert(1, 1);
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([1, 2]));
+        config.participants.insert(1, "+15555550100".to_string());
+        config
+            .participants
+            .insert(2, "jane@example.com".to_string());
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = Config::new(options).unwrap();
+        let exporter = XML::new(&config);
+        assert_eq!(exporter.backup, PathBuf::from("sms.xml"));
+        assert_eq!(exporter.count, 0);
+    }
+
+    #[test]
+    fn can_escape_attribute() {
+        assert_eq!(
+            escape_attribute("<a & \"b\">\nc"),
+            "&lt;a &amp; &quot;b&quot;&gt;&#10;c"
+        );
+    }
+
+    #[test]
+    fn can_format_sms() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        config.participants.insert(1, "+15555550100".to_string());
+        let exporter = XML::new(&config);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello\nworld".to_string());
+        message.handle_id = Some(1);
+
+        let actual = exporter.format_row(&message).unwrap();
+
+        assert!(actual.starts_with("  <sms protocol=\"0\" address=\"+15555550100\" date=\"1652833782885\" type=\"1\" subject=\"null\" body=\"Hello&#10;world\""));
+        assert!(actual.contains(" read=\"0\" "));
+        assert!(actual.contains(" readable_date=\"May 17, 2022  5:29:42 PM\" "));
+        assert!(actual.ends_with(" />\n"));
+    }
+
+    #[test]
+    fn can_format_sms_from_me() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        config.participants.insert(1, "+15555550100".to_string());
+        let exporter = XML::new(&config);
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+
+        // Without a conversation there is no one to send the message to
+        let actual = exporter.format_row(&message).unwrap();
+
+        assert!(actual.contains(" address=\"Unknown\" "));
+        assert!(actual.contains(" type=\"2\" "));
+        assert!(actual.contains(" read=\"1\" "));
+    }
+
+    #[test]
+    fn can_format_group_mms() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        fake_group(&mut config);
+        let exporter = XML::new(&config);
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        message.guid = "ABC".to_string();
+        message.chat_id = Some(1);
+        message.handle_id = Some(2);
+
+        let actual = exporter.format_row(&message).unwrap();
+
+        assert!(actual.starts_with("  <mms date="));
+        assert!(actual.contains(" msg_box=\"1\" address=\"+15555550100~jane@example.com\" "));
+        assert!(actual.contains(" text_only=\"1\" "));
+        assert!(actual.contains(" m_id=\"ABC\" "));
+        assert!(actual.contains(" m_type=\"132\" "));
+        assert!(actual.contains(" cid=\"&lt;text0&gt;\" cl=\"text0.txt\" ctt_s=\"null\" ctt_t=\"null\" text=\"Hello world\" />"));
+        assert!(actual.contains("      <addr address=\"jane@example.com\" type=\"137\" charset=\"106\" />\n      <addr address=\"+15555550100\" type=\"151\" charset=\"106\" />\n      <addr address=\"insert-address-token\" type=\"151\" charset=\"106\" />\n    </addrs>\n  </mms>\n"));
+    }
+
+    #[test]
+    fn can_format_mms_attachment() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = Config::new(options).unwrap();
+        fake_group(&mut config);
+        let exporter = XML::new(&config);
+
+        let mut message = blank();
+        message.is_from_me = true;
+        message.chat_id = Some(1);
+
+        let attachments = vec![MimePart {
+            content_type: "image/jpeg".to_string(),
+            filename: "a.jpg".to_string(),
+            data: vec![1, 2, 3],
+        }];
+        let actual =
+            exporter.format_mms(&message, "", &attachments, &exporter.recipients(&message));
+
+        assert!(actual.contains(" msg_box=\"2\" "));
+        assert!(actual.contains(" text_only=\"0\" "));
+        assert!(actual.contains(" m_type=\"128\" "));
+        assert!(!actual.contains("text/plain"));
+        assert!(actual.contains("      <part seq=\"0\" ct=\"image/jpeg\" name=\"a.jpg\" chset=\"null\" cd=\"null\" fn=\"null\" cid=\"&lt;a.jpg&gt;\" cl=\"a.jpg\" ctt_s=\"null\" ctt_t=\"null\" text=\"null\" data=\"AQID\" />\n"));
+        assert!(actual.contains(
+            "      <addr address=\"insert-address-token\" type=\"137\" charset=\"106\" />\n"
+        ));
+        assert_eq!(actual.matches("type=\"151\"").count(), 2);
+    }
+}
@@ -3,9 +3,13 @@
 mod app;
 mod exporters;
 
-pub use exporters::{exporter::Exporter, html::HTML, txt::TXT};
+pub use exporters::{
+    epub::EPUB, exporter::Exporter, html::HTML, locations::LOCATIONS, mbox::MBOX, pdf::PDF,
+    txt::TXT, vcf::VCF, xml::XML,
+};
 
 use app::{
+    config_file::ConfigFile,
     options::{from_command_line, Options},
     runtime::Config,
 };
@@ -13,8 +17,8 @@ use app::{
 fn main() {
     // Get args from command line
     let args = from_command_line();
-    // Create application options
-    let options = Options::from_args(&args);
+    // Create application options, using the config file for anything not set on the command line
+    let options = ConfigFile::from_args(&args).and_then(|file| Options::from_args(&args, &file));
 
     // Create app state and start
     if let Err(why) = &options {